@@ -0,0 +1,12 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS tax_lots;
+
+ALTER TABLE transactions
+DROP COLUMN IF EXISTS realized_gain;
+
+ALTER TABLE portfolio_assets
+DROP COLUMN IF EXISTS cost_basis,
+DROP COLUMN IF EXISTS realized_pnl;
+
+ALTER TABLE portfolios
+DROP COLUMN IF EXISTS cost_basis_method;
@@ -0,0 +1,23 @@
+-- Add up migration script here
+ALTER TABLE portfolios
+ADD COLUMN cost_basis_method VARCHAR(20) NOT NULL DEFAULT 'FIFO';
+
+ALTER TABLE portfolio_assets
+ADD COLUMN cost_basis DECIMAL NOT NULL DEFAULT 0,
+ADD COLUMN realized_pnl DECIMAL NOT NULL DEFAULT 0;
+
+ALTER TABLE transactions
+ADD COLUMN realized_gain DECIMAL;
+
+-- Open lots left after matching disposals against acquisitions
+CREATE TABLE tax_lots (
+    tx_id BIGINT PRIMARY KEY REFERENCES transactions (id) ON DELETE CASCADE,
+    portfolio_id BIGINT NOT NULL REFERENCES portfolios (id),
+    asset_id VARCHAR(50) NOT NULL REFERENCES assets (id),
+    acquired_at TIMESTAMPTZ NOT NULL,
+    quantity DECIMAL NOT NULL,
+    remaining_quantity DECIMAL NOT NULL,
+    unit_cost DECIMAL NOT NULL
+);
+
+CREATE INDEX idx_tax_lots_portfolio_asset ON tax_lots (portfolio_id, asset_id);
@@ -0,0 +1,21 @@
+-- Add up migration script here
+-- Ledger of how each SELL was closed against earlier acquisitions
+CREATE TABLE realized_gains (
+    id BIGINT PRIMARY KEY,
+    portfolio_id BIGINT NOT NULL REFERENCES portfolios (id),
+    asset_id VARCHAR(50) NOT NULL REFERENCES assets (id),
+    sell_tx_id BIGINT NOT NULL REFERENCES transactions (id) ON DELETE CASCADE,
+    buy_tx_id BIGINT REFERENCES transactions (id) ON DELETE CASCADE,
+    quantity DECIMAL NOT NULL,
+    cost_basis DECIMAL NOT NULL,
+    proceeds DECIMAL NOT NULL,
+    gain DECIMAL NOT NULL,
+    acquired_at TIMESTAMPTZ,
+    disposed_at TIMESTAMPTZ NOT NULL,
+    holding_period_days BIGINT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_realized_gains_portfolio_id ON realized_gains (portfolio_id, disposed_at);
+
+CREATE INDEX idx_realized_gains_sell_tx_id ON realized_gains (sell_tx_id);
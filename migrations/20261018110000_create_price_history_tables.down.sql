@@ -0,0 +1,4 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS portfolio_snapshots;
+
+DROP TABLE IF EXISTS price_history;
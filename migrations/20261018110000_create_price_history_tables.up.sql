@@ -0,0 +1,26 @@
+-- Add up migration script here
+-- Daily close price of an asset quoted in a currency
+CREATE TABLE price_history (
+    asset_id VARCHAR(50) NOT NULL REFERENCES assets (id),
+    currency VARCHAR(10) NOT NULL,
+    price_date DATE NOT NULL,
+    close_price DECIMAL NOT NULL,
+    source VARCHAR(50) NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (asset_id, currency, price_date)
+);
+
+-- End of day valuation of a portfolio
+CREATE TABLE portfolio_snapshots (
+    portfolio_id BIGINT NOT NULL REFERENCES portfolios (id),
+    snapshot_date DATE NOT NULL,
+    currency VARCHAR(10) NOT NULL,
+    holdings_value DECIMAL NOT NULL,
+    cost_basis DECIMAL NOT NULL,
+    realized_pnl DECIMAL NOT NULL,
+    unrealized_pnl DECIMAL NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (portfolio_id, snapshot_date)
+);
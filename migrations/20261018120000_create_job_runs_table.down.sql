@@ -0,0 +1,4 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS job_runs;
+
+ALTER TABLE users DROP COLUMN IF EXISTS is_admin;
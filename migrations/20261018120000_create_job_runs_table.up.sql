@@ -0,0 +1,15 @@
+-- Add up migration script here
+ALTER TABLE users ADD COLUMN is_admin BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- One execution of a background job
+CREATE TABLE job_runs (
+    id BIGINT PRIMARY KEY,
+    job_name VARCHAR(50) NOT NULL,
+    triggered_by VARCHAR(20) NOT NULL,
+    status VARCHAR(20) NOT NULL,
+    error TEXT,
+    started_at TIMESTAMPTZ NOT NULL,
+    finished_at TIMESTAMPTZ
+);
+
+CREATE INDEX idx_job_runs_job_name_started_at ON job_runs (job_name, started_at DESC);
@@ -0,0 +1,6 @@
+-- Add down migration script here
+ALTER TABLE portfolios DROP COLUMN IF EXISTS base_currency;
+
+ALTER TABLE users DROP COLUMN IF EXISTS base_currency;
+
+DROP TABLE IF EXISTS fx_rates;
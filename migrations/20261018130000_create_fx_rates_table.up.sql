@@ -0,0 +1,17 @@
+-- Add up migration script here
+-- Daily exchange rate quoted as units of the currency per 1 USD
+CREATE TABLE fx_rates (
+    currency VARCHAR(10) NOT NULL,
+    rate_date DATE NOT NULL,
+    rate DECIMAL NOT NULL,
+    source VARCHAR(50) NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (currency, rate_date)
+);
+
+-- Default for new portfolios of the user
+ALTER TABLE users ADD COLUMN base_currency VARCHAR(10) NOT NULL DEFAULT 'USD';
+
+-- Currency the portfolio stats, snapshots and returns are reported in
+ALTER TABLE portfolios ADD COLUMN base_currency VARCHAR(10) NOT NULL DEFAULT 'USD';
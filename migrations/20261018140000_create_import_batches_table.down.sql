@@ -0,0 +1,4 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS import_batch_changes;
+ALTER TABLE transactions DROP COLUMN IF EXISTS import_batch_id;
+DROP TABLE IF EXISTS import_batches;
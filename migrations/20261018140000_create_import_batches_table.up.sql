@@ -0,0 +1,39 @@
+-- Add up migration script here
+-- One run of an importer that changed a portfolio
+CREATE TABLE import_batches (
+    id BIGINT PRIMARY KEY,
+    user_id BIGINT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    portfolio_id BIGINT NOT NULL REFERENCES portfolios (id) ON DELETE CASCADE,
+    source VARCHAR(50) NOT NULL,
+    file_hash VARCHAR(64) NOT NULL,
+    created_count INTEGER NOT NULL DEFAULT 0,
+    updated_count INTEGER NOT NULL DEFAULT 0,
+    unchanged_count INTEGER NOT NULL DEFAULT 0,
+    -- Assets and portfolio assets the import created, removed by a rollback
+    -- when nothing else uses them
+    new_asset_ids TEXT[] NOT NULL DEFAULT '{}',
+    new_portfolio_asset_ids TEXT[] NOT NULL DEFAULT '{}',
+    status VARCHAR(20) NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX idx_import_batches_user_id_created_at ON import_batches (user_id, created_at DESC);
+
+-- Latest import that created or modified the transaction
+ALTER TABLE transactions
+    ADD COLUMN import_batch_id BIGINT REFERENCES import_batches (id) ON DELETE SET NULL;
+
+-- Transactions touched by an import, with their state before it for rollbacks
+-- and the state the import left them in, rollbacks refuse transactions edited
+-- since
+CREATE TABLE import_batch_changes (
+    batch_id BIGINT NOT NULL REFERENCES import_batches (id) ON DELETE CASCADE,
+    tx_id BIGINT NOT NULL,
+    asset_id VARCHAR(50) NOT NULL,
+    action VARCHAR(20) NOT NULL,
+    previous JSONB,
+    previous_batch_id BIGINT,
+    after JSONB NOT NULL,
+    PRIMARY KEY (batch_id, tx_id)
+);
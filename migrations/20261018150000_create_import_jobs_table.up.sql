@@ -0,0 +1,27 @@
+-- Add up migration script here
+-- Uploaded file waiting for or processed by the import worker
+CREATE TABLE import_jobs (
+    id BIGINT PRIMARY KEY,
+    user_id BIGINT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    portfolio_id BIGINT NOT NULL REFERENCES portfolios (id) ON DELETE CASCADE,
+    source VARCHAR(50) NOT NULL,
+    file_contents TEXT NOT NULL,
+    options JSONB,
+    status VARCHAR(20) NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    rows_parsed INTEGER NOT NULL DEFAULT 0,
+    rows_inserted INTEGER NOT NULL DEFAULT 0,
+    error_count INTEGER NOT NULL DEFAULT 0,
+    errors TEXT[] NOT NULL DEFAULT '{}',
+    batch_id BIGINT REFERENCES import_batches (id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL,
+    started_at TIMESTAMPTZ,
+    finished_at TIMESTAMPTZ,
+    -- Running jobs are kept by the worker renewing their lease, jobs whose
+    -- lease expired were abandoned
+    lease_expires_at TIMESTAMPTZ,
+    updated_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX idx_import_jobs_status_created_at ON import_jobs (status, created_at);
+CREATE INDEX idx_import_jobs_user_id ON import_jobs (user_id);
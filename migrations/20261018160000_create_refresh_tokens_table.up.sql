@@ -0,0 +1,13 @@
+-- Add up migration script here
+-- Refresh tokens of a session, each one is used once and replaced by the next.
+-- A session is the family of the tokens rotated from one login
+CREATE TABLE refresh_tokens (
+    id BIGINT PRIMARY KEY,
+    session_id BIGINT NOT NULL REFERENCES user_sessions (session_id) ON DELETE CASCADE,
+    token_hash VARCHAR(64) NOT NULL UNIQUE,
+    created_at TIMESTAMPTZ NOT NULL,
+    expires_at TIMESTAMPTZ NOT NULL,
+    used_at TIMESTAMPTZ
+);
+
+CREATE INDEX idx_refresh_tokens_session_id ON refresh_tokens (session_id);
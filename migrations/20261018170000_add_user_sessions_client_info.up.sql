@@ -0,0 +1,7 @@
+-- Add up migration script here
+-- Where a session was opened from and when it was last used, to list them
+ALTER TABLE user_sessions ADD COLUMN user_agent VARCHAR(512);
+ALTER TABLE user_sessions ADD COLUMN ip_address VARCHAR(64);
+ALTER TABLE user_sessions ADD COLUMN last_seen_at TIMESTAMPTZ;
+UPDATE user_sessions SET last_seen_at = created_at;
+ALTER TABLE user_sessions ALTER COLUMN last_seen_at SET NOT NULL;
@@ -0,0 +1,21 @@
+-- Add up migration script here
+-- TOTP secret of a user, two-factor authentication is on once enabled_at is set
+CREATE TABLE user_totp (
+    user_id BIGINT PRIMARY KEY REFERENCES users (id) ON DELETE CASCADE,
+    secret VARCHAR(64) NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL,
+    enabled_at TIMESTAMPTZ,
+    -- Time step of the last accepted code, a code is never accepted twice
+    last_used_step BIGINT
+);
+
+-- One-time codes replacing a TOTP code when the authenticator is lost
+CREATE TABLE user_recovery_codes (
+    id BIGINT PRIMARY KEY,
+    user_id BIGINT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    code_hash VARCHAR(64) NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL,
+    used_at TIMESTAMPTZ
+);
+
+CREATE INDEX idx_user_recovery_codes_user_id ON user_recovery_codes (user_id);
@@ -1,3 +1,20 @@
+pub mod account;
 pub mod asset;
+pub mod auth;
+pub mod backup;
+pub mod coingecko_import;
+pub mod cost_basis;
+pub mod csv_import;
+pub mod exchange_import;
+pub mod export;
+pub mod fx;
+pub mod import_job;
+pub mod import_plan;
+pub mod personal_access_token;
 pub mod portfolio;
 pub mod portfolio_asset;
+pub mod portfolio_snapshot;
+pub mod price_history;
+pub mod returns;
+pub mod tax_report;
+pub mod two_factor;
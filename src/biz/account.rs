@@ -0,0 +1,179 @@
+use tracing::info;
+
+use crate::{
+    biz::{auth::revoke_other_sessions, backup::build_account_backup},
+    db::repositories::user::UserRepo,
+    middleware::{
+        auth::invalidate_cached_tokens,
+        rate_limit::{clear_login_failures, login_lockout_remaining, record_login_failure},
+    },
+    models::{
+        database::user::UserRow,
+        dto::user::{
+            ChangePasswordRequest, DeleteAccountRequest, DeleteAccountResponse, UpdateUser,
+            UpdateUserRequest, UserMeResponse,
+        },
+    },
+    state::AppState,
+    utils::{
+        error::AppError,
+        password::{hash_password, verify_password},
+    },
+};
+
+const MAX_NAME_LEN: usize = 255;
+const MIN_PHONE_DIGITS: usize = 7;
+const MAX_PHONE_DIGITS: usize = 15;
+
+async fn get_user(state: &AppState, user_id: i64) -> Result<UserRow, AppError> {
+    UserRepo::new(state.pool.clone())
+        .get_by_id(user_id)
+        .await?
+        .ok_or_else(|| AppError::Unauthorized("User not found".to_string()))
+}
+
+/// Checks the current password before a sensitive change. Wrong passwords
+/// count toward the login lockout of the account, so a stolen session can not
+/// be used to guess it.
+async fn verify_current_password(
+    state: &AppState,
+    user: &UserRow,
+    password: &str,
+) -> Result<(), AppError> {
+    let Some(hashed_password) = user.hashed_password.clone() else {
+        return Err(AppError::InvalidInput(
+            "The account has no password, set one with a password reset first".to_string(),
+        ));
+    };
+    if login_lockout_remaining(state, &user.email).await.is_some() {
+        return Err(AppError::Forbidden(
+            "Too many failed attempts, try again later".to_string(),
+        ));
+    }
+    if !verify_password(password.to_string(), hashed_password)
+        .map_err(|e| AppError::PasswordError(e.to_string()))?
+    {
+        record_login_failure(state, &user.email).await;
+        return Err(AppError::InvalidInput(
+            "Current password is incorrect".to_string(),
+        ));
+    }
+    clear_login_failures(state, &user.email).await;
+    Ok(())
+}
+
+/// Drops the separators people type in phone numbers and checks what is left
+/// looks like an international number, a leading `+` and 7 to 15 digits
+fn normalize_phone_number(phone_number: &str) -> Result<String, AppError> {
+    let normalized: String = phone_number
+        .chars()
+        .filter(|c| !matches!(c, ' ' | '-' | '(' | ')' | '.'))
+        .collect();
+    let digits = normalized.strip_prefix('+').unwrap_or(&normalized);
+    if !digits.chars().all(|c| c.is_ascii_digit())
+        || !(MIN_PHONE_DIGITS..=MAX_PHONE_DIGITS).contains(&digits.len())
+    {
+        return Err(AppError::InvalidInput(format!(
+            "Invalid phone number: {}",
+            phone_number
+        )));
+    }
+    Ok(normalized)
+}
+
+/// Changes the name and phone number of the user. A phone number can belong
+/// to one account only.
+pub async fn update_profile(
+    state: &AppState,
+    user_id: i64,
+    req: UpdateUserRequest,
+) -> Result<UserMeResponse, AppError> {
+    let name = match req.name {
+        Some(name) => {
+            let name = name.trim().to_string();
+            if name.is_empty() || name.chars().count() > MAX_NAME_LEN {
+                return Err(AppError::InvalidInput(format!(
+                    "Name must be 1 to {} characters",
+                    MAX_NAME_LEN
+                )));
+            }
+            Some(name)
+        }
+        None => None,
+    };
+    let phone_number = match req.phone_number.as_deref().map(str::trim) {
+        Some("") => Some(None),
+        Some(phone_number) => Some(Some(normalize_phone_number(phone_number)?)),
+        None => None,
+    };
+
+    let user_repo = UserRepo::new(state.pool.clone());
+    if let Some(Some(phone_number)) = &phone_number
+        && let Some(other) = user_repo.get_by_phone_number(phone_number).await?
+        && other.id != user_id
+    {
+        return Err(AppError::Conflict(
+            "Phone number is already in use".to_string(),
+        ));
+    }
+    let user = user_repo
+        .update_profile(user_id, UpdateUser { name, phone_number })
+        .await?;
+    info!("Updated the profile of user {}", user_id);
+    Ok(UserMeResponse::from_db_row(user))
+}
+
+/// Sets a new password once the current one is confirmed. The other sessions
+/// are logged out, the one making the change stays.
+pub async fn change_password(
+    state: &AppState,
+    user_id: i64,
+    session_id: i64,
+    req: ChangePasswordRequest,
+) -> Result<(), AppError> {
+    if req.new_password.is_empty() {
+        return Err(AppError::InvalidInput(
+            "Password can not be empty".to_string(),
+        ));
+    }
+    let user = get_user(state, user_id).await?;
+    verify_current_password(state, &user, &req.current_password).await?;
+    let hashed_password =
+        hash_password(req.new_password).map_err(|e| AppError::PasswordError(e.to_string()))?;
+    UserRepo::new(state.pool.clone())
+        .update_password(user_id, &hashed_password)
+        .await?;
+    info!("Changed the password of user {}", user_id);
+    revoke_other_sessions(state, user_id, session_id).await?;
+    Ok(())
+}
+
+/// Deletes the account with every portfolio, transaction and session once the
+/// password is confirmed. The backup, when asked for, is taken first so it
+/// holds the account as it was.
+pub async fn delete_account(
+    state: &AppState,
+    user_id: i64,
+    req: DeleteAccountRequest,
+) -> Result<DeleteAccountResponse, AppError> {
+    let user = get_user(state, user_id).await?;
+    verify_current_password(state, &user, &req.password).await?;
+    let backup = if req.include_backup {
+        Some(build_account_backup(state.pool.clone(), user_id).await?)
+    } else {
+        None
+    };
+    let deleted = UserRepo::new(state.pool.clone())
+        .delete_account(user_id)
+        .await?;
+    invalidate_cached_tokens(state, &deleted.session_ids).await;
+    info!(
+        "Deleted user {}: {} portfolios, {} transactions",
+        user_id, deleted.portfolios, deleted.transactions
+    );
+    Ok(DeleteAccountResponse {
+        deleted_portfolios: deleted.portfolios,
+        deleted_transactions: deleted.transactions,
+        backup,
+    })
+}
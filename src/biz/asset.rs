@@ -1,4 +1,13 @@
-use crate::models::common::asset::AssetType;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+use tracing::{error, info};
+
+use crate::{
+    db::repositories::asset::AssetRepo,
+    models::{common::asset::AssetType, dto::asset::CreateAssetRepo},
+    state::AppState,
+    utils::error::AppError,
+};
 
 // pub struct AssetBiz {
 //     pool: PgPool,
@@ -18,3 +27,73 @@ use crate::models::common::asset::AssetType;
 pub fn generate_asset_id(asset_type: &AssetType, external_id: &str) -> String {
     format!("{}_{}", asset_type.to_string(), external_id.to_lowercase())
 }
+
+/// CoinGecko coin ID of a crypto asset ID, the reverse of `generate_asset_id`
+pub fn crypto_coin_id(asset_id: &str) -> Option<String> {
+    let prefix = format!("{}_", AssetType::Crypto);
+    let (head, coin_id) = asset_id.split_at_checked(prefix.len())?;
+    head.eq_ignore_ascii_case(&prefix)
+        .then(|| coin_id.to_lowercase())
+}
+
+/// Refreshes symbol, name, image and platforms of every crypto asset from the
+/// price provider. One failing asset does not stop the others.
+pub async fn refresh_asset_metadata(state: &AppState) -> Result<(), AppError> {
+    let asset_repo = AssetRepo::new(state.pool.clone());
+    let assets = asset_repo.get_multi_by_type(&AssetType::Crypto).await?;
+    let mut updated = 0;
+    for asset in assets.iter() {
+        let coin_data = match state
+            .clients
+            .price_provider
+            .get_coin_data(&asset.external_id)
+            .await
+        {
+            Ok(coin_data) => coin_data,
+            Err(e) => {
+                error!("Get metadata of {} failed: {:?}", asset.id, e);
+                continue;
+            }
+        };
+        asset_repo
+            .update_metadata(&asset.id, &CreateAssetRepo::from_coin_data(coin_data))
+            .await?;
+        updated += 1;
+    }
+    info!("Refreshed metadata of {}/{} assets", updated, assets.len());
+    Ok(())
+}
+
+/// Assets of the given CoinGecko coins that do not exist yet, built from the
+/// provider metadata so an import can create them. Coins the provider does not
+/// know are returned apart with the reason.
+pub async fn plan_coin_assets(
+    state: &AppState,
+    coin_ids: &BTreeSet<String>,
+) -> Result<(Vec<CreateAssetRepo>, BTreeMap<String, String>), AppError> {
+    let asset_ids: Vec<String> = coin_ids
+        .iter()
+        .map(|coin_id| generate_asset_id(&AssetType::Crypto, coin_id))
+        .collect();
+    let existing: HashSet<String> = AssetRepo::new(state.pool.clone())
+        .get_multi_by_ids(&asset_ids)
+        .await?
+        .into_iter()
+        .map(|row| row.id)
+        .collect();
+    let mut new_assets = Vec::new();
+    let mut unknown = BTreeMap::new();
+    for coin_id in coin_ids {
+        if existing.contains(&generate_asset_id(&AssetType::Crypto, coin_id)) {
+            continue;
+        }
+        match state.clients.price_provider.get_coin_data(coin_id).await {
+            Ok(coin_data) => new_assets.push(CreateAssetRepo::from_coin_data(coin_data)),
+            Err(e) => {
+                info!("Get metadata of coin {} failed: {:?}", coin_id, e);
+                unknown.insert(coin_id.clone(), format!("Unknown coin {}", coin_id));
+            }
+        }
+    }
+    Ok((new_assets, unknown))
+}
@@ -0,0 +1,259 @@
+use time::{Duration, OffsetDateTime};
+use tracing::{info, warn};
+
+use crate::{
+    clients::mailer::Email,
+    db::repositories::{
+        refresh_token::RefreshTokenRepo, user::UserRepo, user_session::UserSessionRepo,
+        user_token::UserTokenRepo,
+    },
+    middleware::{
+        auth::{create_access_token, invalidate_cached_tokens},
+        client::ClientInfo,
+    },
+    models::{
+        database::user::UserRow,
+        domain::user::{UserStatus, UserTokenPurpose},
+        dto::{auth::AuthResponse, user_session::CreateUserSession},
+    },
+    state::AppState,
+    utils::{
+        error::AppError,
+        token::{generate_token, hash_token},
+    },
+};
+
+fn refresh_expires_at(state: &AppState) -> OffsetDateTime {
+    OffsetDateTime::now_utc() + Duration::seconds(state.auth.refresh_token_ttl_secs as i64)
+}
+
+/// Starts a session of the user with an access token and its first refresh token
+pub async fn create_session(
+    state: &AppState,
+    user_id: i64,
+    client: ClientInfo,
+) -> Result<AuthResponse, AppError> {
+    let refresh_token = generate_token();
+    let session = UserSessionRepo::new(state.pool.clone())
+        .create_user_session(CreateUserSession {
+            user_id,
+            expires_at: refresh_expires_at(state),
+            refresh_token_hash: hash_token(&refresh_token),
+            user_agent: client.user_agent,
+            ip_address: client.ip_address,
+        })
+        .await?;
+    Ok(AuthResponse {
+        user_id,
+        token: create_access_token(&session, state).await?,
+        expires_in: state.auth.access_token_ttl_secs,
+        refresh_token,
+    })
+}
+
+/// Exchanges a refresh token for a new access token and the next refresh token.
+/// A refresh token is used once: presenting it again means it leaked, so the
+/// whole session, every token rotated from the same login, is revoked.
+pub async fn refresh_session(
+    state: &AppState,
+    refresh_token: &str,
+    client: ClientInfo,
+) -> Result<AuthResponse, AppError> {
+    let invalid = || AppError::Unauthorized("Invalid refresh token".to_string());
+    let token_repo = RefreshTokenRepo::new(state.pool.clone());
+    let session_repo = UserSessionRepo::new(state.pool.clone());
+    let token_row = token_repo
+        .get_by_hash(&hash_token(refresh_token))
+        .await?
+        .ok_or_else(invalid)?;
+    let mut session = session_repo
+        .get_by_id(token_row.session_id)
+        .await?
+        .ok_or_else(invalid)?;
+    if !session.is_active {
+        return Err(AppError::Unauthorized("Session not active".to_string()));
+    }
+    let reused = AppError::Unauthorized(
+        "Refresh token was already used, the session is revoked".to_string(),
+    );
+    if token_row.used_at.is_some() {
+        warn!(
+            "Reuse of refresh token {} of session {}, revoking the session",
+            token_row.id, session.session_id
+        );
+        revoke_session(state, session.session_id).await?;
+        return Err(reused);
+    }
+    let now = OffsetDateTime::now_utc();
+    if token_row.expires_at < now || session.expires_at < now {
+        return Err(AppError::Unauthorized("Refresh token expired".to_string()));
+    }
+
+    let next_token = generate_token();
+    let expires_at = refresh_expires_at(state);
+    let rotated = token_repo
+        .rotate(
+            token_row.id,
+            session.session_id,
+            &hash_token(&next_token),
+            expires_at,
+            client.ip_address,
+        )
+        .await?;
+    if !rotated {
+        // Another request exchanged the same token first
+        warn!(
+            "Concurrent use of refresh token {} of session {}, revoking the session",
+            token_row.id, session.session_id
+        );
+        revoke_session(state, session.session_id).await?;
+        return Err(reused);
+    }
+    session.expires_at = expires_at;
+    Ok(AuthResponse {
+        user_id: session.user_id,
+        token: create_access_token(&session, state).await?,
+        expires_in: state.auth.access_token_ttl_secs,
+        refresh_token: next_token,
+    })
+}
+
+/// Ends a session: its refresh tokens and access tokens stop working at once
+pub async fn revoke_session(state: &AppState, session_id: i64) -> Result<(), AppError> {
+    UserSessionRepo::new(state.pool.clone())
+        .deactivate(session_id)
+        .await?;
+    invalidate_cached_tokens(state, &[session_id]).await;
+    Ok(())
+}
+
+/// Ends every session of the user, returning how many were active
+pub async fn revoke_all_sessions(state: &AppState, user_id: i64) -> Result<usize, AppError> {
+    let session_ids = UserSessionRepo::new(state.pool.clone())
+        .deactivate_all_by_user_id(user_id)
+        .await?;
+    invalidate_cached_tokens(state, &session_ids).await;
+    info!("Revoked {} sessions of user {}", session_ids.len(), user_id);
+    Ok(session_ids.len())
+}
+
+/// Ends every session of the user but `keep_session_id`, returning how many
+/// were active
+pub async fn revoke_other_sessions(
+    state: &AppState,
+    user_id: i64,
+    keep_session_id: i64,
+) -> Result<usize, AppError> {
+    let session_ids = UserSessionRepo::new(state.pool.clone())
+        .deactivate_others_by_user_id(user_id, keep_session_id)
+        .await?;
+    invalidate_cached_tokens(state, &session_ids).await;
+    info!(
+        "Revoked {} other sessions of user {}",
+        session_ids.len(),
+        user_id
+    );
+    Ok(session_ids.len())
+}
+
+/// Mails the user a link holding a new single-use token, earlier tokens of the
+/// same purpose stop working
+async fn send_user_token(
+    state: &AppState,
+    user: &UserRow,
+    purpose: UserTokenPurpose,
+) -> Result<(), AppError> {
+    let token = generate_token();
+    let ttl_secs = match purpose {
+        UserTokenPurpose::EmailVerification => state.auth.email_verification_ttl_secs,
+        UserTokenPurpose::PasswordReset => state.auth.password_reset_ttl_secs,
+    };
+    UserTokenRepo::new(state.pool.clone())
+        .create_one(
+            user.id,
+            purpose,
+            &hash_token(&token),
+            OffsetDateTime::now_utc() + Duration::seconds(ttl_secs as i64),
+        )
+        .await?;
+    let app_url = state.auth.app_url.trim_end_matches('/');
+    let hours = (ttl_secs / 3600).max(1);
+    let email = match purpose {
+        UserTokenPurpose::EmailVerification => Email {
+            to: user.email.clone(),
+            subject: "Verify your email address".to_string(),
+            body: format!(
+                "Open this link to verify your email address:\n\n{}/verify-email?token={}\n\nIt works once, within {} hours.",
+                app_url, token, hours
+            ),
+        },
+        UserTokenPurpose::PasswordReset => Email {
+            to: user.email.clone(),
+            subject: "Reset your password".to_string(),
+            body: format!(
+                "Open this link to choose a new password:\n\n{}/reset-password?token={}\n\nIt works once, within {} hours. Ignore this mail if you did not ask for it.",
+                app_url, token, hours
+            ),
+        },
+    };
+    state.clients.mailer.send(email).await
+}
+
+pub async fn send_email_verification(state: &AppState, user: &UserRow) -> Result<(), AppError> {
+    send_user_token(state, user, UserTokenPurpose::EmailVerification).await
+}
+
+/// Mails a new verification link to a pending account. Nothing tells whether
+/// the address has an account.
+pub async fn resend_email_verification(state: &AppState, email: &str) -> Result<(), AppError> {
+    let user = UserRepo::new(state.pool.clone())
+        .get_by_email(email)
+        .await?;
+    if let Some(user) = user
+        && user.status == UserStatus::Pending.to_string()
+    {
+        send_email_verification(state, &user).await?;
+    }
+    Ok(())
+}
+
+/// Activates the account of a verification token and logs it in
+pub async fn verify_email(
+    state: &AppState,
+    token: &str,
+    client: ClientInfo,
+) -> Result<AuthResponse, AppError> {
+    let user_id = UserTokenRepo::new(state.pool.clone())
+        .verify_email(&hash_token(token))
+        .await?
+        .ok_or_else(|| AppError::InvalidInput("Invalid or expired token".to_string()))?;
+    info!("Verified the email address of user {}", user_id);
+    create_session(state, user_id, client).await
+}
+
+/// Mails a password reset link when the address has an account. Nothing tells
+/// whether it has one.
+pub async fn request_password_reset(state: &AppState, email: &str) -> Result<(), AppError> {
+    let user = UserRepo::new(state.pool.clone())
+        .get_by_email(email)
+        .await?;
+    if let Some(user) = user {
+        send_user_token(state, &user, UserTokenPurpose::PasswordReset).await?;
+    }
+    Ok(())
+}
+
+/// Sets the password of the user of a reset token and logs out every session
+pub async fn reset_password(
+    state: &AppState,
+    token: &str,
+    hashed_password: &str,
+) -> Result<(), AppError> {
+    let user_id = UserTokenRepo::new(state.pool.clone())
+        .reset_password(&hash_token(token), hashed_password)
+        .await?
+        .ok_or_else(|| AppError::InvalidInput("Invalid or expired token".to_string()))?;
+    info!("Reset the password of user {}", user_id);
+    revoke_all_sessions(state, user_id).await?;
+    Ok(())
+}
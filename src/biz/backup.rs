@@ -0,0 +1,276 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use rust_decimal::Decimal;
+use sqlx::PgPool;
+use time::{Date, OffsetDateTime, UtcOffset};
+use tracing::{error, info};
+
+use crate::{
+    biz::{
+        fx::ensure_tx_fx_rates, portfolio_asset::update_portfolio_stats,
+        portfolio_snapshot::rebuild_portfolio_snapshots,
+    },
+    db::repositories::{
+        asset::AssetRepo, backup::BackupRepo, portfolio::PortfolioRepo,
+        portfolio_asset::PortfolioAssetRepo, transaction::TransactionRepo,
+    },
+    models::{
+        common::currency::Currency,
+        domain::{import::PlannedTx, transaction::BaseTransactionInfo},
+        dto::backup::{
+            AccountBackup, BACKUP_VERSION, BackupAsset, BackupPortfolio, BackupTransaction,
+            RestoreBackup, RestoreConflict, RestorePortfolio, RestoreResponse, RestoreStatus,
+            RestoredPortfolioResponse,
+        },
+    },
+    state::AppState,
+    utils::{error::AppError, snowflake::SNOWFLAKE_GENERATOR},
+};
+
+/// Every portfolio of the user with its assets and transactions, and the
+/// metadata of every asset they use.
+pub async fn build_account_backup(pool: PgPool, user_id: i64) -> Result<AccountBackup, AppError> {
+    let pfl_rows = PortfolioRepo::new(pool.clone())
+        .get_multi_by_owner_id(user_id)
+        .await?;
+    let mut asset_ids: BTreeSet<String> = BTreeSet::new();
+    let mut portfolios = Vec::with_capacity(pfl_rows.len());
+    for pfl_row in pfl_rows.iter() {
+        let mut portfolio = BackupPortfolio::from_db_row(pfl_row);
+        portfolio.asset_ids = PortfolioAssetRepo::new(pool.clone())
+            .get_multi_by_portfolio_id(pfl_row.id)
+            .await?
+            .into_iter()
+            .map(|pa_row| pa_row.asset_id)
+            .collect();
+        portfolio.transactions = TransactionRepo::new(pool.clone())
+            .get_all_txs_by_portfolio(pfl_row.id)
+            .await?
+            .into_iter()
+            .map(BackupTransaction::from_db_row)
+            .collect();
+        asset_ids.extend(portfolio.asset_ids.iter().cloned());
+        asset_ids.extend(portfolio.transactions.iter().map(|tx| tx.asset_id.clone()));
+        portfolios.push(portfolio);
+    }
+    let asset_ids: Vec<String> = asset_ids.into_iter().collect();
+    let assets = AssetRepo::new(pool)
+        .get_multi_by_ids(&asset_ids)
+        .await?
+        .into_iter()
+        .map(BackupAsset::from_db_row)
+        .collect();
+    Ok(AccountBackup {
+        version: BACKUP_VERSION,
+        created_at: OffsetDateTime::now_utc(),
+        assets,
+        portfolios,
+    })
+}
+
+fn parse_backup_decimal(
+    tx: &BackupTransaction,
+    field: &str,
+    value: &str,
+) -> Result<Decimal, AppError> {
+    value.parse().map_err(|_| {
+        AppError::InvalidInput(format!(
+            "Transaction {} has an invalid {}: {}",
+            tx.id, field, value
+        ))
+    })
+}
+
+/// Gives the backed up transaction a new ID, so it can not collide with a row
+/// of this server.
+fn restore_transaction(tx: BackupTransaction) -> Result<PlannedTx, AppError> {
+    let quantity = parse_backup_decimal(&tx, "quantity", &tx.quantity)?;
+    let price = parse_backup_decimal(&tx, "price", &tx.price)?;
+    let fees = parse_backup_decimal(&tx, "fees", &tx.fees)?;
+    Ok(PlannedTx {
+        asset_id: tx.asset_id,
+        tx: BaseTransactionInfo {
+            id: Some(SNOWFLAKE_GENERATOR.generate().unwrap()),
+            external_id: tx.external_id,
+            tx_type: tx.tx_type,
+            quantity,
+            price,
+            fees,
+            currency: tx.currency,
+            executed_at: tx.executed_at,
+            notes: tx.notes,
+        },
+    })
+}
+
+/// First name not used by another portfolio of the account
+fn restored_name(name: &str, taken: &HashSet<String>) -> String {
+    let mut candidate = format!("{} (restored)", name);
+    let mut n = 2;
+    while taken.contains(&candidate) {
+        candidate = format!("{} (restored {})", name, n);
+        n += 1;
+    }
+    candidate
+}
+
+/// Restores a backup into the account under new IDs. A backed up portfolio
+/// conflicts with a portfolio of the account that has its ID, when restoring
+/// on the server it comes from, or its name. The FX rates the transactions
+/// need are fetched before anything is written, then stats, tax lots and
+/// snapshots of the restored portfolios are recomputed.
+pub async fn restore_account_backup(
+    state: &AppState,
+    user_id: i64,
+    backup: AccountBackup,
+    on_conflict: RestoreConflict,
+) -> Result<RestoreResponse, AppError> {
+    if backup.version > BACKUP_VERSION {
+        return Err(AppError::InvalidInput(format!(
+            "Backup version {} is not supported, the latest is {}",
+            backup.version, BACKUP_VERSION
+        )));
+    }
+    let pool = state.pool.clone();
+    let existing = PortfolioRepo::new(pool.clone())
+        .get_multi_by_owner_id(user_id)
+        .await?;
+    let mut taken_names: HashSet<String> = existing.iter().map(|row| row.name.clone()).collect();
+
+    let mut responses = Vec::with_capacity(backup.portfolios.len());
+    let mut replaced_portfolio_ids = Vec::new();
+    let mut portfolios = Vec::new();
+    let mut used_assets: BTreeSet<String> = BTreeSet::new();
+    for backed_up in backup.portfolios {
+        let conflict = existing
+            .iter()
+            .find(|row| backed_up.id.parse() == Ok(row.id) || row.name == backed_up.name);
+        let (name, status) = match (conflict, on_conflict) {
+            (None, _) => (backed_up.name.clone(), RestoreStatus::Restored),
+            (Some(_), RestoreConflict::Skip) => {
+                responses.push(RestoredPortfolioResponse {
+                    backup_id: backed_up.id,
+                    id: None,
+                    name: backed_up.name,
+                    status: RestoreStatus::Skipped,
+                    transactions: 0,
+                });
+                continue;
+            }
+            (Some(_), RestoreConflict::Rename) => (
+                restored_name(&backed_up.name, &taken_names),
+                RestoreStatus::Renamed,
+            ),
+            (Some(row), RestoreConflict::Replace) => {
+                if replaced_portfolio_ids.contains(&row.id) {
+                    return Err(AppError::InvalidInput(format!(
+                        "Several portfolios of the backup would replace portfolio {}",
+                        row.name
+                    )));
+                }
+                replaced_portfolio_ids.push(row.id);
+                (backed_up.name.clone(), RestoreStatus::Replaced)
+            }
+        };
+        taken_names.insert(name.clone());
+
+        let transactions = backed_up
+            .transactions
+            .into_iter()
+            .map(restore_transaction)
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut asset_ids: BTreeSet<String> = backed_up.asset_ids.into_iter().collect();
+        asset_ids.extend(transactions.iter().map(|tx| tx.asset_id.clone()));
+        used_assets.extend(asset_ids.iter().cloned());
+        let pfl_id = SNOWFLAKE_GENERATOR.generate().unwrap();
+        responses.push(RestoredPortfolioResponse {
+            backup_id: backed_up.id,
+            id: Some(pfl_id.to_string()),
+            name: name.clone(),
+            status,
+            transactions: transactions.len(),
+        });
+        portfolios.push(RestorePortfolio {
+            id: pfl_id,
+            name,
+            cost_basis_method: backed_up.cost_basis_method,
+            base_currency: backed_up.base_currency,
+            created_at: backed_up.created_at,
+            asset_ids: asset_ids.into_iter().collect(),
+            transactions,
+        });
+    }
+
+    // Assets neither in the backup nor on this server can not be restored
+    let backup_assets: HashMap<String, BackupAsset> = backup
+        .assets
+        .into_iter()
+        .filter(|asset| used_assets.contains(&asset.id))
+        .map(|asset| (asset.id.clone(), asset))
+        .collect();
+    let missing: Vec<String> = used_assets
+        .iter()
+        .filter(|asset_id| !backup_assets.contains_key(*asset_id))
+        .cloned()
+        .collect();
+    if !missing.is_empty() {
+        let known: HashSet<String> = AssetRepo::new(pool.clone())
+            .get_multi_by_ids(&missing)
+            .await?
+            .into_iter()
+            .map(|row| row.id)
+            .collect();
+        if let Some(asset_id) = missing.iter().find(|asset_id| !known.contains(*asset_id)) {
+            return Err(AppError::InvalidInput(format!(
+                "Asset {} is neither in the backup nor on this server",
+                asset_id
+            )));
+        }
+    }
+
+    // Stats are recomputed once the restore is committed, they need every rate
+    for portfolio in portfolios.iter() {
+        let fx_days: Vec<(Currency, Date)> = portfolio
+            .transactions
+            .iter()
+            .map(|planned| {
+                (
+                    planned.tx.currency,
+                    planned.tx.executed_at.to_offset(UtcOffset::UTC).date(),
+                )
+            })
+            .collect();
+        ensure_tx_fx_rates(state, portfolio.base_currency, &fx_days).await?;
+    }
+
+    let restored_ids: Vec<i64> = portfolios.iter().map(|p| p.id).collect();
+    let new_assets = BackupRepo::new(pool.clone())
+        .restore(RestoreBackup {
+            owner_id: user_id,
+            replaced_portfolio_ids,
+            assets: backup_assets.into_values().collect(),
+            portfolios,
+        })
+        .await?;
+    info!(
+        "Restored {} portfolios of user {}, created {} assets",
+        restored_ids.len(),
+        user_id,
+        new_assets.len()
+    );
+
+    let pfl_repo = PortfolioRepo::new(pool.clone());
+    for pfl_id in restored_ids {
+        update_portfolio_stats(pool.clone(), pfl_id).await?;
+        // Snapshots are written by the daily job as well, a failure here is not fatal
+        if let Some(pfl_row) = pfl_repo.get_one_by_id(pfl_id).await?
+            && let Err(e) = rebuild_portfolio_snapshots(pool.clone(), &pfl_row).await
+        {
+            error!("Rebuild snapshots of portfolio {} failed: {:?}", pfl_id, e);
+        }
+    }
+    Ok(RestoreResponse {
+        portfolios: responses,
+        new_assets,
+    })
+}
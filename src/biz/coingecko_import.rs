@@ -0,0 +1,105 @@
+use std::collections::BTreeSet;
+
+use tracing::info;
+
+use crate::{
+    biz::{
+        asset::{generate_asset_id, plan_coin_assets},
+        import_plan::build_import_plan,
+        import_plan::file_hash,
+    },
+    models::{
+        common::asset::AssetType,
+        domain::{
+            coingecko::RawTransaction,
+            import::{ImportPlan, ImportSource, PlannedTx},
+            transaction::BaseTransactionInfo,
+        },
+    },
+    state::AppState,
+    utils::{
+        coingecko::{parse_html_contents, parse_json_export},
+        error::AppError,
+    },
+};
+
+/// Coin IDs and transactions of a CoinGecko file. JSON exports that do not
+/// name their coin take `coin_id`.
+fn read_coingecko_file(
+    source: ImportSource,
+    contents: &str,
+    coin_id: Option<&str>,
+) -> Result<Vec<(String, Vec<RawTransaction>)>, AppError> {
+    match source {
+        ImportSource::Coingecko => Ok(vec![parse_html_contents(contents)?]),
+        ImportSource::CoingeckoJson => parse_json_export(contents)?
+            .into_iter()
+            .map(|(file_coin_id, raw_txs)| {
+                let coin_id = file_coin_id
+                    .or(coin_id.map(|id| id.to_string()))
+                    .ok_or_else(|| {
+                        AppError::InvalidInput(
+                            "coin_id is required when the export does not name its coin"
+                                .to_string(),
+                        )
+                    })?;
+                Ok((coin_id, raw_txs))
+            })
+            .collect(),
+        source => Err(AppError::InvalidInput(format!(
+            "{} files are not CoinGecko files",
+            source
+        ))),
+    }
+}
+
+/// Plans the import of CoinGecko transactions, from saved portfolio pages or
+/// JSON exports, of any number of coins. Coins missing from the catalog or the
+/// portfolio are added with the import, transactions imported before are
+/// matched by their CoinGecko ID.
+pub async fn plan_coingecko_import(
+    state: &AppState,
+    user_id: i64,
+    portfolio_id: i64,
+    source: ImportSource,
+    files: &[String],
+    coin_id: Option<&str>,
+) -> Result<ImportPlan, AppError> {
+    let mut coins = Vec::new();
+    for contents in files {
+        coins.extend(read_coingecko_file(source, contents, coin_id)?);
+    }
+    let coin_ids: BTreeSet<String> = coins.iter().map(|(coin_id, _)| coin_id.clone()).collect();
+    let (new_assets, unknown) = plan_coin_assets(state, &coin_ids).await?;
+    if let Some(reason) = unknown.into_values().next() {
+        return Err(AppError::InvalidInput(reason));
+    }
+
+    let mut planned_txs = Vec::new();
+    for (coin_id, raw_txs) in coins {
+        info!("Coin ID: {}, raw transactions: {}", coin_id, raw_txs.len());
+        let asset_id = generate_asset_id(&AssetType::Crypto, &coin_id);
+        for raw_tx in raw_txs.into_iter() {
+            let base_tx_info = BaseTransactionInfo::from_raw_tx(raw_tx)?;
+            if base_tx_info.external_id.is_none() {
+                return Err(AppError::InvalidInput(
+                    "Transaction from Coingecko does not have id".to_string(),
+                ));
+            }
+            planned_txs.push(PlannedTx {
+                asset_id: asset_id.clone(),
+                tx: base_tx_info,
+            });
+        }
+    }
+    build_import_plan(
+        state.pool.clone(),
+        user_id,
+        portfolio_id,
+        source,
+        file_hash(&files.concat()),
+        new_assets,
+        planned_txs,
+    )
+    .await
+}
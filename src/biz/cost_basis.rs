@@ -0,0 +1,317 @@
+use rust_decimal::Decimal;
+
+use crate::{
+    models::{
+        database::transaction::TransactionRow,
+        domain::{
+            portfolio::CostBasisMethod,
+            tax_lot::{Disposal, LotMatch, TaxLot},
+            transaction::TxType,
+        },
+    },
+    utils::error::AppError,
+};
+
+#[derive(Debug, Default)]
+pub struct LotMatchingResult {
+    pub open_lots: Vec<TaxLot>,
+    pub disposals: Vec<Disposal>,
+}
+
+impl LotMatchingResult {
+    pub fn holding_cost_basis(&self) -> Decimal {
+        self.open_lots
+            .iter()
+            .map(|lot| lot.remaining_quantity * lot.unit_cost)
+            .sum()
+    }
+
+    pub fn realized_pnl(&self) -> Decimal {
+        self.disposals
+            .iter()
+            .filter_map(|disposal| disposal.realized_gain())
+            .sum()
+    }
+}
+
+/// Replays the transactions of one portfolio asset in execution order and matches
+/// every SELL and TRANSFER_OUT against the open lots using the given method.
+pub fn match_lots(
+    method: CostBasisMethod,
+    tx_rows: &[TransactionRow],
+) -> Result<LotMatchingResult, AppError> {
+    let mut ordered: Vec<&TransactionRow> = tx_rows.iter().collect();
+    ordered.sort_by_key(|row| (row.executed_at, row.id));
+
+    let mut result = LotMatchingResult::default();
+    for row in ordered {
+        let tx_type: TxType = row.tx_type.parse()?;
+        match tx_type {
+            TxType::Buy | TxType::TransferIn => {
+                if row.quantity <= Decimal::ZERO {
+                    continue;
+                }
+                let total_cost = row.price * row.quantity + row.fees;
+                result.open_lots.push(TaxLot {
+                    tx_id: row.id,
+                    acquired_at: row.executed_at,
+                    quantity: row.quantity,
+                    remaining_quantity: row.quantity,
+                    unit_cost: total_cost / row.quantity,
+                });
+            }
+            TxType::Sell | TxType::TransferOut => {
+                let proceeds = match tx_type {
+                    TxType::Sell => Some(row.price * row.quantity - row.fees),
+                    _ => None,
+                };
+                let disposal = dispose(method, &mut result.open_lots, row, tx_type, proceeds);
+                result.disposals.push(disposal);
+            }
+        }
+    }
+    Ok(result)
+}
+
+fn dispose(
+    method: CostBasisMethod,
+    open_lots: &mut Vec<TaxLot>,
+    row: &TransactionRow,
+    tx_type: TxType,
+    proceeds: Option<Decimal>,
+) -> Disposal {
+    if method == CostBasisMethod::Average {
+        // Every unit in the pool carries the same cost under average cost
+        let holding: Decimal = open_lots.iter().map(|lot| lot.remaining_quantity).sum();
+        let cost: Decimal = open_lots
+            .iter()
+            .map(|lot| lot.remaining_quantity * lot.unit_cost)
+            .sum();
+        if let Some(avg_cost) = cost.checked_div(holding) {
+            for lot in open_lots.iter_mut() {
+                lot.unit_cost = avg_cost;
+            }
+        }
+    }
+
+    let mut order: Vec<usize> = (0..open_lots.len()).collect();
+    match method {
+        CostBasisMethod::Fifo | CostBasisMethod::Average => {}
+        CostBasisMethod::Lifo => order.reverse(),
+        CostBasisMethod::Hifo => {
+            order.sort_by(|a, b| open_lots[*b].unit_cost.cmp(&open_lots[*a].unit_cost))
+        }
+    }
+
+    let mut remaining = row.quantity;
+    let mut cost_basis = Decimal::ZERO;
+    let mut matches = Vec::new();
+    for idx in order {
+        if remaining <= Decimal::ZERO {
+            break;
+        }
+        let lot = &mut open_lots[idx];
+        let quantity = remaining.min(lot.remaining_quantity);
+        let lot_cost = quantity * lot.unit_cost;
+        lot.remaining_quantity -= quantity;
+        remaining -= quantity;
+        cost_basis += lot_cost;
+        matches.push(LotMatch {
+            lot_tx_id: lot.tx_id,
+            acquired_at: lot.acquired_at,
+            quantity,
+            cost_basis: lot_cost,
+        });
+    }
+    open_lots.retain(|lot| lot.remaining_quantity > Decimal::ZERO);
+
+    Disposal {
+        tx_id: row.id,
+        tx_type,
+        disposed_at: row.executed_at,
+        quantity: row.quantity,
+        proceeds,
+        cost_basis,
+        unmatched_quantity: remaining.max(Decimal::ZERO),
+        matches,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use time::{Date, Duration, Month};
+
+    use super::*;
+
+    fn tx(
+        id: i64,
+        day: i64,
+        tx_type: TxType,
+        quantity: i64,
+        price: i64,
+        fees: i64,
+    ) -> TransactionRow {
+        let executed_at = Date::from_calendar_date(2024, Month::January, 1)
+            .unwrap()
+            .midnight()
+            .assume_utc()
+            + Duration::days(day);
+        TransactionRow {
+            id,
+            external_id: None,
+            portfolio_id: 1,
+            asset_id: "bitcoin".to_string(),
+            tx_type: tx_type.to_string(),
+            quantity: Decimal::from(quantity),
+            price: Decimal::from(price),
+            fees: Decimal::from(fees),
+            currency: "USD".to_string(),
+            executed_at,
+            notes: None,
+            realized_gain: None,
+            created_at: executed_at,
+            updated_at: executed_at,
+            import_batch_id: None,
+        }
+    }
+
+    /// Three lots of 2 @ 10, 1 @ 30 and 2 @ 20 followed by a sale
+    fn sale_after_three_lots(sell_quantity: i64) -> Vec<TransactionRow> {
+        vec![
+            tx(1, 0, TxType::Buy, 2, 10, 0),
+            tx(2, 1, TxType::Buy, 1, 30, 0),
+            tx(3, 2, TxType::Buy, 2, 20, 0),
+            tx(4, 3, TxType::Sell, sell_quantity, 40, 0),
+        ]
+    }
+
+    fn open_lots(result: &LotMatchingResult) -> Vec<(i64, Decimal)> {
+        result
+            .open_lots
+            .iter()
+            .map(|lot| (lot.tx_id, lot.remaining_quantity))
+            .collect()
+    }
+
+    fn matched_lots(disposal: &Disposal) -> Vec<(i64, Decimal)> {
+        disposal
+            .matches
+            .iter()
+            .map(|m| (m.lot_tx_id, m.quantity))
+            .collect()
+    }
+
+    #[test]
+    fn fifo_sells_oldest_lots_first() {
+        let result = match_lots(CostBasisMethod::Fifo, &sale_after_three_lots(3)).unwrap();
+        let disposal = &result.disposals[0];
+        assert_eq!(
+            matched_lots(disposal),
+            vec![(1, Decimal::TWO), (2, Decimal::ONE)]
+        );
+        assert_eq!(disposal.cost_basis, Decimal::from(50));
+        assert_eq!(disposal.realized_gain(), Some(Decimal::from(70)));
+        assert_eq!(open_lots(&result), vec![(3, Decimal::TWO)]);
+        assert_eq!(result.holding_cost_basis(), Decimal::from(40));
+    }
+
+    #[test]
+    fn lifo_sells_newest_lots_first() {
+        let result = match_lots(CostBasisMethod::Lifo, &sale_after_three_lots(2)).unwrap();
+        let disposal = &result.disposals[0];
+        assert_eq!(matched_lots(disposal), vec![(3, Decimal::TWO)]);
+        assert_eq!(disposal.cost_basis, Decimal::from(40));
+        assert_eq!(
+            open_lots(&result),
+            vec![(1, Decimal::TWO), (2, Decimal::ONE)]
+        );
+    }
+
+    #[test]
+    fn hifo_sells_costliest_lots_first_and_keeps_partial_lot() {
+        let result = match_lots(CostBasisMethod::Hifo, &sale_after_three_lots(2)).unwrap();
+        let disposal = &result.disposals[0];
+        assert_eq!(
+            matched_lots(disposal),
+            vec![(2, Decimal::ONE), (3, Decimal::ONE)]
+        );
+        assert_eq!(disposal.cost_basis, Decimal::from(50));
+        assert_eq!(
+            open_lots(&result),
+            vec![(1, Decimal::TWO), (3, Decimal::ONE)]
+        );
+        assert_eq!(result.holding_cost_basis(), Decimal::from(40));
+    }
+
+    #[test]
+    fn average_cost_spreads_cost_over_all_units() {
+        let result = match_lots(CostBasisMethod::Average, &sale_after_three_lots(2)).unwrap();
+        // 90 over 5 units is 18 a unit
+        let disposal = &result.disposals[0];
+        assert_eq!(disposal.cost_basis, Decimal::from(36));
+        assert_eq!(disposal.realized_gain(), Some(Decimal::from(44)));
+        assert!(
+            result
+                .open_lots
+                .iter()
+                .all(|lot| lot.unit_cost == Decimal::from(18))
+        );
+        assert_eq!(result.holding_cost_basis(), Decimal::from(54));
+    }
+
+    #[test]
+    fn fifo_consumes_part_of_a_lot() {
+        let result = match_lots(CostBasisMethod::Fifo, &sale_after_three_lots(1)).unwrap();
+        assert_eq!(matched_lots(&result.disposals[0]), vec![(1, Decimal::ONE)]);
+        assert_eq!(
+            open_lots(&result),
+            vec![(1, Decimal::ONE), (2, Decimal::ONE), (3, Decimal::TWO)]
+        );
+        assert_eq!(result.open_lots[0].quantity, Decimal::TWO);
+    }
+
+    #[test]
+    fn selling_more_than_held_leaves_quantity_unmatched() {
+        for method in [
+            CostBasisMethod::Fifo,
+            CostBasisMethod::Lifo,
+            CostBasisMethod::Hifo,
+            CostBasisMethod::Average,
+        ] {
+            let result = match_lots(method, &sale_after_three_lots(6)).unwrap();
+            let disposal = &result.disposals[0];
+            assert_eq!(disposal.unmatched_quantity, Decimal::ONE, "{method:?}");
+            assert_eq!(disposal.cost_basis, Decimal::from(90), "{method:?}");
+            assert_eq!(
+                disposal.realized_gain(),
+                Some(Decimal::from(150)),
+                "{method:?}"
+            );
+            assert!(result.open_lots.is_empty(), "{method:?}");
+        }
+    }
+
+    #[test]
+    fn fees_raise_cost_and_lower_proceeds() {
+        let txs = vec![
+            tx(1, 0, TxType::Buy, 2, 10, 2),
+            tx(2, 1, TxType::Sell, 1, 40, 4),
+            tx(3, 2, TxType::TransferOut, 1, 0, 0),
+        ];
+        let result = match_lots(CostBasisMethod::Fifo, &txs).unwrap();
+        // The lot costs 22 for 2 units
+        assert_eq!(result.disposals[0].cost_basis, Decimal::from(11));
+        assert_eq!(result.disposals[0].proceeds, Some(Decimal::from(36)));
+        assert_eq!(result.disposals[1].realized_gain(), None);
+        assert_eq!(result.realized_pnl(), Decimal::from(25));
+        assert!(result.open_lots.is_empty());
+    }
+
+    #[test]
+    fn replays_transactions_in_execution_order() {
+        let mut txs = sale_after_three_lots(2);
+        txs.reverse();
+        let result = match_lots(CostBasisMethod::Fifo, &txs).unwrap();
+        assert_eq!(matched_lots(&result.disposals[0]), vec![(1, Decimal::TWO)]);
+    }
+}
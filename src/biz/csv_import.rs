@@ -0,0 +1,335 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use rust_decimal::Decimal;
+use sqlx::PgPool;
+use time::{
+    Date, OffsetDateTime, PrimitiveDateTime,
+    format_description::{self, BorrowedFormatItem, well_known::Rfc3339},
+};
+
+use crate::{
+    biz::{
+        asset::{crypto_coin_id, generate_asset_id, plan_coin_assets},
+        exchange_import::known_coin_id,
+    },
+    db::repositories::{asset::AssetRepo, portfolio_asset::PortfolioAssetRepo},
+    models::{
+        common::asset::AssetType,
+        database::portfolio::PortfolioRow,
+        domain::{
+            import::{ImportRow, PlannedTx},
+            transaction::TxType,
+        },
+        dto::{asset::CreateAssetRepo, import::CsvColumnMapping},
+    },
+    state::AppState,
+    utils::{csv::parse_csv, error::AppError},
+};
+
+/// Positions of the mapped columns in a CSV record
+struct CsvColumns {
+    date: usize,
+    tx_type: usize,
+    quantity: usize,
+    price: usize,
+    fee: Option<usize>,
+    currency: Option<usize>,
+    asset: usize,
+    notes: Option<usize>,
+    external_id: Option<usize>,
+}
+
+impl CsvColumns {
+    fn from_header(header: &[String], mapping: &CsvColumnMapping) -> Result<Self, AppError> {
+        let find = |name: &str| {
+            header
+                .iter()
+                .position(|h| h.trim().eq_ignore_ascii_case(name.trim()))
+                .ok_or_else(|| {
+                    AppError::InvalidInput(format!("Column {} not found in the CSV header", name))
+                })
+        };
+        let find_optional = |name: &Option<String>| name.as_deref().map(find).transpose();
+        Ok(Self {
+            date: find(&mapping.date)?,
+            tx_type: find(&mapping.tx_type)?,
+            quantity: find(&mapping.quantity)?,
+            price: find(&mapping.price)?,
+            fee: find_optional(&mapping.fee)?,
+            currency: find_optional(&mapping.currency)?,
+            asset: find(&mapping.asset)?,
+            notes: find_optional(&mapping.notes)?,
+            external_id: find_optional(&mapping.external_id)?,
+        })
+    }
+}
+
+/// Reads a number written with the given decimal separator, the other one of
+/// `.` and `,` being taken as a thousands separator.
+pub fn parse_decimal(value: &str, decimal_separator: char) -> Option<Decimal> {
+    let thousands_separator = if decimal_separator == ',' { '.' } else { ',' };
+    let value: String = value
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != thousands_separator)
+        .map(|c| if c == decimal_separator { '.' } else { c })
+        .collect();
+    value
+        .parse()
+        .ok()
+        .or_else(|| Decimal::from_scientific(&value).ok())
+}
+
+fn parse_datetime(value: &str, format: Option<&[BorrowedFormatItem]>) -> Option<OffsetDateTime> {
+    let Some(format) = format else {
+        return OffsetDateTime::parse(value, &Rfc3339).ok();
+    };
+    OffsetDateTime::parse(value, format)
+        .or_else(|_| PrimitiveDateTime::parse(value, format).map(|dt| dt.assume_utc()))
+        .or_else(|_| Date::parse(value, format).map(|d| d.midnight().assume_utc()))
+        .ok()
+}
+
+/// Maps what a row may use to name an asset of the portfolio, its ID, coin ID or
+/// symbol, to the asset IDs it may stand for.
+async fn portfolio_asset_lookup(
+    pool: PgPool,
+    portfolio_id: i64,
+) -> Result<HashMap<String, Vec<String>>, AppError> {
+    let asset_ids: Vec<String> = PortfolioAssetRepo::new(pool.clone())
+        .get_multi_by_portfolio_id(portfolio_id)
+        .await?
+        .into_iter()
+        .map(|pa_row| pa_row.asset_id)
+        .collect();
+    let mut lookup: HashMap<String, Vec<String>> = HashMap::new();
+    for asset_row in AssetRepo::new(pool).get_multi_by_ids(&asset_ids).await? {
+        for key in [&asset_row.id, &asset_row.external_id, &asset_row.symbol] {
+            let ids = lookup.entry(key.to_lowercase()).or_default();
+            if !ids.contains(&asset_row.id) {
+                ids.push(asset_row.id.clone());
+            }
+        }
+    }
+    Ok(lookup)
+}
+
+/// CoinGecko coin ID of an asset the portfolio does not hold: the coin of a
+/// crypto asset ID, a mapped or well known symbol, or else the value itself
+fn outside_coin_id(asset: &str, symbols: &HashMap<String, String>) -> String {
+    if let Some(coin_id) = crypto_coin_id(asset) {
+        return coin_id;
+    }
+    symbols
+        .iter()
+        .find(|(symbol, _)| symbol.eq_ignore_ascii_case(asset))
+        .map(|(_, coin_id)| coin_id.clone())
+        .or_else(|| known_coin_id(asset).map(str::to_string))
+        .unwrap_or_else(|| asset.to_lowercase())
+}
+
+/// Gives the rows of assets outside the portfolio the asset of their coin, or
+/// the reason their coin is unknown
+fn resolve_outside_assets(
+    rows: &mut [ImportRow],
+    outside_assets: Vec<(usize, String)>,
+    unknown: &BTreeMap<String, String>,
+) {
+    for (idx, coin_id) in outside_assets {
+        match unknown.get(&coin_id) {
+            Some(reason) => rows[idx].errors.push(format!(
+                "{}, assets not in the portfolio are named by their CoinGecko coin ID or a mapped symbol",
+                reason
+            )),
+            None => rows[idx].asset_id = Some(generate_asset_id(&AssetType::Crypto, &coin_id)),
+        }
+    }
+}
+
+/// Parses and validates every row of a CSV file against the portfolio without
+/// writing anything. Rows that can not be imported carry their errors. Assets
+/// the import would add to the catalog are returned with the rows.
+pub async fn parse_csv_import(
+    state: &AppState,
+    pfl_row: &PortfolioRow,
+    contents: &str,
+    mapping: &CsvColumnMapping,
+) -> Result<(Vec<ImportRow>, Vec<CreateAssetRepo>), AppError> {
+    let records = parse_csv(contents, mapping.delimiter)?;
+    let Some((header, records)) = records.split_first() else {
+        return Err(AppError::InvalidInput("CSV file is empty".to_string()));
+    };
+    let columns = CsvColumns::from_header(header, mapping)?;
+    let date_format = match mapping.date_format {
+        Some(ref date_format) => Some(
+            format_description::parse_borrowed::<2>(date_format).map_err(|e| {
+                AppError::InvalidInput(format!("Invalid date format {}: {}", date_format, e))
+            })?,
+        ),
+        None => None,
+    };
+    let default_currency = match mapping.default_currency {
+        Some(currency) => currency,
+        None => pfl_row.base_currency.parse()?,
+    };
+    let asset_lookup = portfolio_asset_lookup(state.pool.clone(), pfl_row.id).await?;
+
+    let mut rows = Vec::new();
+    let mut outside_assets: Vec<(usize, String)> = Vec::new();
+    for (idx, record) in records.iter().enumerate() {
+        let field = |col: usize| record.get(col).map(|v| v.trim()).unwrap_or_default();
+        let optional_field = |col: Option<usize>| col.map(field).filter(|v| !v.is_empty());
+        let mut row = ImportRow {
+            line: idx + 2,
+            notes: optional_field(columns.notes).map(str::to_string),
+            external_id: optional_field(columns.external_id).map(str::to_string),
+            ..Default::default()
+        };
+
+        let date = field(columns.date);
+        row.executed_at = parse_datetime(date, date_format.as_deref());
+        if row.executed_at.is_none() {
+            row.errors.push(format!("Invalid date: {}", date));
+        }
+
+        let tx_type = field(columns.tx_type);
+        row.tx_type = TxType::from_import_label(tx_type);
+        if row.tx_type.is_none() {
+            row.errors
+                .push(format!("Invalid transaction type: {}", tx_type));
+        }
+
+        let quantity = field(columns.quantity);
+        row.quantity = parse_decimal(quantity, mapping.decimal_separator);
+        match row.quantity {
+            Some(q) if q > Decimal::ZERO => (),
+            Some(_) => row.errors.push("Quantity must be positive".to_string()),
+            None => row.errors.push(format!("Invalid quantity: {}", quantity)),
+        }
+
+        let price = field(columns.price);
+        row.price = parse_decimal(price, mapping.decimal_separator);
+        match row.price {
+            Some(p) if p >= Decimal::ZERO => (),
+            Some(_) => row.errors.push("Price must not be negative".to_string()),
+            None => row.errors.push(format!("Invalid price: {}", price)),
+        }
+
+        let fee = optional_field(columns.fee).unwrap_or("0");
+        row.fees = parse_decimal(fee, mapping.decimal_separator);
+        match row.fees {
+            Some(f) if f >= Decimal::ZERO => (),
+            Some(_) => row.errors.push("Fee must not be negative".to_string()),
+            None => row.errors.push(format!("Invalid fee: {}", fee)),
+        }
+
+        row.currency = match optional_field(columns.currency) {
+            Some(currency) => {
+                let parsed = currency.to_uppercase().parse().ok();
+                if parsed.is_none() {
+                    row.errors
+                        .push(format!("Unsupported currency: {}", currency));
+                }
+                parsed
+            }
+            None => Some(default_currency),
+        };
+
+        let asset = field(columns.asset);
+        match asset_lookup.get(&asset.to_lowercase()).map(Vec::as_slice) {
+            Some([asset_id]) => row.asset_id = Some(asset_id.clone()),
+            Some([_, _, ..]) => row.errors.push(format!(
+                "{} matches several assets, use the asset ID",
+                asset
+            )),
+            _ if asset.is_empty() => row.errors.push("Asset is missing".to_string()),
+            _ => outside_assets.push((rows.len(), outside_coin_id(asset, &mapping.symbols))),
+        }
+        rows.push(row);
+    }
+
+    // Assets outside the portfolio are read as CoinGecko coins, added with the import
+    let coin_ids: BTreeSet<String> = outside_assets
+        .iter()
+        .map(|(_, coin_id)| coin_id.clone())
+        .collect();
+    let (new_assets, unknown) = plan_coin_assets(state, &coin_ids).await?;
+    resolve_outside_assets(&mut rows, outside_assets, &unknown);
+
+    mark_duplicates(&mut rows);
+    Ok((rows, new_assets))
+}
+
+/// Flags rows whose external ID repeats in the file, rows imported before are
+/// matched when planning the import.
+pub fn mark_duplicates(rows: &mut [ImportRow]) {
+    let mut seen: HashSet<String> = HashSet::new();
+    for row in rows.iter_mut() {
+        let Some(external_id) = row.external_id.clone() else {
+            continue;
+        };
+        if !seen.insert(external_id.clone()) {
+            row.errors
+                .push(format!("Duplicate external ID {} in the file", external_id));
+        }
+    }
+}
+
+/// Transactions of the valid rows, ready to be planned
+pub fn planned_txs(rows: &[ImportRow]) -> Vec<PlannedTx> {
+    rows.iter()
+        .filter_map(|row| {
+            Some(PlannedTx {
+                asset_id: row.asset_id.clone()?,
+                tx: row.to_tx_info()?,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_assets_outside_the_portfolio() {
+        let symbols = HashMap::from([("QNT".to_string(), "quant-network".to_string())]);
+        assert_eq!(outside_coin_id("BTC", &symbols), "bitcoin");
+        assert_eq!(outside_coin_id("eth", &symbols), "ethereum");
+        assert_eq!(outside_coin_id("qnt", &symbols), "quant-network");
+        assert_eq!(
+            outside_coin_id(&generate_asset_id(&AssetType::Crypto, "dogecoin"), &symbols),
+            "dogecoin"
+        );
+        assert_eq!(outside_coin_id("Pepe", &symbols), "pepe");
+    }
+
+    #[test]
+    fn resolves_known_and_unknown_assets_of_a_file() {
+        let symbols = HashMap::new();
+        let mut rows: Vec<ImportRow> = (0..3)
+            .map(|idx| ImportRow {
+                line: idx + 2,
+                ..Default::default()
+            })
+            .collect();
+        let outside_assets = ["BTC", "NOTACOIN", "eth"]
+            .iter()
+            .enumerate()
+            .map(|(idx, asset)| (idx, outside_coin_id(asset, &symbols)))
+            .collect();
+        let unknown =
+            BTreeMap::from([("notacoin".to_string(), "Unknown coin notacoin".to_string())]);
+        resolve_outside_assets(&mut rows, outside_assets, &unknown);
+        assert_eq!(
+            rows[0].asset_id,
+            Some(generate_asset_id(&AssetType::Crypto, "bitcoin"))
+        );
+        assert!(rows[0].errors.is_empty());
+        assert_eq!(rows[1].asset_id, None);
+        assert!(rows[1].errors[0].starts_with("Unknown coin notacoin"));
+        assert_eq!(
+            rows[2].asset_id,
+            Some(generate_asset_id(&AssetType::Crypto, "ethereum"))
+        );
+    }
+}
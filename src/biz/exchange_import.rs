@@ -0,0 +1,1033 @@
+use std::collections::{BTreeSet, HashMap};
+
+use rust_decimal::Decimal;
+use sha2::{Digest, Sha256};
+use time::{Date, OffsetDateTime, format_description::well_known::Rfc3339};
+
+use crate::{
+    biz::{
+        asset::{crypto_coin_id, generate_asset_id, plan_coin_assets},
+        csv_import::{mark_duplicates, parse_decimal},
+    },
+    clients::price_provider::PriceProvider,
+    db::repositories::{asset::AssetRepo, portfolio_asset::PortfolioAssetRepo},
+    models::{
+        common::{asset::AssetType, currency::Currency},
+        database::portfolio::PortfolioRow,
+        domain::{
+            import::{Exchange, ImportRow},
+            transaction::{BaseTransactionInfo, TxType},
+        },
+        dto::{asset::CreateAssetRepo, import::ExchangeImportOptions},
+    },
+    state::AppState,
+    utils::{csv::parse_csv, error::AppError},
+};
+
+// Quote currencies of pairs written without a separator, such as BTCUSDT
+const QUOTE_SYMBOLS: &[&str] = &[
+    "USDT", "USDC", "FDUSD", "BUSD", "TUSD", "DAI", "USD", "EUR", "GBP", "TRY", "BRL", "AUD",
+    "CAD", "CHF", "JPY", "BTC", "XBT", "ETH", "BNB",
+];
+
+// CoinGecko coin IDs of common symbols, used when the portfolio holds no asset
+// with the symbol
+const KNOWN_COINS: &[(&str, &str)] = &[
+    ("BTC", "bitcoin"),
+    ("ETH", "ethereum"),
+    ("USDT", "tether"),
+    ("USDC", "usd-coin"),
+    ("BNB", "binancecoin"),
+    ("SOL", "solana"),
+    ("XRP", "ripple"),
+    ("ADA", "cardano"),
+    ("DOGE", "dogecoin"),
+    ("DOT", "polkadot"),
+    ("TRX", "tron"),
+    ("LTC", "litecoin"),
+    ("LINK", "chainlink"),
+    ("AVAX", "avalanche-2"),
+    ("MATIC", "matic-network"),
+    ("ATOM", "cosmos"),
+    ("XLM", "stellar"),
+    ("DAI", "dai"),
+    ("BUSD", "binance-usd"),
+    ("FDUSD", "first-digital-usd"),
+];
+
+/// A fill read from an exchange export, before it is split into transactions
+struct ExchangeTrade {
+    trade_id: String,
+    executed_at: OffsetDateTime,
+    base: String,
+    quote: String,
+    /// Buy or sell of the base asset
+    side: TxType,
+    quantity: Decimal,
+    /// Quote units per base unit
+    price: Decimal,
+    /// Quote units exchanged, fees excluded
+    total: Decimal,
+    fee: Decimal,
+    fee_asset: String,
+}
+
+/// Columns of an export found by header name, ignoring case
+struct Columns(HashMap<String, usize>);
+
+impl Columns {
+    fn new(header: &[String]) -> Self {
+        Self(
+            header
+                .iter()
+                .enumerate()
+                .map(|(idx, name)| (name.trim().to_lowercase(), idx))
+                .collect(),
+        )
+    }
+
+    fn has_all(&self, names: &[&str]) -> bool {
+        names
+            .iter()
+            .all(|name| self.0.contains_key(&name.to_lowercase()))
+    }
+
+    /// Value of the first of the named columns found in the record
+    fn field<'a>(&self, record: &'a [String], names: &[&str]) -> &'a str {
+        names
+            .iter()
+            .find_map(|name| self.0.get(&name.to_lowercase()))
+            .and_then(|idx| record.get(*idx))
+            .map(|v| v.trim())
+            .unwrap_or_default()
+    }
+}
+
+/// Splits the records at the first one holding the given columns, exports may
+/// start with a few lines of account details.
+fn find_header<'a>(
+    records: &'a [Vec<String>],
+    required: &[&str],
+) -> Option<(usize, Columns, &'a [Vec<String>])> {
+    records.iter().enumerate().find_map(|(idx, record)| {
+        let columns = Columns::new(record);
+        columns
+            .has_all(required)
+            .then(|| (idx, columns, &records[idx + 1..]))
+    })
+}
+
+fn parse_number(value: &str) -> Result<Decimal, String> {
+    let trimmed = value.trim();
+    // Exponents only count in plain scientific notation such as 1.5E-7, an E
+    // elsewhere belongs to a unit such as in 12.5 EUR
+    if trimmed.contains(['e', 'E'])
+        && let Ok(number) = Decimal::from_scientific(trimmed)
+    {
+        return Ok(number.abs());
+    }
+    // Drops currency signs such as in $1,234.56
+    let cleaned: String = trimmed
+        .chars()
+        .filter(|c| c.is_ascii_digit() || matches!(c, '.' | '-'))
+        .collect();
+    parse_decimal(&cleaned, '.')
+        .map(|v| v.abs())
+        .ok_or_else(|| format!("Invalid number: {}", value))
+}
+
+/// Splits an amount followed by its asset, such as `0.00100000BTC`. Symbols may
+/// start with a digit, as in `1.51INCH`, so the longest known symbol ending the
+/// value is taken, the expected ones first. Other symbols start at the first letter.
+fn parse_amount(value: &str, expected: &[&str]) -> Result<(Decimal, String), String> {
+    let value = value.trim();
+    let upper = value.to_uppercase();
+    let known = expected
+        .iter()
+        .copied()
+        .chain(QUOTE_SYMBOLS.iter().copied())
+        .chain(KNOWN_COINS.iter().map(|(symbol, _)| *symbol));
+    let split = known
+        .filter(|symbol| !symbol.is_empty() && upper.len() > symbol.len())
+        .filter(|symbol| upper.ends_with(&symbol.to_uppercase()))
+        .map(|symbol| upper.len() - symbol.len())
+        // The symbol must take every letter, WBTC does not end in BTC
+        .filter(|split| !upper[..*split].contains(|c: char| c.is_ascii_alphabetic()))
+        .min()
+        .or_else(|| value.find(|c: char| c.is_ascii_alphabetic()))
+        .ok_or_else(|| format!("Amount {} has no asset", value))?;
+    let (number, asset) = value.split_at(split);
+    Ok((parse_number(number)?, asset.trim().to_uppercase()))
+}
+
+fn parse_datetime(value: &str) -> Result<OffsetDateTime, String> {
+    let value = value.trim().trim_end_matches(" UTC").replacen(' ', "T", 1);
+    OffsetDateTime::parse(&value, &Rfc3339)
+        .or_else(|_| OffsetDateTime::parse(&format!("{}Z", value), &Rfc3339))
+        .map_err(|_| format!("Invalid date: {}", value))
+}
+
+fn parse_side(value: &str) -> Result<TxType, String> {
+    match TxType::from_import_label(value) {
+        Some(side @ (TxType::Buy | TxType::Sell)) => Ok(side),
+        _ => Err(format!("Invalid side: {}", value)),
+    }
+}
+
+/// Splits a trading pair such as `BTC-USD`, `ETH/BTC` or `BTCUSDT`
+fn split_pair(pair: &str) -> Result<(String, String), String> {
+    let pair = pair.trim().to_uppercase();
+    if let Some((base, quote)) = pair.split_once(['/', '-', '_']) {
+        return Ok((base.to_string(), quote.to_string()));
+    }
+    QUOTE_SYMBOLS
+        .iter()
+        .filter(|quote| pair.len() > quote.len() && pair.ends_with(*quote))
+        .max_by_key(|quote| quote.len())
+        .map(|quote| {
+            (
+                pair[..pair.len() - quote.len()].to_string(),
+                quote.to_string(),
+            )
+        })
+        .ok_or_else(|| format!("Unknown trading pair: {}", pair))
+}
+
+/// Trade IDs of exports without one, stable across exports of the same fills.
+/// Identical fills, such as two equal orders filled in the same second, are told
+/// apart by how many came before them.
+#[derive(Default)]
+struct RecordHasher {
+    seen: HashMap<String, usize>,
+}
+
+impl RecordHasher {
+    fn hash(&mut self, record: &[String]) -> String {
+        let joined = record.join(",");
+        let occurrence = self.seen.entry(joined.clone()).or_default();
+        let input = match *occurrence {
+            0 => joined,
+            n => format!("{}#{}", joined, n),
+        };
+        *occurrence += 1;
+        format!("{:x}", Sha256::digest(input.as_bytes()))[..16].to_string()
+    }
+}
+
+type TradeLine = (usize, Result<Option<ExchangeTrade>, String>);
+
+fn parse_binance(records: &[Vec<String>]) -> Result<Vec<TradeLine>, AppError> {
+    const CURRENT: &[&str] = &[
+        "Date(UTC)",
+        "Pair",
+        "Side",
+        "Price",
+        "Executed",
+        "Amount",
+        "Fee",
+    ];
+    const LEGACY: &[&str] = &[
+        "Date(UTC)",
+        "Market",
+        "Type",
+        "Price",
+        "Amount",
+        "Total",
+        "Fee",
+        "Fee Coin",
+    ];
+    let (header_idx, columns, records, legacy) =
+        if let Some((idx, columns, records)) = find_header(records, CURRENT) {
+            (idx, columns, records, false)
+        } else if let Some((idx, columns, records)) = find_header(records, LEGACY) {
+            (idx, columns, records, true)
+        } else {
+            return Err(AppError::InvalidInput(
+                "Not a Binance trade history export".to_string(),
+            ));
+        };
+    let mut hasher = RecordHasher::default();
+    Ok(records
+        .iter()
+        .enumerate()
+        .map(|(idx, record)| {
+            let line = header_idx + idx + 2;
+            let record_hash = hasher.hash(record);
+            let trade = (|| {
+                let field = |names: &[&str]| columns.field(record, names);
+                let ((quantity, base), (total, quote), (fee, fee_asset)) = if legacy {
+                    let (base, quote) = split_pair(field(&["Market"]))?;
+                    (
+                        (parse_number(field(&["Amount"]))?, base),
+                        (parse_number(field(&["Total"]))?, quote),
+                        (
+                            parse_number(field(&["Fee"]))?,
+                            field(&["Fee Coin"]).to_uppercase(),
+                        ),
+                    )
+                } else {
+                    let (base, quote) = split_pair(field(&["Pair"]))?;
+                    (
+                        parse_amount(field(&["Executed"]), &[&base])?,
+                        parse_amount(field(&["Amount"]), &[&quote])?,
+                        parse_amount(field(&["Fee"]), &[&base, &quote])?,
+                    )
+                };
+                let trade_id = match field(&["Trade ID", "TradeId"]) {
+                    "" => record_hash,
+                    id => id.to_string(),
+                };
+                Ok(Some(ExchangeTrade {
+                    trade_id,
+                    executed_at: parse_datetime(field(&["Date(UTC)"]))?,
+                    base,
+                    quote,
+                    side: parse_side(field(&["Side", "Type"]))?,
+                    quantity,
+                    price: parse_number(field(&["Price"]))?,
+                    total,
+                    fee,
+                    fee_asset,
+                }))
+            })();
+            (line, trade)
+        })
+        .collect())
+}
+
+fn parse_coinbase(records: &[Vec<String>]) -> Result<Vec<TradeLine>, AppError> {
+    const FILLS: &[&str] = &[
+        "trade id",
+        "product",
+        "side",
+        "created at",
+        "size",
+        "price",
+        "fee",
+        "price/fee/total unit",
+    ];
+    const HISTORY: &[&str] = &[
+        "Timestamp",
+        "Transaction Type",
+        "Asset",
+        "Quantity Transacted",
+        "Subtotal",
+    ];
+    if let Some((header_idx, columns, records)) = find_header(records, FILLS) {
+        return Ok(records
+            .iter()
+            .enumerate()
+            .map(|(idx, record)| {
+                let trade = (|| {
+                    let field = |names: &[&str]| columns.field(record, names);
+                    let (base, quote) = split_pair(field(&["product"]))?;
+                    let quantity = parse_number(field(&["size"]))?;
+                    let price = parse_number(field(&["price"]))?;
+                    Ok(Some(ExchangeTrade {
+                        trade_id: field(&["trade id"]).to_string(),
+                        executed_at: parse_datetime(field(&["created at"]))?,
+                        base,
+                        side: parse_side(field(&["side"]))?,
+                        quantity,
+                        price,
+                        total: quantity * price,
+                        fee: parse_number(field(&["fee"]))?,
+                        fee_asset: field(&["price/fee/total unit"]).to_uppercase(),
+                        quote,
+                    }))
+                })();
+                (header_idx + idx + 2, trade)
+            })
+            .collect());
+    }
+    let Some((header_idx, columns, records)) = find_header(records, HISTORY) else {
+        return Err(AppError::InvalidInput(
+            "Not a Coinbase fills or transaction history export".to_string(),
+        ));
+    };
+    let mut hasher = RecordHasher::default();
+    Ok(records
+        .iter()
+        .enumerate()
+        .map(|(idx, record)| {
+            let record_hash = hasher.hash(record);
+            let trade = (|| {
+                let field = |names: &[&str]| columns.field(record, names);
+                // Transfers, rewards and conversions are not trades
+                let side = match parse_side(
+                    field(&["Transaction Type"]).trim_start_matches("Advanced Trade "),
+                ) {
+                    Ok(side) => side,
+                    Err(_) => return Ok(None),
+                };
+                let quantity = parse_number(field(&["Quantity Transacted"]))?;
+                let quote = field(&["Price Currency", "Spot Price Currency"]).to_uppercase();
+                let trade_id = match field(&["ID"]) {
+                    "" => record_hash,
+                    id => id.to_string(),
+                };
+                Ok(Some(ExchangeTrade {
+                    trade_id,
+                    executed_at: parse_datetime(field(&["Timestamp"]))?,
+                    base: field(&["Asset"]).to_uppercase(),
+                    side,
+                    quantity,
+                    price: parse_number(field(&[
+                        "Price at Transaction",
+                        "Spot Price at Transaction",
+                    ]))?,
+                    total: parse_number(field(&["Subtotal"]))?,
+                    fee: parse_number(field(&["Fees and/or Spread", "Fees"])).unwrap_or_default(),
+                    fee_asset: quote.clone(),
+                    quote,
+                }))
+            })();
+            (header_idx + idx + 2, trade)
+        })
+        .collect())
+}
+
+/// Symbol of a Kraken asset code, legacy codes such as XXBT and ZUSD carry a
+/// class prefix
+fn kraken_symbol(code: &str) -> String {
+    let code = code.to_uppercase();
+    let code = if code.len() == 4 && (code.starts_with('X') || code.starts_with('Z')) {
+        &code[1..]
+    } else {
+        code.as_str()
+    };
+    match code {
+        "XBT" => "BTC".to_string(),
+        "XDG" => "DOGE".to_string(),
+        code => code.to_string(),
+    }
+}
+
+fn split_kraken_pair(pair: &str) -> Result<(String, String), String> {
+    let pair = pair.trim().to_uppercase();
+    let (base, quote) = if !pair.contains('/')
+        && pair.len() == 8
+        && pair.starts_with(['X', 'Z'])
+        && pair[4..].starts_with(['X', 'Z'])
+    {
+        (pair[..4].to_string(), pair[4..].to_string())
+    } else {
+        split_pair(&pair)?
+    };
+    Ok((kraken_symbol(&base), kraken_symbol(&quote)))
+}
+
+fn parse_kraken(records: &[Vec<String>]) -> Result<Vec<TradeLine>, AppError> {
+    const REQUIRED: &[&str] = &[
+        "txid", "pair", "time", "type", "price", "cost", "fee", "vol",
+    ];
+    let Some((header_idx, columns, records)) = find_header(records, REQUIRED) else {
+        return Err(AppError::InvalidInput(
+            "Not a Kraken trades export".to_string(),
+        ));
+    };
+    Ok(records
+        .iter()
+        .enumerate()
+        .map(|(idx, record)| {
+            let trade = (|| {
+                let field = |names: &[&str]| columns.field(record, names);
+                let (base, quote) = split_kraken_pair(field(&["pair"]))?;
+                Ok(Some(ExchangeTrade {
+                    trade_id: field(&["txid"]).to_string(),
+                    executed_at: parse_datetime(field(&["time"]))?,
+                    base,
+                    side: parse_side(field(&["type"]))?,
+                    quantity: parse_number(field(&["vol"]))?,
+                    price: parse_number(field(&["price"]))?,
+                    total: parse_number(field(&["cost"]))?,
+                    fee: parse_number(field(&["fee"]))?,
+                    fee_asset: quote.clone(),
+                    quote,
+                }))
+            })();
+            (header_idx + idx + 2, trade)
+        })
+        .collect())
+}
+
+/// CoinGecko coin ID of a well known symbol
+pub fn known_coin_id(symbol: &str) -> Option<&'static str> {
+    KNOWN_COINS
+        .iter()
+        .find(|(known, _)| known.eq_ignore_ascii_case(symbol))
+        .map(|(_, coin_id)| *coin_id)
+}
+
+/// Finds the asset of the portfolio an exchange symbol stands for
+struct SymbolResolver {
+    overrides: HashMap<String, String>,
+    /// Coin IDs of the crypto assets of the portfolio, by symbol
+    portfolio_coins: HashMap<String, Vec<String>>,
+}
+
+impl SymbolResolver {
+    fn coin_id(&self, symbol: &str) -> Result<String, String> {
+        if let Some(coin_id) = self.overrides.get(symbol) {
+            return Ok(coin_id.clone());
+        }
+        match self.portfolio_coins.get(symbol).map(Vec::as_slice) {
+            Some([coin_id]) => return Ok(coin_id.clone()),
+            Some([_, _, ..]) => {
+                return Err(format!(
+                    "{} matches several assets, map it to a coin ID",
+                    symbol
+                ));
+            }
+            _ => (),
+        }
+        known_coin_id(symbol)
+            .map(str::to_string)
+            .ok_or_else(|| format!("Unknown symbol {}, map it to a coin ID", symbol))
+    }
+
+    fn asset_id(&self, symbol: &str) -> Result<String, String> {
+        Ok(generate_asset_id(
+            &AssetType::Crypto,
+            &self.coin_id(symbol)?,
+        ))
+    }
+}
+
+/// Past prices fetched while splitting trades, so each coin and day is asked once
+struct PriceLookup<'a> {
+    provider: &'a dyn PriceProvider,
+    prices: HashMap<(String, Currency, Date), Option<Decimal>>,
+}
+
+impl PriceLookup<'_> {
+    async fn price(
+        &mut self,
+        coin_id: &str,
+        currency: Currency,
+        date: Date,
+    ) -> Result<Decimal, String> {
+        let key = (coin_id.to_string(), currency, date);
+        if !self.prices.contains_key(&key) {
+            // Stablecoins are priced in US dollars
+            let vs_currency = if currency.is_usd_stablecoin() {
+                "usd".to_string()
+            } else {
+                currency.to_string().to_lowercase()
+            };
+            let price = self
+                .provider
+                .get_historical_price(coin_id, &vs_currency, date)
+                .await
+                .ok()
+                .flatten();
+            self.prices.insert(key.clone(), price);
+        }
+        self.prices[&key].ok_or_else(|| format!("No {} price of {} on {}", currency, coin_id, date))
+    }
+}
+
+fn fiat_currency(symbol: &str) -> Option<Currency> {
+    symbol
+        .parse::<Currency>()
+        .ok()
+        .filter(|currency| !currency.is_usd_stablecoin())
+}
+
+fn leg_row(line: usize, asset_id: Result<String, String>, tx: BaseTransactionInfo) -> ImportRow {
+    let mut row = ImportRow {
+        line,
+        external_id: tx.external_id,
+        tx_type: Some(tx.tx_type),
+        quantity: Some(tx.quantity),
+        price: Some(tx.price),
+        fees: Some(tx.fees),
+        currency: Some(tx.currency),
+        executed_at: Some(tx.executed_at),
+        notes: tx.notes,
+        ..Default::default()
+    };
+    match asset_id {
+        Ok(asset_id) => row.asset_id = Some(asset_id),
+        Err(e) => row.errors.push(e),
+    }
+    if tx.quantity <= Decimal::ZERO {
+        row.errors.push("Quantity must be positive".to_string());
+    }
+    row
+}
+
+/// Splits a trade into the transactions of its base asset, of its quote asset
+/// unless it is a fiat currency, and of the asset its fee was paid in when that
+/// is a third one. Prices are in the quote currency, or in US dollars when the
+/// quote is a coin.
+async fn trade_rows(
+    exchange: Exchange,
+    line: usize,
+    trade: ExchangeTrade,
+    resolver: &SymbolResolver,
+    prices: &mut PriceLookup<'_>,
+) -> Vec<ImportRow> {
+    let date = trade.executed_at.date();
+    let external_id = format!("{}:{}", exchange, trade.trade_id);
+    let notes = format!("{} trade {}/{}", exchange, trade.base, trade.quote);
+    let mut errors = Vec::new();
+
+    let (currency, quote_value) = match trade.quote.parse::<Currency>() {
+        Ok(currency) => (currency, Decimal::ONE),
+        Err(_) => {
+            let value = match resolver.coin_id(&trade.quote) {
+                Ok(coin_id) => prices.price(&coin_id, Currency::USD, date).await,
+                Err(e) => Err(e),
+            };
+            (
+                Currency::USD,
+                value.unwrap_or_else(|e| {
+                    errors.push(e);
+                    Decimal::ZERO
+                }),
+            )
+        }
+    };
+    let quote_is_coin = fiat_currency(&trade.quote).is_none();
+    let fee_asset = match trade.fee_asset.as_str() {
+        "" => trade.quote.clone(),
+        asset => asset.to_string(),
+    };
+
+    let mut quote_quantity = trade.total;
+    let mut fee_value = Decimal::ZERO;
+    // Fee paid in the base asset or a third one, sold at its price of the day
+    let mut fee_leg: Option<(Result<String, String>, Decimal)> = None;
+    if trade.fee > Decimal::ZERO {
+        if fee_asset == trade.quote {
+            fee_value = trade.fee * quote_value;
+            match trade.side {
+                TxType::Sell => quote_quantity -= trade.fee,
+                _ => quote_quantity += trade.fee,
+            }
+        } else if fee_asset == trade.base {
+            let unit_price = trade.price * quote_value;
+            fee_value = trade.fee * unit_price;
+            fee_leg = Some((resolver.asset_id(&fee_asset), unit_price));
+        } else if let Some(fee_currency) = fiat_currency(&fee_asset) {
+            if fee_currency == currency {
+                fee_value = trade.fee;
+            } else {
+                errors.push(format!(
+                    "Fee in {} of a trade priced in {} is not supported",
+                    fee_currency, currency
+                ));
+            }
+        } else {
+            let unit_price = match resolver.coin_id(&fee_asset) {
+                Ok(coin_id) => prices.price(&coin_id, currency, date).await,
+                Err(e) => Err(e),
+            };
+            match unit_price {
+                Ok(unit_price) => {
+                    fee_value = trade.fee * unit_price;
+                    fee_leg = Some((resolver.asset_id(&fee_asset), unit_price));
+                }
+                Err(e) => errors.push(e),
+            }
+        }
+    }
+
+    let mut base_row = leg_row(
+        line,
+        resolver.asset_id(&trade.base),
+        BaseTransactionInfo {
+            id: None,
+            external_id: Some(external_id.clone()),
+            tx_type: trade.side,
+            quantity: trade.quantity,
+            price: trade.price * quote_value,
+            fees: fee_value,
+            currency,
+            executed_at: trade.executed_at,
+            notes: Some(notes.clone()),
+        },
+    );
+    base_row.errors.extend(errors);
+    let mut rows = vec![base_row];
+    if quote_is_coin {
+        let side = match trade.side {
+            TxType::Sell => TxType::Buy,
+            _ => TxType::Sell,
+        };
+        rows.push(leg_row(
+            line,
+            resolver.asset_id(&trade.quote),
+            BaseTransactionInfo {
+                id: None,
+                external_id: Some(format!("{}:quote", external_id)),
+                tx_type: side,
+                quantity: quote_quantity,
+                price: quote_value,
+                fees: Decimal::ZERO,
+                currency,
+                executed_at: trade.executed_at,
+                notes: Some(notes.clone()),
+            },
+        ));
+    }
+    if let Some((asset_id, unit_price)) = fee_leg {
+        rows.push(leg_row(
+            line,
+            asset_id,
+            BaseTransactionInfo {
+                id: None,
+                external_id: Some(format!("{}:fee", external_id)),
+                tx_type: TxType::Sell,
+                quantity: trade.fee,
+                price: unit_price,
+                fees: Decimal::ZERO,
+                currency,
+                executed_at: trade.executed_at,
+                notes: Some(format!("{} fee", notes)),
+            },
+        ));
+    }
+    rows
+}
+
+/// Parses the trade history export of an exchange into the transactions of
+/// every trade, without writing anything. Rows that can not be imported carry
+/// their errors, rows of one trade share its line. Coins traded but missing
+/// from the catalog are returned as the assets the import would add.
+pub async fn parse_exchange_import(
+    state: &AppState,
+    pfl_row: &PortfolioRow,
+    exchange: Exchange,
+    contents: &str,
+    options: &ExchangeImportOptions,
+) -> Result<(Vec<ImportRow>, Vec<CreateAssetRepo>), AppError> {
+    let records = parse_csv(contents, ',')?;
+    let trades = match exchange {
+        Exchange::Binance => parse_binance(&records)?,
+        Exchange::Coinbase => parse_coinbase(&records)?,
+        Exchange::Kraken => parse_kraken(&records)?,
+    };
+
+    let asset_id_list: Vec<String> = PortfolioAssetRepo::new(state.pool.clone())
+        .get_multi_by_portfolio_id(pfl_row.id)
+        .await?
+        .into_iter()
+        .map(|pa_row| pa_row.asset_id)
+        .collect();
+    let mut portfolio_coins: HashMap<String, Vec<String>> = HashMap::new();
+    for asset_row in AssetRepo::new(state.pool.clone())
+        .get_multi_by_ids(&asset_id_list)
+        .await?
+    {
+        if asset_row.asset_type == AssetType::Crypto.to_string() {
+            portfolio_coins
+                .entry(asset_row.symbol.to_uppercase())
+                .or_default()
+                .push(asset_row.external_id);
+        }
+    }
+    let resolver = SymbolResolver {
+        overrides: options
+            .symbols
+            .iter()
+            .map(|(symbol, coin_id)| (symbol.to_uppercase(), coin_id.clone()))
+            .collect(),
+        portfolio_coins,
+    };
+    let mut prices = PriceLookup {
+        provider: state.clients.price_provider.as_ref(),
+        prices: HashMap::new(),
+    };
+
+    let mut rows = Vec::new();
+    for (line, trade) in trades {
+        match trade {
+            Ok(Some(trade)) => {
+                rows.extend(trade_rows(exchange, line, trade, &resolver, &mut prices).await)
+            }
+            Ok(None) => (),
+            Err(e) => rows.push(ImportRow {
+                line,
+                errors: vec![e],
+                ..Default::default()
+            }),
+        }
+    }
+
+    let coin_ids: BTreeSet<String> = rows
+        .iter()
+        .filter_map(|row| crypto_coin_id(row.asset_id.as_deref()?))
+        .collect();
+    let (new_assets, unknown) = plan_coin_assets(state, &coin_ids).await?;
+    for row in rows.iter_mut() {
+        let coin_id = row.asset_id.as_deref().and_then(crypto_coin_id);
+        if let Some(reason) = coin_id.and_then(|coin_id| unknown.get(&coin_id)) {
+            row.errors.push(reason.clone());
+        }
+    }
+    mark_duplicates(&mut rows);
+    Ok((rows, new_assets))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::clients::mock::MockPriceProvider;
+
+    use super::*;
+
+    fn records(csv: &str) -> Vec<Vec<String>> {
+        parse_csv(csv, ',').unwrap()
+    }
+
+    fn trades(lines: Vec<TradeLine>) -> Vec<ExchangeTrade> {
+        lines
+            .into_iter()
+            .map(|(_, trade)| trade.unwrap().unwrap())
+            .collect()
+    }
+
+    fn dec(value: &str) -> Decimal {
+        Decimal::from_str(value).unwrap()
+    }
+
+    #[test]
+    fn splits_pairs() {
+        let pair = |p: &str| split_pair(p).unwrap();
+        assert_eq!(pair("BTC-USD"), ("BTC".into(), "USD".into()));
+        assert_eq!(pair("eth/btc"), ("ETH".into(), "BTC".into()));
+        assert_eq!(pair("BTCUSDT"), ("BTC".into(), "USDT".into()));
+        assert_eq!(pair("1INCHUSDT"), ("1INCH".into(), "USDT".into()));
+        assert_eq!(pair("ETHBTC"), ("ETH".into(), "BTC".into()));
+        assert!(split_pair("FOOBAR").is_err());
+        assert_eq!(
+            split_kraken_pair("XXBTZUSD").unwrap(),
+            ("BTC".into(), "USD".into())
+        );
+        assert_eq!(
+            split_kraken_pair("XETHXXBT").unwrap(),
+            ("ETH".into(), "BTC".into())
+        );
+        assert_eq!(
+            split_kraken_pair("DOT/EUR").unwrap(),
+            ("DOT".into(), "EUR".into())
+        );
+    }
+
+    #[test]
+    fn parses_amounts() {
+        assert_eq!(
+            parse_amount("0.00100000BTC", &["BTC"]).unwrap(),
+            (dec("0.001"), "BTC".into())
+        );
+        assert_eq!(
+            parse_amount("1.51INCH", &["1INCH"]).unwrap(),
+            (dec("1.5"), "1INCH".into())
+        );
+        assert_eq!(
+            parse_amount("12.5BUSD", &[]).unwrap(),
+            (dec("12.5"), "BUSD".into())
+        );
+        assert_eq!(
+            parse_amount("2.0WBTC", &[]).unwrap(),
+            (dec("2"), "WBTC".into())
+        );
+        assert_eq!(
+            parse_amount("0.00075 BNB", &[]).unwrap(),
+            (dec("0.00075"), "BNB".into())
+        );
+        assert!(parse_amount("42", &[]).is_err());
+    }
+
+    #[test]
+    fn parses_numbers() {
+        assert_eq!(parse_number("$1,234.56").unwrap(), dec("1234.56"));
+        assert_eq!(parse_number("1.5E-7").unwrap(), dec("0.00000015"));
+        assert_eq!(parse_number("12.5 EUR").unwrap(), dec("12.5"));
+        assert_eq!(parse_number("-3").unwrap(), dec("3"));
+        assert!(parse_number("n/a").is_err());
+    }
+
+    #[test]
+    fn maps_kraken_symbols() {
+        assert_eq!(kraken_symbol("XXBT"), "BTC");
+        assert_eq!(kraken_symbol("XBT"), "BTC");
+        assert_eq!(kraken_symbol("XXDG"), "DOGE");
+        assert_eq!(kraken_symbol("ZUSD"), "USD");
+        assert_eq!(kraken_symbol("zeur"), "EUR");
+        assert_eq!(kraken_symbol("USDT"), "USDT");
+        assert_eq!(kraken_symbol("DOT"), "DOT");
+    }
+
+    #[test]
+    fn parses_binance_export() {
+        let csv = "Date(UTC),Pair,Side,Price,Executed,Amount,Fee\n\
+            2024-03-01 10:00:00,BTCUSDT,BUY,60000,0.001BTC,60USDT,0.000001BTC\n\
+            2024-03-01 10:00:00,BTCUSDT,BUY,60000,0.001BTC,60USDT,0.000001BTC\n\
+            2024-03-02 11:30:00,1INCHUSDT,SELL,0.5,100.51INCH,50.25USDT,0.05025USDT\n";
+        let trades = trades(parse_binance(&records(csv)).unwrap());
+        assert_eq!(trades.len(), 3);
+        // Identical fills get their own IDs, the first keeps the plain row hash
+        assert_ne!(trades[0].trade_id, trades[1].trade_id);
+        let first_row: Vec<String> = csv
+            .lines()
+            .nth(1)
+            .unwrap()
+            .split(',')
+            .map(String::from)
+            .collect();
+        assert_eq!(trades[0].trade_id, RecordHasher::default().hash(&first_row));
+        assert_eq!(trades[0].fee_asset, "BTC");
+        assert_eq!(trades[2].base, "1INCH");
+        assert_eq!(trades[2].quantity, dec("100.5"));
+        assert_eq!(trades[2].total, dec("50.25"));
+        assert_eq!(trades[2].side, TxType::Sell);
+    }
+
+    #[test]
+    fn parses_coinbase_fills() {
+        let csv = "portfolio,trade id,product,side,created at,size,size unit,price,fee,total,price/fee/total unit\n\
+            default,123,ETH-USD,BUY,2024-03-01T10:00:00.000Z,0.5,ETH,3000,4.5,-1504.5,USD\n";
+        let trades = trades(parse_coinbase(&records(csv)).unwrap());
+        assert_eq!(trades[0].trade_id, "123");
+        assert_eq!(
+            (trades[0].base.as_str(), trades[0].quote.as_str()),
+            ("ETH", "USD")
+        );
+        assert_eq!(trades[0].total, dec("1500"));
+        assert_eq!(trades[0].fee, dec("4.5"));
+    }
+
+    #[test]
+    fn parses_kraken_trades() {
+        let csv = "txid,ordertxid,pair,time,type,ordertype,price,cost,fee,vol,margin,misc,ledgers\n\
+            TX1,OX1,XXBTZEUR,2024-03-01 10:00:00.1234,sell,limit,55000,550,0.88,0.01,0,,\n";
+        let trades = trades(parse_kraken(&records(csv)).unwrap());
+        assert_eq!(trades[0].trade_id, "TX1");
+        assert_eq!(
+            (trades[0].base.as_str(), trades[0].quote.as_str()),
+            ("BTC", "EUR")
+        );
+        assert_eq!(trades[0].side, TxType::Sell);
+        assert_eq!(trades[0].fee_asset, "EUR");
+    }
+
+    fn trade(
+        pair: (&str, &str),
+        side: TxType,
+        qty: &str,
+        price: &str,
+        fee: (&str, &str),
+    ) -> ExchangeTrade {
+        ExchangeTrade {
+            trade_id: "1".to_string(),
+            executed_at: OffsetDateTime::UNIX_EPOCH,
+            base: pair.0.to_string(),
+            quote: pair.1.to_string(),
+            side,
+            quantity: dec(qty),
+            price: dec(price),
+            total: dec(qty) * dec(price),
+            fee: dec(fee.0),
+            fee_asset: fee.1.to_string(),
+        }
+    }
+
+    async fn rows_of(trade: ExchangeTrade) -> Vec<ImportRow> {
+        let provider = MockPriceProvider::new(
+            Decimal::ONE,
+            HashMap::from([
+                ("bitcoin".to_string(), dec("60000")),
+                ("binancecoin".to_string(), dec("500")),
+            ]),
+        );
+        let resolver = SymbolResolver {
+            overrides: HashMap::new(),
+            portfolio_coins: HashMap::new(),
+        };
+        let mut prices = PriceLookup {
+            provider: &provider,
+            prices: HashMap::new(),
+        };
+        trade_rows(Exchange::Binance, 2, trade, &resolver, &mut prices).await
+    }
+
+    #[tokio::test]
+    async fn fee_in_fiat_quote_is_a_fee_of_the_trade() {
+        let rows = rows_of(trade(
+            ("ETH", "USD"),
+            TxType::Buy,
+            "2",
+            "3000",
+            ("6", "USD"),
+        ))
+        .await;
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].price, Some(dec("3000")));
+        assert_eq!(rows[0].fees, Some(dec("6")));
+        assert_eq!(rows[0].currency, Some(Currency::USD));
+        assert!(rows[0].errors.is_empty());
+    }
+
+    #[tokio::test]
+    async fn fee_in_coin_quote_adds_to_the_quote_spent() {
+        let rows = rows_of(trade(
+            ("BTC", "USDT"),
+            TxType::Buy,
+            "0.1",
+            "60000",
+            ("6", "USDT"),
+        ))
+        .await;
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].fees, Some(dec("6")));
+        let quote = &rows[1];
+        assert_eq!(quote.tx_type, Some(TxType::Sell));
+        assert_eq!(quote.quantity, Some(dec("6006")));
+        assert_eq!(quote.external_id.as_deref(), Some("binance:1:quote"));
+    }
+
+    #[tokio::test]
+    async fn fee_in_base_is_sold_at_the_trade_price() {
+        let rows = rows_of(trade(
+            ("ETH", "USD"),
+            TxType::Buy,
+            "2",
+            "3000",
+            ("0.002", "ETH"),
+        ))
+        .await;
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].fees, Some(dec("6")));
+        let fee = &rows[1];
+        assert_eq!(fee.tx_type, Some(TxType::Sell));
+        assert_eq!(fee.quantity, Some(dec("0.002")));
+        assert_eq!(fee.price, Some(dec("3000")));
+        assert_eq!(fee.asset_id, rows[0].asset_id);
+    }
+
+    #[tokio::test]
+    async fn fee_in_third_coin_is_valued_at_its_price() {
+        // ETH/BTC is priced in US dollars through the price of bitcoin
+        let rows = rows_of(trade(
+            ("ETH", "BTC"),
+            TxType::Sell,
+            "1",
+            "0.05",
+            ("0.01", "BNB"),
+        ))
+        .await;
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0].price, Some(dec("3000")));
+        assert_eq!(rows[0].currency, Some(Currency::USD));
+        assert_eq!(rows[0].fees, Some(dec("5")));
+        // Selling for BTC buys the quote, the fee is not taken from it
+        assert_eq!(rows[1].tx_type, Some(TxType::Buy));
+        assert_eq!(rows[1].quantity, Some(dec("0.05")));
+        assert_eq!(rows[2].quantity, Some(dec("0.01")));
+        assert_eq!(rows[2].price, Some(dec("500")));
+    }
+}
@@ -0,0 +1,196 @@
+use std::collections::HashMap;
+
+use rust_xlsxwriter::{Format, Workbook};
+use sqlx::PgPool;
+use time::format_description::well_known::Rfc3339;
+
+use crate::{
+    db::repositories::{
+        asset::AssetRepo, portfolio_asset::PortfolioAssetRepo, transaction::TransactionRepo,
+    },
+    models::{
+        database::{asset::AssetRow, portfolio::PortfolioRow},
+        dto::export::{ExportSheet, ExportedHolding, ExportedTransaction, PortfolioExport},
+    },
+    utils::{csv::write_csv, error::AppError},
+};
+
+/// Rows of one sheet of an export, numbers kept as written so CSV loses nothing
+struct ExportTable {
+    name: &'static str,
+    header: &'static [&'static str],
+    /// Columns written as numbers in spreadsheets
+    numeric: &'static [usize],
+    rows: Vec<Vec<String>>,
+}
+
+// Headers match what the CSV import reads, so a CSV export can be imported back
+const TRANSACTION_HEADER: &[&str] = &[
+    "Date",
+    "Type",
+    "Asset ID",
+    "Coin ID",
+    "Symbol",
+    "Name",
+    "Quantity",
+    "Price",
+    "Fee",
+    "Currency",
+    "Notes",
+    "External ID",
+    "Realized Gain",
+    "Transaction ID",
+];
+
+const HOLDING_HEADER: &[&str] = &[
+    "Asset ID",
+    "Asset Type",
+    "Symbol",
+    "Name",
+    "Holding Amount",
+    "Total Cost",
+    "Avg Buy Price",
+    "Sold Amount",
+    "Total Revenue",
+    "Avg Sell Price",
+    "Cost Basis",
+    "Realized PnL",
+];
+
+/// Every transaction of the portfolio with the metadata of its asset, and the
+/// stats of every asset it holds.
+pub async fn build_portfolio_export(
+    pool: PgPool,
+    pfl_row: &PortfolioRow,
+) -> Result<PortfolioExport, AppError> {
+    let pa_rows = PortfolioAssetRepo::new(pool.clone())
+        .get_multi_by_portfolio_id(pfl_row.id)
+        .await?;
+    let tx_rows = TransactionRepo::new(pool.clone())
+        .get_all_txs_by_portfolio(pfl_row.id)
+        .await?;
+    let mut asset_ids: Vec<String> = pa_rows.iter().map(|row| row.asset_id.clone()).collect();
+    asset_ids.extend(tx_rows.iter().map(|row| row.asset_id.clone()));
+    asset_ids.sort();
+    asset_ids.dedup();
+    let assets: HashMap<String, AssetRow> = AssetRepo::new(pool)
+        .get_multi_by_ids(&asset_ids)
+        .await?
+        .into_iter()
+        .map(|row| (row.id.clone(), row))
+        .collect();
+
+    let mut export = PortfolioExport::from_db_row(pfl_row);
+    for pa_row in pa_rows.iter() {
+        if let Some(asset_row) = assets.get(&pa_row.asset_id) {
+            export
+                .holdings
+                .push(ExportedHolding::from_db_row(pa_row, asset_row));
+        }
+    }
+    for tx_row in tx_rows {
+        if let Some(asset_row) = assets.get(&tx_row.asset_id) {
+            export
+                .transactions
+                .push(ExportedTransaction::from_db_row(tx_row, asset_row));
+        }
+    }
+    Ok(export)
+}
+
+fn transaction_table(export: &PortfolioExport) -> Result<ExportTable, AppError> {
+    let mut rows = Vec::with_capacity(export.transactions.len());
+    for tx in export.transactions.iter() {
+        rows.push(vec![
+            tx.executed_at.format(&Rfc3339)?,
+            tx.tx_type.to_string(),
+            tx.asset_id.clone(),
+            tx.asset_external_id.clone(),
+            tx.asset_symbol.clone(),
+            tx.asset_name.clone(),
+            tx.quantity.clone(),
+            tx.price.clone(),
+            tx.fees.clone(),
+            tx.currency.to_string(),
+            tx.notes.clone().unwrap_or_default(),
+            tx.external_id.clone().unwrap_or_default(),
+            tx.realized_gain.clone().unwrap_or_default(),
+            tx.id.clone(),
+        ]);
+    }
+    Ok(ExportTable {
+        name: "Transactions",
+        header: TRANSACTION_HEADER,
+        numeric: &[6, 7, 8, 12],
+        rows,
+    })
+}
+
+fn holding_table(export: &PortfolioExport) -> ExportTable {
+    ExportTable {
+        name: "Holdings",
+        header: HOLDING_HEADER,
+        numeric: &[4, 5, 6, 7, 8, 9, 10, 11],
+        rows: export
+            .holdings
+            .iter()
+            .map(|holding| {
+                vec![
+                    holding.asset_id.clone(),
+                    holding.asset_type.to_string(),
+                    holding.asset_symbol.clone(),
+                    holding.asset_name.clone(),
+                    holding.holding_amount.clone(),
+                    holding.total_cost.clone(),
+                    holding.avg_buy_price.clone(),
+                    holding.sold_amount.clone(),
+                    holding.total_revenue.clone(),
+                    holding.avg_sell_price.clone(),
+                    holding.cost_basis.clone(),
+                    holding.realized_pnl.clone(),
+                ]
+            })
+            .collect(),
+    }
+}
+
+/// One table of the export as CSV
+pub fn export_csv(export: &PortfolioExport, sheet: ExportSheet) -> Result<String, AppError> {
+    let table = match sheet {
+        ExportSheet::Transactions => transaction_table(export)?,
+        ExportSheet::Holdings => holding_table(export),
+    };
+    let mut records = vec![table.header.iter().map(|h| h.to_string()).collect()];
+    records.extend(table.rows);
+    Ok(write_csv(&records, ','))
+}
+
+/// A workbook with a transactions sheet and a holdings summary sheet
+pub fn export_xlsx(export: &PortfolioExport) -> Result<Vec<u8>, AppError> {
+    let mut workbook = Workbook::new();
+    let header_format = Format::new().set_bold();
+    for table in [transaction_table(export)?, holding_table(export)] {
+        let worksheet = workbook.add_worksheet();
+        worksheet.set_name(table.name)?;
+        for (col, name) in table.header.iter().enumerate() {
+            worksheet.write_string_with_format(0, col as u16, *name, &header_format)?;
+        }
+        for (idx, row) in table.rows.iter().enumerate() {
+            let row_num = idx as u32 + 1;
+            for (col, value) in row.iter().enumerate() {
+                let number = table
+                    .numeric
+                    .contains(&col)
+                    .then(|| value.parse::<f64>().ok())
+                    .flatten();
+                match number {
+                    Some(number) => worksheet.write_number(row_num, col as u16, number)?,
+                    None => worksheet.write_string(row_num, col as u16, value)?,
+                };
+            }
+        }
+        worksheet.set_freeze_panes(1, 0)?;
+        worksheet.autofit();
+    }
+    Ok(workbook.save_to_buffer()?)
+}
@@ -0,0 +1,316 @@
+use std::collections::{BTreeMap, HashMap};
+
+use rust_decimal::Decimal;
+use sqlx::PgPool;
+use strum::IntoEnumIterator;
+use time::{Date, OffsetDateTime};
+use tracing::{info, warn};
+
+use crate::{
+    db::repositories::{fx_rate::FxRateRepo, portfolio::PortfolioRepo},
+    models::{common::currency::Currency, database::transaction::TransactionRow},
+    state::AppState,
+    utils::error::AppError,
+};
+
+/// Days of transactions backfilled per run, the provider rate limits history calls
+const MAX_BACKFILL_DATES: i64 = 30;
+
+/// Daily exchange rates of a set of currencies, quoted per 1 USD.
+#[derive(Debug, Default)]
+pub struct FxRates {
+    rates: HashMap<Currency, BTreeMap<Date, Decimal>>,
+}
+
+impl FxRates {
+    pub async fn load(pool: PgPool, currencies: &[Currency]) -> Result<Self, AppError> {
+        let codes: Vec<String> = currencies
+            .iter()
+            .filter(|c| **c != Currency::USD)
+            .map(|c| c.to_string())
+            .collect();
+        let mut rates: HashMap<Currency, BTreeMap<Date, Decimal>> = HashMap::new();
+        if codes.is_empty() {
+            return Ok(Self { rates });
+        }
+        for row in FxRateRepo::new(pool)
+            .get_multi_by_currencies(&codes)
+            .await?
+        {
+            rates
+                .entry(row.currency.parse()?)
+                .or_default()
+                .insert(row.rate_date, row.rate);
+        }
+        Ok(Self { rates })
+    }
+
+    /// Units of the currency per 1 USD on the given day, the latest rate on or
+    /// before it. A later rate is never used, a day before the first stored rate
+    /// is reported as missing.
+    fn usd_rate(&self, currency: Currency, date: Date) -> Result<Decimal, AppError> {
+        if currency == Currency::USD {
+            return Ok(Decimal::ONE);
+        }
+        let rate = self
+            .rates
+            .get(&currency)
+            .and_then(|rates| rates.range(..=date).next_back())
+            .map(|(_, rate)| *rate);
+        match rate {
+            Some(rate) if rate > Decimal::ZERO => Ok(rate),
+            _ if currency.is_usd_stablecoin() => Ok(Decimal::ONE),
+            _ => Err(AppError::FxRateNotFound(format!(
+                "{} on {}",
+                currency, date
+            ))),
+        }
+    }
+
+    pub fn convert(
+        &self,
+        amount: Decimal,
+        from: Currency,
+        to: Currency,
+        date: Date,
+    ) -> Result<Decimal, AppError> {
+        if from == to || amount.is_zero() {
+            return Ok(amount);
+        }
+        Ok(amount / self.usd_rate(from, date)? * self.usd_rate(to, date)?)
+    }
+}
+
+/// Rewrites the price and fees of every transaction into the base currency at
+/// the rate of its execution day.
+pub async fn convert_tx_rows(
+    pool: PgPool,
+    tx_rows: Vec<TransactionRow>,
+    base_currency: Currency,
+) -> Result<Vec<TransactionRow>, AppError> {
+    let mut currencies = vec![base_currency];
+    for row in tx_rows.iter() {
+        let currency: Currency = row.currency.parse()?;
+        if !currencies.contains(&currency) {
+            currencies.push(currency);
+        }
+    }
+    if currencies.len() == 1 {
+        return Ok(tx_rows);
+    }
+    let fx_rates = FxRates::load(pool, &currencies).await?;
+    tx_rows
+        .into_iter()
+        .map(|mut row| {
+            let currency: Currency = row.currency.parse()?;
+            let date = row.executed_at.date();
+            row.price = fx_rates.convert(row.price, currency, base_currency, date)?;
+            row.fees = fx_rates.convert(row.fees, currency, base_currency, date)?;
+            row.currency = base_currency.to_string();
+            Ok(row)
+        })
+        .collect()
+}
+
+/// Currencies that need a stored rate, stablecoins fall back to 1:1
+fn rate_currencies(currencies: impl IntoIterator<Item = Currency>) -> Vec<String> {
+    currencies
+        .into_iter()
+        .filter(|c| *c != Currency::USD && !c.is_usd_stablecoin())
+        .map(|c| c.to_string())
+        .collect()
+}
+
+/// Fetches and stores the rates of every supported currency on each day, today's
+/// from the current rates. Returns the days that could not be fetched.
+async fn fetch_fx_rates(state: &AppState, dates: &[Date]) -> Result<Vec<Date>, AppError> {
+    let provider = &state.clients.price_provider;
+    let today = OffsetDateTime::now_utc().date();
+    let repo = FxRateRepo::new(state.pool.clone());
+    let mut failed = Vec::new();
+    for date in dates.iter().copied() {
+        let usd_rates = if date >= today {
+            provider.get_exchange_rates().await
+        } else {
+            provider.get_historical_exchange_rates(date).await
+        };
+        let usd_rates = match usd_rates {
+            Ok(usd_rates) => usd_rates,
+            Err(e) => {
+                warn!("Fetch FX rates of {} failed: {:?}", date, e);
+                failed.push(date);
+                continue;
+            }
+        };
+        let rate_date = date.min(today);
+        let rates: Vec<(String, Date, Decimal)> = Currency::iter()
+            .filter(|c| *c != Currency::USD)
+            .filter_map(|c| {
+                let rate = usd_rates.get(&c.to_string().to_lowercase())?;
+                Some((c.to_string(), rate_date, *rate))
+            })
+            .collect();
+        let saved = repo.upsert_multi(provider.source(), &rates).await?;
+        info!("Saved {} FX rates of {}", saved, rate_date);
+    }
+    Ok(failed)
+}
+
+/// Fetches the rates of the days missing one of the currencies, so amounts of
+/// those days are converted at their own rate. Fetch failures are logged, a day
+/// left without a rate to convert at is reported as `FxRateNotFound`.
+pub async fn ensure_fx_rates(
+    state: &AppState,
+    currencies: &[Currency],
+    dates: &[Date],
+) -> Result<(), AppError> {
+    let codes = rate_currencies(currencies.iter().copied());
+    if codes.is_empty() || dates.is_empty() {
+        return Ok(());
+    }
+    let mut dates = dates.to_vec();
+    dates.sort();
+    dates.dedup();
+    let missing = FxRateRepo::new(state.pool.clone())
+        .get_missing_dates(&codes, &dates)
+        .await?;
+    if missing.is_empty() {
+        return Ok(());
+    }
+    fetch_fx_rates(state, &missing).await?;
+    let fx_rates = FxRates::load(state.pool.clone(), currencies).await?;
+    for date in missing {
+        for currency in currencies.iter() {
+            fx_rates.usd_rate(*currency, date)?;
+        }
+    }
+    Ok(())
+}
+
+/// Fetches the rates transactions need to be converted to the base currency,
+/// in their own currency and the base currency, on their execution days
+pub async fn ensure_tx_fx_rates(
+    state: &AppState,
+    base_currency: Currency,
+    txs: &[(Currency, Date)],
+) -> Result<(), AppError> {
+    let mut currencies: Vec<Currency> = vec![base_currency];
+    for (currency, _) in txs.iter() {
+        if !currencies.contains(currency) {
+            currencies.push(*currency);
+        }
+    }
+    let dates: Vec<Date> = txs.iter().map(|(_, date)| *date).collect();
+    ensure_fx_rates(state, &currencies, &dates).await
+}
+
+/// `ensure_tx_fx_rates` in the base currency of the portfolio
+pub async fn ensure_portfolio_fx_rates(
+    state: &AppState,
+    portfolio_id: i64,
+    txs: &[(Currency, Date)],
+) -> Result<(), AppError> {
+    let Some(pfl_row) = PortfolioRepo::new(state.pool.clone())
+        .get_one_by_id(portfolio_id)
+        .await?
+    else {
+        return Ok(());
+    };
+    ensure_tx_fx_rates(
+        state,
+        pfl_row.base_currency.parse().unwrap_or_default(),
+        txs,
+    )
+    .await
+}
+
+/// Fetches the rates of the days with transactions that have none yet, latest
+/// first and at most `MAX_BACKFILL_DATES` per run.
+pub async fn backfill_fx_rates(state: &AppState) -> Result<(), AppError> {
+    let missing = FxRateRepo::new(state.pool.clone())
+        .get_missing_transaction_dates(&rate_currencies(Currency::iter()), MAX_BACKFILL_DATES)
+        .await?;
+    if missing.is_empty() {
+        return Ok(());
+    }
+    let failed = fetch_fx_rates(state, &missing).await?;
+    info!(
+        "Backfilled FX rates of {} days, {} failed",
+        missing.len() - failed.len(),
+        failed.len()
+    );
+    Ok(())
+}
+
+/// Stores today's exchange rate of every supported currency from the price
+/// provider, then backfills the days of transactions without a rate.
+pub async fn refresh_fx_rates(state: &AppState) -> Result<(), AppError> {
+    let today = OffsetDateTime::now_utc().date();
+    if !fetch_fx_rates(state, &[today]).await?.is_empty() {
+        return Err(AppError::PriceProviderError(format!(
+            "Fetch FX rates of {} failed",
+            today
+        )));
+    }
+    backfill_fx_rates(state).await
+}
+
+#[cfg(test)]
+mod tests {
+    use time::Month;
+
+    use super::*;
+
+    fn day(year: i32, month: Month, day: u8) -> Date {
+        Date::from_calendar_date(year, month, day).unwrap()
+    }
+
+    fn eur_rates() -> FxRates {
+        FxRates {
+            rates: HashMap::from([(
+                Currency::EUR,
+                BTreeMap::from([
+                    (day(2024, Month::January, 10), Decimal::new(9, 1)),
+                    (day(2024, Month::February, 10), Decimal::new(8, 1)),
+                ]),
+            )]),
+        }
+    }
+
+    #[test]
+    fn converts_at_latest_rate_on_or_before_day() {
+        let fx_rates = eur_rates();
+        let converted = |date| {
+            fx_rates
+                .convert(Decimal::ONE_HUNDRED, Currency::USD, Currency::EUR, date)
+                .unwrap()
+        };
+        assert_eq!(converted(day(2024, Month::January, 10)), Decimal::from(90));
+        assert_eq!(converted(day(2024, Month::February, 9)), Decimal::from(90));
+        assert_eq!(converted(day(2024, Month::March, 1)), Decimal::from(80));
+    }
+
+    #[test]
+    fn reports_day_before_first_rate() {
+        let fx_rates = eur_rates();
+        let err = fx_rates
+            .convert(
+                Decimal::ONE_HUNDRED,
+                Currency::EUR,
+                Currency::USD,
+                day(2021, Month::March, 4),
+            )
+            .unwrap_err();
+        assert!(matches!(err, AppError::FxRateNotFound(msg) if msg == "EUR on 2021-03-04"));
+        // Stablecoins without a rate stay pegged to the dollar
+        let pegged = fx_rates
+            .convert(
+                Decimal::TEN,
+                Currency::USDT,
+                Currency::USD,
+                day(2021, Month::March, 4),
+            )
+            .unwrap();
+        assert_eq!(pegged, Decimal::TEN);
+    }
+}
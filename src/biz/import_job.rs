@@ -0,0 +1,171 @@
+use tracing::{error, info, warn};
+
+use crate::{
+    biz::{
+        coingecko_import::plan_coingecko_import,
+        csv_import::{parse_csv_import, planned_txs},
+        exchange_import::parse_exchange_import,
+        import_plan::{apply_import_plan, build_import_plan, file_hash},
+    },
+    db::repositories::{import_job::ImportJobRepo, portfolio::PortfolioRepo},
+    models::{
+        database::{
+            import_batch::ImportBatchRow, import_job::ImportJobRow, portfolio::PortfolioRow,
+        },
+        domain::import::{Exchange, ImportJobStatus, ImportPlan, ImportRow, ImportSource},
+        dto::{
+            asset::CreateAssetRepo,
+            import::{CsvColumnMapping, ExchangeImportOptions, ImportJobClaim},
+        },
+    },
+    state::AppState,
+    utils::error::AppError,
+};
+
+async fn job_portfolio(state: &AppState, job: &ImportJobRow) -> Result<PortfolioRow, AppError> {
+    PortfolioRepo::new(state.pool.clone())
+        .get_one_by_id(job.portfolio_id)
+        .await?
+        .ok_or_else(|| AppError::InvalidInput("Portfolio not found".to_string()))
+}
+
+/// Records the rows read from a queued file and plans them. Invalid rows fail
+/// the whole job, their errors are recorded on it.
+async fn plan_job_rows(
+    state: &AppState,
+    job: &ImportJobRow,
+    source: ImportSource,
+    (rows, new_assets): (Vec<ImportRow>, Vec<CreateAssetRepo>),
+) -> Result<ImportPlan, AppError> {
+    let errors: Vec<String> = rows
+        .iter()
+        .flat_map(|row| {
+            row.errors
+                .iter()
+                .map(move |e| format!("Row {}: {}", row.line, e))
+        })
+        .collect();
+    ImportJobRepo::new(state.pool.clone())
+        .update_progress(job.id, job.attempts, rows.len() as i32, &errors)
+        .await?;
+    let invalid_rows = rows.iter().filter(|row| !row.is_valid()).count();
+    if invalid_rows > 0 {
+        return Err(AppError::InvalidInput(format!(
+            "{} rows are invalid, fix them before importing",
+            invalid_rows
+        )));
+    }
+    build_import_plan(
+        state.pool.clone(),
+        job.user_id,
+        job.portfolio_id,
+        source,
+        file_hash(&job.file_contents),
+        new_assets,
+        planned_txs(&rows),
+    )
+    .await
+}
+
+async fn plan_csv_job(state: &AppState, job: &ImportJobRow) -> Result<ImportPlan, AppError> {
+    let mapping: CsvColumnMapping = match job.options.clone() {
+        Some(options) => serde_json::from_value(options)?,
+        None => {
+            return Err(AppError::InvalidInput(
+                "CSV import job has no column mapping".to_string(),
+            ));
+        }
+    };
+    let pfl_row = job_portfolio(state, job).await?;
+    let parsed = parse_csv_import(state, &pfl_row, &job.file_contents, &mapping).await?;
+    plan_job_rows(state, job, ImportSource::Csv, parsed).await
+}
+
+async fn plan_exchange_job(
+    state: &AppState,
+    job: &ImportJobRow,
+    exchange: Exchange,
+) -> Result<ImportPlan, AppError> {
+    let options: ExchangeImportOptions = match job.options.clone() {
+        Some(options) => serde_json::from_value(options)?,
+        None => ExchangeImportOptions::default(),
+    };
+    let pfl_row = job_portfolio(state, job).await?;
+    let parsed =
+        parse_exchange_import(state, &pfl_row, exchange, &job.file_contents, &options).await?;
+    plan_job_rows(state, job, exchange.into(), parsed).await
+}
+
+async fn process_import_job(
+    state: &AppState,
+    job: &ImportJobRow,
+) -> Result<ImportBatchRow, AppError> {
+    let job_repo = ImportJobRepo::new(state.pool.clone());
+    let plan = match job.source.parse()? {
+        ImportSource::Csv => plan_csv_job(state, job).await?,
+        source @ (ImportSource::Binance | ImportSource::Coinbase | ImportSource::Kraken) => {
+            // Sources of exchanges always have one
+            plan_exchange_job(state, job, source.exchange().unwrap()).await?
+        }
+        source @ (ImportSource::Coingecko | ImportSource::CoingeckoJson) => {
+            let coin_id = job
+                .options
+                .as_ref()
+                .and_then(|options| options.get("coin_id"))
+                .and_then(|coin_id| coin_id.as_str());
+            let plan = plan_coingecko_import(
+                state,
+                job.user_id,
+                job.portfolio_id,
+                source,
+                std::slice::from_ref(&job.file_contents),
+                coin_id,
+            )
+            .await?;
+            let rows_parsed = plan.creates.len() + plan.updates.len() + plan.unchanged.len();
+            job_repo
+                .update_progress(job.id, job.attempts, rows_parsed as i32, &[])
+                .await?;
+            plan
+        }
+    };
+    let claim = ImportJobClaim {
+        job_id: job.id,
+        attempt: job.attempts,
+    };
+    apply_import_plan(state, plan, Some(claim)).await
+}
+
+/// Processes a claimed job and records whether it succeeded. A job succeeds
+/// with the batch it writes, its failures are recorded while the attempt still
+/// holds it.
+pub async fn run_import_job(state: AppState, job: ImportJobRow) {
+    info!(
+        "Import job {} started, source {}, attempt {}",
+        job.id, job.source, job.attempts
+    );
+    let error = match process_import_job(&state, &job).await {
+        Ok(batch) => {
+            info!("Import job {} succeeded with batch {}", job.id, batch.id);
+            return;
+        }
+        Err(e) => e,
+    };
+    error!("Import job {} failed: {:?}", job.id, error);
+    let (_, message) = error.get_status_code_and_error_msg();
+    match ImportJobRepo::new(state.pool.clone())
+        .fail(job.id, job.attempts, message)
+        .await
+    {
+        Ok(true) => info!(
+            "Import job {} finished with {}",
+            job.id,
+            ImportJobStatus::Failed
+        ),
+        Ok(false) => warn!(
+            "Import job {} is no longer held by attempt {}, its failure is dropped",
+            job.id, job.attempts
+        ),
+        Err(e) => error!("Record outcome of import job {} failed: {:?}", job.id, e),
+    }
+}
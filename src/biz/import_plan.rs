@@ -0,0 +1,409 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use redis::{AsyncCommands, aio::ConnectionManager};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::{
+    biz::{fx::ensure_portfolio_fx_rates, portfolio_asset::update_portfolio_asset_stat},
+    db::repositories::{
+        import_batch::ImportBatchRepo, portfolio_asset::PortfolioAssetRepo,
+        transaction::TransactionRepo,
+    },
+    models::{
+        database::{import_batch::ImportBatchRow, transaction::TransactionRow},
+        domain::{
+            import::{
+                ImportBatchStatus, ImportChangeAction, ImportPlan, ImportSource, PlannedTx,
+                PlannedUpdate,
+            },
+            transaction::BaseTransactionInfo,
+        },
+        dto::{
+            asset::CreateAssetRepo,
+            import::{
+                ApplyImportBatch, CreateImportBatch, CreateImportBatchChange, ImportJobClaim,
+                ImportResponse,
+            },
+        },
+    },
+    state::AppState,
+    utils::{error::AppError, snowflake::SNOWFLAKE_GENERATOR},
+};
+
+// How long a previewed plan can be committed
+const IMPORT_PLAN_TTL_SECS: u64 = 1800;
+
+fn plan_key(token: &str) -> String {
+    format!("import_plan:{}", token)
+}
+
+fn plan_lock_key(token: &str) -> String {
+    format!("import_plan_lock:{}", token)
+}
+
+/// Sorts the transactions of an import into new ones, changes to transactions
+/// imported before, matched by external ID within the asset, and untouched ones.
+/// Assets of new transactions the portfolio does not hold are attached to it.
+pub async fn build_import_plan(
+    pool: PgPool,
+    user_id: i64,
+    portfolio_id: i64,
+    source: ImportSource,
+    file_hash: String,
+    new_assets: Vec<CreateAssetRepo>,
+    txs: Vec<PlannedTx>,
+) -> Result<ImportPlan, AppError> {
+    let external_ids: Vec<String> = txs
+        .iter()
+        .filter_map(|p| p.tx.external_id.clone())
+        .collect();
+    let existing: HashMap<(String, String), TransactionRow> = if external_ids.is_empty() {
+        HashMap::new()
+    } else {
+        TransactionRepo::new(pool.clone())
+            .get_multi_by_external_ids(portfolio_id, &external_ids)
+            .await?
+            .into_iter()
+            .filter_map(|row| Some(((row.asset_id.clone(), row.external_id.clone()?), row)))
+            .collect()
+    };
+
+    let mut plan = ImportPlan {
+        user_id,
+        portfolio_id,
+        source,
+        file_hash,
+        new_assets,
+        new_portfolio_assets: Vec::new(),
+        creates: Vec::new(),
+        updates: Vec::new(),
+        unchanged: Vec::new(),
+    };
+    for planned in txs {
+        let row = planned
+            .tx
+            .external_id
+            .clone()
+            .and_then(|external_id| existing.get(&(planned.asset_id.clone(), external_id)));
+        let Some(row) = row else {
+            plan.creates.push(planned);
+            continue;
+        };
+        let before = BaseTransactionInfo::from_db_row(row)?;
+        if before.differs_from(&planned.tx) {
+            plan.updates.push(PlannedUpdate {
+                tx_id: row.id,
+                asset_id: planned.asset_id,
+                updated_at: row.updated_at,
+                previous_batch_id: row.import_batch_id,
+                before,
+                after: planned.tx,
+            });
+        } else {
+            plan.unchanged.push(PlannedTx {
+                asset_id: planned.asset_id,
+                tx: before,
+            });
+        }
+    }
+
+    let held: HashSet<String> = PortfolioAssetRepo::new(pool)
+        .get_multi_by_portfolio_id(portfolio_id)
+        .await?
+        .into_iter()
+        .map(|row| row.asset_id)
+        .collect();
+    plan.new_portfolio_assets = plan
+        .creates
+        .iter()
+        .map(|p| p.asset_id.clone())
+        .filter(|asset_id| !held.contains(asset_id))
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+    Ok(plan)
+}
+
+/// Keeps a dry run plan so it can be committed later, returns its token.
+pub async fn save_import_plan(
+    redis_conn: &ConnectionManager,
+    plan: &ImportPlan,
+) -> Result<String, AppError> {
+    let token = Uuid::new_v4().to_string();
+    let mut redis_conn = redis_conn.clone();
+    let _: () = redis_conn
+        .set_ex(
+            plan_key(&token),
+            serde_json::to_string(plan)?,
+            IMPORT_PLAN_TTL_SECS,
+        )
+        .await?;
+    Ok(token)
+}
+
+/// Returns a saved plan, it is kept until `delete_import_plan`.
+pub async fn get_import_plan(
+    redis_conn: &ConnectionManager,
+    token: &str,
+) -> Result<Option<ImportPlan>, AppError> {
+    let mut redis_conn = redis_conn.clone();
+    let value: Option<String> = redis_conn.get(plan_key(token)).await?;
+    Ok(value.map(|v| serde_json::from_str(&v)).transpose()?)
+}
+
+/// Claims a saved plan for committing. Returns `false` when it is already
+/// being or was committed, so each plan is applied at most once.
+pub async fn lock_import_plan(
+    redis_conn: &ConnectionManager,
+    token: &str,
+) -> Result<bool, AppError> {
+    let mut redis_conn = redis_conn.clone();
+    let acquired: Option<String> = redis::cmd("SET")
+        .arg(plan_lock_key(token))
+        .arg(1)
+        .arg("NX")
+        .arg("EX")
+        .arg(IMPORT_PLAN_TTL_SECS)
+        .query_async(&mut redis_conn)
+        .await?;
+    Ok(acquired.is_some())
+}
+
+/// Lets a plan that failed to apply be committed again
+pub async fn unlock_import_plan(
+    redis_conn: &ConnectionManager,
+    token: &str,
+) -> Result<(), AppError> {
+    let mut redis_conn = redis_conn.clone();
+    let _: () = redis_conn.del(plan_lock_key(token)).await?;
+    Ok(())
+}
+
+/// Removes a committed plan. Its lock is left to expire, a commit that read
+/// the plan before it was removed still finds it taken.
+pub async fn delete_import_plan(
+    redis_conn: &ConnectionManager,
+    token: &str,
+) -> Result<(), AppError> {
+    let mut redis_conn = redis_conn.clone();
+    let _: () = redis_conn.del(plan_key(token)).await?;
+    Ok(())
+}
+
+/// Refuses plans made before the transactions they touch changed or holding
+/// transactions without an FX rate, then writes them as a new import batch,
+/// with the assets and portfolio assets they need, and recomputes the stats of
+/// every asset involved.
+pub async fn apply_import_plan(
+    state: &AppState,
+    plan: ImportPlan,
+    job: Option<ImportJobClaim>,
+) -> Result<ImportBatchRow, AppError> {
+    let pool = state.pool.clone();
+    let tx_repo = TransactionRepo::new(pool.clone());
+    if !plan.updates.is_empty() {
+        let tx_ids: Vec<i64> = plan.updates.iter().map(|u| u.tx_id).collect();
+        let current: HashMap<i64, OffsetDateTime> = tx_repo
+            .get_multi_by_ids(&tx_ids)
+            .await?
+            .into_iter()
+            .map(|row| (row.id, row.updated_at))
+            .collect();
+        if plan
+            .updates
+            .iter()
+            .any(|u| current.get(&u.tx_id) != Some(&u.updated_at))
+        {
+            return Err(AppError::Conflict(
+                "Transactions changed since the import was previewed".to_string(),
+            ));
+        }
+    }
+    let new_external_ids: Vec<String> = plan
+        .creates
+        .iter()
+        .filter_map(|p| p.tx.external_id.clone())
+        .collect();
+    if !new_external_ids.is_empty() {
+        let imported = tx_repo
+            .get_multi_by_external_ids(plan.portfolio_id, &new_external_ids)
+            .await?;
+        if imported.iter().any(|row| {
+            plan.creates
+                .iter()
+                .any(|p| p.asset_id == row.asset_id && p.tx.external_id == row.external_id)
+        }) {
+            return Err(AppError::Conflict(
+                "Transactions were imported since the import was previewed".to_string(),
+            ));
+        }
+    }
+    ensure_portfolio_fx_rates(state, plan.portfolio_id, &plan.fx_days()).await?;
+
+    let mut touched_assets: BTreeSet<String> = BTreeSet::new();
+    let mut changes: Vec<CreateImportBatchChange> = Vec::new();
+    let mut creates = Vec::with_capacity(plan.creates.len());
+    for mut planned in plan.creates {
+        let tx_id = SNOWFLAKE_GENERATOR.generate().unwrap();
+        planned.tx.id = Some(tx_id);
+        touched_assets.insert(planned.asset_id.clone());
+        changes.push(CreateImportBatchChange {
+            tx_id,
+            asset_id: planned.asset_id.clone(),
+            action: ImportChangeAction::Created,
+            previous: None,
+            previous_batch_id: None,
+            after: serde_json::to_value(&planned.tx)?,
+        });
+        creates.push(planned);
+    }
+    let mut updates = Vec::with_capacity(plan.updates.len());
+    for update in plan.updates {
+        touched_assets.insert(update.asset_id.clone());
+        changes.push(CreateImportBatchChange {
+            tx_id: update.tx_id,
+            asset_id: update.asset_id,
+            action: ImportChangeAction::Updated,
+            previous: Some(serde_json::to_value(&update.before)?),
+            previous_batch_id: update.previous_batch_id,
+            after: serde_json::to_value(&update.after)?,
+        });
+        updates.push((update.tx_id, update.after));
+    }
+    let batch = ImportBatchRepo::new(pool.clone())
+        .apply(ApplyImportBatch {
+            batch: CreateImportBatch {
+                user_id: plan.user_id,
+                portfolio_id: plan.portfolio_id,
+                source: plan.source.to_string(),
+                file_hash: plan.file_hash,
+                created_count: creates.len() as i32,
+                updated_count: updates.len() as i32,
+                unchanged_count: plan.unchanged.len() as i32,
+            },
+            new_assets: plan.new_assets,
+            new_portfolio_assets: plan.new_portfolio_assets,
+            creates,
+            updates,
+            changes,
+            job,
+        })
+        .await?;
+
+    for asset_id in touched_assets {
+        update_portfolio_asset_stat(pool.clone(), plan.portfolio_id, &asset_id).await?;
+    }
+    Ok(batch)
+}
+
+/// Whether the transaction is still as the import wrote it. Timestamps are
+/// stored to the microsecond.
+fn unchanged_since_import(current: &BaseTransactionInfo, after: &BaseTransactionInfo) -> bool {
+    let micros = |at: OffsetDateTime| at.unix_timestamp_nanos() / 1000;
+    current.tx_type == after.tx_type
+        && current.quantity == after.quantity
+        && current.price == after.price
+        && current.fees == after.fees
+        && current.currency == after.currency
+        && micros(current.executed_at) == micros(after.executed_at)
+        && current.notes == after.notes
+}
+
+/// Undoes an import: deletes the transactions it created and puts back the ones
+/// it modified, then recomputes the stats of every asset involved. Refused once
+/// a later import modified the same transactions, or once one of them was
+/// edited by hand.
+pub async fn rollback_import_batch(
+    pool: PgPool,
+    batch: &ImportBatchRow,
+) -> Result<ImportBatchRow, AppError> {
+    if batch.status != ImportBatchStatus::Completed.to_string() {
+        return Err(AppError::Conflict(
+            "Import is already rolled back".to_string(),
+        ));
+    }
+    let batch_repo = ImportBatchRepo::new(pool.clone());
+    let changes = batch_repo.get_changes(batch.id).await?;
+    let tx_ids: Vec<i64> = changes.iter().map(|c| c.tx_id).collect();
+    let current: HashMap<i64, TransactionRow> = TransactionRepo::new(pool.clone())
+        .get_multi_by_ids(&tx_ids)
+        .await?
+        .into_iter()
+        .map(|row| (row.id, row))
+        .collect();
+    if current
+        .values()
+        .any(|row| row.import_batch_id != Some(batch.id))
+    {
+        return Err(AppError::Conflict(
+            "A later import modified these transactions, roll it back first".to_string(),
+        ));
+    }
+    for change in changes.iter() {
+        let Some(row) = current.get(&change.tx_id) else {
+            continue;
+        };
+        let after: BaseTransactionInfo = serde_json::from_value(change.after.clone())?;
+        if !unchanged_since_import(&BaseTransactionInfo::from_db_row(row)?, &after) {
+            return Err(AppError::Conflict(format!(
+                "Transaction {} was edited after the import, rolling back would lose the edit",
+                row.id
+            )));
+        }
+    }
+
+    let mut touched_assets: BTreeSet<String> = BTreeSet::new();
+    let mut created_tx_ids = Vec::new();
+    let mut restores = Vec::new();
+    for change in changes {
+        // Transactions deleted since the import have nothing left to undo
+        if !current.contains_key(&change.tx_id) {
+            continue;
+        }
+        touched_assets.insert(change.asset_id);
+        match change.action.parse()? {
+            ImportChangeAction::Created => created_tx_ids.push(change.tx_id),
+            ImportChangeAction::Updated => {
+                let Some(previous) = change.previous else {
+                    continue;
+                };
+                let previous: BaseTransactionInfo = serde_json::from_value(previous)?;
+                restores.push((change.tx_id, previous, change.previous_batch_id));
+            }
+        }
+    }
+    let (batch, removed_assets) = batch_repo
+        .rollback(batch.id, &created_tx_ids, &restores)
+        .await?;
+    // Portfolio assets removed with the batch have no stats left to update
+    let removed_assets: BTreeSet<String> = removed_assets.into_iter().collect();
+    for asset_id in touched_assets.difference(&removed_assets) {
+        update_portfolio_asset_stat(pool.clone(), batch.portfolio_id, asset_id).await?;
+    }
+    Ok(batch)
+}
+
+/// SHA-256 of an imported file, kept on its batch
+pub fn file_hash(contents: &str) -> String {
+    format!("{:x}", Sha256::digest(contents.as_bytes()))
+}
+
+/// Saves the plan of a dry run and returns its preview with the commit token, or
+/// applies the plan right away.
+pub async fn preview_or_apply(
+    state: &AppState,
+    plan: ImportPlan,
+    dry_run: bool,
+) -> Result<ImportResponse, AppError> {
+    if dry_run {
+        let token = save_import_plan(&state.redis_conn, &plan).await?;
+        return Ok(ImportResponse::from_plan(&plan, true, Some(token)));
+    }
+    let mut response = ImportResponse::from_plan(&plan, false, None);
+    let batch = apply_import_plan(state, plan, None).await?;
+    response.batch_id = Some(batch.id.to_string());
+    Ok(response)
+}
@@ -0,0 +1,73 @@
+use sqlx::PgPool;
+use time::OffsetDateTime;
+use tracing::info;
+
+use crate::{
+    db::repositories::personal_access_token::PersonalAccessTokenRepo,
+    models::dto::personal_access_token::{
+        CreatePersonalAccessToken, CreatePersonalAccessTokenRequest,
+        CreatedPersonalAccessTokenResponse, PersonalAccessTokenResponse,
+    },
+    utils::{
+        error::AppError,
+        token::{generate_token, hash_token},
+    },
+};
+
+/// Personal access tokens start with this, telling them apart from JWTs
+pub const PERSONAL_ACCESS_TOKEN_PREFIX: &str = "pat_";
+const MAX_NAME_LEN: usize = 100;
+
+/// Creates a named token of the user with the requested scopes. The token is
+/// returned once, only its hash is stored.
+pub async fn create_personal_access_token(
+    pool: PgPool,
+    user_id: i64,
+    req: CreatePersonalAccessTokenRequest,
+) -> Result<CreatedPersonalAccessTokenResponse, AppError> {
+    let name = req.name.trim().to_string();
+    if name.is_empty() || name.chars().count() > MAX_NAME_LEN {
+        return Err(AppError::InvalidInput(format!(
+            "Name must be between 1 and {} characters",
+            MAX_NAME_LEN
+        )));
+    }
+    let mut scopes = Vec::with_capacity(req.scopes.len());
+    for scope in req.scopes {
+        if !scopes.contains(&scope) {
+            scopes.push(scope);
+        }
+    }
+    if scopes.is_empty() {
+        return Err(AppError::InvalidInput(
+            "At least one scope is required".to_string(),
+        ));
+    }
+    if let Some(expires_at) = req.expires_at
+        && expires_at <= OffsetDateTime::now_utc()
+    {
+        return Err(AppError::InvalidInput(
+            "Expiry must be in the future".to_string(),
+        ));
+    }
+
+    let token = format!("{}{}", PERSONAL_ACCESS_TOKEN_PREFIX, generate_token());
+    let row = PersonalAccessTokenRepo::new(pool)
+        .create_one(CreatePersonalAccessToken {
+            user_id,
+            name,
+            token_hash: hash_token(&token),
+            token_prefix: token[..PERSONAL_ACCESS_TOKEN_PREFIX.len() + 8].to_string(),
+            scopes,
+            expires_at: req.expires_at,
+        })
+        .await?;
+    info!(
+        "User {} created personal access token {} with scopes {:?}",
+        user_id, row.id, row.scopes
+    );
+    Ok(CreatedPersonalAccessTokenResponse {
+        token,
+        details: PersonalAccessTokenResponse::from_db_row(row),
+    })
+}
@@ -2,8 +2,15 @@ use rust_decimal::Decimal;
 use sqlx::PgPool;
 
 use crate::{
-    db::repositories::{portfolio_asset::PortfolioAssetRepo, transaction::TransactionRepo},
-    models::{domain::transaction::TxType, dto::portfolio_asset::UpdatePortfolioAsset},
+    biz::{cost_basis::match_lots, fx::convert_tx_rows},
+    db::repositories::{
+        portfolio::PortfolioRepo, portfolio_asset::PortfolioAssetRepo, transaction::TransactionRepo,
+    },
+    models::{
+        common::currency::Currency,
+        domain::{portfolio::CostBasisMethod, transaction::TxType},
+        dto::portfolio_asset::UpdatePortfolioAsset,
+    },
     utils::error::AppError,
 };
 
@@ -12,20 +19,33 @@ pub async fn update_portfolio_asset_stat(
     pfl_id: i64,
     asset_id: &String,
 ) -> Result<(), AppError> {
+    let pfl_repo = PortfolioRepo::new(pool.clone());
+    let (cost_basis_method, base_currency): (CostBasisMethod, Currency) =
+        match pfl_repo.get_one_by_id(pfl_id).await? {
+            Some(pfl_row) => (
+                pfl_row.cost_basis_method.parse()?,
+                pfl_row.base_currency.parse()?,
+            ),
+            None => (CostBasisMethod::default(), Currency::default()),
+        };
     let tx_repo = TransactionRepo::new(pool.clone());
     let tx_rows = tx_repo
-        .get_multi_txs_by_portfolio_and_asset_with_paging(pfl_id, asset_id, 1, 10_000)
+        .get_all_txs_by_portfolio_and_asset(pfl_id, asset_id)
         .await?;
+    // Stats are kept in the base currency of the portfolio
+    let tx_rows = convert_tx_rows(pool.clone(), tx_rows, base_currency).await?;
 
     let mut holding_amount = Decimal::ZERO;
+    let mut bought_amount = Decimal::ZERO;
     let mut total_cost = Decimal::ZERO;
     let mut sold_amount = Decimal::ZERO;
     let mut total_revenue = Decimal::ZERO;
-    for row in tx_rows {
-        let tx_type: TxType = row.tx_type.parse().unwrap();
+    for row in tx_rows.iter() {
+        let tx_type: TxType = row.tx_type.parse()?;
         match tx_type {
             TxType::Buy => {
                 holding_amount += row.quantity;
+                bought_amount += row.quantity;
                 total_cost += row.price * row.quantity + row.fees;
             }
             TxType::Sell => {
@@ -41,8 +61,14 @@ pub async fn update_portfolio_asset_stat(
             }
         }
     }
-    let avg_buy_price = total_cost / holding_amount;
-    let avg_sell_price = total_revenue / holding_amount;
+    let avg_buy_price = total_cost
+        .checked_div(bought_amount)
+        .unwrap_or(Decimal::ZERO);
+    let avg_sell_price = total_revenue
+        .checked_div(sold_amount)
+        .unwrap_or(Decimal::ZERO);
+
+    let lots = match_lots(cost_basis_method, &tx_rows)?;
     let update_pa = UpdatePortfolioAsset {
         portfolio_id: pfl_id,
         asset_id: asset_id.clone(),
@@ -52,8 +78,22 @@ pub async fn update_portfolio_asset_stat(
         sold_amount,
         total_revenue,
         avg_sell_price,
+        cost_basis: lots.holding_cost_basis(),
+        realized_pnl: lots.realized_pnl(),
+        open_lots: lots.open_lots,
+        disposals: lots.disposals,
     };
     let pa_repo = PortfolioAssetRepo::new(pool.clone());
     pa_repo.update(&update_pa).await?;
     Ok(())
 }
+
+/// Recomputes every asset of a portfolio, e.g. after its cost basis method changed.
+pub async fn update_portfolio_stats(pool: PgPool, pfl_id: i64) -> Result<(), AppError> {
+    let pa_repo = PortfolioAssetRepo::new(pool.clone());
+    let pa_rows = pa_repo.get_multi_by_portfolio_id(pfl_id).await?;
+    for pa_row in pa_rows {
+        update_portfolio_asset_stat(pool.clone(), pfl_id, &pa_row.asset_id).await?;
+    }
+    Ok(())
+}
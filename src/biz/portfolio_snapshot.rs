@@ -0,0 +1,198 @@
+use std::collections::{BTreeMap, HashMap};
+
+use rust_decimal::Decimal;
+use sqlx::PgPool;
+use time::{Date, Duration, OffsetDateTime};
+use tracing::{error, info};
+
+use crate::{
+    biz::{
+        cost_basis::match_lots,
+        fx::{FxRates, convert_tx_rows},
+        price_history::{price_on_or_before, sync_price_history},
+    },
+    db::repositories::{
+        asset::AssetRepo, portfolio::PortfolioRepo, portfolio_asset::PortfolioAssetRepo,
+        portfolio_snapshot::PortfolioSnapshotRepo, price_history::PriceHistoryRepo,
+        transaction::TransactionRepo,
+    },
+    models::{
+        common::currency::Currency,
+        database::{
+            portfolio::PortfolioRow, portfolio_snapshot::PortfolioSnapshotRow,
+            transaction::TransactionRow,
+        },
+        domain::portfolio::CostBasisMethod,
+        dto::portfolio_snapshot::{CreatePortfolioSnapshot, HistoryInterval},
+    },
+    state::AppState,
+    utils::error::AppError,
+};
+
+const MAX_BACKFILL_DAYS: i64 = 365;
+// How far back to look for a close price when an asset has no price on a given day
+const PRICE_LOOKBACK_DAYS: i64 = 30;
+
+/// Values a portfolio at the end of each day in `[from, to]` by replaying its
+/// transactions up to that day and pricing the holdings at the stored daily close,
+/// all in the base currency of the portfolio.
+pub async fn write_portfolio_snapshots(
+    pool: PgPool,
+    pfl_row: &PortfolioRow,
+    from: Date,
+    to: Date,
+) -> Result<u64, AppError> {
+    let method: CostBasisMethod = pfl_row.cost_basis_method.parse()?;
+    let base_currency: Currency = pfl_row.base_currency.parse()?;
+    let price_currency = Currency::USD;
+    let fx_rates = FxRates::load(pool.clone(), &[price_currency, base_currency]).await?;
+    let pa_rows = PortfolioAssetRepo::new(pool.clone())
+        .get_multi_by_portfolio_id(pfl_row.id)
+        .await?;
+    let tx_repo = TransactionRepo::new(pool.clone());
+    let mut asset_txs: HashMap<String, Vec<TransactionRow>> = HashMap::new();
+    for pa_row in pa_rows.iter() {
+        let tx_rows = tx_repo
+            .get_all_txs_by_portfolio_and_asset(pfl_row.id, &pa_row.asset_id)
+            .await?;
+        let tx_rows = convert_tx_rows(pool.clone(), tx_rows, base_currency).await?;
+        asset_txs.insert(pa_row.asset_id.clone(), tx_rows);
+    }
+
+    let asset_ids: Vec<String> = asset_txs.keys().cloned().collect();
+    let price_rows = PriceHistoryRepo::new(pool.clone())
+        .get_multi_in_range(
+            &asset_ids,
+            &price_currency.to_string(),
+            from - Duration::days(PRICE_LOOKBACK_DAYS),
+            to,
+        )
+        .await?;
+    let mut asset_prices: HashMap<String, BTreeMap<Date, Decimal>> = HashMap::new();
+    for row in price_rows {
+        asset_prices
+            .entry(row.asset_id)
+            .or_default()
+            .insert(row.price_date, row.close_price);
+    }
+
+    let mut snapshots = Vec::new();
+    let mut date = from;
+    while date <= to {
+        let end_of_day = date.next_day().unwrap_or(date).midnight().assume_utc();
+        let mut has_txs = false;
+        let mut holdings_value = Decimal::ZERO;
+        let mut cost_basis = Decimal::ZERO;
+        let mut realized_pnl = Decimal::ZERO;
+        for (asset_id, tx_rows) in asset_txs.iter() {
+            let count = tx_rows.partition_point(|row| row.executed_at < end_of_day);
+            if count == 0 {
+                continue;
+            }
+            has_txs = true;
+            let lots = match_lots(method, &tx_rows[..count])?;
+            let holding: Decimal = lots
+                .open_lots
+                .iter()
+                .map(|lot| lot.remaining_quantity)
+                .sum();
+            let price = asset_prices
+                .get(asset_id)
+                .and_then(|prices| price_on_or_before(prices, date))
+                .unwrap_or(Decimal::ZERO);
+            let price = fx_rates.convert(price, price_currency, base_currency, date)?;
+            holdings_value += holding * price;
+            cost_basis += lots.holding_cost_basis();
+            realized_pnl += lots.realized_pnl();
+        }
+        if has_txs {
+            snapshots.push(CreatePortfolioSnapshot {
+                portfolio_id: pfl_row.id,
+                snapshot_date: date,
+                currency: base_currency.to_string(),
+                holdings_value,
+                cost_basis,
+                realized_pnl,
+                unrealized_pnl: holdings_value - cost_basis,
+            });
+        }
+        date = match date.next_day() {
+            Some(next) => next,
+            None => break,
+        };
+    }
+    PortfolioSnapshotRepo::new(pool)
+        .upsert_multi(&snapshots)
+        .await
+}
+
+/// Refreshes prices of every held asset, then writes the missing daily snapshots
+/// of every portfolio. Yesterday and today are always rewritten since their
+/// closes may have changed since the last run.
+pub async fn refresh_daily_snapshots(state: &AppState) -> Result<(), AppError> {
+    let currency = Currency::USD.to_string();
+    let assets = AssetRepo::new(state.pool.clone()).get_multi_held().await?;
+    for asset in assets.iter() {
+        if let Err(e) = sync_price_history(state, asset, &currency).await {
+            error!("Sync price history of {} failed: {}", asset.id, e);
+        }
+    }
+
+    let today = OffsetDateTime::now_utc().date();
+    let snapshot_repo = PortfolioSnapshotRepo::new(state.pool.clone());
+    let pfl_rows = PortfolioRepo::new(state.pool.clone()).get_all().await?;
+    for pfl_row in pfl_rows.iter() {
+        let yesterday = today - Duration::days(1);
+        let from = match snapshot_repo.get_latest_date(pfl_row.id).await? {
+            Some(latest) => latest.min(yesterday),
+            None => today - Duration::days(MAX_BACKFILL_DAYS),
+        };
+        let written = write_portfolio_snapshots(state.pool.clone(), pfl_row, from, today).await?;
+        info!("Wrote {} snapshots of portfolio {}", written, pfl_row.id);
+    }
+    Ok(())
+}
+
+/// Rewrites the whole snapshot history of a portfolio, e.g. after its base
+/// currency changed.
+pub async fn rebuild_portfolio_snapshots(
+    pool: PgPool,
+    pfl_row: &PortfolioRow,
+) -> Result<u64, AppError> {
+    PortfolioSnapshotRepo::new(pool.clone())
+        .delete_by_portfolio_id(pfl_row.id)
+        .await?;
+    let today = OffsetDateTime::now_utc().date();
+    write_portfolio_snapshots(
+        pool,
+        pfl_row,
+        today - Duration::days(MAX_BACKFILL_DAYS),
+        today,
+    )
+    .await
+}
+
+/// Keeps the last snapshot of every day, week or month.
+pub fn downsample_snapshots(
+    rows: Vec<PortfolioSnapshotRow>,
+    interval: HistoryInterval,
+) -> Vec<PortfolioSnapshotRow> {
+    let bucket = |date: Date| match interval {
+        HistoryInterval::Day => (date.year(), date.ordinal() as u32),
+        HistoryInterval::Week => {
+            let (year, week, _) = date.to_iso_week_date();
+            (year, week as u32)
+        }
+        HistoryInterval::Month => (date.year(), date.month() as u32),
+    };
+    let mut result: Vec<PortfolioSnapshotRow> = Vec::new();
+    for row in rows {
+        match result.last() {
+            Some(last) if bucket(last.snapshot_date) == bucket(row.snapshot_date) => {
+                *result.last_mut().unwrap() = row;
+            }
+            _ => result.push(row),
+        }
+    }
+    result
+}
@@ -0,0 +1,102 @@
+use std::collections::BTreeMap;
+
+use rust_decimal::Decimal;
+use time::{Date, Duration, OffsetDateTime};
+use tracing::{info, warn};
+
+use crate::{
+    db::repositories::{asset::AssetRepo, price_history::PriceHistoryRepo},
+    models::{common::currency::Currency, database::asset::AssetRow},
+    state::AppState,
+    utils::error::AppError,
+};
+
+const MAX_HISTORY_DAYS: i64 = 365;
+// Days fetched one by one when the daily prices of the provider fail
+const MAX_FALLBACK_DAYS: i64 = 7;
+
+/// Brings the stored daily close prices of an asset up to today.
+pub async fn sync_price_history(
+    state: &AppState,
+    asset: &AssetRow,
+    currency: &str,
+) -> Result<u64, AppError> {
+    let ph_repo = PriceHistoryRepo::new(state.pool.clone());
+    let today = OffsetDateTime::now_utc().date();
+    let days = match ph_repo.get_latest_date(&asset.id, currency).await? {
+        // Always refetch the latest stored day, its close may have been partial
+        Some(latest) => (today - latest).whole_days().clamp(1, MAX_HISTORY_DAYS),
+        None => MAX_HISTORY_DAYS,
+    };
+
+    let provider = &state.clients.price_provider;
+    let prices = match provider
+        .get_daily_prices(&asset.external_id, currency, days as u32)
+        .await
+    {
+        Ok(prices) => prices,
+        Err(e) => {
+            warn!(
+                "Daily prices of {} failed, falling back to history: {}",
+                asset.id, e
+            );
+            let mut prices = BTreeMap::new();
+            for offset in 1..=days.min(MAX_FALLBACK_DAYS) {
+                let date = today - Duration::days(offset);
+                if let Some(price) = provider
+                    .get_historical_price(&asset.external_id, currency, date)
+                    .await?
+                {
+                    prices.insert(date, price);
+                }
+            }
+            prices
+        }
+    };
+    let prices: Vec<(Date, Decimal)> = prices.into_iter().collect();
+    let saved = ph_repo
+        .upsert_multi(&asset.id, currency, provider.source(), &prices)
+        .await?;
+    info!(
+        "Saved {} daily prices of {} in {}",
+        saved, asset.id, currency
+    );
+    Ok(saved)
+}
+
+/// Fetches the spot price of every held asset in one batch, which also refreshes
+/// the price cache, and stores it as today's close so far.
+pub async fn refresh_spot_prices(state: &AppState) -> Result<(), AppError> {
+    let currency = Currency::USD.to_string();
+    let assets = AssetRepo::new(state.pool.clone()).get_multi_held().await?;
+    let external_ids: Vec<String> = assets.iter().map(|a| a.external_id.clone()).collect();
+    let prices = state
+        .clients
+        .prices
+        .get_spot_prices(&external_ids, &currency)
+        .await?;
+
+    let today = OffsetDateTime::now_utc().date();
+    let ph_repo = PriceHistoryRepo::new(state.pool.clone());
+    let source = state.clients.price_provider.source();
+    let mut saved = 0;
+    for asset in assets.iter() {
+        let Some(spot) = prices.get(&asset.external_id) else {
+            continue;
+        };
+        // A stale price is older than what may already be stored for today
+        if spot.stale {
+            continue;
+        }
+        saved += ph_repo
+            .upsert_multi(&asset.id, &currency, source, &[(today, spot.price)])
+            .await?;
+    }
+    info!("Refreshed spot prices of {}/{} assets", saved, assets.len());
+    Ok(())
+}
+
+/// Close price of the asset on the given day, or the latest close before it.
+pub fn price_on_or_before(prices: &BTreeMap<Date, Decimal>, date: Date) -> Option<Decimal> {
+    prices.range(..=date).next_back().map(|(_, price)| *price)
+}
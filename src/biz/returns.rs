@@ -0,0 +1,412 @@
+use std::collections::{BTreeMap, HashMap};
+
+use rust_decimal::{Decimal, prelude::ToPrimitive};
+use sqlx::PgPool;
+use time::{Date, Duration, OffsetDateTime};
+
+use crate::{
+    biz::{
+        fx::{FxRates, convert_tx_rows},
+        price_history::price_on_or_before,
+    },
+    db::repositories::{
+        portfolio_asset::PortfolioAssetRepo, price_history::PriceHistoryRepo,
+        transaction::TransactionRepo,
+    },
+    models::{
+        common::currency::Currency,
+        database::transaction::TransactionRow,
+        domain::transaction::TxType,
+        dto::performance::{PerformancePeriod, PerformanceResponseItem},
+    },
+    utils::error::AppError,
+};
+
+const DAYS_PER_YEAR: f64 = 365.0;
+const XIRR_MAX_ITERATIONS: usize = 100;
+const XIRR_TOLERANCE: f64 = 1e-7;
+
+/// End of day value of the tracked holdings and the net external cash flow of that
+/// day. Buying or transferring in is money put into the portfolio (positive),
+/// selling or transferring out is money taken out (negative).
+#[derive(Debug, Clone)]
+pub struct DailyValue {
+    pub date: Date,
+    pub value: f64,
+    pub net_flow: f64,
+}
+
+/// Builds the daily valuation series of a portfolio, or of a single asset of it,
+/// from its first transaction until `to`, in the given base currency.
+pub async fn daily_values(
+    pool: PgPool,
+    portfolio_id: i64,
+    asset_id: Option<&str>,
+    base_currency: Currency,
+    to: Date,
+) -> Result<Vec<DailyValue>, AppError> {
+    let asset_ids: Vec<String> = match asset_id {
+        Some(asset_id) => vec![asset_id.to_string()],
+        None => PortfolioAssetRepo::new(pool.clone())
+            .get_multi_by_portfolio_id(portfolio_id)
+            .await?
+            .into_iter()
+            .map(|pa_row| pa_row.asset_id)
+            .collect(),
+    };
+    let tx_repo = TransactionRepo::new(pool.clone());
+    let mut tx_rows: Vec<TransactionRow> = Vec::new();
+    for asset_id in asset_ids.iter() {
+        tx_rows.extend(
+            tx_repo
+                .get_all_txs_by_portfolio_and_asset(portfolio_id, asset_id)
+                .await?,
+        );
+    }
+    let tx_rows = convert_tx_rows(pool.clone(), tx_rows, base_currency).await?;
+    let Some(first_date) = tx_rows.iter().map(|row| row.executed_at.date()).min() else {
+        return Ok(Vec::new());
+    };
+    // Start one day early so the first day has a zero opening value
+    let from = first_date - Duration::days(1);
+
+    // Closes are stored in USD, convert them on their own day
+    let fx_rates = FxRates::load(pool.clone(), &[Currency::USD, base_currency]).await?;
+    let mut asset_prices: HashMap<String, BTreeMap<Date, Decimal>> = HashMap::new();
+    let price_rows = PriceHistoryRepo::new(pool)
+        .get_multi_in_range(
+            &asset_ids,
+            &Currency::USD.to_string(),
+            from - Duration::days(30),
+            to,
+        )
+        .await?;
+    for row in price_rows {
+        let close = fx_rates.convert(
+            row.close_price,
+            Currency::USD,
+            base_currency,
+            row.price_date,
+        )?;
+        asset_prices
+            .entry(row.asset_id)
+            .or_default()
+            .insert(row.price_date, close);
+    }
+    let close_price = |asset_id: &str, date: Date| {
+        asset_prices
+            .get(asset_id)
+            .and_then(|prices| price_on_or_before(prices, date))
+            .unwrap_or(Decimal::ZERO)
+    };
+
+    let mut txs_by_date: BTreeMap<Date, Vec<&TransactionRow>> = BTreeMap::new();
+    for row in tx_rows.iter() {
+        txs_by_date
+            .entry(row.executed_at.date())
+            .or_default()
+            .push(row);
+    }
+
+    let mut quantities: HashMap<&str, Decimal> = HashMap::new();
+    let mut values = Vec::new();
+    let mut date = from;
+    while date <= to {
+        let mut net_flow = Decimal::ZERO;
+        for row in txs_by_date.get(&date).into_iter().flatten() {
+            let tx_type: TxType = row.tx_type.parse()?;
+            // Transfers carry no cash, value them at their price or the close of the day
+            let unit_price = if row.price > Decimal::ZERO {
+                row.price
+            } else {
+                close_price(&row.asset_id, date)
+            };
+            let quantity = quantities.entry(row.asset_id.as_str()).or_default();
+            match tx_type {
+                TxType::Buy => {
+                    *quantity += row.quantity;
+                    net_flow += row.price * row.quantity + row.fees;
+                }
+                TxType::Sell => {
+                    *quantity -= row.quantity;
+                    net_flow -= row.price * row.quantity - row.fees;
+                }
+                TxType::TransferIn => {
+                    *quantity += row.quantity;
+                    net_flow += unit_price * row.quantity;
+                }
+                TxType::TransferOut => {
+                    *quantity -= row.quantity;
+                    net_flow -= unit_price * row.quantity;
+                }
+            }
+        }
+        let value: Decimal = quantities
+            .iter()
+            .map(|(asset_id, quantity)| quantity.max(&Decimal::ZERO) * close_price(asset_id, date))
+            .sum();
+        values.push(DailyValue {
+            date,
+            value: value.to_f64().unwrap_or_default(),
+            net_flow: net_flow.to_f64().unwrap_or_default(),
+        });
+        date = match date.next_day() {
+            Some(next) => next,
+            None => break,
+        };
+    }
+    Ok(values)
+}
+
+/// First day included in the period ending on `today`.
+pub fn period_start(period: PerformancePeriod, today: Date, first_date: Date) -> Date {
+    let start = match period {
+        PerformancePeriod::OneDay => today,
+        PerformancePeriod::SevenDays => today - Duration::days(6),
+        PerformancePeriod::MonthToDate => today.replace_day(1).unwrap_or(today),
+        PerformancePeriod::YearToDate => today.replace_ordinal(1).unwrap_or(today),
+        PerformancePeriod::OneYear => today - Duration::days(364),
+        PerformancePeriod::AllTime => first_date,
+    };
+    start.max(first_date)
+}
+
+/// Chains the daily returns of the period so that the size and timing of cash
+/// flows do not affect the result. Flows are assumed to happen at the start of
+/// their day. `values` starts with the day before the period as its base.
+pub fn time_weighted_return(values: &[DailyValue]) -> Option<f64> {
+    let mut growth = 1.0;
+    let mut has_return = false;
+    for window in values.windows(2) {
+        let (prev, day) = (&window[0], &window[1]);
+        let invested = prev.value + day.net_flow;
+        if invested <= 0.0 {
+            continue;
+        }
+        growth *= day.value / invested;
+        has_return = true;
+    }
+    has_return.then_some(growth - 1.0)
+}
+
+/// Annualized internal rate of return of the investor cash flows over the period:
+/// the opening value and every contribution are paid in, every withdrawal and the
+/// closing value are paid out.
+pub fn money_weighted_return(values: &[DailyValue]) -> Option<f64> {
+    let (base, days) = values.split_first()?;
+    let last = days.last()?;
+    let mut flows: Vec<(Date, f64)> = Vec::new();
+    if base.value > 0.0 {
+        flows.push((base.date, -base.value));
+    }
+    for day in days {
+        if day.net_flow != 0.0 {
+            flows.push((day.date, -day.net_flow));
+        }
+    }
+    flows.push((last.date, last.value));
+    xirr(&flows)
+}
+
+fn xirr(flows: &[(Date, f64)]) -> Option<f64> {
+    let has_inflow = flows.iter().any(|(_, amount)| *amount > 0.0);
+    let has_outflow = flows.iter().any(|(_, amount)| *amount < 0.0);
+    if !has_inflow || !has_outflow {
+        return None;
+    }
+    let start = flows.iter().map(|(date, _)| *date).min()?;
+    let years: Vec<(f64, f64)> = flows
+        .iter()
+        .map(|(date, amount)| ((*date - start).whole_days() as f64 / DAYS_PER_YEAR, *amount))
+        .collect();
+    let npv = |rate: f64| -> f64 {
+        years
+            .iter()
+            .map(|(t, amount)| amount / (1.0 + rate).powf(*t))
+            .sum()
+    };
+    let d_npv = |rate: f64| -> f64 {
+        years
+            .iter()
+            .map(|(t, amount)| -t * amount / (1.0 + rate).powf(t + 1.0))
+            .sum()
+    };
+
+    // Newton's method first, it converges quickly for well behaved flows
+    let mut rate = 0.1;
+    for _ in 0..XIRR_MAX_ITERATIONS {
+        let value = npv(rate);
+        if value.abs() < XIRR_TOLERANCE {
+            return Some(rate);
+        }
+        let derivative = d_npv(rate);
+        if derivative == 0.0 || !derivative.is_finite() {
+            break;
+        }
+        let next = rate - value / derivative;
+        if !next.is_finite() || next <= -1.0 {
+            break;
+        }
+        rate = next;
+    }
+
+    // Fall back to bisection over a wide bracket
+    let (mut low, mut high) = (-0.9999, 1_000.0);
+    let (mut npv_low, npv_high) = (npv(low), npv(high));
+    if npv_low.signum() == npv_high.signum() {
+        return None;
+    }
+    for _ in 0..1_000 {
+        let mid = (low + high) / 2.0;
+        let npv_mid = npv(mid);
+        if npv_mid.abs() < XIRR_TOLERANCE || (high - low) / 2.0 < XIRR_TOLERANCE {
+            return Some(mid);
+        }
+        if npv_mid.signum() == npv_low.signum() {
+            low = mid;
+            npv_low = npv_mid;
+        } else {
+            high = mid;
+        }
+    }
+    None
+}
+
+/// Computes the performance of the series for each requested period ending today.
+pub fn compute_performance(
+    values: &[DailyValue],
+    periods: &[PerformancePeriod],
+) -> Vec<PerformanceResponseItem> {
+    let (Some(base), Some(last)) = (values.first(), values.last()) else {
+        return Vec::new();
+    };
+    let first_date = base.date.next_day().unwrap_or(base.date);
+    let today = last.date.min(OffsetDateTime::now_utc().date());
+    periods
+        .iter()
+        .map(|period| {
+            let start = period_start(*period, today, first_date);
+            // Include the day before the period as the opening value
+            let window: Vec<DailyValue> = values
+                .iter()
+                .filter(|v| v.date >= start - Duration::days(1) && v.date <= today)
+                .cloned()
+                .collect();
+            let start_value = window.first().map(|v| v.value).unwrap_or_default();
+            let end_value = window.last().map(|v| v.value).unwrap_or_default();
+            let net_cash_flow = window.iter().skip(1).map(|v| v.net_flow).sum();
+            PerformanceResponseItem {
+                period: *period,
+                start_date: start.to_string(),
+                end_date: today.to_string(),
+                start_value,
+                end_value,
+                net_cash_flow,
+                time_weighted_return: time_weighted_return(&window),
+                money_weighted_return: money_weighted_return(&window),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use time::Month;
+
+    use super::*;
+
+    fn day(offset: i64) -> Date {
+        Date::from_calendar_date(2023, Month::January, 1).unwrap() + Duration::days(offset)
+    }
+
+    fn daily(offset: i64, value: f64, net_flow: f64) -> DailyValue {
+        DailyValue {
+            date: day(offset),
+            value,
+            net_flow,
+        }
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-6,
+            "expected {expected}, got {actual}"
+        );
+    }
+
+    #[test]
+    fn xirr_converges_for_one_year() {
+        let rate = xirr(&[(day(0), -1_000.0), (day(365), 1_100.0)]).unwrap();
+        assert_close(rate, 0.1);
+    }
+
+    #[test]
+    fn xirr_converges_for_several_flows() {
+        let flows = [
+            (day(0), -1_000.0),
+            (day(100), -500.0),
+            (day(200), 300.0),
+            (day(365), 1_400.0),
+        ];
+        let rate = xirr(&flows).unwrap();
+        let npv: f64 = flows
+            .iter()
+            .map(|(date, amount)| {
+                amount / (1.0 + rate).powf((*date - day(0)).whole_days() as f64 / DAYS_PER_YEAR)
+            })
+            .sum();
+        assert!(npv.abs() < 1e-6, "npv {npv} at rate {rate}");
+    }
+
+    #[test]
+    fn xirr_converges_for_a_total_loss() {
+        let rate = xirr(&[(day(0), -1_000.0), (day(365), 1.0)]).unwrap();
+        assert_close(rate, -0.999);
+    }
+
+    #[test]
+    fn xirr_has_no_rate_without_a_root() {
+        // The paid in amounts outweigh the payout at any rate
+        let flows = [(day(0), -100.0), (day(365), 50.0), (day(730), -100.0)];
+        assert_eq!(xirr(&flows), None);
+    }
+
+    #[test]
+    fn xirr_needs_flows_in_both_directions() {
+        assert_eq!(xirr(&[]), None);
+        assert_eq!(xirr(&[(day(0), -1_000.0)]), None);
+        assert_eq!(xirr(&[(day(0), -1_000.0), (day(365), -100.0)]), None);
+    }
+
+    #[test]
+    fn returns_of_empty_or_single_day_series() {
+        assert_eq!(time_weighted_return(&[]), None);
+        assert_eq!(money_weighted_return(&[]), None);
+        let single = [daily(0, 1_000.0, 1_000.0)];
+        assert_eq!(time_weighted_return(&single), None);
+        assert_eq!(money_weighted_return(&single), None);
+        assert!(compute_performance(&[], &[PerformancePeriod::AllTime]).is_empty());
+    }
+
+    #[test]
+    fn twr_ignores_external_flows() {
+        // 10% on the first day, then 1000 is added and the whole grows 10% again
+        let values = [
+            daily(0, 100.0, 0.0),
+            daily(1, 110.0, 0.0),
+            daily(2, 1_221.0, 1_000.0),
+        ];
+        assert_close(time_weighted_return(&values).unwrap(), 0.21);
+    }
+
+    #[test]
+    fn twr_skips_days_with_nothing_invested() {
+        let values = [
+            daily(0, 0.0, 0.0),
+            daily(1, 0.0, 0.0),
+            daily(2, 105.0, 100.0),
+            daily(3, 0.0, -105.0),
+        ];
+        assert_close(time_weighted_return(&values).unwrap(), 0.05);
+    }
+}
@@ -0,0 +1,357 @@
+use std::collections::BTreeMap;
+
+use rust_decimal::Decimal;
+use sqlx::PgPool;
+use time::{Date, Month, OffsetDateTime};
+
+use crate::{
+    db::repositories::realized_gain::RealizedGainRepo,
+    models::{
+        database::realized_gain::TaxReportGainRow,
+        dto::tax_report::{HoldingTerm, TaxReportItem, TaxReportResponse, TaxReportTotal},
+    },
+    utils::{csv::write_csv, error::AppError},
+};
+
+// Columns of IRS Form 8949, the currency and portfolio are added as a report
+// may span portfolios of several base currencies
+const FORM_8949_HEADER: &[&str] = &[
+    "Term",
+    "(a) Description of property",
+    "(b) Date acquired",
+    "(c) Date sold or disposed of",
+    "(d) Proceeds",
+    "(e) Cost or other basis",
+    "(h) Gain or (loss)",
+    "Currency",
+    "Portfolio",
+];
+
+fn start_of_year(year: i32) -> Result<OffsetDateTime, AppError> {
+    Date::from_calendar_date(year, Month::January, 1)
+        .map(|date| date.midnight().assume_utc())
+        .map_err(|_| AppError::InvalidInput(format!("Invalid tax year: {}", year)))
+}
+
+/// Realized gains of the disposals made in the calendar year, from the lot
+/// matching ledger of the portfolios, with their totals per term and currency.
+pub async fn build_tax_report(
+    pool: PgPool,
+    user_id: i64,
+    portfolio_id: Option<i64>,
+    year: i32,
+    long_term_threshold_days: i64,
+) -> Result<TaxReportResponse, AppError> {
+    let from = start_of_year(year)?;
+    let to = start_of_year(year + 1)?;
+    let rows = RealizedGainRepo::new(pool)
+        .get_all_by_owner_disposed_between(user_id, portfolio_id, from, to)
+        .await?;
+    Ok(tax_report_from_rows(
+        rows,
+        portfolio_id,
+        year,
+        long_term_threshold_days,
+    ))
+}
+
+/// Classifies the ledger rows of a tax year, ordered by disposal date, and
+/// totals them per term and currency.
+fn tax_report_from_rows(
+    rows: Vec<TaxReportGainRow>,
+    portfolio_id: Option<i64>,
+    year: i32,
+    long_term_threshold_days: i64,
+) -> TaxReportResponse {
+    let mut totals: BTreeMap<(HoldingTerm, String), (usize, Decimal, Decimal, Decimal)> =
+        BTreeMap::new();
+    let mut items = Vec::with_capacity(rows.len());
+    for row in rows {
+        let term = HoldingTerm::classify(row.holding_period_days, long_term_threshold_days);
+        let total = totals.entry((term, row.base_currency.clone())).or_default();
+        total.0 += 1;
+        total.1 += row.proceeds;
+        total.2 += row.cost_basis;
+        total.3 += row.gain;
+        items.push(TaxReportItem::from_db_row(row, long_term_threshold_days));
+    }
+    // Stable, so each term stays ordered by disposal date
+    items.sort_by_key(|item| item.term);
+
+    TaxReportResponse {
+        year,
+        portfolio_id: portfolio_id.map(|id| id.to_string()),
+        long_term_threshold_days,
+        totals: totals
+            .into_iter()
+            .map(
+                |((term, currency), (items, proceeds, cost_basis, gain))| TaxReportTotal {
+                    term,
+                    currency,
+                    items,
+                    proceeds: proceeds.to_string(),
+                    cost_basis: cost_basis.to_string(),
+                    gain: gain.to_string(),
+                },
+            )
+            .collect(),
+        items,
+    }
+}
+
+fn form_date(datetime: OffsetDateTime) -> String {
+    format!(
+        "{:02}/{:02}/{}",
+        u8::from(datetime.month()),
+        datetime.day(),
+        datetime.year()
+    )
+}
+
+/// Amounts are reported in cents, as on the form
+fn form_amount(value: &str) -> Result<String, AppError> {
+    Ok(format!("{:.2}", value.parse::<Decimal>()?.round_dp(2)))
+}
+
+fn term_label(term: HoldingTerm) -> &'static str {
+    match term {
+        HoldingTerm::ShortTerm => "Short-term",
+        HoldingTerm::LongTerm => "Long-term",
+    }
+}
+
+/// The report in the layout of Form 8949: short-term disposals then long-term
+/// ones, each term followed by its totals per currency.
+pub fn tax_report_csv(report: &TaxReportResponse) -> Result<String, AppError> {
+    let mut records: Vec<Vec<String>> =
+        vec![FORM_8949_HEADER.iter().map(|h| h.to_string()).collect()];
+    for term in [HoldingTerm::ShortTerm, HoldingTerm::LongTerm] {
+        for item in report.items.iter().filter(|item| item.term == term) {
+            records.push(vec![
+                term_label(term).to_string(),
+                format!("{} {}", item.quantity, item.asset_symbol.to_uppercase()),
+                // Quantity sold without a matching lot was acquired outside the ledger
+                item.acquired_at
+                    .map(form_date)
+                    .unwrap_or_else(|| "VARIOUS".to_string()),
+                form_date(item.disposed_at),
+                form_amount(&item.proceeds)?,
+                form_amount(&item.cost_basis)?,
+                form_amount(&item.gain)?,
+                item.currency.clone(),
+                item.portfolio_name.clone(),
+            ]);
+        }
+        for total in report.totals.iter().filter(|total| total.term == term) {
+            records.push(vec![
+                term_label(term).to_string(),
+                "Totals".to_string(),
+                String::new(),
+                String::new(),
+                form_amount(&total.proceeds)?,
+                form_amount(&total.cost_basis)?,
+                form_amount(&total.gain)?,
+                total.currency.clone(),
+                String::new(),
+            ]);
+        }
+    }
+    Ok(write_csv(&records, ','))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        biz::cost_basis::match_lots,
+        models::{
+            database::transaction::TransactionRow,
+            domain::{portfolio::CostBasisMethod, transaction::TxType},
+        },
+    };
+
+    use super::*;
+
+    const LONG_TERM_THRESHOLD_DAYS: i64 = 365;
+
+    fn tx(
+        id: i64,
+        executed_on: (i32, Month, u8),
+        tx_type: TxType,
+        quantity: i64,
+        price: i64,
+        fees: i64,
+    ) -> TransactionRow {
+        let (year, month, day) = executed_on;
+        let executed_at = Date::from_calendar_date(year, month, day)
+            .unwrap()
+            .midnight()
+            .assume_utc();
+        TransactionRow {
+            id,
+            external_id: None,
+            portfolio_id: 1,
+            asset_id: "bitcoin".to_string(),
+            tx_type: tx_type.to_string(),
+            quantity: Decimal::from(quantity),
+            price: Decimal::from(price),
+            fees: Decimal::from(fees),
+            currency: "USD".to_string(),
+            executed_at,
+            notes: None,
+            realized_gain: None,
+            created_at: executed_at,
+            updated_at: executed_at,
+            import_batch_id: None,
+        }
+    }
+
+    fn ledger() -> Vec<TransactionRow> {
+        vec![
+            // 2 units at 101 each once the fee is spread over them
+            tx(1, (2022, Month::January, 10), TxType::Buy, 2, 100, 2),
+            tx(2, (2023, Month::March, 1), TxType::Sell, 1, 300, 10),
+            tx(3, (2023, Month::June, 1), TxType::Buy, 1, 200, 0),
+            tx(4, (2024, Month::February, 1), TxType::Sell, 2, 250, 0),
+            // Nothing left to match
+            tx(5, (2024, Month::March, 1), TxType::Sell, 1, 100, 0),
+        ]
+    }
+
+    /// The realized gains rows of the year, as the repository returns them
+    fn gain_rows(txs: &[TransactionRow], year: i32) -> Vec<TaxReportGainRow> {
+        let (from, to) = (
+            start_of_year(year).unwrap(),
+            start_of_year(year + 1).unwrap(),
+        );
+        let lots = match_lots(CostBasisMethod::Fifo, txs).unwrap();
+        lots.disposals
+            .iter()
+            .flat_map(|disposal| disposal.ledger_entries())
+            .filter(|entry| entry.disposed_at >= from && entry.disposed_at < to)
+            .enumerate()
+            .map(|(idx, entry)| TaxReportGainRow {
+                id: idx as i64,
+                portfolio_id: 1,
+                portfolio_name: "Main".to_string(),
+                base_currency: "USD".to_string(),
+                asset_id: "bitcoin".to_string(),
+                asset_symbol: "btc".to_string(),
+                asset_name: "Bitcoin".to_string(),
+                sell_tx_id: entry.sell_tx_id,
+                buy_tx_id: entry.buy_tx_id,
+                quantity: entry.quantity,
+                cost_basis: entry.cost_basis,
+                proceeds: entry.proceeds,
+                gain: entry.gain(),
+                acquired_at: entry.acquired_at,
+                disposed_at: entry.disposed_at,
+                holding_period_days: entry.holding_period_days(),
+            })
+            .collect()
+    }
+
+    fn report(year: i32) -> TaxReportResponse {
+        tax_report_from_rows(
+            gain_rows(&ledger(), year),
+            None,
+            year,
+            LONG_TERM_THRESHOLD_DAYS,
+        )
+    }
+
+    fn summary(items: &[TaxReportItem]) -> Vec<(HoldingTerm, &str, Option<&str>, &str)> {
+        items
+            .iter()
+            .map(|item| {
+                (
+                    item.term,
+                    item.sell_tx_id.as_str(),
+                    item.buy_tx_id.as_deref(),
+                    item.gain.as_str(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reports_only_disposals_of_the_year() {
+        assert!(report(2022).items.is_empty());
+        assert_eq!(
+            summary(&report(2023).items),
+            vec![(HoldingTerm::LongTerm, "2", Some("1"), "189")]
+        );
+        assert_eq!(report(2024).items.len(), 3);
+    }
+
+    #[test]
+    fn splits_short_and_long_term_disposals() {
+        let report = report(2024);
+        // Short-term first, each term in disposal order
+        assert_eq!(
+            summary(&report.items),
+            vec![
+                (HoldingTerm::ShortTerm, "4", Some("3"), "50"),
+                (HoldingTerm::ShortTerm, "5", None, "100"),
+                (HoldingTerm::LongTerm, "4", Some("1"), "149"),
+            ]
+        );
+        let totals: Vec<(HoldingTerm, usize, &str, &str, &str)> = report
+            .totals
+            .iter()
+            .map(|total| {
+                (
+                    total.term,
+                    total.items,
+                    total.proceeds.as_str(),
+                    total.cost_basis.as_str(),
+                    total.gain.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            totals,
+            vec![
+                (HoldingTerm::ShortTerm, 2, "350", "200", "150"),
+                (HoldingTerm::LongTerm, 1, "250", "101", "149"),
+            ]
+        );
+    }
+
+    #[test]
+    fn holding_exactly_the_threshold_is_short_term() {
+        let txs = vec![
+            tx(1, (2023, Month::January, 1), TxType::Buy, 1, 100, 0),
+            tx(2, (2024, Month::January, 1), TxType::Sell, 1, 150, 0),
+        ];
+        let report =
+            tax_report_from_rows(gain_rows(&txs, 2024), None, 2024, LONG_TERM_THRESHOLD_DAYS);
+        assert_eq!(report.items[0].holding_period_days, Some(365));
+        assert_eq!(report.items[0].term, HoldingTerm::ShortTerm);
+    }
+
+    #[test]
+    fn fees_reduce_the_gain() {
+        let report = report(2023);
+        let item = &report.items[0];
+        // Sold for 300 less a fee of 10, bought for 100 plus half of a fee of 2
+        assert_eq!(item.proceeds, "290");
+        assert_eq!(item.cost_basis, "101");
+        assert_eq!(item.gain, "189");
+    }
+
+    #[test]
+    fn csv_lists_terms_with_their_totals() {
+        let csv = tax_report_csv(&report(2024)).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 6);
+        assert_eq!(
+            lines[2],
+            "Short-term,1 BTC,VARIOUS,03/01/2024,100.00,0.00,100.00,USD,Main"
+        );
+        assert_eq!(lines[3], "Short-term,Totals,,,350.00,200.00,150.00,USD,");
+        assert_eq!(
+            lines[4],
+            "Long-term,1 BTC,01/10/2022,02/01/2024,250.00,101.00,149.00,USD,Main"
+        );
+    }
+}
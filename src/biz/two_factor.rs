@@ -0,0 +1,430 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use totp_rs::{Algorithm, Secret, TOTP};
+use tracing::{info, warn};
+
+use crate::{
+    biz::auth::create_session,
+    db::repositories::{user::UserRepo, user_totp::UserTotpRepo},
+    middleware::{
+        client::ClientInfo,
+        rate_limit::{clear_login_failures, login_lockout_remaining, record_login_failure},
+    },
+    models::{
+        database::user_totp::UserTotpRow,
+        domain::user::UserStatus,
+        dto::{
+            auth::AuthResponse,
+            two_factor::{RecoveryCodesResponse, TotpEnrollResponse, TwoFactorStatusResponse},
+        },
+    },
+    state::AppState,
+    utils::{
+        error::AppError,
+        token::{generate_token, hash_token},
+    },
+};
+
+const TOTP_DIGITS: usize = 6;
+const TOTP_STEP_SECS: u64 = 30;
+/// Codes of the step before and after the current one are accepted too, for
+/// clocks running a little off
+const TOTP_SKEW_STEPS: u64 = 1;
+const RECOVERY_CODE_COUNT: usize = 10;
+/// Letters and digits that can not be mistaken for one another
+const RECOVERY_CODE_ALPHABET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+/// Wrong codes a login challenge takes before it is dropped
+const MAX_CHALLENGE_ATTEMPTS: i64 = 5;
+
+fn challenge_key(challenge_token: &str) -> String {
+    format!("login_challenge:{}", hash_token(challenge_token))
+}
+
+fn build_totp(state: &AppState, secret: &str, account_name: String) -> Result<TOTP, AppError> {
+    let secret = Secret::Encoded(secret.to_string())
+        .to_bytes()
+        .map_err(|e| AppError::TotpError(e.to_string()))?;
+    TOTP::new(
+        Algorithm::SHA1,
+        TOTP_DIGITS,
+        TOTP_SKEW_STEPS as u8,
+        TOTP_STEP_SECS,
+        secret,
+        Some(state.auth.totp_issuer.clone()),
+        account_name,
+    )
+    .map_err(|e| AppError::TotpError(e.to_string()))
+}
+
+/// Time step the code belongs to, among the current one and its neighbours.
+/// The step is kept so a code is never accepted twice.
+fn find_code_step(totp: &TOTP, code: &str) -> Option<i64> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs();
+    find_code_step_at(totp, code, now)
+}
+
+fn find_code_step_at(totp: &TOTP, code: &str, now: u64) -> Option<i64> {
+    let current = now / TOTP_STEP_SECS;
+    (current.saturating_sub(TOTP_SKEW_STEPS)..=current + TOTP_SKEW_STEPS)
+        .find(|step| totp.generate(step * TOTP_STEP_SECS) == code)
+        .map(|step| step as i64)
+}
+
+/// A step is accepted once, and never after a later one was
+fn is_unused_step(last_used_step: Option<i64>, step: i64) -> bool {
+    last_used_step.is_none_or(|last| last < step)
+}
+
+fn is_totp_code(code: &str) -> bool {
+    code.len() == TOTP_DIGITS && code.chars().all(|c| c.is_ascii_digit())
+}
+
+/// Recovery codes are typed by hand, case and dashes do not matter
+fn normalize_recovery_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+/// New recovery codes formatted `xxxx-xxxx`, with their hashes
+fn generate_recovery_codes() -> (Vec<String>, Vec<String>) {
+    let codes: Vec<String> = (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let chars: String = (0..8)
+                .map(|_| {
+                    let idx = OsRng.next_u32() as usize % RECOVERY_CODE_ALPHABET.len();
+                    RECOVERY_CODE_ALPHABET[idx] as char
+                })
+                .collect();
+            format!("{}-{}", &chars[..4], &chars[4..])
+        })
+        .collect();
+    let hashes = codes
+        .iter()
+        .map(|code| hash_token(&normalize_recovery_code(code)))
+        .collect();
+    (codes, hashes)
+}
+
+async fn get_enabled_totp(state: &AppState, user_id: i64) -> Result<Option<UserTotpRow>, AppError> {
+    Ok(UserTotpRepo::new(state.pool.clone())
+        .get_by_user_id(user_id)
+        .await?
+        .filter(|row| row.enabled_at.is_some()))
+}
+
+/// Checks a code of the authenticator app or an unused recovery code of an
+/// account with two-factor authentication, using it up
+async fn verify_second_factor(
+    state: &AppState,
+    totp_row: &UserTotpRow,
+    code: &str,
+) -> Result<bool, AppError> {
+    let code = code.trim();
+    let repo = UserTotpRepo::new(state.pool.clone());
+    if is_totp_code(code) {
+        let totp = build_totp(state, &totp_row.secret, totp_row.user_id.to_string())?;
+        return match find_code_step(&totp, code) {
+            // Checked again by the update, a replay may race this one
+            Some(step) if is_unused_step(totp_row.last_used_step, step) => {
+                repo.use_step(totp_row.user_id, step).await
+            }
+            _ => Ok(false),
+        };
+    }
+    let used = repo
+        .use_recovery_code(
+            totp_row.user_id,
+            &hash_token(&normalize_recovery_code(code)),
+        )
+        .await?;
+    if used {
+        info!("User {} used a recovery code", totp_row.user_id);
+    }
+    Ok(used)
+}
+
+pub async fn get_two_factor_status(
+    state: &AppState,
+    user_id: i64,
+) -> Result<TwoFactorStatusResponse, AppError> {
+    let Some(totp_row) = get_enabled_totp(state, user_id).await? else {
+        return Ok(TwoFactorStatusResponse {
+            enabled: false,
+            enabled_at: None,
+            recovery_codes_left: 0,
+        });
+    };
+    Ok(TwoFactorStatusResponse {
+        enabled: true,
+        enabled_at: totp_row.enabled_at,
+        recovery_codes_left: UserTotpRepo::new(state.pool.clone())
+            .count_unused_recovery_codes(user_id)
+            .await?,
+    })
+}
+
+/// Starts an enrollment with a new secret, replacing the one of an enrollment
+/// never confirmed. Two-factor authentication stays off until a code of the
+/// secret is confirmed.
+pub async fn enroll_totp(state: &AppState, user_id: i64) -> Result<TotpEnrollResponse, AppError> {
+    let user = UserRepo::new(state.pool.clone())
+        .get_by_id(user_id)
+        .await?
+        .ok_or_else(|| AppError::Unauthorized("User not found".to_string()))?;
+    let secret = Secret::generate_secret().to_encoded().to_string();
+    let totp = build_totp(state, &secret, user.email)?;
+    let stored = UserTotpRepo::new(state.pool.clone())
+        .upsert_pending(user_id, &secret)
+        .await?;
+    if !stored {
+        return Err(AppError::Conflict(
+            "Two-factor authentication is already enabled".to_string(),
+        ));
+    }
+    Ok(TotpEnrollResponse {
+        secret,
+        otpauth_uri: totp.get_url(),
+    })
+}
+
+/// Enables two-factor authentication once the app shows a valid code of the
+/// enrolled secret, returning the first recovery codes
+pub async fn confirm_totp(
+    state: &AppState,
+    user_id: i64,
+    code: &str,
+) -> Result<RecoveryCodesResponse, AppError> {
+    let repo = UserTotpRepo::new(state.pool.clone());
+    let totp_row = repo.get_by_user_id(user_id).await?;
+    let Some(totp_row) = totp_row else {
+        return Err(AppError::InvalidInput(
+            "No two-factor enrollment to confirm".to_string(),
+        ));
+    };
+    if totp_row.enabled_at.is_some() {
+        return Err(AppError::Conflict(
+            "Two-factor authentication is already enabled".to_string(),
+        ));
+    }
+    let totp = build_totp(state, &totp_row.secret, user_id.to_string())?;
+    let step = find_code_step(&totp, code.trim())
+        .ok_or_else(|| AppError::InvalidInput("Invalid code".to_string()))?;
+    let (recovery_codes, hashes) = generate_recovery_codes();
+    if !repo.enable(user_id, step, &hashes).await? {
+        return Err(AppError::Conflict(
+            "Two-factor authentication is already enabled".to_string(),
+        ));
+    }
+    info!("User {} enabled two-factor authentication", user_id);
+    Ok(RecoveryCodesResponse { recovery_codes })
+}
+
+/// Replaces every recovery code, used or not, after checking a code
+pub async fn regenerate_recovery_codes(
+    state: &AppState,
+    user_id: i64,
+    code: &str,
+) -> Result<RecoveryCodesResponse, AppError> {
+    let totp_row = get_enabled_totp(state, user_id).await?.ok_or_else(|| {
+        AppError::InvalidInput("Two-factor authentication is not enabled".to_string())
+    })?;
+    if !verify_second_factor(state, &totp_row, code).await? {
+        return Err(AppError::InvalidInput("Invalid code".to_string()));
+    }
+    let (recovery_codes, hashes) = generate_recovery_codes();
+    UserTotpRepo::new(state.pool.clone())
+        .replace_recovery_codes(user_id, &hashes)
+        .await?;
+    info!("User {} regenerated recovery codes", user_id);
+    Ok(RecoveryCodesResponse { recovery_codes })
+}
+
+/// Turns two-factor authentication off after checking a code
+pub async fn disable_totp(state: &AppState, user_id: i64, code: &str) -> Result<(), AppError> {
+    let totp_row = get_enabled_totp(state, user_id).await?.ok_or_else(|| {
+        AppError::InvalidInput("Two-factor authentication is not enabled".to_string())
+    })?;
+    if !verify_second_factor(state, &totp_row, code).await? {
+        return Err(AppError::InvalidInput("Invalid code".to_string()));
+    }
+    UserTotpRepo::new(state.pool.clone())
+        .delete(user_id)
+        .await?;
+    info!("User {} disabled two-factor authentication", user_id);
+    Ok(())
+}
+
+pub async fn is_two_factor_enabled(state: &AppState, user_id: i64) -> Result<bool, AppError> {
+    Ok(get_enabled_totp(state, user_id).await?.is_some())
+}
+
+/// Remembers that the user passed the password step, returns the token to
+/// present with the second factor
+pub async fn create_login_challenge(state: &AppState, user_id: i64) -> Result<String, AppError> {
+    let challenge_token = generate_token();
+    let mut redis_conn = state.redis_conn.clone();
+    let _: () = redis::pipe()
+        .atomic()
+        .hset(challenge_key(&challenge_token), "user_id", user_id)
+        .ignore()
+        .hset(challenge_key(&challenge_token), "attempts", 0)
+        .ignore()
+        .expire(
+            challenge_key(&challenge_token),
+            state.auth.login_challenge_ttl_secs as i64,
+        )
+        .ignore()
+        .query_async(&mut redis_conn)
+        .await?;
+    Ok(challenge_token)
+}
+
+/// Second step of a login: starts a session when the code matches. A challenge
+/// is used once and dropped after too many wrong codes.
+pub async fn complete_login_challenge(
+    state: &AppState,
+    challenge_token: &str,
+    code: &str,
+    client: ClientInfo,
+) -> Result<AuthResponse, AppError> {
+    let invalid = || AppError::Unauthorized("Invalid or expired challenge".to_string());
+    let key = challenge_key(challenge_token);
+    let mut redis_conn = state.redis_conn.clone();
+    let user_id: Option<i64> = redis::cmd("HGET")
+        .arg(&key)
+        .arg("user_id")
+        .query_async(&mut redis_conn)
+        .await?;
+    let user_id = user_id.ok_or_else(invalid)?;
+    let user = UserRepo::new(state.pool.clone())
+        .get_by_id(user_id)
+        .await?
+        .ok_or_else(invalid)?;
+    if login_lockout_remaining(state, &user.email).await.is_some() {
+        return Err(AppError::Unauthorized(
+            "Too many failed logins, try again later".to_string(),
+        ));
+    }
+    let totp_row = get_enabled_totp(state, user_id)
+        .await?
+        .ok_or_else(invalid)?;
+
+    if !verify_second_factor(state, &totp_row, code).await? {
+        // Wrong codes count toward the lockout of the account too, a new
+        // challenge does not start over
+        record_login_failure(state, &user.email).await;
+        let attempts: i64 = redis::cmd("HINCRBY")
+            .arg(&key)
+            .arg("attempts")
+            .arg(1)
+            .query_async(&mut redis_conn)
+            .await?;
+        if attempts >= MAX_CHALLENGE_ATTEMPTS {
+            warn!(
+                "Too many wrong codes for the login of user {}, dropping the challenge",
+                user_id
+            );
+            let _: () = redis::cmd("DEL")
+                .arg(&key)
+                .query_async(&mut redis_conn)
+                .await?;
+        }
+        return Err(AppError::Unauthorized("Invalid code".to_string()));
+    }
+    // Deleting claims the challenge, a concurrent request with it fails
+    let deleted: i64 = redis::cmd("DEL")
+        .arg(&key)
+        .query_async(&mut redis_conn)
+        .await?;
+    if deleted == 0 {
+        return Err(invalid());
+    }
+    if user.status != UserStatus::Active.to_string() {
+        return Err(AppError::Unauthorized("Account is not active".to_string()));
+    }
+    clear_login_failures(state, &user.email).await;
+    info!("User {} passed two-factor authentication", user_id);
+    create_session(state, user_id, client).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_760_000_000;
+
+    fn test_totp() -> TOTP {
+        TOTP::new(
+            Algorithm::SHA1,
+            TOTP_DIGITS,
+            TOTP_SKEW_STEPS as u8,
+            TOTP_STEP_SECS,
+            b"12345678901234567890".to_vec(),
+            None,
+            "test".to_string(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn accepts_codes_of_neighbouring_steps() {
+        let totp = test_totp();
+        let current = (NOW / TOTP_STEP_SECS) as i64;
+        for offset in [-1, 0, 1] {
+            let code = totp.generate(NOW.saturating_add_signed(offset * TOTP_STEP_SECS as i64));
+            assert_eq!(find_code_step_at(&totp, &code, NOW), Some(current + offset));
+        }
+    }
+
+    #[test]
+    fn refuses_codes_outside_the_skew_window() {
+        let totp = test_totp();
+        for offset in [-2, 2] {
+            let code = totp.generate(NOW.saturating_add_signed(offset * TOTP_STEP_SECS as i64));
+            assert_eq!(find_code_step_at(&totp, &code, NOW), None);
+        }
+    }
+
+    #[test]
+    fn refuses_used_steps() {
+        assert!(is_unused_step(None, 10));
+        assert!(is_unused_step(Some(9), 10));
+        assert!(!is_unused_step(Some(10), 10));
+        assert!(!is_unused_step(Some(11), 10));
+    }
+
+    #[test]
+    fn normalizes_recovery_codes() {
+        assert_eq!(normalize_recovery_code(" AbCd-eF23 "), "abcdef23");
+        assert_eq!(
+            normalize_recovery_code("abcdef23"),
+            normalize_recovery_code("ABCD-EF23")
+        );
+    }
+
+    #[test]
+    fn generates_distinct_recovery_codes_with_their_hashes() {
+        let (codes, hashes) = generate_recovery_codes();
+        assert_eq!(codes.len(), RECOVERY_CODE_COUNT);
+        assert_eq!(hashes.len(), RECOVERY_CODE_COUNT);
+        for (code, hash) in codes.iter().zip(&hashes) {
+            assert_eq!(code.len(), 9);
+            assert_eq!(code.as_bytes()[4], b'-');
+            assert!(
+                code.bytes()
+                    .filter(|c| *c != b'-')
+                    .all(|c| RECOVERY_CODE_ALPHABET.contains(&c))
+            );
+            // A code typed in capitals matches the stored hash
+            assert_eq!(
+                &hash_token(&normalize_recovery_code(&code.to_uppercase())),
+                hash
+            );
+        }
+        let unique: std::collections::HashSet<&String> = codes.iter().collect();
+        assert_eq!(unique.len(), codes.len());
+    }
+}
@@ -1,2 +1,9 @@
 pub mod app_client;
 pub mod coingecko;
+pub mod file_mailer;
+pub mod mailer;
+pub mod mock;
+pub mod price_provider;
+pub mod price_service;
+pub mod smtp_mailer;
+pub mod static_file;
@@ -1,15 +1,82 @@
-use crate::config::ClientsConfig;
+use std::sync::Arc;
 
-use super::coingecko::CoinGeckoClient;
+use redis::aio::ConnectionManager;
+use rust_decimal::Decimal;
+
+use crate::{
+    config::{ClientsConfig, MailerKind, PriceProviderKind},
+    utils::error::AppError,
+};
+
+use super::{
+    coingecko::CoinGeckoClient, file_mailer::FileMailer, mailer::Mailer, mock::MockPriceProvider,
+    price_provider::PriceProvider, price_service::PriceService, smtp_mailer::SmtpMailer,
+    static_file::StaticFilePriceProvider,
+};
 
 #[derive(Clone)]
 pub struct AppClients {
-    pub coingecko: CoinGeckoClient,
+    pub price_provider: Arc<dyn PriceProvider>,
+    pub prices: PriceService,
+    pub mailer: Arc<dyn Mailer>,
 }
 
 impl AppClients {
-    pub fn new(config: &ClientsConfig) -> Self {
-        let coingecko = CoinGeckoClient::new(config.coingecko.api_key.clone());
-        Self { coingecko }
+    pub fn new(config: &ClientsConfig, redis_conn: ConnectionManager) -> Result<Self, AppError> {
+        let price_provider: Arc<dyn PriceProvider> = match config.price_provider {
+            PriceProviderKind::Coingecko => {
+                let coingecko = config.coingecko.as_ref().ok_or_else(|| {
+                    AppError::PriceProviderError("Missing [clients.coingecko] config".to_string())
+                })?;
+                Arc::new(CoinGeckoClient::new(coingecko.api_key.clone()))
+            }
+            PriceProviderKind::StaticFile => {
+                let static_file = config.static_file.as_ref().ok_or_else(|| {
+                    AppError::PriceProviderError("Missing [clients.static_file] config".to_string())
+                })?;
+                Arc::new(StaticFilePriceProvider::from_file(&static_file.path)?)
+            }
+            PriceProviderKind::Mock => {
+                let (default_price, prices) = match config.mock.as_ref() {
+                    Some(mock) => (
+                        Decimal::from_f64_retain(mock.default_price).unwrap_or(Decimal::ONE),
+                        mock.prices
+                            .iter()
+                            .filter_map(|(coin_id, price)| {
+                                Some((coin_id.clone(), Decimal::from_f64_retain(*price)?))
+                            })
+                            .collect(),
+                    ),
+                    None => (Decimal::ONE, Default::default()),
+                };
+                Arc::new(MockPriceProvider::new(default_price, prices))
+            }
+        };
+        let prices = PriceService::new(
+            price_provider.clone(),
+            redis_conn,
+            config.price_cache.clone(),
+        );
+        let mailer_config = &config.mailer;
+        let mailer: Arc<dyn Mailer> = match mailer_config.kind {
+            MailerKind::Smtp => {
+                let smtp = mailer_config.smtp.as_ref().ok_or_else(|| {
+                    AppError::MailerError("Missing [clients.mailer.smtp] config".to_string())
+                })?;
+                Arc::new(SmtpMailer::new(smtp, &mailer_config.from)?)
+            }
+            MailerKind::File => Arc::new(FileMailer::new(
+                mailer_config
+                    .file
+                    .as_ref()
+                    .and_then(|file| file.path.clone()),
+                &mailer_config.from,
+            )?),
+        };
+        Ok(Self {
+            price_provider,
+            prices,
+            mailer,
+        })
     }
 }
@@ -1,10 +1,19 @@
+use std::collections::{BTreeMap, HashMap};
+
+use async_trait::async_trait;
 use reqwest::Client;
 use reqwest::header::{HeaderMap, HeaderValue};
-use scraper::{Html, Selector};
-use tracing::info;
+use rust_decimal::Decimal;
+use time::{Date, OffsetDateTime, Time};
 
-use crate::models::domain::coingecko::RawTransaction;
-use crate::{models::dto::coingecko::CoinDataResponse, utils::error::AppError};
+use crate::{
+    clients::price_provider::PriceProvider,
+    models::dto::coingecko::{
+        CoinDataResponse, CoinHistoryResponse, ExchangeRatesResponse, MarketChartResponse,
+        SimplePriceResponse,
+    },
+    utils::error::AppError,
+};
 
 const BASE_URL: &str = "https://api.coingecko.com/api/v3";
 const API_HEADER: &str = "x-cg-demo-api-key";
@@ -38,43 +47,208 @@ impl CoinGeckoClient {
         Ok(data)
     }
 
-    pub fn parse_html_contents(
+    /// Current prices of many coins in a single request
+    pub async fn get_simple_price(
         &self,
-        contents: &String,
-    ) -> Result<(String, Vec<RawTransaction>), AppError> {
-        let doc = Html::parse_document(contents);
-        let table_sel = Selector::parse(r"body > div.container > main > div:nth-child(3) > div:nth-child(3) > div.tw-overflow-x-auto.\32 lg\:tw-overflow-x-visible.\32 lg\:tw-flex.\32 lg\:tw-justify-center > table > tbody").unwrap();
-        let table = doc.select(&table_sel).next().expect("Table not found");
-
-        let row_sel = Selector::parse("tr").unwrap();
-        let rows = table.select(&row_sel);
-
-        let mut transactions: Vec<RawTransaction> = Vec::new();
-        let edit_sel = Selector::parse(r"td.tw-text-center.\!tw-pr-0.tw-px-1.tw-py-2\.5.\32 lg\:tw-p-2\.5.tw-bg-inherit.tw-text-gray-900.dark\:tw-text-moon-50 > div > span:nth-child(1)").unwrap();
-        for row in rows {
-            if let Some(span) = row.select(&edit_sel).next() {
-                if let Some(transaction_data) =
-                    span.value().attr("data-portfolio-coin-transaction-data")
-                {
-                    let transaction: RawTransaction = serde_json::from_str(transaction_data)?;
-                    transactions.push(transaction);
-                }
-            }
-        }
-        let coin_slug_sel =
-            Selector::parse(r"body > div:nth-child(3) > main:nth-child(2) > div:nth-child(3)")
-                .unwrap();
-        let coin_slug = doc
-            .select(&coin_slug_sel)
-            .next()
-            .expect("Coin slug not found");
-        let coin_id = coin_slug.value().attr("data-coin-slug");
-        if coin_id.is_none() {
-            info!("Coin ID is null");
-            return Err(AppError::CoinGeckoError(
-                "Got error when parsing data from HTML import".to_string(),
-            ));
+        coin_ids: &[String],
+        vs_currency: &str,
+    ) -> Result<SimplePriceResponse, AppError> {
+        let response = self
+            .res_client
+            .get(format!("{}/simple/price", self.base_url))
+            .query(&[
+                ("ids", coin_ids.join(",")),
+                ("vs_currencies", vs_currency.to_lowercase()),
+            ])
+            .headers(self.headers.clone())
+            .send()
+            .await?;
+        let data = response.json::<SimplePriceResponse>().await?;
+        Ok(data)
+    }
+
+    /// Exchange rates of fiat and crypto currencies against BTC
+    pub async fn get_btc_exchange_rates(&self) -> Result<ExchangeRatesResponse, AppError> {
+        let response = self
+            .res_client
+            .get(format!("{}/exchange_rates", self.base_url))
+            .headers(self.headers.clone())
+            .send()
+            .await?;
+        let data = response.json::<ExchangeRatesResponse>().await?;
+        Ok(data)
+    }
+
+    /// Daily prices for the last `days` days, one point per day
+    pub async fn get_market_chart(
+        &self,
+        coin_id: &str,
+        vs_currency: &str,
+        days: u32,
+    ) -> Result<MarketChartResponse, AppError> {
+        let response = self
+            .res_client
+            .get(format!("{}/coins/{}/market_chart", self.base_url, coin_id))
+            .query(&[
+                ("vs_currency", vs_currency.to_lowercase()),
+                ("days", days.to_string()),
+                ("interval", "daily".to_string()),
+            ])
+            .headers(self.headers.clone())
+            .send()
+            .await?;
+        let data = response.json::<MarketChartResponse>().await?;
+        Ok(data)
+    }
+
+    /// Snapshot of the coin market data at 00:00 UTC of the given date
+    pub async fn get_coin_history(
+        &self,
+        coin_id: &str,
+        date: Date,
+    ) -> Result<CoinHistoryResponse, AppError> {
+        let date_param = format!(
+            "{:02}-{:02}-{}",
+            date.day(),
+            u8::from(date.month()),
+            date.year()
+        );
+        let response = self
+            .res_client
+            .get(format!("{}/coins/{}/history", self.base_url, coin_id))
+            .query(&[("date", date_param), ("localization", "false".to_string())])
+            .headers(self.headers.clone())
+            .send()
+            .await?;
+        let data = response.json::<CoinHistoryResponse>().await?;
+        Ok(data)
+    }
+}
+
+#[async_trait]
+impl PriceProvider for CoinGeckoClient {
+    fn source(&self) -> &'static str {
+        "coingecko"
+    }
+
+    async fn get_coin_data(&self, coin_id: &str) -> Result<CoinDataResponse, AppError> {
+        CoinGeckoClient::get_coin_data(self, coin_id).await
+    }
+
+    async fn get_spot_prices(
+        &self,
+        coin_ids: &[String],
+        currency: &str,
+    ) -> Result<HashMap<String, Decimal>, AppError> {
+        if coin_ids.is_empty() {
+            return Ok(HashMap::new());
         }
-        Ok((coin_id.unwrap().to_string(), transactions))
+        let vs_currency = currency.to_lowercase();
+        let data = self.get_simple_price(coin_ids, &vs_currency).await?;
+        Ok(data
+            .into_iter()
+            .filter_map(|(coin_id, prices)| {
+                let price = prices.get(&vs_currency).copied()?;
+                Some((coin_id, Decimal::from_f64_retain(price)?))
+            })
+            .collect())
+    }
+
+    async fn get_daily_prices(
+        &self,
+        coin_id: &str,
+        currency: &str,
+        days: u32,
+    ) -> Result<BTreeMap<Date, Decimal>, AppError> {
+        let chart = self.get_market_chart(coin_id, currency, days).await?;
+        Ok(daily_closes(&chart.prices))
+    }
+
+    async fn get_historical_price(
+        &self,
+        coin_id: &str,
+        currency: &str,
+        date: Date,
+    ) -> Result<Option<Decimal>, AppError> {
+        // The history endpoint returns the price at 00:00 UTC, which closes the day before
+        let history = self
+            .get_coin_history(coin_id, date.next_day().unwrap_or(date))
+            .await?;
+        Ok(history
+            .get_price(currency)
+            .and_then(Decimal::from_f64_retain))
+    }
+
+    async fn get_exchange_rates(&self) -> Result<HashMap<String, Decimal>, AppError> {
+        let data = self.get_btc_exchange_rates().await?;
+        let usd_per_btc = data
+            .rates
+            .get("usd")
+            .and_then(|rate| Decimal::from_f64_retain(rate.value))
+            .ok_or_else(|| {
+                AppError::CoinGeckoError("Exchange rates have no USD rate".to_string())
+            })?;
+        Ok(data
+            .rates
+            .into_iter()
+            .filter_map(|(currency, rate)| {
+                let per_btc = Decimal::from_f64_retain(rate.value)?;
+                Some((currency, per_btc.checked_div(usd_per_btc)?))
+            })
+            .collect())
+    }
+
+    async fn get_historical_exchange_rates(
+        &self,
+        date: Date,
+    ) -> Result<HashMap<String, Decimal>, AppError> {
+        // Bitcoin is quoted in every currency, so its prices of the day give the
+        // rates. The history endpoint returns 00:00 UTC, which closes the day before.
+        let history = self
+            .get_coin_history("bitcoin", date.next_day().unwrap_or(date))
+            .await?;
+        let Some(market_data) = history.market_data else {
+            return Err(AppError::CoinGeckoError(format!(
+                "No exchange rates on {}",
+                date
+            )));
+        };
+        let usd_per_btc = market_data
+            .current_price
+            .get("usd")
+            .and_then(|price| Decimal::from_f64_retain(*price))
+            .ok_or_else(|| {
+                AppError::CoinGeckoError(format!("Exchange rates of {} have no USD rate", date))
+            })?;
+        Ok(market_data
+            .current_price
+            .into_iter()
+            .filter_map(|(currency, price)| {
+                let per_btc = Decimal::from_f64_retain(price)?;
+                Some((currency, per_btc.checked_div(usd_per_btc)?))
+            })
+            .collect())
+    }
+}
+
+/// Turns market chart points into one close price per day. A point at exactly
+/// 00:00 UTC closes the previous day, any other point is the latest price of its day.
+fn daily_closes(points: &[(i64, f64)]) -> BTreeMap<Date, Decimal> {
+    let mut closes = BTreeMap::new();
+    for (timestamp_ms, price) in points {
+        let Ok(at) = OffsetDateTime::from_unix_timestamp_nanos(*timestamp_ms as i128 * 1_000_000)
+        else {
+            continue;
+        };
+        let Some(price) = Decimal::from_f64_retain(*price) else {
+            continue;
+        };
+        let date = if at.time() == Time::MIDNIGHT {
+            at.date().previous_day().unwrap_or(at.date())
+        } else {
+            at.date()
+        };
+        closes.insert(date, price);
     }
+    closes
 }
@@ -0,0 +1,55 @@
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+use time::{OffsetDateTime, format_description::well_known::Rfc2822};
+use tracing::info;
+
+use crate::{
+    clients::mailer::{Email, Mailer},
+    utils::{error::AppError, snowflake::SNOWFLAKE_GENERATOR},
+};
+
+/// Keeps mails instead of sending them: each one is written to a file of the
+/// directory when set, and logged.
+pub struct FileMailer {
+    dir: Option<PathBuf>,
+    from: String,
+}
+
+impl FileMailer {
+    pub fn new(dir: Option<String>, from: &str) -> Result<Self, AppError> {
+        let dir = dir.map(PathBuf::from);
+        if let Some(dir) = dir.as_ref() {
+            std::fs::create_dir_all(dir).map_err(|e| {
+                AppError::MailerError(format!("Create mail directory {:?}: {}", dir, e))
+            })?;
+        }
+        Ok(Self {
+            dir,
+            from: from.to_string(),
+        })
+    }
+}
+
+#[async_trait]
+impl Mailer for FileMailer {
+    async fn send(&self, email: Email) -> Result<(), AppError> {
+        info!("Mail to {}: {}\n{}", email.to, email.subject, email.body);
+        let Some(dir) = self.dir.as_ref() else {
+            return Ok(());
+        };
+        let contents = format!(
+            "From: {}\r\nTo: {}\r\nDate: {}\r\nSubject: {}\r\n\r\n{}\r\n",
+            self.from,
+            email.to,
+            OffsetDateTime::now_utc().format(&Rfc2822)?,
+            email.subject,
+            email.body
+        );
+        let path = dir.join(format!("{}.eml", SNOWFLAKE_GENERATOR.generate().unwrap()));
+        tokio::fs::write(&path, contents)
+            .await
+            .map_err(|e| AppError::MailerError(format!("Write mail {:?}: {}", path, e)))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,18 @@
+use async_trait::async_trait;
+
+use crate::utils::error::AppError;
+
+#[derive(Debug, Clone)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    /// Plain text body
+    pub body: String,
+}
+
+/// Sends the mails of the app. The implementation is picked in
+/// `Settings.clients.mailer`.
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, email: Email) -> Result<(), AppError>;
+}
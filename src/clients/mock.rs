@@ -0,0 +1,108 @@
+use std::collections::{BTreeMap, HashMap};
+
+use async_trait::async_trait;
+use rust_decimal::Decimal;
+use strum::IntoEnumIterator;
+use time::{Date, Duration, OffsetDateTime};
+
+use crate::{
+    clients::price_provider::PriceProvider,
+    models::{
+        common::{asset::AssetImage, currency::Currency},
+        dto::coingecko::{CoinDataResponse, MarketData},
+    },
+    utils::error::AppError,
+};
+
+/// In-memory provider that knows every coin and prices it at a fixed value in any
+/// currency, both now and in the past. Meant for local development and tests.
+pub struct MockPriceProvider {
+    default_price: Decimal,
+    prices: HashMap<String, Decimal>,
+}
+
+impl MockPriceProvider {
+    pub fn new(default_price: Decimal, prices: HashMap<String, Decimal>) -> Self {
+        Self {
+            default_price,
+            prices,
+        }
+    }
+
+    fn price_of(&self, coin_id: &str) -> Decimal {
+        self.prices
+            .get(coin_id)
+            .copied()
+            .unwrap_or(self.default_price)
+    }
+}
+
+#[async_trait]
+impl PriceProvider for MockPriceProvider {
+    fn source(&self) -> &'static str {
+        "mock"
+    }
+
+    async fn get_coin_data(&self, coin_id: &str) -> Result<CoinDataResponse, AppError> {
+        let price = self.price_of(coin_id).try_into().unwrap_or_default();
+        Ok(CoinDataResponse {
+            id: coin_id.to_string(),
+            symbol: coin_id.to_string(),
+            name: coin_id.to_string(),
+            platforms: None,
+            image: AssetImage {
+                thumb: None,
+                small: None,
+                large: None,
+            },
+            market_data: MarketData::from_prices(HashMap::from([("usd".to_string(), price)])),
+        })
+    }
+
+    async fn get_spot_prices(
+        &self,
+        coin_ids: &[String],
+        _currency: &str,
+    ) -> Result<HashMap<String, Decimal>, AppError> {
+        Ok(coin_ids
+            .iter()
+            .map(|coin_id| (coin_id.clone(), self.price_of(coin_id)))
+            .collect())
+    }
+
+    async fn get_daily_prices(
+        &self,
+        coin_id: &str,
+        _currency: &str,
+        days: u32,
+    ) -> Result<BTreeMap<Date, Decimal>, AppError> {
+        let today = OffsetDateTime::now_utc().date();
+        let price = self.price_of(coin_id);
+        Ok((0..=days as i64)
+            .map(|offset| (today - Duration::days(offset), price))
+            .collect())
+    }
+
+    async fn get_historical_price(
+        &self,
+        coin_id: &str,
+        _currency: &str,
+        _date: Date,
+    ) -> Result<Option<Decimal>, AppError> {
+        Ok(Some(self.price_of(coin_id)))
+    }
+
+    async fn get_exchange_rates(&self) -> Result<HashMap<String, Decimal>, AppError> {
+        // Every currency is worth one US dollar, matching the currency agnostic prices
+        Ok(Currency::iter()
+            .map(|currency| (currency.to_string().to_lowercase(), Decimal::ONE))
+            .collect())
+    }
+
+    async fn get_historical_exchange_rates(
+        &self,
+        _date: Date,
+    ) -> Result<HashMap<String, Decimal>, AppError> {
+        self.get_exchange_rates().await
+    }
+}
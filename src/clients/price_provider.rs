@@ -0,0 +1,51 @@
+use std::collections::{BTreeMap, HashMap};
+
+use async_trait::async_trait;
+use rust_decimal::Decimal;
+use time::Date;
+
+use crate::{models::dto::coingecko::CoinDataResponse, utils::error::AppError};
+
+/// Source of coin metadata and prices. Handlers and biz code only talk to this
+/// trait so the backend can be swapped in `Settings.clients`.
+#[async_trait]
+pub trait PriceProvider: Send + Sync {
+    /// Name stored as the source of the prices fetched from this provider
+    fn source(&self) -> &'static str;
+
+    /// Metadata and current market data of a coin
+    async fn get_coin_data(&self, coin_id: &str) -> Result<CoinDataResponse, AppError>;
+
+    /// Current prices of the given coins in one call, coins without a price are left out
+    async fn get_spot_prices(
+        &self,
+        coin_ids: &[String],
+        currency: &str,
+    ) -> Result<HashMap<String, Decimal>, AppError>;
+
+    /// Close prices of the last `days` days, one per day
+    async fn get_daily_prices(
+        &self,
+        coin_id: &str,
+        currency: &str,
+        days: u32,
+    ) -> Result<BTreeMap<Date, Decimal>, AppError>;
+
+    /// Close price of a coin on the given day
+    async fn get_historical_price(
+        &self,
+        coin_id: &str,
+        currency: &str,
+        date: Date,
+    ) -> Result<Option<Decimal>, AppError>;
+
+    /// Current exchange rates as units of each currency per 1 USD, keyed by lowercase currency
+    async fn get_exchange_rates(&self) -> Result<HashMap<String, Decimal>, AppError>;
+
+    /// Exchange rates of the given day, as units of each currency per 1 USD keyed by
+    /// lowercase currency
+    async fn get_historical_exchange_rates(
+        &self,
+        date: Date,
+    ) -> Result<HashMap<String, Decimal>, AppError>;
+}
@@ -0,0 +1,145 @@
+use std::{collections::HashMap, sync::Arc};
+
+use redis::{AsyncCommands, aio::ConnectionManager};
+use time::OffsetDateTime;
+use tracing::warn;
+
+use crate::{
+    clients::price_provider::PriceProvider,
+    config::PriceCacheConfig,
+    models::domain::price::{CachedPrice, SpotPrice},
+    utils::error::AppError,
+};
+
+/// Spot prices backed by the Redis cache. Prices missing from the cache or older
+/// than the TTL are fetched from the provider in a single batch call.
+#[derive(Clone)]
+pub struct PriceService {
+    provider: Arc<dyn PriceProvider>,
+    redis_conn: ConnectionManager,
+    config: PriceCacheConfig,
+}
+
+impl PriceService {
+    pub fn new(
+        provider: Arc<dyn PriceProvider>,
+        redis_conn: ConnectionManager,
+        config: PriceCacheConfig,
+    ) -> Self {
+        Self {
+            provider,
+            redis_conn,
+            config,
+        }
+    }
+
+    fn cache_key(&self, coin_id: &str, currency: &str) -> String {
+        format!("price:{}:{}:{}", self.provider.source(), currency, coin_id)
+    }
+
+    /// Current prices of the given coins. When the provider fails, cached prices
+    /// past their TTL are returned flagged as stale. Coins without any price are
+    /// left out.
+    pub async fn get_spot_prices(
+        &self,
+        coin_ids: &[String],
+        currency: &str,
+    ) -> Result<HashMap<String, SpotPrice>, AppError> {
+        if coin_ids.is_empty() {
+            return Ok(HashMap::new());
+        }
+        let currency = currency.to_lowercase();
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        let mut redis_conn = self.redis_conn.clone();
+
+        let keys: Vec<String> = coin_ids
+            .iter()
+            .map(|coin_id| self.cache_key(coin_id, &currency))
+            .collect();
+        let cached: Vec<Option<String>> = match redis_conn.mget(&keys).await {
+            Ok(values) => values,
+            Err(e) => {
+                warn!("Can not read cached prices: {}", e);
+                vec![None; keys.len()]
+            }
+        };
+
+        let mut prices = HashMap::new();
+        let mut expired: HashMap<String, CachedPrice> = HashMap::new();
+        let mut to_fetch: Vec<String> = Vec::new();
+        for (coin_id, value) in coin_ids.iter().zip(cached) {
+            let cached_price = value.and_then(|v| serde_json::from_str::<CachedPrice>(&v).ok());
+            match cached_price {
+                Some(cached_price)
+                    if now - cached_price.fetched_at < self.config.ttl_secs as i64 =>
+                {
+                    prices.insert(
+                        coin_id.clone(),
+                        SpotPrice {
+                            price: cached_price.price,
+                            stale: false,
+                        },
+                    );
+                }
+                Some(cached_price) => {
+                    expired.insert(coin_id.clone(), cached_price);
+                    to_fetch.push(coin_id.clone());
+                }
+                None => to_fetch.push(coin_id.clone()),
+            }
+        }
+        if to_fetch.is_empty() {
+            return Ok(prices);
+        }
+
+        match self.provider.get_spot_prices(&to_fetch, &currency).await {
+            Ok(fetched) => {
+                let mut pipe = redis::pipe();
+                for (coin_id, price) in fetched {
+                    let cached_price = CachedPrice {
+                        price,
+                        fetched_at: now,
+                    };
+                    if let Ok(json) = serde_json::to_string(&cached_price) {
+                        pipe.set_ex(
+                            self.cache_key(&coin_id, &currency),
+                            json,
+                            self.config.stale_ttl_secs,
+                        )
+                        .ignore();
+                    }
+                    prices.insert(
+                        coin_id,
+                        SpotPrice {
+                            price,
+                            stale: false,
+                        },
+                    );
+                }
+                if let Err(e) = pipe.query_async::<()>(&mut redis_conn).await {
+                    warn!("Can not cache prices: {}", e);
+                }
+            }
+            Err(e) => {
+                if expired.is_empty() {
+                    return Err(e);
+                }
+                warn!(
+                    "Price provider failed, serving {} stale prices: {:?}",
+                    expired.len(),
+                    e
+                );
+                for (coin_id, cached_price) in expired {
+                    prices.insert(
+                        coin_id,
+                        SpotPrice {
+                            price: cached_price.price,
+                            stale: true,
+                        },
+                    );
+                }
+            }
+        }
+        Ok(prices)
+    }
+}
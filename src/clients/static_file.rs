@@ -0,0 +1,176 @@
+use std::collections::{BTreeMap, HashMap};
+
+use async_trait::async_trait;
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use time::{Date, Duration, OffsetDateTime, format_description::well_known::Iso8601};
+
+use crate::{
+    clients::price_provider::PriceProvider,
+    models::{
+        common::asset::AssetImage,
+        dto::coingecko::{CoinDataResponse, MarketData},
+    },
+    utils::error::AppError,
+};
+
+#[derive(Debug, Deserialize)]
+struct PriceFixture {
+    coins: Vec<CoinFixture>,
+    /// Units of each currency per 1 USD, keyed by lowercase currency
+    #[serde(default)]
+    fx_rates: HashMap<String, f64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CoinFixture {
+    id: String,
+    symbol: String,
+    name: String,
+    image: AssetImage,
+    #[serde(default)]
+    platforms: Option<HashMap<String, String>>,
+    /// Current prices keyed by lowercase currency
+    #[serde(default)]
+    prices: HashMap<String, f64>,
+    /// Daily close prices keyed by lowercase currency, then by `YYYY-MM-DD` date
+    #[serde(default)]
+    history: HashMap<String, HashMap<String, f64>>,
+}
+
+struct StaticCoin {
+    data: CoinFixture,
+    history: HashMap<String, BTreeMap<Date, Decimal>>,
+}
+
+/// Serves coin metadata and prices from a JSON fixture file, so the service can
+/// run without network access.
+pub struct StaticFilePriceProvider {
+    coins: HashMap<String, StaticCoin>,
+    fx_rates: HashMap<String, Decimal>,
+}
+
+impl StaticFilePriceProvider {
+    pub fn from_file(path: &str) -> Result<Self, AppError> {
+        let contents = std::fs::read_to_string(path).map_err(|e| {
+            AppError::PriceProviderError(format!("Can not read price fixture {}: {}", path, e))
+        })?;
+        let fixture: PriceFixture = serde_json::from_str(&contents)?;
+
+        let mut coins = HashMap::new();
+        for coin in fixture.coins {
+            let mut history = HashMap::new();
+            for (currency, points) in coin.history.iter() {
+                let mut closes = BTreeMap::new();
+                for (date, price) in points {
+                    let date = Date::parse(date, &Iso8601::DATE)?;
+                    if let Some(price) = Decimal::from_f64_retain(*price) {
+                        closes.insert(date, price);
+                    }
+                }
+                history.insert(currency.to_lowercase(), closes);
+            }
+            coins.insert(
+                coin.id.clone(),
+                StaticCoin {
+                    data: coin,
+                    history,
+                },
+            );
+        }
+        let fx_rates = fixture
+            .fx_rates
+            .into_iter()
+            .filter_map(|(currency, rate)| {
+                Some((currency.to_lowercase(), Decimal::from_f64_retain(rate)?))
+            })
+            .collect();
+        Ok(Self { coins, fx_rates })
+    }
+
+    fn get_coin(&self, coin_id: &str) -> Result<&StaticCoin, AppError> {
+        self.coins.get(coin_id).ok_or_else(|| {
+            AppError::PriceProviderError(format!("Coin {} not found in price fixture", coin_id))
+        })
+    }
+}
+
+#[async_trait]
+impl PriceProvider for StaticFilePriceProvider {
+    fn source(&self) -> &'static str {
+        "static_file"
+    }
+
+    async fn get_coin_data(&self, coin_id: &str) -> Result<CoinDataResponse, AppError> {
+        let coin = &self.get_coin(coin_id)?.data;
+        Ok(CoinDataResponse {
+            id: coin.id.clone(),
+            symbol: coin.symbol.clone(),
+            name: coin.name.clone(),
+            platforms: coin.platforms.clone(),
+            image: coin.image.clone(),
+            market_data: MarketData::from_prices(coin.prices.clone()),
+        })
+    }
+
+    async fn get_spot_prices(
+        &self,
+        coin_ids: &[String],
+        currency: &str,
+    ) -> Result<HashMap<String, Decimal>, AppError> {
+        let currency = currency.to_lowercase();
+        Ok(coin_ids
+            .iter()
+            .filter_map(|coin_id| {
+                let price = self.coins.get(coin_id)?.data.prices.get(&currency)?;
+                Some((coin_id.clone(), Decimal::from_f64_retain(*price)?))
+            })
+            .collect())
+    }
+
+    async fn get_daily_prices(
+        &self,
+        coin_id: &str,
+        currency: &str,
+        days: u32,
+    ) -> Result<BTreeMap<Date, Decimal>, AppError> {
+        let today = OffsetDateTime::now_utc().date();
+        let from = today - Duration::days(days as i64);
+        Ok(self
+            .get_coin(coin_id)?
+            .history
+            .get(&currency.to_lowercase())
+            .map(|closes| {
+                closes
+                    .range(from..=today)
+                    .map(|(date, price)| (*date, *price))
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    async fn get_historical_price(
+        &self,
+        coin_id: &str,
+        currency: &str,
+        date: Date,
+    ) -> Result<Option<Decimal>, AppError> {
+        Ok(self
+            .get_coin(coin_id)?
+            .history
+            .get(&currency.to_lowercase())
+            .and_then(|closes| closes.get(&date).copied()))
+    }
+
+    async fn get_exchange_rates(&self) -> Result<HashMap<String, Decimal>, AppError> {
+        Ok(self.fx_rates.clone())
+    }
+
+    async fn get_historical_exchange_rates(
+        &self,
+        _date: Date,
+    ) -> Result<HashMap<String, Decimal>, AppError> {
+        // The fixture has one set of rates, used for every day
+        Ok(self.fx_rates.clone())
+    }
+}
@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use config::{Config, Environment, File};
 use serde::Deserialize;
 
@@ -6,6 +8,9 @@ pub struct Server {
     pub port: u16,
     pub secret_key: String,
     pub allow_origins: Vec<String>,
+    /// Proxies, as addresses or networks, whose `X-Forwarded-For` is believed
+    #[serde(default)]
+    pub trusted_proxies: Vec<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -28,9 +33,399 @@ pub struct CoingeckoConfig {
     pub api_key: String,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct StaticFileConfig {
+    pub path: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MockConfig {
+    #[serde(default = "default_mock_price")]
+    pub default_price: f64,
+    #[serde(default)]
+    pub prices: HashMap<String, f64>,
+}
+
+fn default_mock_price() -> f64 {
+    1.0
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PriceCacheConfig {
+    /// Seconds a cached spot price is served without asking the provider again
+    #[serde(default = "default_price_cache_ttl")]
+    pub ttl_secs: u64,
+    /// Seconds a cached spot price is kept to be served as stale when the provider fails
+    #[serde(default = "default_price_cache_stale_ttl")]
+    pub stale_ttl_secs: u64,
+}
+
+fn default_price_cache_ttl() -> u64 {
+    60
+}
+
+fn default_price_cache_stale_ttl() -> u64 {
+    24 * 60 * 60
+}
+
+impl Default for PriceCacheConfig {
+    fn default() -> Self {
+        Self {
+            ttl_secs: default_price_cache_ttl(),
+            stale_ttl_secs: default_price_cache_stale_ttl(),
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PriceProviderKind {
+    #[default]
+    Coingecko,
+    StaticFile,
+    Mock,
+}
+
+#[derive(Debug, Default, Clone, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum MailerKind {
+    Smtp,
+    /// Writes mails to a directory or the log, for local development and tests
+    #[default]
+    File,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SmtpConfig {
+    pub host: String,
+    #[serde(default = "default_smtp_port")]
+    pub port: u16,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Upgrade the connection with STARTTLS, plain text otherwise, e.g. for a local relay
+    #[serde(default = "default_smtp_starttls")]
+    pub starttls: bool,
+}
+
+fn default_smtp_port() -> u16 {
+    587
+}
+
+fn default_smtp_starttls() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct FileMailerConfig {
+    /// Directory mails are written to, they are only logged when missing
+    pub path: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct MailerConfig {
+    #[serde(default)]
+    pub kind: MailerKind,
+    #[serde(default = "default_mail_from")]
+    pub from: String,
+    pub smtp: Option<SmtpConfig>,
+    pub file: Option<FileMailerConfig>,
+}
+
+fn default_mail_from() -> String {
+    "Portfolio <no-reply@localhost>".to_string()
+}
+
+impl Default for MailerConfig {
+    fn default() -> Self {
+        Self {
+            kind: MailerKind::default(),
+            from: default_mail_from(),
+            smtp: None,
+            file: None,
+        }
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct ClientsConfig {
-    pub coingecko: CoingeckoConfig,
+    #[serde(default)]
+    pub price_provider: PriceProviderKind,
+    pub coingecko: Option<CoingeckoConfig>,
+    pub static_file: Option<StaticFileConfig>,
+    pub mock: Option<MockConfig>,
+    #[serde(default)]
+    pub price_cache: PriceCacheConfig,
+    #[serde(default)]
+    pub mailer: MailerConfig,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SchedulerConfig {
+    #[serde(default = "default_scheduler_enabled")]
+    pub enabled: bool,
+    #[serde(default = "default_price_refresh_interval")]
+    pub price_refresh_interval_secs: u64,
+    #[serde(default = "default_daily_interval")]
+    pub daily_snapshots_interval_secs: u64,
+    #[serde(default = "default_daily_interval")]
+    pub asset_metadata_interval_secs: u64,
+    #[serde(default = "default_daily_interval")]
+    pub fx_rates_interval_secs: u64,
+    /// Seconds a job lock is held at most, a crashed replica frees it after this
+    #[serde(default = "default_job_lock_ttl")]
+    pub lock_ttl_secs: u64,
+}
+
+fn default_scheduler_enabled() -> bool {
+    true
+}
+
+fn default_price_refresh_interval() -> u64 {
+    5 * 60
+}
+
+fn default_daily_interval() -> u64 {
+    24 * 60 * 60
+}
+
+fn default_job_lock_ttl() -> u64 {
+    60 * 60
+}
+
+impl Default for SchedulerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_scheduler_enabled(),
+            price_refresh_interval_secs: default_price_refresh_interval(),
+            daily_snapshots_interval_secs: default_daily_interval(),
+            asset_metadata_interval_secs: default_daily_interval(),
+            fx_rates_interval_secs: default_daily_interval(),
+            lock_ttl_secs: default_job_lock_ttl(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ImportWorkerConfig {
+    #[serde(default = "default_import_worker_enabled")]
+    pub enabled: bool,
+    /// Seconds between two looks at the queue when it is empty
+    #[serde(default = "default_import_poll_interval")]
+    pub poll_interval_secs: u64,
+    /// Seconds a running job stays claimed, the worker renews the lease while
+    /// it works on the job and jobs whose lease expired are queued again
+    #[serde(default = "default_import_lease")]
+    pub lease_secs: u64,
+}
+
+fn default_import_worker_enabled() -> bool {
+    true
+}
+
+fn default_import_poll_interval() -> u64 {
+    2
+}
+
+fn default_import_lease() -> u64 {
+    60
+}
+
+impl Default for ImportWorkerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_import_worker_enabled(),
+            poll_interval_secs: default_import_poll_interval(),
+            lease_secs: default_import_lease(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct AuthConfig {
+    /// Seconds an access token is accepted, refresh tokens get new ones
+    #[serde(default = "default_access_token_ttl")]
+    pub access_token_ttl_secs: u64,
+    /// Seconds a refresh token can be used, a session ends when it is not
+    /// refreshed for that long
+    #[serde(default = "default_refresh_token_ttl")]
+    pub refresh_token_ttl_secs: u64,
+    /// Seconds a link sent to verify an email address works
+    #[serde(default = "default_email_verification_ttl")]
+    pub email_verification_ttl_secs: u64,
+    /// Seconds a link sent to reset a password works
+    #[serde(default = "default_password_reset_ttl")]
+    pub password_reset_ttl_secs: u64,
+    /// Base URL of the web app, links sent by mail point to its pages
+    #[serde(default = "default_app_url")]
+    pub app_url: String,
+    /// Issuer shown by authenticator apps next to the account
+    #[serde(default = "default_totp_issuer")]
+    pub totp_issuer: String,
+    /// Seconds a password login of a two-factor account has to send its code
+    #[serde(default = "default_login_challenge_ttl")]
+    pub login_challenge_ttl_secs: u64,
+}
+
+fn default_access_token_ttl() -> u64 {
+    15 * 60
+}
+
+fn default_refresh_token_ttl() -> u64 {
+    30 * 24 * 60 * 60
+}
+
+fn default_email_verification_ttl() -> u64 {
+    24 * 60 * 60
+}
+
+fn default_password_reset_ttl() -> u64 {
+    60 * 60
+}
+
+fn default_app_url() -> String {
+    "http://localhost:4000".to_string()
+}
+
+fn default_totp_issuer() -> String {
+    "Portfolio".to_string()
+}
+
+fn default_login_challenge_ttl() -> u64 {
+    5 * 60
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        Self {
+            access_token_ttl_secs: default_access_token_ttl(),
+            refresh_token_ttl_secs: default_refresh_token_ttl(),
+            email_verification_ttl_secs: default_email_verification_ttl(),
+            password_reset_ttl_secs: default_password_reset_ttl(),
+            app_url: default_app_url(),
+            totp_issuer: default_totp_issuer(),
+            login_challenge_ttl_secs: default_login_challenge_ttl(),
+        }
+    }
+}
+
+/// Requests allowed per fixed window, per client IP and per account. A limit
+/// of 0 turns that bucket off.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RateLimitRule {
+    pub per_ip: u64,
+    /// Accounts are the authenticated user, or the `email` of a JSON body on
+    /// public routes
+    pub per_account: u64,
+    pub window_secs: u64,
+}
+
+/// Failed logins of an email within the window lock it for `lockout_secs`
+#[derive(Debug, Clone, Deserialize)]
+pub struct LoginLockoutConfig {
+    #[serde(default = "default_lockout_max_failures")]
+    pub max_failures: u64,
+    #[serde(default = "default_lockout_failure_window")]
+    pub failure_window_secs: u64,
+    #[serde(default = "default_lockout_duration")]
+    pub lockout_secs: u64,
+}
+
+fn default_lockout_max_failures() -> u64 {
+    5
+}
+
+fn default_lockout_failure_window() -> u64 {
+    15 * 60
+}
+
+fn default_lockout_duration() -> u64 {
+    15 * 60
+}
+
+impl Default for LoginLockoutConfig {
+    fn default() -> Self {
+        Self {
+            max_failures: default_lockout_max_failures(),
+            failure_window_secs: default_lockout_failure_window(),
+            lockout_secs: default_lockout_duration(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RateLimitConfig {
+    #[serde(default = "default_rate_limit_enabled")]
+    pub enabled: bool,
+    /// Password and second factor login routes
+    #[serde(default = "default_login_rate_limit")]
+    pub login: RateLimitRule,
+    /// Other public auth routes: signup, verification and password reset
+    #[serde(default = "default_auth_rate_limit")]
+    pub auth: RateLimitRule,
+    /// Authenticated API routes
+    #[serde(default = "default_api_rate_limit")]
+    pub api: RateLimitRule,
+    #[serde(default)]
+    pub lockout: LoginLockoutConfig,
+}
+
+fn default_rate_limit_enabled() -> bool {
+    true
+}
+
+fn default_login_rate_limit() -> RateLimitRule {
+    RateLimitRule {
+        per_ip: 20,
+        per_account: 10,
+        window_secs: 60,
+    }
+}
+
+fn default_auth_rate_limit() -> RateLimitRule {
+    RateLimitRule {
+        per_ip: 10,
+        per_account: 3,
+        window_secs: 60,
+    }
+}
+
+fn default_api_rate_limit() -> RateLimitRule {
+    RateLimitRule {
+        per_ip: 600,
+        per_account: 300,
+        window_secs: 60,
+    }
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_rate_limit_enabled(),
+            login: default_login_rate_limit(),
+            auth: default_auth_rate_limit(),
+            api: default_api_rate_limit(),
+            lockout: LoginLockoutConfig::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TaxReportConfig {
+    /// Disposals of lots held longer than this many days are long-term gains
+    #[serde(default = "default_long_term_threshold")]
+    pub long_term_threshold_days: i64,
+}
+
+fn default_long_term_threshold() -> i64 {
+    365
+}
+
+impl Default for TaxReportConfig {
+    fn default() -> Self {
+        Self {
+            long_term_threshold_days: default_long_term_threshold(),
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -39,7 +434,17 @@ pub struct Settings {
     pub logging: Logging,
     pub postgres: Postgres,
     pub redis: Redis,
+    #[serde(default)]
+    pub auth: AuthConfig,
     pub clients: ClientsConfig,
+    #[serde(default)]
+    pub scheduler: SchedulerConfig,
+    #[serde(default)]
+    pub import_worker: ImportWorkerConfig,
+    #[serde(default)]
+    pub tax_report: TaxReportConfig,
+    #[serde(default)]
+    pub rate_limit: RateLimitConfig,
 }
 
 pub fn load_config() -> Result<Settings, config::ConfigError> {
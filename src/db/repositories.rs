@@ -1,6 +1,19 @@
 pub mod asset;
+pub mod backup;
+pub mod fx_rate;
+pub mod import_batch;
+pub mod import_job;
+pub mod job_run;
+pub mod personal_access_token;
 pub mod portfolio;
 pub mod portfolio_asset;
+pub mod portfolio_snapshot;
+pub mod price_history;
+pub mod realized_gain;
+pub mod refresh_token;
+pub mod tax_lot;
 pub mod transaction;
 pub mod user;
 pub mod user_session;
+pub mod user_token;
+pub mod user_totp;
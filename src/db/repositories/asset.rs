@@ -2,6 +2,7 @@ use sqlx::PgPool;
 use time::OffsetDateTime;
 
 use crate::biz::asset::generate_asset_id;
+use crate::models::common::asset::AssetType;
 use crate::models::database::asset::AssetRow;
 use crate::models::dto::asset::CreateAssetRepo;
 use crate::utils::error::AppError;
@@ -66,6 +67,61 @@ impl AssetRepo {
         .await?)
     }
 
+    pub async fn get_multi_by_type(
+        &self,
+        asset_type: &AssetType,
+    ) -> Result<Vec<AssetRow>, AppError> {
+        Ok(sqlx::query_as!(
+            AssetRow,
+            r#"
+                SELECT id, asset_type, external_id, source, symbol, name, image, ext, created_at, updated_at
+                FROM assets
+                WHERE asset_type = $1
+            "#,
+            asset_type.to_string()
+        )
+        .fetch_all(&self.pool)
+        .await?)
+    }
+
+    /// Overwrites the descriptive fields of an asset, its ID and external ID are kept
+    pub async fn update_metadata(
+        &self,
+        asset_id: &str,
+        inp: &CreateAssetRepo,
+    ) -> Result<(), AppError> {
+        sqlx::query!(
+            r#"
+            UPDATE assets
+            SET symbol = $2, name = $3, image = $4, ext = $5, updated_at = $6
+            WHERE id = $1
+            "#,
+            asset_id,
+            inp.symbol,
+            inp.name,
+            serde_json::to_value(&inp.image)?,
+            serde_json::to_value(&inp.ext)?,
+            OffsetDateTime::now_utc()
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Assets currently held in at least one portfolio
+    pub async fn get_multi_held(&self) -> Result<Vec<AssetRow>, AppError> {
+        Ok(sqlx::query_as!(
+            AssetRow,
+            r#"
+                SELECT id, asset_type, external_id, source, symbol, name, image, ext, created_at, updated_at
+                FROM assets
+                WHERE id IN (SELECT asset_id FROM portfolio_assets WHERE holding_amount > 0)
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?)
+    }
+
     pub async fn get_multi_with_paging(
         &self,
         asset_type: Option<String>,
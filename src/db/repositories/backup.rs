@@ -0,0 +1,146 @@
+use sqlx::PgPool;
+use time::OffsetDateTime;
+
+use crate::models::dto::backup::RestoreBackup;
+use crate::utils::error::AppError;
+use crate::utils::snowflake::SNOWFLAKE_GENERATOR;
+
+pub struct BackupRepo {
+    pool: PgPool,
+}
+
+impl BackupRepo {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Deletes the replaced portfolios with everything they hold, creates the
+    /// missing assets, then writes the restored portfolios, their assets and
+    /// transactions, all or nothing. Returns the IDs of the assets created.
+    pub async fn restore(&self, inp: RestoreBackup) -> Result<Vec<String>, AppError> {
+        let now = OffsetDateTime::now_utc();
+        let mut db_tx = self.pool.begin().await?;
+
+        let replaced = &inp.replaced_portfolio_ids;
+        if !replaced.is_empty() {
+            sqlx::query!(
+                r#"DELETE FROM realized_gains WHERE portfolio_id = ANY($1)"#,
+                replaced
+            )
+            .execute(&mut *db_tx)
+            .await?;
+            sqlx::query!(
+                r#"DELETE FROM tax_lots WHERE portfolio_id = ANY($1)"#,
+                replaced
+            )
+            .execute(&mut *db_tx)
+            .await?;
+            sqlx::query!(
+                r#"DELETE FROM transactions WHERE portfolio_id = ANY($1)"#,
+                replaced
+            )
+            .execute(&mut *db_tx)
+            .await?;
+            sqlx::query!(
+                r#"DELETE FROM portfolio_snapshots WHERE portfolio_id = ANY($1)"#,
+                replaced
+            )
+            .execute(&mut *db_tx)
+            .await?;
+            sqlx::query!(
+                r#"DELETE FROM portfolio_assets WHERE portfolio_id = ANY($1)"#,
+                replaced
+            )
+            .execute(&mut *db_tx)
+            .await?;
+            // Import batches and jobs of the portfolios go with them
+            sqlx::query!(
+                r#"DELETE FROM portfolios WHERE id = ANY($1) AND owner_id = $2"#,
+                replaced,
+                inp.owner_id
+            )
+            .execute(&mut *db_tx)
+            .await?;
+        }
+
+        let mut new_assets = Vec::new();
+        if !inp.assets.is_empty() {
+            let mut query_builder = sqlx::QueryBuilder::new(
+                "INSERT INTO assets (id, asset_type, external_id, source, symbol, name, image, ext, created_at, updated_at) ",
+            );
+            query_builder.push_values(inp.assets.iter(), |mut b, asset| {
+                b.push_bind(&asset.id)
+                    .push_bind(asset.asset_type.to_string())
+                    .push_bind(&asset.external_id)
+                    .push_bind(&asset.source)
+                    .push_bind(&asset.symbol)
+                    .push_bind(&asset.name)
+                    .push_bind(&asset.image)
+                    .push_bind(&asset.ext)
+                    .push_bind(now)
+                    .push_bind(now);
+            });
+            query_builder.push(" ON CONFLICT (id) DO NOTHING RETURNING id");
+            new_assets = query_builder
+                .build_query_scalar::<String>()
+                .fetch_all(&mut *db_tx)
+                .await?;
+        }
+
+        for portfolio in inp.portfolios {
+            sqlx::query!(
+                r#"
+                INSERT INTO portfolios (id, owner_id, name, cost_basis_method, base_currency, created_at, updated_at)
+                VALUES ($1, $2, $3, $4, $5, $6, $7)
+                "#,
+                portfolio.id,
+                inp.owner_id,
+                portfolio.name,
+                portfolio.cost_basis_method.to_string(),
+                portfolio.base_currency.to_string(),
+                portfolio.created_at,
+                now
+            )
+            .execute(&mut *db_tx)
+            .await?;
+            if !portfolio.asset_ids.is_empty() {
+                let mut query_builder = sqlx::QueryBuilder::new(
+                    "INSERT INTO portfolio_assets (portfolio_id, asset_id, created_at, updated_at) ",
+                );
+                query_builder.push_values(portfolio.asset_ids.iter(), |mut b, asset_id| {
+                    b.push_bind(portfolio.id)
+                        .push_bind(asset_id)
+                        .push_bind(now)
+                        .push_bind(now);
+                });
+                query_builder.build().execute(&mut *db_tx).await?;
+            }
+            // Bind parameters are limited to 65535 per statement
+            for chunk in portfolio.transactions.chunks(5000) {
+                let mut query_builder = sqlx::QueryBuilder::new(
+                    "INSERT INTO transactions (id, external_id, portfolio_id, asset_id, tx_type, quantity, price, fees, currency, executed_at, notes) ",
+                );
+                query_builder.push_values(chunk, |mut b, item| {
+                    b.push_bind(
+                        item.tx
+                            .id
+                            .unwrap_or(SNOWFLAKE_GENERATOR.generate().unwrap()),
+                    )
+                    .push_bind(&item.tx.external_id)
+                    .push_bind(portfolio.id)
+                    .push_bind(&item.asset_id)
+                    .push_bind(item.tx.tx_type.to_string())
+                    .push_bind(item.tx.quantity)
+                    .push_bind(item.tx.price)
+                    .push_bind(item.tx.fees)
+                    .push_bind(item.tx.currency.to_string())
+                    .push_bind(item.tx.executed_at)
+                    .push_bind(&item.tx.notes);
+                });
+                query_builder.build().execute(&mut *db_tx).await?;
+            }
+        }
+        db_tx.commit().await?;
+        Ok(new_assets)
+    }
+}
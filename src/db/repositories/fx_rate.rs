@@ -0,0 +1,118 @@
+use rust_decimal::Decimal;
+use sqlx::PgPool;
+use time::{Date, OffsetDateTime};
+
+use crate::models::database::fx_rate::FxRateRow;
+use crate::utils::error::AppError;
+
+pub struct FxRateRepo {
+    pool: PgPool,
+}
+
+impl FxRateRepo {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Inserts daily rates per 1 USD, overwriting the ones already stored for the same day.
+    pub async fn upsert_multi(
+        &self,
+        source: &str,
+        rates: &[(String, Date, Decimal)],
+    ) -> Result<u64, AppError> {
+        if rates.is_empty() {
+            return Ok(0);
+        }
+        let now = OffsetDateTime::now_utc();
+        let mut query_builder = sqlx::QueryBuilder::new(
+            "INSERT INTO fx_rates (currency, rate_date, rate, source, created_at, updated_at) ",
+        );
+        query_builder.push_values(rates, |mut b, (currency, rate_date, rate)| {
+            b.push_bind(currency)
+                .push_bind(rate_date)
+                .push_bind(rate)
+                .push_bind(source)
+                .push_bind(now)
+                .push_bind(now);
+        });
+        query_builder.push(
+            " ON CONFLICT (currency, rate_date) DO UPDATE SET rate = EXCLUDED.rate, source = EXCLUDED.source, updated_at = EXCLUDED.updated_at",
+        );
+        let result = query_builder.build().execute(&self.pool).await?;
+        Ok(result.rows_affected())
+    }
+
+    pub async fn get_multi_by_currencies(
+        &self,
+        currencies: &[String],
+    ) -> Result<Vec<FxRateRow>, AppError> {
+        Ok(sqlx::query_as!(
+            FxRateRow,
+            r#"
+                SELECT currency, rate_date, rate, source, created_at, updated_at
+                FROM fx_rates
+                WHERE currency = ANY($1)
+                ORDER BY rate_date ASC
+            "#,
+            currencies
+        )
+        .fetch_all(&self.pool)
+        .await?)
+    }
+
+    /// Days of the list lacking the rate of at least one of the currencies
+    pub async fn get_missing_dates(
+        &self,
+        currencies: &[String],
+        dates: &[Date],
+    ) -> Result<Vec<Date>, AppError> {
+        Ok(sqlx::query_scalar!(
+            r#"
+                SELECT d.rate_date AS "rate_date!"
+                FROM unnest($2::date[]) AS d(rate_date)
+                WHERE EXISTS (
+                    SELECT 1
+                    FROM unnest($1::varchar[]) AS c(currency)
+                    WHERE NOT EXISTS (
+                        SELECT 1 FROM fx_rates f
+                        WHERE f.currency = c.currency AND f.rate_date = d.rate_date
+                    )
+                )
+                ORDER BY d.rate_date DESC
+            "#,
+            currencies,
+            dates
+        )
+        .fetch_all(&self.pool)
+        .await?)
+    }
+
+    /// Execution days of transactions that need a rate of one of the currencies,
+    /// as the transaction or portfolio currency, and have none stored. Latest first.
+    pub async fn get_missing_transaction_dates(
+        &self,
+        currencies: &[String],
+        limit: i64,
+    ) -> Result<Vec<Date>, AppError> {
+        Ok(sqlx::query_scalar!(
+            r#"
+                SELECT DISTINCT (t.executed_at AT TIME ZONE 'UTC')::date AS "rate_date!"
+                FROM transactions t
+                JOIN portfolios p ON p.id = t.portfolio_id
+                CROSS JOIN LATERAL (VALUES (t.currency), (p.base_currency)) AS c(currency)
+                WHERE c.currency = ANY($1)
+                  AND NOT EXISTS (
+                    SELECT 1 FROM fx_rates f
+                    WHERE f.currency = c.currency
+                      AND f.rate_date = (t.executed_at AT TIME ZONE 'UTC')::date
+                  )
+                ORDER BY 1 DESC
+                LIMIT $2
+            "#,
+            currencies,
+            limit
+        )
+        .fetch_all(&self.pool)
+        .await?)
+    }
+}
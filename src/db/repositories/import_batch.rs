@@ -0,0 +1,402 @@
+use sqlx::PgPool;
+use time::OffsetDateTime;
+
+use crate::biz::asset::generate_asset_id;
+use crate::models::database::import_batch::{ImportBatchChangeRow, ImportBatchRow};
+use crate::models::domain::import::{ImportBatchStatus, ImportJobStatus};
+use crate::models::domain::transaction::BaseTransactionInfo;
+use crate::models::dto::import::ApplyImportBatch;
+use crate::utils::error::AppError;
+use crate::utils::snowflake::SNOWFLAKE_GENERATOR;
+
+pub struct ImportBatchRepo {
+    pool: PgPool,
+}
+
+impl ImportBatchRepo {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Writes an import as a new batch: creates the missing assets, attaches the
+    /// missing portfolio assets, inserts and updates the transactions and records
+    /// the changes, all or nothing.
+    pub async fn apply(&self, inp: ApplyImportBatch) -> Result<ImportBatchRow, AppError> {
+        let now = OffsetDateTime::now_utc();
+        let mut db_tx = self.pool.begin().await?;
+        // Only what the import created is recorded, another import may have
+        // created the same assets meanwhile
+        let mut new_asset_ids: Vec<String> = Vec::new();
+        let mut new_portfolio_asset_ids: Vec<String> = Vec::new();
+        if !inp.new_assets.is_empty() {
+            let mut query_builder = sqlx::QueryBuilder::new(
+                "INSERT INTO assets (id, asset_type, external_id, source, symbol, name, image, ext, created_at, updated_at) ",
+            );
+            let mut values = Vec::with_capacity(inp.new_assets.len());
+            for asset in inp.new_assets.iter() {
+                values.push((
+                    serde_json::to_value(&asset.image)?,
+                    serde_json::to_value(&asset.ext)?,
+                ));
+            }
+            query_builder.push_values(
+                inp.new_assets.iter().zip(values),
+                |mut b, (asset, (image, ext))| {
+                    b.push_bind(generate_asset_id(&asset.asset_type, &asset.external_id))
+                        .push_bind(asset.asset_type.to_string())
+                        .push_bind(&asset.external_id)
+                        .push_bind(&asset.source)
+                        .push_bind(&asset.symbol)
+                        .push_bind(&asset.name)
+                        .push_bind(image)
+                        .push_bind(ext)
+                        .push_bind(now)
+                        .push_bind(now);
+                },
+            );
+            query_builder.push(" ON CONFLICT (id) DO NOTHING RETURNING id");
+            new_asset_ids = query_builder
+                .build_query_scalar()
+                .fetch_all(&mut *db_tx)
+                .await?;
+        }
+
+        if !inp.new_portfolio_assets.is_empty() {
+            let mut query_builder = sqlx::QueryBuilder::new(
+                "INSERT INTO portfolio_assets (portfolio_id, asset_id, created_at, updated_at) ",
+            );
+            query_builder.push_values(inp.new_portfolio_assets.iter(), |mut b, asset_id| {
+                b.push_bind(inp.batch.portfolio_id)
+                    .push_bind(asset_id)
+                    .push_bind(now)
+                    .push_bind(now);
+            });
+            query_builder
+                .push(" ON CONFLICT (portfolio_id, asset_id) DO NOTHING RETURNING asset_id");
+            new_portfolio_asset_ids = query_builder
+                .build_query_scalar()
+                .fetch_all(&mut *db_tx)
+                .await?;
+        }
+
+        let batch = sqlx::query_as!(
+            ImportBatchRow,
+            r#"
+            INSERT INTO import_batches (id, user_id, portfolio_id, source, file_hash, created_count, updated_count, unchanged_count, new_asset_ids, new_portfolio_asset_ids, status, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
+            RETURNING *
+            "#,
+            SNOWFLAKE_GENERATOR.generate().unwrap(),
+            inp.batch.user_id,
+            inp.batch.portfolio_id,
+            inp.batch.source,
+            inp.batch.file_hash,
+            inp.batch.created_count,
+            inp.batch.updated_count,
+            inp.batch.unchanged_count,
+            &new_asset_ids,
+            &new_portfolio_asset_ids,
+            ImportBatchStatus::Completed.to_string(),
+            now,
+            now
+        )
+        .fetch_one(&mut *db_tx)
+        .await?;
+
+        // A job whose lease was lost may be applied by another worker meanwhile,
+        // only the attempt holding it writes
+        if let Some(job) = inp.job {
+            let finished = sqlx::query!(
+                r#"
+                UPDATE import_jobs
+                SET status = $3, batch_id = $4, rows_inserted = $5, lease_expires_at = NULL,
+                    finished_at = $6, updated_at = $6
+                WHERE id = $1 AND attempts = $2 AND status = $7
+                "#,
+                job.job_id,
+                job.attempt,
+                ImportJobStatus::Succeeded.to_string(),
+                batch.id,
+                batch.created_count + batch.updated_count,
+                now,
+                ImportJobStatus::Running.to_string()
+            )
+            .execute(&mut *db_tx)
+            .await?;
+            if finished.rows_affected() == 0 {
+                return Err(AppError::Conflict(format!(
+                    "Import job {} is no longer held by this worker",
+                    job.job_id
+                )));
+            }
+        }
+
+        if !inp.creates.is_empty() {
+            let mut query_builder = sqlx::QueryBuilder::new(
+                "INSERT INTO transactions (id, external_id, portfolio_id, asset_id, tx_type, quantity, price, fees, currency, executed_at, notes, import_batch_id) ",
+            );
+            query_builder.push_values(inp.creates, |mut b, item| {
+                b.push_bind(
+                    item.tx
+                        .id
+                        .unwrap_or(SNOWFLAKE_GENERATOR.generate().unwrap()),
+                )
+                .push_bind(item.tx.external_id)
+                .push_bind(batch.portfolio_id)
+                .push_bind(item.asset_id)
+                .push_bind(item.tx.tx_type.to_string())
+                .push_bind(item.tx.quantity)
+                .push_bind(item.tx.price)
+                .push_bind(item.tx.fees)
+                .push_bind(item.tx.currency.to_string())
+                .push_bind(item.tx.executed_at)
+                .push_bind(item.tx.notes)
+                .push_bind(batch.id);
+            });
+            query_builder.build().execute(&mut *db_tx).await?;
+        }
+
+        for (tx_id, after) in inp.updates.iter() {
+            sqlx::query!(
+                r#"
+                UPDATE transactions
+                SET tx_type = $2, quantity = $3, price = $4, fees = $5, currency = $6,
+                    executed_at = $7, notes = $8, import_batch_id = $9, updated_at = $10
+                WHERE id = $1
+                "#,
+                tx_id,
+                after.tx_type.to_string(),
+                after.quantity,
+                after.price,
+                after.fees,
+                after.currency.to_string(),
+                after.executed_at,
+                after.notes,
+                batch.id,
+                now
+            )
+            .execute(&mut *db_tx)
+            .await?;
+        }
+
+        if !inp.changes.is_empty() {
+            let mut query_builder = sqlx::QueryBuilder::new(
+                "INSERT INTO import_batch_changes (batch_id, tx_id, asset_id, action, previous, previous_batch_id, after) ",
+            );
+            query_builder.push_values(inp.changes.iter(), |mut b, item| {
+                b.push_bind(batch.id)
+                    .push_bind(item.tx_id)
+                    .push_bind(&item.asset_id)
+                    .push_bind(item.action.to_string())
+                    .push_bind(&item.previous)
+                    .push_bind(item.previous_batch_id)
+                    .push_bind(&item.after);
+            });
+            query_builder.build().execute(&mut *db_tx).await?;
+        }
+        db_tx.commit().await?;
+        Ok(batch)
+    }
+
+    pub async fn get_one_by_id(&self, id: i64) -> Result<Option<ImportBatchRow>, AppError> {
+        Ok(sqlx::query_as!(
+            ImportBatchRow,
+            r#"SELECT * FROM import_batches WHERE id = $1"#,
+            id
+        )
+        .fetch_optional(&self.pool)
+        .await?)
+    }
+
+    pub async fn get_changes(&self, batch_id: i64) -> Result<Vec<ImportBatchChangeRow>, AppError> {
+        Ok(sqlx::query_as!(
+            ImportBatchChangeRow,
+            r#"SELECT * FROM import_batch_changes WHERE batch_id = $1"#,
+            batch_id
+        )
+        .fetch_all(&self.pool)
+        .await?)
+    }
+
+    pub async fn get_multi_by_user_with_paging(
+        &self,
+        user_id: i64,
+        portfolio_id: Option<i64>,
+        page: u32,
+        limit: u32,
+    ) -> Result<Vec<ImportBatchRow>, AppError> {
+        let query_limit = limit as i64;
+        let query_offset = ((page - 1) * limit) as i64;
+        Ok(sqlx::query_as!(
+            ImportBatchRow,
+            r#"
+                SELECT *
+                FROM import_batches
+                WHERE user_id = $1 AND ($2::BIGINT IS NULL OR portfolio_id = $2)
+                ORDER BY created_at DESC
+                LIMIT $3 OFFSET $4
+            "#,
+            user_id,
+            portfolio_id,
+            query_limit,
+            query_offset
+        )
+        .fetch_all(&self.pool)
+        .await?)
+    }
+
+    pub async fn count_by_user(
+        &self,
+        user_id: i64,
+        portfolio_id: Option<i64>,
+    ) -> Result<i64, AppError> {
+        Ok(sqlx::query_scalar!(
+            r#"
+                SELECT COUNT(*) AS "count!"
+                FROM import_batches
+                WHERE user_id = $1 AND ($2::BIGINT IS NULL OR portfolio_id = $2)
+            "#,
+            user_id,
+            portfolio_id
+        )
+        .fetch_one(&self.pool)
+        .await?)
+    }
+
+    /// Deletes the transactions the import created, puts back the ones it
+    /// modified and marks the batch as rolled back, all or nothing.
+    /// Undoes a completed batch: deletes the transactions it created, restores
+    /// the ones it updated and removes the assets and portfolio assets it created
+    /// that nothing uses anymore. Returns the batch with the removed portfolio
+    /// assets.
+    pub async fn rollback(
+        &self,
+        batch_id: i64,
+        created_tx_ids: &[i64],
+        restores: &[(i64, BaseTransactionInfo, Option<i64>)],
+    ) -> Result<(ImportBatchRow, Vec<String>), AppError> {
+        let now = OffsetDateTime::now_utc();
+        let mut db_tx = self.pool.begin().await?;
+        // Claims the batch first, a concurrent rollback waits on the row lock
+        // and then finds it rolled back
+        let Some(row) = sqlx::query_as!(
+            ImportBatchRow,
+            r#"
+            UPDATE import_batches
+            SET status = $2, updated_at = $3
+            WHERE id = $1 AND status = $4
+            RETURNING *
+            "#,
+            batch_id,
+            ImportBatchStatus::RolledBack.to_string(),
+            now,
+            ImportBatchStatus::Completed.to_string()
+        )
+        .fetch_optional(&mut *db_tx)
+        .await?
+        else {
+            return Err(AppError::Conflict(
+                "Import is already rolled back".to_string(),
+            ));
+        };
+
+        let deleted = sqlx::query!(
+            r#"DELETE FROM transactions WHERE id = ANY($1) AND import_batch_id = $2"#,
+            created_tx_ids,
+            batch_id
+        )
+        .execute(&mut *db_tx)
+        .await?;
+        if deleted.rows_affected() != created_tx_ids.len() as u64 {
+            return Err(AppError::Conflict(
+                "The imported transactions changed during the rollback, try again".to_string(),
+            ));
+        }
+        for (tx_id, previous, previous_batch_id) in restores {
+            let restored = sqlx::query!(
+                r#"
+                UPDATE transactions
+                SET tx_type = $2, quantity = $3, price = $4, fees = $5, currency = $6,
+                    executed_at = $7, notes = $8, import_batch_id = $9, updated_at = $10
+                WHERE id = $1 AND import_batch_id = $11
+                "#,
+                tx_id,
+                previous.tx_type.to_string(),
+                previous.quantity,
+                previous.price,
+                previous.fees,
+                previous.currency.to_string(),
+                previous.executed_at,
+                previous.notes,
+                *previous_batch_id,
+                now,
+                batch_id
+            )
+            .execute(&mut *db_tx)
+            .await?;
+            if restored.rows_affected() == 0 {
+                return Err(AppError::Conflict(
+                    "The imported transactions changed during the rollback, try again".to_string(),
+                ));
+            }
+        }
+
+        // Portfolio assets left without transactions go with their derived rows
+        let removed_portfolio_assets: Vec<String> = sqlx::query_scalar!(
+            r#"
+            SELECT pa.asset_id
+            FROM portfolio_assets pa
+            WHERE pa.portfolio_id = $1 AND pa.asset_id = ANY($2)
+                AND NOT EXISTS (
+                    SELECT 1 FROM transactions t
+                    WHERE t.portfolio_id = pa.portfolio_id AND t.asset_id = pa.asset_id
+                )
+            "#,
+            row.portfolio_id,
+            &row.new_portfolio_asset_ids
+        )
+        .fetch_all(&mut *db_tx)
+        .await?;
+        if !removed_portfolio_assets.is_empty() {
+            sqlx::query!(
+                r#"DELETE FROM tax_lots WHERE portfolio_id = $1 AND asset_id = ANY($2)"#,
+                row.portfolio_id,
+                &removed_portfolio_assets
+            )
+            .execute(&mut *db_tx)
+            .await?;
+            sqlx::query!(
+                r#"DELETE FROM realized_gains WHERE portfolio_id = $1 AND asset_id = ANY($2)"#,
+                row.portfolio_id,
+                &removed_portfolio_assets
+            )
+            .execute(&mut *db_tx)
+            .await?;
+            sqlx::query!(
+                r#"DELETE FROM portfolio_assets WHERE portfolio_id = $1 AND asset_id = ANY($2)"#,
+                row.portfolio_id,
+                &removed_portfolio_assets
+            )
+            .execute(&mut *db_tx)
+            .await?;
+        }
+
+        // Assets another portfolio picked up since the import are kept
+        sqlx::query!(
+            r#"
+            DELETE FROM assets a
+            WHERE a.id = ANY($1)
+                AND NOT EXISTS (SELECT 1 FROM transactions WHERE asset_id = a.id)
+                AND NOT EXISTS (SELECT 1 FROM portfolio_assets WHERE asset_id = a.id)
+                AND NOT EXISTS (SELECT 1 FROM tax_lots WHERE asset_id = a.id)
+                AND NOT EXISTS (SELECT 1 FROM realized_gains WHERE asset_id = a.id)
+                AND NOT EXISTS (SELECT 1 FROM price_history WHERE asset_id = a.id)
+            "#,
+            &row.new_asset_ids
+        )
+        .execute(&mut *db_tx)
+        .await?;
+        db_tx.commit().await?;
+        Ok((row, removed_portfolio_assets))
+    }
+}
@@ -0,0 +1,215 @@
+use sqlx::PgPool;
+use time::{Duration, OffsetDateTime};
+
+use crate::models::database::import_job::ImportJobRow;
+use crate::models::domain::import::ImportJobStatus;
+use crate::models::dto::import::CreateImportJob;
+use crate::utils::error::AppError;
+use crate::utils::snowflake::SNOWFLAKE_GENERATOR;
+
+pub struct ImportJobRepo {
+    pool: PgPool,
+}
+
+impl ImportJobRepo {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn create_one(&self, inp: CreateImportJob) -> Result<ImportJobRow, AppError> {
+        let now = OffsetDateTime::now_utc();
+        Ok(sqlx::query_as!(
+            ImportJobRow,
+            r#"
+            INSERT INTO import_jobs (id, user_id, portfolio_id, source, file_contents, options, status, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            RETURNING *
+            "#,
+            SNOWFLAKE_GENERATOR.generate().unwrap(),
+            inp.user_id,
+            inp.portfolio_id,
+            inp.source.to_string(),
+            inp.file_contents,
+            inp.options,
+            ImportJobStatus::Queued.to_string(),
+            now,
+            now
+        )
+        .fetch_one(&self.pool)
+        .await?)
+    }
+
+    pub async fn get_one_by_id(&self, id: i64) -> Result<Option<ImportJobRow>, AppError> {
+        Ok(sqlx::query_as!(
+            ImportJobRow,
+            r#"
+            SELECT *
+            FROM import_jobs
+            WHERE id = $1
+            "#,
+            id
+        )
+        .fetch_optional(&self.pool)
+        .await?)
+    }
+
+    /// Marks the oldest queued job as running, leased for `lease`, and returns
+    /// it. Replicas polling at the same time never claim the same job.
+    pub async fn claim_next(&self, lease: Duration) -> Result<Option<ImportJobRow>, AppError> {
+        let now = OffsetDateTime::now_utc();
+        Ok(sqlx::query_as!(
+            ImportJobRow,
+            r#"
+            UPDATE import_jobs
+            SET status = $1, attempts = attempts + 1, started_at = $2, lease_expires_at = $3, updated_at = $2
+            WHERE id = (
+                SELECT id
+                FROM import_jobs
+                WHERE status = $4
+                ORDER BY created_at
+                LIMIT 1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING *
+            "#,
+            ImportJobStatus::Running.to_string(),
+            now,
+            now + lease,
+            ImportJobStatus::Queued.to_string()
+        )
+        .fetch_optional(&self.pool)
+        .await?)
+    }
+
+    /// Keeps a running job claimed for `lease` more. Returns `false` when the
+    /// attempt no longer holds the job, its lease expired and it was queued or
+    /// claimed again, or it finished.
+    pub async fn renew_lease(
+        &self,
+        id: i64,
+        attempt: i32,
+        lease: Duration,
+    ) -> Result<bool, AppError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE import_jobs
+            SET lease_expires_at = $3
+            WHERE id = $1 AND attempts = $2 AND status = $4
+            "#,
+            id,
+            attempt,
+            OffsetDateTime::now_utc() + lease,
+            ImportJobStatus::Running.to_string()
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Records the rows read by an attempt, which stops with a conflict once it
+    /// no longer holds the job
+    pub async fn update_progress(
+        &self,
+        id: i64,
+        attempt: i32,
+        rows_parsed: i32,
+        errors: &[String],
+    ) -> Result<(), AppError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE import_jobs
+            SET rows_parsed = $3, error_count = $4, errors = $5, updated_at = $6
+            WHERE id = $1 AND attempts = $2 AND status = $7
+            "#,
+            id,
+            attempt,
+            rows_parsed,
+            errors.len() as i32,
+            errors,
+            OffsetDateTime::now_utc(),
+            ImportJobStatus::Running.to_string()
+        )
+        .execute(&self.pool)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Err(AppError::Conflict(format!(
+                "Import job {} is no longer held by this worker",
+                id
+            )));
+        }
+        Ok(())
+    }
+
+    /// Marks a job failed, `error` is added to the errors of the rows. Returns
+    /// `false` when the attempt no longer holds the job. Jobs succeed with the
+    /// batch they write, see `ImportBatchRepo::apply`.
+    pub async fn fail(&self, id: i64, attempt: i32, error: String) -> Result<bool, AppError> {
+        let now = OffsetDateTime::now_utc();
+        let result = sqlx::query!(
+            r#"
+            UPDATE import_jobs
+            SET status = $3,
+                errors = array_append(errors, $4),
+                error_count = error_count + 1,
+                lease_expires_at = NULL,
+                finished_at = $5,
+                updated_at = $5
+            WHERE id = $1 AND attempts = $2 AND status = $6
+            "#,
+            id,
+            attempt,
+            ImportJobStatus::Failed.to_string(),
+            error,
+            now,
+            ImportJobStatus::Running.to_string()
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Puts a failed job back in the queue with its progress cleared. Returns
+    /// `None` when the job is not failed.
+    pub async fn requeue_failed(&self, id: i64) -> Result<Option<ImportJobRow>, AppError> {
+        Ok(sqlx::query_as!(
+            ImportJobRow,
+            r#"
+            UPDATE import_jobs
+            SET status = $2,
+                rows_parsed = 0,
+                rows_inserted = 0,
+                error_count = 0,
+                errors = '{}',
+                started_at = NULL,
+                finished_at = NULL,
+                updated_at = $3
+            WHERE id = $1 AND status = $4
+            RETURNING *
+            "#,
+            id,
+            ImportJobStatus::Queued.to_string(),
+            OffsetDateTime::now_utc(),
+            ImportJobStatus::Failed.to_string()
+        )
+        .fetch_optional(&self.pool)
+        .await?)
+    }
+
+    /// Queues again the running jobs whose lease was not renewed in time, left
+    /// behind by a replica that stopped before finishing them
+    pub async fn requeue_expired(&self) -> Result<u64, AppError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE import_jobs
+            SET status = $1, lease_expires_at = NULL, updated_at = $2
+            WHERE status = $3 AND lease_expires_at < $2
+            "#,
+            ImportJobStatus::Queued.to_string(),
+            OffsetDateTime::now_utc(),
+            ImportJobStatus::Running.to_string()
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+}
@@ -0,0 +1,117 @@
+use sqlx::PgPool;
+use time::OffsetDateTime;
+
+use crate::models::database::job_run::JobRunRow;
+use crate::models::domain::job::{JobName, JobStatus, JobTrigger};
+use crate::utils::error::AppError;
+use crate::utils::snowflake::SNOWFLAKE_GENERATOR;
+
+pub struct JobRunRepo {
+    pool: PgPool,
+}
+
+impl JobRunRepo {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn create_one(
+        &self,
+        job_name: JobName,
+        triggered_by: JobTrigger,
+    ) -> Result<JobRunRow, AppError> {
+        Ok(sqlx::query_as!(
+            JobRunRow,
+            r#"
+            INSERT INTO job_runs (id, job_name, triggered_by, status, started_at)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING *
+            "#,
+            SNOWFLAKE_GENERATOR.generate().unwrap(),
+            job_name.to_string(),
+            triggered_by.to_string(),
+            JobStatus::Running.to_string(),
+            OffsetDateTime::now_utc()
+        )
+        .fetch_one(&self.pool)
+        .await?)
+    }
+
+    pub async fn finish(
+        &self,
+        id: i64,
+        status: JobStatus,
+        error: Option<String>,
+    ) -> Result<(), AppError> {
+        sqlx::query!(
+            r#"
+            UPDATE job_runs
+            SET status = $2, error = $3, finished_at = $4
+            WHERE id = $1
+            "#,
+            id,
+            status.to_string(),
+            error,
+            OffsetDateTime::now_utc()
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Start time of the latest run of a job that did not fail
+    pub async fn get_latest_started_at(
+        &self,
+        job_name: JobName,
+    ) -> Result<Option<OffsetDateTime>, AppError> {
+        Ok(sqlx::query_scalar!(
+            r#"
+            SELECT MAX(started_at)
+            FROM job_runs
+            WHERE job_name = $1 AND status <> $2
+            "#,
+            job_name.to_string(),
+            JobStatus::Failed.to_string()
+        )
+        .fetch_one(&self.pool)
+        .await?)
+    }
+
+    pub async fn get_multi_with_paging(
+        &self,
+        job_name: Option<String>,
+        page: u32,
+        limit: u32,
+    ) -> Result<Vec<JobRunRow>, AppError> {
+        let query_limit = limit as i64;
+        let query_offset = ((page - 1) * limit) as i64;
+        Ok(sqlx::query_as!(
+            JobRunRow,
+            r#"
+                SELECT *
+                FROM job_runs
+                WHERE ($1::VARCHAR IS NULL OR job_name = $1)
+                ORDER BY started_at DESC
+                LIMIT $2 OFFSET $3
+            "#,
+            job_name,
+            query_limit,
+            query_offset
+        )
+        .fetch_all(&self.pool)
+        .await?)
+    }
+
+    pub async fn count(&self, job_name: Option<String>) -> Result<i64, AppError> {
+        Ok(sqlx::query_scalar!(
+            r#"
+                SELECT COUNT(*) AS "count!"
+                FROM job_runs
+                WHERE ($1::VARCHAR IS NULL OR job_name = $1)
+            "#,
+            job_name
+        )
+        .fetch_one(&self.pool)
+        .await?)
+    }
+}
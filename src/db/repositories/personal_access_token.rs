@@ -0,0 +1,111 @@
+use sqlx::PgPool;
+use time::OffsetDateTime;
+
+use crate::models::{
+    database::personal_access_token::PersonalAccessTokenRow,
+    dto::personal_access_token::CreatePersonalAccessToken,
+};
+use crate::utils::error::AppError;
+use crate::utils::snowflake::SNOWFLAKE_GENERATOR;
+
+pub struct PersonalAccessTokenRepo {
+    pool: PgPool,
+}
+
+impl PersonalAccessTokenRepo {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn create_one(
+        &self,
+        token: CreatePersonalAccessToken,
+    ) -> Result<PersonalAccessTokenRow, AppError> {
+        let scopes: Vec<String> = token.scopes.iter().map(|s| s.to_string()).collect();
+        Ok(sqlx::query_as!(
+            PersonalAccessTokenRow,
+            r#"
+            INSERT INTO personal_access_tokens
+                (id, user_id, name, token_hash, token_prefix, scopes, created_at, expires_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            RETURNING *
+            "#,
+            SNOWFLAKE_GENERATOR.generate().unwrap(),
+            token.user_id,
+            token.name,
+            token.token_hash,
+            token.token_prefix,
+            &scopes,
+            OffsetDateTime::now_utc(),
+            token.expires_at
+        )
+        .fetch_one(&self.pool)
+        .await?)
+    }
+
+    /// Tokens of the user not revoked, expired ones included, newest first
+    pub async fn get_multi_by_user_id(
+        &self,
+        user_id: i64,
+    ) -> Result<Vec<PersonalAccessTokenRow>, AppError> {
+        Ok(sqlx::query_as!(
+            PersonalAccessTokenRow,
+            r#"
+            SELECT * FROM personal_access_tokens
+            WHERE user_id = $1 AND revoked_at IS NULL
+            ORDER BY created_at DESC
+            "#,
+            user_id
+        )
+        .fetch_all(&self.pool)
+        .await?)
+    }
+
+    /// The token of the hash, when it is neither revoked nor expired
+    pub async fn get_usable_by_hash(
+        &self,
+        token_hash: &str,
+    ) -> Result<Option<PersonalAccessTokenRow>, AppError> {
+        Ok(sqlx::query_as!(
+            PersonalAccessTokenRow,
+            r#"
+            SELECT * FROM personal_access_tokens
+            WHERE token_hash = $1
+                AND revoked_at IS NULL
+                AND (expires_at IS NULL OR expires_at > $2)
+            "#,
+            token_hash,
+            OffsetDateTime::now_utc()
+        )
+        .fetch_optional(&self.pool)
+        .await?)
+    }
+
+    pub async fn touch(&self, id: i64, last_used_at: OffsetDateTime) -> Result<(), AppError> {
+        sqlx::query!(
+            r#"UPDATE personal_access_tokens SET last_used_at = $2 WHERE id = $1"#,
+            id,
+            last_used_at
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Revokes a token of the user, false when it has no such token
+    pub async fn revoke(&self, user_id: i64, id: i64) -> Result<bool, AppError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE personal_access_tokens
+            SET revoked_at = $3
+            WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL
+            "#,
+            id,
+            user_id,
+            OffsetDateTime::now_utc()
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+}
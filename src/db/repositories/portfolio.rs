@@ -1,6 +1,10 @@
 use sqlx::PgPool;
+use time::OffsetDateTime;
 
+use crate::models::common::currency::Currency;
 use crate::models::database::portfolio::PortfolioRow;
+use crate::models::domain::portfolio::CostBasisMethod;
+use crate::models::dto::portfolio::UpdatePortfolioRequest;
 use crate::utils::error::AppError;
 use crate::utils::snowflake::SNOWFLAKE_GENERATOR;
 
@@ -12,17 +16,25 @@ impl PortfolioRepo {
     pub fn new(pool: PgPool) -> Self {
         Self { pool }
     }
-    pub async fn create_one(&self, owner_id: i64, name: &str) -> Result<PortfolioRow, AppError> {
+    pub async fn create_one(
+        &self,
+        owner_id: i64,
+        name: &str,
+        cost_basis_method: CostBasisMethod,
+        base_currency: Currency,
+    ) -> Result<PortfolioRow, AppError> {
         Ok(sqlx::query_as!(
             PortfolioRow,
             r#"
-                INSERT INTO portfolios (id, owner_id, name)
-                VALUES ($1, $2, $3)
-                RETURNING id, owner_id, name, created_at, updated_at
+                INSERT INTO portfolios (id, owner_id, name, cost_basis_method, base_currency)
+                VALUES ($1, $2, $3, $4, $5)
+                RETURNING id, owner_id, name, cost_basis_method, base_currency, created_at, updated_at
             "#,
             SNOWFLAKE_GENERATOR.generate().unwrap(),
             owner_id,
-            name
+            name,
+            cost_basis_method.to_string(),
+            base_currency.to_string()
         )
         .fetch_one(&self.pool)
         .await?)
@@ -32,7 +44,7 @@ impl PortfolioRepo {
         Ok(sqlx::query_as!(
             PortfolioRow,
             r#"
-                SELECT id, owner_id, name, created_at, updated_at
+                SELECT id, owner_id, name, cost_basis_method, base_currency, created_at, updated_at
                 FROM portfolios
                 WHERE id = $1
             "#,
@@ -49,7 +61,7 @@ impl PortfolioRepo {
         Ok(sqlx::query_as!(
             PortfolioRow,
             r#"
-                SELECT id, owner_id, name, created_at, updated_at
+                SELECT id, owner_id, name, cost_basis_method, base_currency, created_at, updated_at
                 FROM portfolios
                 WHERE owner_id = $1
             "#,
@@ -58,4 +70,38 @@ impl PortfolioRepo {
         .fetch_all(&self.pool)
         .await?)
     }
+
+    pub async fn get_all(&self) -> Result<Vec<PortfolioRow>, AppError> {
+        Ok(sqlx::query_as!(
+            PortfolioRow,
+            r#"
+                SELECT id, owner_id, name, cost_basis_method, base_currency, created_at, updated_at
+                FROM portfolios
+                ORDER BY id ASC
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?)
+    }
+
+    pub async fn update_one(&self, id: i64, inp: &UpdatePortfolioRequest) -> Result<(), AppError> {
+        sqlx::query!(
+            r#"
+                UPDATE portfolios
+                SET name = COALESCE($1, name),
+                    cost_basis_method = COALESCE($2, cost_basis_method),
+                    base_currency = COALESCE($3, base_currency),
+                    updated_at = $4
+                WHERE id = $5
+            "#,
+            inp.name,
+            inp.cost_basis_method.map(|m| m.to_string()),
+            inp.base_currency.map(|c| c.to_string()),
+            OffsetDateTime::now_utc(),
+            id
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
 }
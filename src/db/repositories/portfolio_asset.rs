@@ -1,9 +1,12 @@
+use rust_decimal::Decimal;
 use sqlx::PgPool;
 use time::OffsetDateTime;
 
 use crate::models::database::portfolio_asset::PortfolioAssetRow;
+use crate::models::domain::tax_lot::RealizedGainEntry;
 use crate::models::dto::portfolio_asset::UpdatePortfolioAsset;
 use crate::utils::error::AppError;
+use crate::utils::snowflake::SNOWFLAKE_GENERATOR;
 
 pub struct PortfolioAssetRepo {
     pool: PgPool,
@@ -48,7 +51,9 @@ impl PortfolioAssetRepo {
                     avg_buy_price,
                     sold_amount,
                     total_revenue,
-                    avg_sell_price
+                    avg_sell_price,
+                    cost_basis,
+                    realized_pnl
                 FROM portfolio_assets
                 WHERE portfolio_id = $1 AND asset_id = $2
             "#,
@@ -76,7 +81,9 @@ impl PortfolioAssetRepo {
                     avg_buy_price,
                     sold_amount,
                     total_revenue,
-                    avg_sell_price
+                    avg_sell_price,
+                    cost_basis,
+                    realized_pnl
                 FROM portfolio_assets
                 WHERE portfolio_id = $1
             "#,
@@ -86,7 +93,11 @@ impl PortfolioAssetRepo {
         .await?)
     }
 
+    /// Persists the recomputed stats together with the open lots, the realized
+    /// gain of each disposal and the realized gains ledger, so they never disagree
+    /// with each other.
     pub async fn update(&self, inp: &UpdatePortfolioAsset) -> Result<(), AppError> {
+        let mut db_tx = self.pool.begin().await?;
         sqlx::query!(
             r#"
             UPDATE portfolio_assets
@@ -96,8 +107,10 @@ impl PortfolioAssetRepo {
                 avg_buy_price = $4,
                 sold_amount = $5,
                 total_revenue = $6,
-                avg_sell_price = $7
-            WHERE portfolio_id = $8 AND asset_id = $9
+                avg_sell_price = $7,
+                cost_basis = $8,
+                realized_pnl = $9
+            WHERE portfolio_id = $10 AND asset_id = $11
         "#,
             OffsetDateTime::now_utc(),
             inp.holding_amount,
@@ -106,11 +119,101 @@ impl PortfolioAssetRepo {
             inp.sold_amount,
             inp.total_revenue,
             inp.avg_sell_price,
+            inp.cost_basis,
+            inp.realized_pnl,
             inp.portfolio_id,
             inp.asset_id
         )
-        .execute(&self.pool)
+        .execute(&mut *db_tx)
+        .await?;
+
+        sqlx::query!(
+            r#"DELETE FROM tax_lots WHERE portfolio_id = $1 AND asset_id = $2"#,
+            inp.portfolio_id,
+            inp.asset_id
+        )
+        .execute(&mut *db_tx)
+        .await?;
+        if !inp.open_lots.is_empty() {
+            let mut query_builder = sqlx::QueryBuilder::new(
+                "INSERT INTO tax_lots (tx_id, portfolio_id, asset_id, acquired_at, quantity, remaining_quantity, unit_cost) ",
+            );
+            query_builder.push_values(&inp.open_lots, |mut b, lot| {
+                b.push_bind(lot.tx_id)
+                    .push_bind(inp.portfolio_id)
+                    .push_bind(&inp.asset_id)
+                    .push_bind(lot.acquired_at)
+                    .push_bind(lot.quantity)
+                    .push_bind(lot.remaining_quantity)
+                    .push_bind(lot.unit_cost);
+            });
+            query_builder.build().execute(&mut *db_tx).await?;
+        }
+
+        let (tx_ids, gains): (Vec<i64>, Vec<Decimal>) = inp
+            .disposals
+            .iter()
+            .filter_map(|d| d.realized_gain().map(|gain| (d.tx_id, gain)))
+            .unzip();
+        sqlx::query!(
+            r#"
+            UPDATE transactions
+            SET realized_gain = data.gain
+            FROM (SELECT * FROM UNNEST($1::BIGINT[], $2::DECIMAL[])) AS data(id, gain)
+            WHERE transactions.id = data.id
+        "#,
+            &tx_ids,
+            &gains
+        )
+        .execute(&mut *db_tx)
         .await?;
+        sqlx::query!(
+            r#"
+            UPDATE transactions
+            SET realized_gain = NULL
+            WHERE portfolio_id = $1 AND asset_id = $2 AND NOT (id = ANY($3))
+        "#,
+            inp.portfolio_id,
+            inp.asset_id,
+            &tx_ids
+        )
+        .execute(&mut *db_tx)
+        .await?;
+
+        sqlx::query!(
+            r#"DELETE FROM realized_gains WHERE portfolio_id = $1 AND asset_id = $2"#,
+            inp.portfolio_id,
+            inp.asset_id
+        )
+        .execute(&mut *db_tx)
+        .await?;
+        let entries: Vec<RealizedGainEntry> = inp
+            .disposals
+            .iter()
+            .flat_map(|d| d.ledger_entries())
+            .collect();
+        if !entries.is_empty() {
+            let mut query_builder = sqlx::QueryBuilder::new(
+                "INSERT INTO realized_gains (id, portfolio_id, asset_id, sell_tx_id, buy_tx_id, quantity, cost_basis, proceeds, gain, acquired_at, disposed_at, holding_period_days) ",
+            );
+            query_builder.push_values(&entries, |mut b, entry| {
+                b.push_bind(SNOWFLAKE_GENERATOR.generate().unwrap())
+                    .push_bind(inp.portfolio_id)
+                    .push_bind(&inp.asset_id)
+                    .push_bind(entry.sell_tx_id)
+                    .push_bind(entry.buy_tx_id)
+                    .push_bind(entry.quantity)
+                    .push_bind(entry.cost_basis)
+                    .push_bind(entry.proceeds)
+                    .push_bind(entry.gain())
+                    .push_bind(entry.acquired_at)
+                    .push_bind(entry.disposed_at)
+                    .push_bind(entry.holding_period_days());
+            });
+            query_builder.build().execute(&mut *db_tx).await?;
+        }
+
+        db_tx.commit().await?;
         Ok(())
     }
 }
@@ -0,0 +1,83 @@
+use sqlx::PgPool;
+use time::{Date, OffsetDateTime};
+
+use crate::models::database::portfolio_snapshot::PortfolioSnapshotRow;
+use crate::models::dto::portfolio_snapshot::CreatePortfolioSnapshot;
+use crate::utils::error::AppError;
+
+pub struct PortfolioSnapshotRepo {
+    pool: PgPool,
+}
+
+impl PortfolioSnapshotRepo {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn upsert_multi(&self, inp: &[CreatePortfolioSnapshot]) -> Result<u64, AppError> {
+        if inp.is_empty() {
+            return Ok(0);
+        }
+        let now = OffsetDateTime::now_utc();
+        let mut query_builder = sqlx::QueryBuilder::new(
+            "INSERT INTO portfolio_snapshots (portfolio_id, snapshot_date, currency, holdings_value, cost_basis, realized_pnl, unrealized_pnl, created_at, updated_at) ",
+        );
+        query_builder.push_values(inp, |mut b, item| {
+            b.push_bind(item.portfolio_id)
+                .push_bind(item.snapshot_date)
+                .push_bind(&item.currency)
+                .push_bind(item.holdings_value)
+                .push_bind(item.cost_basis)
+                .push_bind(item.realized_pnl)
+                .push_bind(item.unrealized_pnl)
+                .push_bind(now)
+                .push_bind(now);
+        });
+        query_builder.push(
+            " ON CONFLICT (portfolio_id, snapshot_date) DO UPDATE SET currency = EXCLUDED.currency, holdings_value = EXCLUDED.holdings_value, cost_basis = EXCLUDED.cost_basis, realized_pnl = EXCLUDED.realized_pnl, unrealized_pnl = EXCLUDED.unrealized_pnl, updated_at = EXCLUDED.updated_at",
+        );
+        let result = query_builder.build().execute(&self.pool).await?;
+        Ok(result.rows_affected())
+    }
+
+    pub async fn delete_by_portfolio_id(&self, portfolio_id: i64) -> Result<u64, AppError> {
+        let result = sqlx::query!(
+            r#"DELETE FROM portfolio_snapshots WHERE portfolio_id = $1"#,
+            portfolio_id
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+
+    pub async fn get_latest_date(&self, portfolio_id: i64) -> Result<Option<Date>, AppError> {
+        Ok(sqlx::query_scalar!(
+            r#"SELECT MAX(snapshot_date) FROM portfolio_snapshots WHERE portfolio_id = $1"#,
+            portfolio_id
+        )
+        .fetch_one(&self.pool)
+        .await?)
+    }
+
+    pub async fn get_multi_in_range(
+        &self,
+        portfolio_id: i64,
+        from: Date,
+        to: Date,
+    ) -> Result<Vec<PortfolioSnapshotRow>, AppError> {
+        Ok(sqlx::query_as!(
+            PortfolioSnapshotRow,
+            r#"
+                SELECT portfolio_id, snapshot_date, currency, holdings_value, cost_basis, realized_pnl, unrealized_pnl, created_at, updated_at
+                FROM portfolio_snapshots
+                WHERE portfolio_id = $1 AND snapshot_date BETWEEN $2 AND $3
+                ORDER BY snapshot_date ASC
+            "#,
+            portfolio_id,
+            from,
+            to
+        )
+        .fetch_all(&self.pool)
+        .await?)
+    }
+}
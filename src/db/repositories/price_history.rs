@@ -0,0 +1,85 @@
+use rust_decimal::Decimal;
+use sqlx::PgPool;
+use time::{Date, OffsetDateTime};
+
+use crate::models::database::price_history::PriceHistoryRow;
+use crate::utils::error::AppError;
+
+pub struct PriceHistoryRepo {
+    pool: PgPool,
+}
+
+impl PriceHistoryRepo {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Inserts daily close prices, overwriting the ones already stored for the same day.
+    pub async fn upsert_multi(
+        &self,
+        asset_id: &str,
+        currency: &str,
+        source: &str,
+        prices: &[(Date, Decimal)],
+    ) -> Result<u64, AppError> {
+        if prices.is_empty() {
+            return Ok(0);
+        }
+        let now = OffsetDateTime::now_utc();
+        let mut query_builder = sqlx::QueryBuilder::new(
+            "INSERT INTO price_history (asset_id, currency, price_date, close_price, source, created_at, updated_at) ",
+        );
+        query_builder.push_values(prices, |mut b, (price_date, close_price)| {
+            b.push_bind(asset_id)
+                .push_bind(currency)
+                .push_bind(price_date)
+                .push_bind(close_price)
+                .push_bind(source)
+                .push_bind(now)
+                .push_bind(now);
+        });
+        query_builder.push(
+            " ON CONFLICT (asset_id, currency, price_date) DO UPDATE SET close_price = EXCLUDED.close_price, source = EXCLUDED.source, updated_at = EXCLUDED.updated_at",
+        );
+        let result = query_builder.build().execute(&self.pool).await?;
+        Ok(result.rows_affected())
+    }
+
+    pub async fn get_latest_date(
+        &self,
+        asset_id: &str,
+        currency: &str,
+    ) -> Result<Option<Date>, AppError> {
+        Ok(sqlx::query_scalar!(
+            r#"SELECT MAX(price_date) FROM price_history WHERE asset_id = $1 AND currency = $2"#,
+            asset_id,
+            currency
+        )
+        .fetch_one(&self.pool)
+        .await?)
+    }
+
+    pub async fn get_multi_in_range(
+        &self,
+        asset_ids: &[String],
+        currency: &str,
+        from: Date,
+        to: Date,
+    ) -> Result<Vec<PriceHistoryRow>, AppError> {
+        Ok(sqlx::query_as!(
+            PriceHistoryRow,
+            r#"
+                SELECT asset_id, currency, price_date, close_price, source, created_at, updated_at
+                FROM price_history
+                WHERE asset_id = ANY($1) AND currency = $2 AND price_date BETWEEN $3 AND $4
+                ORDER BY price_date ASC
+            "#,
+            asset_ids,
+            currency,
+            from,
+            to
+        )
+        .fetch_all(&self.pool)
+        .await?)
+    }
+}
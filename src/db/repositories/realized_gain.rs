@@ -0,0 +1,127 @@
+use sqlx::PgPool;
+
+use time::OffsetDateTime;
+
+use crate::models::database::realized_gain::{
+    RealizedGainRow, RealizedGainSummaryRow, TaxReportGainRow,
+};
+use crate::models::dto::realized_gain::RealizedGainFilter;
+use crate::utils::error::AppError;
+
+pub struct RealizedGainRepo {
+    pool: PgPool,
+}
+
+impl RealizedGainRepo {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn get_multi_by_portfolio_with_paging(
+        &self,
+        portfolio_id: i64,
+        filter: &RealizedGainFilter,
+        page: u32,
+        limit: u32,
+    ) -> Result<Vec<RealizedGainRow>, AppError> {
+        let query_limit = limit as i64;
+        let query_offset = ((page - 1) * limit) as i64;
+        Ok(sqlx::query_as!(
+            RealizedGainRow,
+            r#"
+                SELECT *
+                FROM realized_gains
+                WHERE portfolio_id = $1
+                    AND ($2::VARCHAR IS NULL OR asset_id = $2)
+                    AND ($3::TIMESTAMPTZ IS NULL OR disposed_at >= $3)
+                    AND ($4::TIMESTAMPTZ IS NULL OR disposed_at < $4)
+                ORDER BY disposed_at ASC, sell_tx_id ASC, acquired_at ASC
+                LIMIT $5 OFFSET $6
+            "#,
+            portfolio_id,
+            filter.asset_id,
+            filter.from,
+            filter.to,
+            query_limit,
+            query_offset
+        )
+        .fetch_all(&self.pool)
+        .await?)
+    }
+
+    pub async fn summarize_by_portfolio(
+        &self,
+        portfolio_id: i64,
+        filter: &RealizedGainFilter,
+    ) -> Result<RealizedGainSummaryRow, AppError> {
+        Ok(sqlx::query_as!(
+            RealizedGainSummaryRow,
+            r#"
+                SELECT
+                    COUNT(*) AS "total_items!",
+                    COALESCE(SUM(quantity), 0) AS "total_quantity!",
+                    COALESCE(SUM(cost_basis), 0) AS "total_cost_basis!",
+                    COALESCE(SUM(proceeds), 0) AS "total_proceeds!",
+                    COALESCE(SUM(gain), 0) AS "total_gain!"
+                FROM realized_gains
+                WHERE portfolio_id = $1
+                    AND ($2::VARCHAR IS NULL OR asset_id = $2)
+                    AND ($3::TIMESTAMPTZ IS NULL OR disposed_at >= $3)
+                    AND ($4::TIMESTAMPTZ IS NULL OR disposed_at < $4)
+            "#,
+            portfolio_id,
+            filter.asset_id,
+            filter.from,
+            filter.to
+        )
+        .fetch_one(&self.pool)
+        .await?)
+    }
+
+    /// Gains of the portfolios of the owner disposed of in `[from, to)`, of one
+    /// portfolio when `portfolio_id` is set
+    pub async fn get_all_by_owner_disposed_between(
+        &self,
+        owner_id: i64,
+        portfolio_id: Option<i64>,
+        from: OffsetDateTime,
+        to: OffsetDateTime,
+    ) -> Result<Vec<TaxReportGainRow>, AppError> {
+        Ok(sqlx::query_as!(
+            TaxReportGainRow,
+            r#"
+                SELECT
+                    rg.id,
+                    rg.portfolio_id,
+                    p.name AS portfolio_name,
+                    p.base_currency,
+                    rg.asset_id,
+                    a.symbol AS asset_symbol,
+                    a.name AS asset_name,
+                    rg.sell_tx_id,
+                    rg.buy_tx_id,
+                    rg.quantity,
+                    rg.cost_basis,
+                    rg.proceeds,
+                    rg.gain,
+                    rg.acquired_at,
+                    rg.disposed_at,
+                    rg.holding_period_days
+                FROM realized_gains rg
+                JOIN portfolios p ON p.id = rg.portfolio_id
+                JOIN assets a ON a.id = rg.asset_id
+                WHERE p.owner_id = $1
+                    AND ($2::BIGINT IS NULL OR rg.portfolio_id = $2)
+                    AND rg.disposed_at >= $3
+                    AND rg.disposed_at < $4
+                ORDER BY rg.disposed_at ASC, rg.sell_tx_id ASC, rg.acquired_at ASC
+            "#,
+            owner_id,
+            portfolio_id,
+            from,
+            to
+        )
+        .fetch_all(&self.pool)
+        .await?)
+    }
+}
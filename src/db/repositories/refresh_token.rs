@@ -0,0 +1,87 @@
+use sqlx::PgPool;
+use time::OffsetDateTime;
+
+use crate::models::database::refresh_token::RefreshTokenRow;
+use crate::utils::error::AppError;
+use crate::utils::snowflake::SNOWFLAKE_GENERATOR;
+
+pub struct RefreshTokenRepo {
+    pool: PgPool,
+}
+
+impl RefreshTokenRepo {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn get_by_hash(&self, token_hash: &str) -> Result<Option<RefreshTokenRow>, AppError> {
+        Ok(sqlx::query_as!(
+            RefreshTokenRow,
+            r#"
+            SELECT id, session_id, expires_at, used_at
+            FROM refresh_tokens
+            WHERE token_hash = $1
+            "#,
+            token_hash
+        )
+        .fetch_optional(&self.pool)
+        .await?)
+    }
+
+    /// Marks the token used and issues the next one of the session, which is
+    /// extended to its expiry and records the address it is used from. Returns
+    /// false, changing nothing, when the token was used in the meantime.
+    pub async fn rotate(
+        &self,
+        used_id: i64,
+        session_id: i64,
+        token_hash: &str,
+        expires_at: OffsetDateTime,
+        ip_address: Option<String>,
+    ) -> Result<bool, AppError> {
+        let now = OffsetDateTime::now_utc();
+        let mut db_tx = self.pool.begin().await?;
+        let used = sqlx::query!(
+            r#"
+            UPDATE refresh_tokens
+            SET used_at = $2
+            WHERE id = $1 AND used_at IS NULL
+            "#,
+            used_id,
+            now
+        )
+        .execute(&mut *db_tx)
+        .await?;
+        if used.rows_affected() == 0 {
+            return Ok(false);
+        }
+        sqlx::query!(
+            r#"
+            INSERT INTO refresh_tokens (id, session_id, token_hash, created_at, expires_at)
+            VALUES ($1, $2, $3, $4, $5)
+            "#,
+            SNOWFLAKE_GENERATOR.generate().unwrap(),
+            session_id,
+            token_hash,
+            now,
+            expires_at
+        )
+        .execute(&mut *db_tx)
+        .await?;
+        sqlx::query!(
+            r#"
+            UPDATE user_sessions
+            SET expires_at = $2, last_seen_at = $3, ip_address = COALESCE($4, ip_address)
+            WHERE session_id = $1
+            "#,
+            session_id,
+            expires_at,
+            now,
+            ip_address
+        )
+        .execute(&mut *db_tx)
+        .await?;
+        db_tx.commit().await?;
+        Ok(true)
+    }
+}
@@ -0,0 +1,35 @@
+use sqlx::PgPool;
+
+use crate::models::database::tax_lot::TaxLotRow;
+use crate::utils::error::AppError;
+
+pub struct TaxLotRepo {
+    pool: PgPool,
+}
+
+impl TaxLotRepo {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Open lots of a portfolio as of the last stats update, oldest first
+    pub async fn get_multi_by_portfolio(
+        &self,
+        portfolio_id: i64,
+        asset_id: Option<&str>,
+    ) -> Result<Vec<TaxLotRow>, AppError> {
+        Ok(sqlx::query_as!(
+            TaxLotRow,
+            r#"
+                SELECT tx_id, asset_id, acquired_at, quantity, remaining_quantity, unit_cost
+                FROM tax_lots
+                WHERE portfolio_id = $1 AND ($2::VARCHAR IS NULL OR asset_id = $2)
+                ORDER BY asset_id ASC, acquired_at ASC, tx_id ASC
+            "#,
+            portfolio_id,
+            asset_id
+        )
+        .fetch_all(&self.pool)
+        .await?)
+    }
+}
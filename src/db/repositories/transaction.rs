@@ -22,9 +22,10 @@ impl TransactionRepo {
 
         let portfolio_id = inp.portfolio_id;
         let asset_id = inp.asset_id.clone();
+        let import_batch_id = inp.import_batch_id;
 
         let mut query_builder = sqlx::QueryBuilder::new(
-            "INSERT INTO transactions (id, external_id, portfolio_id, asset_id, tx_type, quantity, price, fees, currency, executed_at, notes) ",
+            "INSERT INTO transactions (id, external_id, portfolio_id, asset_id, tx_type, quantity, price, fees, currency, executed_at, notes, import_batch_id) ",
         );
 
         query_builder.push_values(inp.transactions, |mut b, item| {
@@ -38,7 +39,8 @@ impl TransactionRepo {
                 .push_bind(item.fees)
                 .push_bind(item.currency.to_string())
                 .push_bind(item.executed_at)
-                .push_bind(item.notes);
+                .push_bind(item.notes)
+                .push_bind(import_batch_id);
         });
 
         let result = query_builder.build().execute(&self.pool).await?;
@@ -67,6 +69,34 @@ impl TransactionRepo {
         .await?)
     }
 
+    pub async fn get_all_txs_by_portfolio_and_asset(
+        &self,
+        portfolio_id: i64,
+        asset_id: &str,
+    ) -> Result<Vec<TransactionRow>, AppError> {
+        Ok(sqlx::query_as!(
+            TransactionRow,
+            r#"SELECT * FROM transactions WHERE portfolio_id = $1 AND asset_id = $2 ORDER BY executed_at ASC, id ASC"#,
+            portfolio_id,
+            asset_id
+        )
+        .fetch_all(&self.pool)
+        .await?)
+    }
+
+    pub async fn get_all_txs_by_portfolio(
+        &self,
+        portfolio_id: i64,
+    ) -> Result<Vec<TransactionRow>, AppError> {
+        Ok(sqlx::query_as!(
+            TransactionRow,
+            r#"SELECT * FROM transactions WHERE portfolio_id = $1 ORDER BY executed_at ASC, id ASC"#,
+            portfolio_id
+        )
+        .fetch_all(&self.pool)
+        .await?)
+    }
+
     pub async fn get_one_by_id(&self, tx_id: i64) -> Result<Option<TransactionRow>, AppError> {
         Ok(sqlx::query_as!(
             TransactionRow,
@@ -77,6 +107,31 @@ impl TransactionRepo {
         .await?)
     }
 
+    pub async fn get_multi_by_ids(&self, tx_ids: &[i64]) -> Result<Vec<TransactionRow>, AppError> {
+        Ok(sqlx::query_as!(
+            TransactionRow,
+            r#"SELECT * FROM transactions WHERE id = ANY($1)"#,
+            tx_ids
+        )
+        .fetch_all(&self.pool)
+        .await?)
+    }
+
+    pub async fn get_multi_by_external_ids(
+        &self,
+        portfolio_id: i64,
+        external_ids: &[String],
+    ) -> Result<Vec<TransactionRow>, AppError> {
+        Ok(sqlx::query_as!(
+            TransactionRow,
+            r#"SELECT * FROM transactions WHERE portfolio_id = $1 AND external_id = ANY($2)"#,
+            portfolio_id,
+            external_ids
+        )
+        .fetch_all(&self.pool)
+        .await?)
+    }
+
     pub async fn count_txs_by_portfolio_and_asset(
         &self,
         portfolio_id: i64,
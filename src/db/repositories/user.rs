@@ -1,8 +1,8 @@
 use sqlx::PgPool;
+use time::OffsetDateTime;
 
 use crate::models::database::user::UserRow;
-use crate::models::domain::user::UserStatus;
-use crate::models::dto::user::CreateUser;
+use crate::models::dto::user::{CreateUser, DeletedAccount, UpdateUser};
 use crate::utils::error::AppError;
 use crate::utils::snowflake::SNOWFLAKE_GENERATOR;
 
@@ -20,15 +20,16 @@ impl UserRepo {
         Ok(sqlx::query_as!(
             UserRow,
             r#"
-            INSERT INTO users (id, status, email, hashed_password, name)
-            VALUES ($1, $2, $3, $4, $5)
-            RETURNING id, email, phone_number, hashed_password, name, status, created_at, updated_at
+            INSERT INTO users (id, status, email, hashed_password, name, base_currency)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING id, email, phone_number, hashed_password, name, status, base_currency, created_at, updated_at
             "#,
             SNOWFLAKE_GENERATOR.generate().unwrap() as i64,
-            UserStatus::Active.to_string(),
+            inp.status.to_string(),
             inp.email,
             inp.hashed_password,
             inp.name,
+            inp.base_currency.to_string(),
         )
         .fetch_one(&self.pool)
         .await?)
@@ -38,7 +39,7 @@ impl UserRepo {
         Ok(sqlx::query_as!(
             UserRow,
             r#"
-            SELECT id, email, phone_number, hashed_password, name, status, created_at, updated_at
+            SELECT id, email, phone_number, hashed_password, name, status, base_currency, created_at, updated_at
             FROM users
             WHERE id = $1
             "#,
@@ -52,7 +53,7 @@ impl UserRepo {
         Ok(sqlx::query_as!(
             UserRow,
             r#"
-            SELECT id, email, phone_number, hashed_password, name, status, created_at, updated_at
+            SELECT id, email, phone_number, hashed_password, name, status, base_currency, created_at, updated_at
             FROM users
             WHERE email = $1
             "#,
@@ -61,4 +62,140 @@ impl UserRepo {
         .fetch_optional(&self.pool)
         .await?)
     }
+
+    pub async fn is_admin(&self, id: i64) -> Result<bool, AppError> {
+        Ok(sqlx::query_scalar!(
+            r#"SELECT EXISTS(SELECT 1 FROM users WHERE id = $1 AND is_admin) AS "exists!""#,
+            id
+        )
+        .fetch_one(&self.pool)
+        .await?)
+    }
+
+    pub async fn get_by_phone_number(
+        &self,
+        phone_number: &str,
+    ) -> Result<Option<UserRow>, AppError> {
+        Ok(sqlx::query_as!(
+            UserRow,
+            r#"
+            SELECT id, email, phone_number, hashed_password, name, status, base_currency, created_at, updated_at
+            FROM users
+            WHERE phone_number = $1
+            "#,
+            phone_number
+        )
+        .fetch_optional(&self.pool)
+        .await?)
+    }
+
+    /// Updates the fields that are set, `phone_number: Some(None)` clears it.
+    /// A phone number of another user is a conflict.
+    pub async fn update_profile(&self, id: i64, inp: UpdateUser) -> Result<UserRow, AppError> {
+        let result = sqlx::query_as!(
+            UserRow,
+            r#"
+            UPDATE users
+            SET name = COALESCE($2, name),
+                phone_number = CASE WHEN $3 THEN $4 ELSE phone_number END,
+                updated_at = $5
+            WHERE id = $1
+            RETURNING id, email, phone_number, hashed_password, name, status, base_currency, created_at, updated_at
+            "#,
+            id,
+            inp.name,
+            inp.phone_number.is_some(),
+            inp.phone_number.flatten(),
+            OffsetDateTime::now_utc()
+        )
+        .fetch_one(&self.pool)
+        .await;
+        match result {
+            Ok(user) => Ok(user),
+            Err(sqlx::Error::Database(e)) if e.is_unique_violation() => Err(AppError::Conflict(
+                "Phone number is already in use".to_string(),
+            )),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub async fn update_password(&self, id: i64, hashed_password: &str) -> Result<(), AppError> {
+        sqlx::query!(
+            r#"
+            UPDATE users
+            SET hashed_password = $2, updated_at = $3
+            WHERE id = $1
+            "#,
+            id,
+            hashed_password,
+            OffsetDateTime::now_utc()
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Deletes the user with the portfolios, their transactions and history,
+    /// and the sessions, in one transaction. Tokens, two-factor settings and
+    /// imports are removed by their cascading foreign keys.
+    pub async fn delete_account(&self, id: i64) -> Result<DeletedAccount, AppError> {
+        let mut db_tx = self.pool.begin().await?;
+
+        let portfolio_ids =
+            sqlx::query_scalar!(r#"SELECT id FROM portfolios WHERE owner_id = $1"#, id)
+                .fetch_all(&mut *db_tx)
+                .await?;
+        sqlx::query!(
+            r#"DELETE FROM realized_gains WHERE portfolio_id = ANY($1)"#,
+            &portfolio_ids
+        )
+        .execute(&mut *db_tx)
+        .await?;
+        sqlx::query!(
+            r#"DELETE FROM tax_lots WHERE portfolio_id = ANY($1)"#,
+            &portfolio_ids
+        )
+        .execute(&mut *db_tx)
+        .await?;
+        let transactions = sqlx::query!(
+            r#"DELETE FROM transactions WHERE portfolio_id = ANY($1)"#,
+            &portfolio_ids
+        )
+        .execute(&mut *db_tx)
+        .await?
+        .rows_affected();
+        sqlx::query!(
+            r#"DELETE FROM portfolio_snapshots WHERE portfolio_id = ANY($1)"#,
+            &portfolio_ids
+        )
+        .execute(&mut *db_tx)
+        .await?;
+        sqlx::query!(
+            r#"DELETE FROM portfolio_assets WHERE portfolio_id = ANY($1)"#,
+            &portfolio_ids
+        )
+        .execute(&mut *db_tx)
+        .await?;
+        sqlx::query!(r#"DELETE FROM portfolios WHERE owner_id = $1"#, id)
+            .execute(&mut *db_tx)
+            .await?;
+
+        // Refresh tokens go with their sessions
+        let session_ids = sqlx::query_scalar!(
+            r#"DELETE FROM user_sessions WHERE user_id = $1 RETURNING session_id"#,
+            id
+        )
+        .fetch_all(&mut *db_tx)
+        .await?;
+        sqlx::query!(r#"DELETE FROM users WHERE id = $1"#, id)
+            .execute(&mut *db_tx)
+            .await?;
+
+        db_tx.commit().await?;
+        Ok(DeletedAccount {
+            portfolios: portfolio_ids.len(),
+            transactions,
+            session_ids,
+        })
+    }
 }
@@ -16,25 +16,44 @@ impl UserSessionRepo {
         Self { pool }
     }
 
+    /// Creates the session with its first refresh token
     pub async fn create_user_session(
         &self,
         inp: CreateUserSession,
     ) -> Result<UserSession, AppError> {
+        let now = OffsetDateTime::now_utc();
+        let mut db_tx = self.pool.begin().await?;
         let entity = sqlx::query_as!(
             UserSession,
             r#"
-            INSERT INTO user_sessions (session_id, user_id, is_active, created_at, expires_at)
-            VALUES ($1, $2, $3, $4, $5)
-            RETURNING session_id, user_id, is_active, created_at, expires_at
+            INSERT INTO user_sessions (session_id, user_id, is_active, created_at, expires_at, user_agent, ip_address, last_seen_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $4)
+            RETURNING session_id, user_id, is_active, created_at, expires_at, user_agent, ip_address, last_seen_at
             "#,
             SNOWFLAKE_GENERATOR.generate().unwrap() as i64,
             inp.user_id,
             true,
-            OffsetDateTime::now_utc(),
+            now,
+            inp.expires_at,
+            inp.user_agent,
+            inp.ip_address
+        )
+        .fetch_one(&mut *db_tx)
+        .await?;
+        sqlx::query!(
+            r#"
+            INSERT INTO refresh_tokens (id, session_id, token_hash, created_at, expires_at)
+            VALUES ($1, $2, $3, $4, $5)
+            "#,
+            SNOWFLAKE_GENERATOR.generate().unwrap(),
+            entity.session_id,
+            inp.refresh_token_hash,
+            now,
             inp.expires_at
         )
-        .fetch_one(&self.pool)
+        .execute(&mut *db_tx)
         .await?;
+        db_tx.commit().await?;
         Ok(entity)
     }
 
@@ -42,7 +61,7 @@ impl UserSessionRepo {
         let entity = sqlx::query_as!(
             UserSession,
             r#"
-            SELECT session_id, user_id, is_active, created_at, expires_at
+            SELECT session_id, user_id, is_active, created_at, expires_at, user_agent, ip_address, last_seen_at
             FROM user_sessions
             WHERE session_id = $1
             "#,
@@ -52,4 +71,79 @@ impl UserSessionRepo {
         .await?;
         Ok(entity)
     }
+
+    /// Sessions of the user that are neither revoked nor expired, the most
+    /// recently used first
+    pub async fn get_active_by_user_id(&self, user_id: i64) -> Result<Vec<UserSession>, AppError> {
+        Ok(sqlx::query_as!(
+            UserSession,
+            r#"
+            SELECT session_id, user_id, is_active, created_at, expires_at, user_agent, ip_address, last_seen_at
+            FROM user_sessions
+            WHERE user_id = $1 AND is_active AND expires_at > $2
+            ORDER BY last_seen_at DESC
+            "#,
+            user_id,
+            OffsetDateTime::now_utc()
+        )
+        .fetch_all(&self.pool)
+        .await?)
+    }
+
+    pub async fn touch(&self, id: i64, last_seen_at: OffsetDateTime) -> Result<(), AppError> {
+        sqlx::query!(
+            r#"UPDATE user_sessions SET last_seen_at = $2 WHERE session_id = $1"#,
+            id,
+            last_seen_at
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn deactivate(&self, id: i64) -> Result<(), AppError> {
+        sqlx::query!(
+            r#"UPDATE user_sessions SET is_active = false WHERE session_id = $1"#,
+            id
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Deactivates every active session of the user, returning their IDs
+    pub async fn deactivate_all_by_user_id(&self, user_id: i64) -> Result<Vec<i64>, AppError> {
+        Ok(sqlx::query_scalar!(
+            r#"
+            UPDATE user_sessions
+            SET is_active = false
+            WHERE user_id = $1 AND is_active
+            RETURNING session_id
+            "#,
+            user_id
+        )
+        .fetch_all(&self.pool)
+        .await?)
+    }
+
+    /// Deactivates every active session of the user but `keep_session_id`,
+    /// returning their IDs
+    pub async fn deactivate_others_by_user_id(
+        &self,
+        user_id: i64,
+        keep_session_id: i64,
+    ) -> Result<Vec<i64>, AppError> {
+        Ok(sqlx::query_scalar!(
+            r#"
+            UPDATE user_sessions
+            SET is_active = false
+            WHERE user_id = $1 AND is_active AND session_id <> $2
+            RETURNING session_id
+            "#,
+            user_id,
+            keep_session_id
+        )
+        .fetch_all(&self.pool)
+        .await?)
+    }
 }
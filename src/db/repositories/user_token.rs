@@ -0,0 +1,138 @@
+use sqlx::{PgConnection, PgPool};
+use time::OffsetDateTime;
+
+use crate::models::domain::user::{UserStatus, UserTokenPurpose};
+use crate::utils::error::AppError;
+use crate::utils::snowflake::SNOWFLAKE_GENERATOR;
+
+pub struct UserTokenRepo {
+    pool: PgPool,
+}
+
+/// Marks the token used when it is unused and not expired, returning its user
+async fn consume(
+    conn: &mut PgConnection,
+    purpose: UserTokenPurpose,
+    token_hash: &str,
+) -> Result<Option<i64>, AppError> {
+    let now = OffsetDateTime::now_utc();
+    Ok(sqlx::query_scalar!(
+        r#"
+        UPDATE user_tokens
+        SET used_at = $3
+        WHERE token_hash = $1 AND purpose = $2 AND used_at IS NULL AND expires_at > $3
+        RETURNING user_id
+        "#,
+        token_hash,
+        purpose.to_string(),
+        now
+    )
+    .fetch_optional(conn)
+    .await?)
+}
+
+impl UserTokenRepo {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Stores a new token of the user, the unused ones of the same purpose stop working
+    pub async fn create_one(
+        &self,
+        user_id: i64,
+        purpose: UserTokenPurpose,
+        token_hash: &str,
+        expires_at: OffsetDateTime,
+    ) -> Result<(), AppError> {
+        let now = OffsetDateTime::now_utc();
+        let mut db_tx = self.pool.begin().await?;
+        sqlx::query!(
+            r#"
+            UPDATE user_tokens
+            SET used_at = $3
+            WHERE user_id = $1 AND purpose = $2 AND used_at IS NULL
+            "#,
+            user_id,
+            purpose.to_string(),
+            now
+        )
+        .execute(&mut *db_tx)
+        .await?;
+        sqlx::query!(
+            r#"
+            INSERT INTO user_tokens (id, user_id, purpose, token_hash, created_at, expires_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            "#,
+            SNOWFLAKE_GENERATOR.generate().unwrap(),
+            user_id,
+            purpose.to_string(),
+            token_hash,
+            now,
+            expires_at
+        )
+        .execute(&mut *db_tx)
+        .await?;
+        db_tx.commit().await?;
+        Ok(())
+    }
+
+    /// Uses an email verification token and activates its pending user.
+    /// Returns the user, `None` when the token is unknown, used or expired.
+    pub async fn verify_email(&self, token_hash: &str) -> Result<Option<i64>, AppError> {
+        let mut db_tx = self.pool.begin().await?;
+        let Some(user_id) =
+            consume(&mut db_tx, UserTokenPurpose::EmailVerification, token_hash).await?
+        else {
+            return Ok(None);
+        };
+        sqlx::query!(
+            r#"
+            UPDATE users
+            SET status = $2, updated_at = $4
+            WHERE id = $1 AND status = $3
+            "#,
+            user_id,
+            UserStatus::Active.to_string(),
+            UserStatus::Pending.to_string(),
+            OffsetDateTime::now_utc()
+        )
+        .execute(&mut *db_tx)
+        .await?;
+        db_tx.commit().await?;
+        Ok(Some(user_id))
+    }
+
+    /// Uses a password reset token and sets the password of its user. Receiving
+    /// the mail proves the address, so a pending user is activated as well.
+    /// Returns the user, `None` when the token is unknown, used or expired.
+    pub async fn reset_password(
+        &self,
+        token_hash: &str,
+        hashed_password: &str,
+    ) -> Result<Option<i64>, AppError> {
+        let mut db_tx = self.pool.begin().await?;
+        let Some(user_id) =
+            consume(&mut db_tx, UserTokenPurpose::PasswordReset, token_hash).await?
+        else {
+            return Ok(None);
+        };
+        sqlx::query!(
+            r#"
+            UPDATE users
+            SET hashed_password = $2,
+                status = CASE WHEN status = $4 THEN $3 ELSE status END,
+                updated_at = $5
+            WHERE id = $1
+            "#,
+            user_id,
+            hashed_password,
+            UserStatus::Active.to_string(),
+            UserStatus::Pending.to_string(),
+            OffsetDateTime::now_utc()
+        )
+        .execute(&mut *db_tx)
+        .await?;
+        db_tx.commit().await?;
+        Ok(Some(user_id))
+    }
+}
@@ -0,0 +1,176 @@
+use sqlx::{PgConnection, PgPool, QueryBuilder};
+use time::OffsetDateTime;
+
+use crate::models::database::user_totp::UserTotpRow;
+use crate::utils::error::AppError;
+use crate::utils::snowflake::SNOWFLAKE_GENERATOR;
+
+pub struct UserTotpRepo {
+    pool: PgPool,
+}
+
+/// Replaces every recovery code of the user
+async fn replace_recovery_codes(
+    conn: &mut PgConnection,
+    user_id: i64,
+    code_hashes: &[String],
+) -> Result<(), AppError> {
+    sqlx::query!(
+        r#"DELETE FROM user_recovery_codes WHERE user_id = $1"#,
+        user_id
+    )
+    .execute(&mut *conn)
+    .await?;
+    if code_hashes.is_empty() {
+        return Ok(());
+    }
+    let now = OffsetDateTime::now_utc();
+    let mut query_builder =
+        QueryBuilder::new("INSERT INTO user_recovery_codes (id, user_id, code_hash, created_at) ");
+    query_builder.push_values(code_hashes, |mut b, code_hash| {
+        b.push_bind(SNOWFLAKE_GENERATOR.generate().unwrap())
+            .push_bind(user_id)
+            .push_bind(code_hash)
+            .push_bind(now);
+    });
+    query_builder.build().execute(&mut *conn).await?;
+    Ok(())
+}
+
+impl UserTotpRepo {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn get_by_user_id(&self, user_id: i64) -> Result<Option<UserTotpRow>, AppError> {
+        Ok(sqlx::query_as!(
+            UserTotpRow,
+            r#"
+            SELECT user_id, secret, enabled_at, last_used_step
+            FROM user_totp
+            WHERE user_id = $1
+            "#,
+            user_id
+        )
+        .fetch_optional(&self.pool)
+        .await?)
+    }
+
+    /// Stores the secret of an enrollment to confirm. Returns false, changing
+    /// nothing, when two-factor authentication is already enabled.
+    pub async fn upsert_pending(&self, user_id: i64, secret: &str) -> Result<bool, AppError> {
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO user_totp (user_id, secret, created_at)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (user_id) DO UPDATE
+            SET secret = EXCLUDED.secret, created_at = EXCLUDED.created_at, last_used_step = NULL
+            WHERE user_totp.enabled_at IS NULL
+            "#,
+            user_id,
+            secret,
+            OffsetDateTime::now_utc()
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Enables two-factor authentication with the step of the confirmation
+    /// code and the first recovery codes
+    pub async fn enable(
+        &self,
+        user_id: i64,
+        step: i64,
+        code_hashes: &[String],
+    ) -> Result<bool, AppError> {
+        let mut db_tx = self.pool.begin().await?;
+        let result = sqlx::query!(
+            r#"
+            UPDATE user_totp
+            SET enabled_at = $2, last_used_step = $3
+            WHERE user_id = $1 AND enabled_at IS NULL
+            "#,
+            user_id,
+            OffsetDateTime::now_utc(),
+            step
+        )
+        .execute(&mut *db_tx)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
+        replace_recovery_codes(&mut db_tx, user_id, code_hashes).await?;
+        db_tx.commit().await?;
+        Ok(true)
+    }
+
+    /// Records the step of an accepted code. Returns false when a code of this
+    /// step or a later one was already accepted.
+    pub async fn use_step(&self, user_id: i64, step: i64) -> Result<bool, AppError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE user_totp
+            SET last_used_step = $2
+            WHERE user_id = $1 AND (last_used_step IS NULL OR last_used_step < $2)
+            "#,
+            user_id,
+            step
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn replace_recovery_codes(
+        &self,
+        user_id: i64,
+        code_hashes: &[String],
+    ) -> Result<(), AppError> {
+        let mut db_tx = self.pool.begin().await?;
+        replace_recovery_codes(&mut db_tx, user_id, code_hashes).await?;
+        db_tx.commit().await?;
+        Ok(())
+    }
+
+    /// Marks an unused recovery code of the user used, false when there is none
+    pub async fn use_recovery_code(&self, user_id: i64, code_hash: &str) -> Result<bool, AppError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE user_recovery_codes
+            SET used_at = $3
+            WHERE user_id = $1 AND code_hash = $2 AND used_at IS NULL
+            "#,
+            user_id,
+            code_hash,
+            OffsetDateTime::now_utc()
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn count_unused_recovery_codes(&self, user_id: i64) -> Result<i64, AppError> {
+        Ok(sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(*) AS "count!"
+            FROM user_recovery_codes
+            WHERE user_id = $1 AND used_at IS NULL
+            "#,
+            user_id
+        )
+        .fetch_one(&self.pool)
+        .await?)
+    }
+
+    /// Turns two-factor authentication off, dropping the secret and recovery codes
+    pub async fn delete(&self, user_id: i64) -> Result<(), AppError> {
+        let mut db_tx = self.pool.begin().await?;
+        replace_recovery_codes(&mut db_tx, user_id, &[]).await?;
+        sqlx::query!(r#"DELETE FROM user_totp WHERE user_id = $1"#, user_id)
+            .execute(&mut *db_tx)
+            .await?;
+        db_tx.commit().await?;
+        Ok(())
+    }
+}
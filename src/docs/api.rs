@@ -12,11 +12,32 @@ use crate::models::dto;
 
         // Auth endpoints
         handlers::auth::login_with_password,
+        handlers::auth::login_with_totp,
         handlers::auth::signup,
+        handlers::auth::refresh_token,
+        handlers::auth::logout,
+        handlers::auth::verify_email,
+        handlers::auth::resend_verification,
+        handlers::auth::forgot_password,
+        handlers::auth::reset_password,
 
         // User endpoints
         handlers::users::get_user_by_id,
         handlers::users::get_user_me,
+        handlers::users::update_user_me,
+        handlers::users::change_my_password,
+        handlers::users::delete_user_me,
+        handlers::users::get_my_sessions,
+        handlers::users::revoke_my_session,
+        handlers::users::revoke_all_my_sessions,
+        handlers::personal_access_tokens::create_my_token,
+        handlers::personal_access_tokens::get_my_tokens,
+        handlers::personal_access_tokens::revoke_my_token,
+        handlers::two_factor::get_my_two_factor,
+        handlers::two_factor::enroll_my_totp,
+        handlers::two_factor::confirm_my_totp,
+        handlers::two_factor::regenerate_my_recovery_codes,
+        handlers::two_factor::disable_my_totp,
 
         // Asset endpoints
         handlers::assets::get_all_assets,
@@ -27,17 +48,42 @@ use crate::models::dto;
         handlers::portfolios::create_portfolio,
         handlers::portfolios::create_portfolio_asset,
         handlers::portfolios::get_portfolio_by_id,
+        handlers::portfolios::update_portfolio,
+        handlers::portfolios::get_realized_gains,
+        handlers::portfolios::get_open_lots,
+        handlers::portfolios::get_portfolio_history,
+        handlers::portfolios::get_portfolio_performance,
+        handlers::portfolios::export_portfolio,
+        handlers::users::download_backup,
+        handlers::users::restore_backup,
         handlers::portfolios::get_my_portfolios,
 
         // Import endpoints
         handlers::import::coingecko::import_portfolio_file,
+        handlers::import::coingecko::import_json_file,
         handlers::import::coingecko::get_coin_data_by_id,
+        handlers::import::csv::import_csv,
+        handlers::import::exchange::import_exchange_csv,
+        handlers::import::plan::commit_import_plan,
+        handlers::import::batch::get_import_batches,
+        handlers::import::batch::rollback_import,
+        handlers::import::job::create_import_job,
+        handlers::import::job::get_import_job,
+        handlers::import::job::stream_import_job,
+        handlers::import::job::retry_import_job,
 
         // Transaction endpoints
         handlers::transactions::get_transactions,
         handlers::transactions::create_transaction,
         handlers::transactions::get_detail_transaction,
         handlers::transactions::update_transaction,
+
+        // Report endpoints
+        handlers::reports::get_tax_report,
+
+        // Admin endpoints
+        handlers::admin::get_job_runs,
+        handlers::admin::trigger_job,
     ),
     components(
         // List your schema components here
@@ -46,20 +92,76 @@ use crate::models::dto;
             dto::asset::AssetListResponse,
             dto::auth::LoginWithPasswordRequest,
             dto::auth::SignUpWithPasswordRequest,
+            dto::auth::RefreshTokenRequest,
             dto::auth::AuthResponse,
+            dto::auth::LoginResponse,
+            dto::auth::LoginWithTotpRequest,
+            dto::auth::SignUpResponse,
+            dto::auth::VerifyEmailRequest,
+            dto::auth::ResendVerificationRequest,
+            dto::auth::ForgotPasswordRequest,
+            dto::auth::ResetPasswordRequest,
             dto::pagination::CursorPaginationResponse,
             dto::pagination::NumberPaginationResponse,
             dto::portfolio::CreatePortfolioRequest,
+            dto::portfolio::UpdatePortfolioRequest,
             dto::portfolio::PortfolioResponse,
             dto::portfolio::BriefPortfolioListResponse,
             dto::portfolio_asset::CreatePortfolioAssetRequest,
             dto::portfolio_asset::PortfolioAssetResponse,
+            dto::performance::PerformanceResponse,
+            dto::export::PortfolioExport,
+            dto::export::ExportedTransaction,
+            dto::export::ExportedHolding,
+            dto::export::ExportFormat,
+            dto::export::ExportSheet,
+            dto::backup::AccountBackup,
+            dto::backup::BackupAsset,
+            dto::backup::BackupPortfolio,
+            dto::backup::BackupTransaction,
+            dto::backup::RestoreConflict,
+            dto::backup::RestoreStatus,
+            dto::backup::RestoredPortfolioResponse,
+            dto::backup::RestoreResponse,
+            dto::portfolio_snapshot::PortfolioHistoryResponse,
+            dto::realized_gain::RealizedGainResponse,
+            dto::realized_gain::RealizedGainListResponse,
+            dto::tax_lot::TaxLotResponse,
+            dto::tax_lot::TaxLotListResponse,
+            dto::tax_report::TaxReportFormat,
+            dto::tax_report::HoldingTerm,
+            dto::tax_report::TaxReportItem,
+            dto::tax_report::TaxReportTotal,
+            dto::tax_report::TaxReportResponse,
             dto::user::UserResponse,
             dto::user::UserMeResponse,
+            dto::user::UpdateUserRequest,
+            dto::user::ChangePasswordRequest,
+            dto::user::DeleteAccountRequest,
+            dto::user::DeleteAccountResponse,
+            dto::user_session::UserSessionResponse,
+            dto::user_session::UserSessionListResponse,
+            dto::user_session::RevokeSessionsResponse,
+            dto::personal_access_token::CreatePersonalAccessTokenRequest,
+            dto::personal_access_token::PersonalAccessTokenResponse,
+            dto::personal_access_token::CreatedPersonalAccessTokenResponse,
+            dto::personal_access_token::PersonalAccessTokenListResponse,
+            dto::two_factor::TwoFactorStatusResponse,
+            dto::two_factor::TotpEnrollResponse,
+            dto::two_factor::TotpCodeRequest,
+            dto::two_factor::RecoveryCodesResponse,
             dto::api_response::GeneralResponse,
             dto::api_response::IdResponse,
             dto::health::HealthResponse,
+            dto::job::JobRunResponse,
+            dto::job::JobRunListResponse,
             dto::coingecko::CoinDataResponse,
+            dto::import::CsvColumnMapping,
+            dto::import::ExchangeImportOptions,
+            dto::import::ImportResponse,
+            dto::import::ImportBatchResponse,
+            dto::import::ImportBatchListResponse,
+            dto::import::ImportJobResponse,
             dto::transaction::TransactionResponse,
             dto::transaction::TransactionListResponse,
             dto::transaction::CreateTransactionRequest,
@@ -72,7 +174,9 @@ use crate::models::dto;
         (name = "portfolios", description = "Portfolio endpoints"),
         (name = "transactions", description = "Transaction endpoints"),
         (name = "assets", description = "Asset endpoints"),
-        (name = "imports", description = "Import endpoints")
+        (name = "imports", description = "Import endpoints"),
+        (name = "reports", description = "Report endpoints"),
+        (name = "admin", description = "Admin endpoints")
     )
 )]
 pub struct ApiDoc;
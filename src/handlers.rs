@@ -1,7 +1,11 @@
+pub mod admin;
 pub mod assets;
 pub mod auth;
 pub mod health;
 pub mod import;
+pub mod personal_access_tokens;
 pub mod portfolios;
+pub mod reports;
 pub mod transactions;
+pub mod two_factor;
 pub mod users;
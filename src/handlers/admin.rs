@@ -0,0 +1,102 @@
+use axum::{
+    Extension,
+    extract::{Path, Query, State},
+    http::StatusCode,
+};
+use tracing::info;
+
+use crate::{
+    db::repositories::{job_run::JobRunRepo, user::UserRepo},
+    models::{
+        domain::{
+            auth::Claims,
+            job::{JobName, JobTrigger},
+        },
+        dto::{
+            api_response::ApiResponse,
+            job::{JobRunListResponse, JobRunQueryParams, JobRunResponse},
+            pagination::NumberPaginationResponse,
+        },
+    },
+    scheduler::{execute_job, start_job},
+    state::AppState,
+    to_api_res,
+};
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/jobs/runs",
+    params(JobRunQueryParams),
+    responses(
+        (status = 200, description = "Success", body = ApiResponse<JobRunListResponse>),
+        (status = 403, description = "Forbidden"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn get_job_runs(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Query(params): Query<JobRunQueryParams>,
+) -> ApiResponse<JobRunListResponse> {
+    to_api_res!(claims.require_session());
+    let user_repo = UserRepo::new(state.pool.clone());
+    if !to_api_res!(user_repo.is_admin(claims.user_id).await) {
+        return ApiResponse::error(StatusCode::FORBIDDEN, "Admin access required");
+    }
+    let job_name = params.job_name.map(|name| name.to_string());
+    let job_run_repo = JobRunRepo::new(state.pool.clone());
+    let rows = to_api_res!(
+        job_run_repo
+            .get_multi_with_paging(job_name.clone(), params.page, params.limit)
+            .await
+    );
+    let total = to_api_res!(job_run_repo.count(job_name).await);
+    ApiResponse::success(JobRunListResponse {
+        number_pagination: NumberPaginationResponse::new(params.page, params.limit, total as u32),
+        items: rows.into_iter().map(JobRunResponse::from_db_row).collect(),
+    })
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/jobs/{job_name}/trigger",
+    responses(
+        (status = 200, description = "Success", body = ApiResponse<JobRunResponse>),
+        (status = 400, description = "Bad request"),
+        (status = 403, description = "Forbidden"),
+        (status = 409, description = "Job is already running"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn trigger_job(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(job_name): Path<String>,
+) -> ApiResponse<JobRunResponse> {
+    to_api_res!(claims.require_session());
+    let user_repo = UserRepo::new(state.pool.clone());
+    if !to_api_res!(user_repo.is_admin(claims.user_id).await) {
+        return ApiResponse::error(StatusCode::FORBIDDEN, "Admin access required");
+    }
+    let job_name: JobName = match job_name.parse() {
+        Ok(v) => v,
+        Err(_) => {
+            return ApiResponse::error(
+                StatusCode::BAD_REQUEST,
+                format!("Invalid job name: {}", job_name),
+            );
+        }
+    };
+    let (run, lock) = match to_api_res!(start_job(&state, job_name, JobTrigger::Manual).await) {
+        Some(started) => started,
+        None => {
+            return ApiResponse::error(
+                StatusCode::CONFLICT,
+                format!("Job {} is already running", job_name),
+            );
+        }
+    };
+    info!("User {} triggered job {}", claims.user_id, job_name);
+    tokio::spawn(execute_job(state.clone(), job_name, run.id, lock));
+    ApiResponse::success(JobRunResponse::from_db_row(run))
+}
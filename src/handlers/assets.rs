@@ -13,7 +13,7 @@ use crate::{
     db::repositories::asset::AssetRepo,
     models::{
         common::asset::AssetType,
-        domain::auth::Claims,
+        domain::auth::{Claims, TokenScope},
         dto::{
             api_response::ApiResponse,
             asset::{
@@ -88,16 +88,17 @@ pub async fn get_detail_asset(
 )]
 pub async fn create_asset(
     State(state): State<AppState>,
-    Extension(_claims): Extension<Claims>,
+    Extension(claims): Extension<Claims>,
     Json(req): Json<CreateAssetRequest>,
 ) -> ApiResponse<IdResponse> {
+    to_api_res!(claims.require_scope(TokenScope::WriteTransactions));
     if req.asset_type != AssetType::Crypto {
         return ApiResponse::error(StatusCode::BAD_REQUEST, "Invalid asset type".to_string());
     }
     let coin_data = to_api_res!(
         state
             .clients
-            .coingecko
+            .price_provider
             .get_coin_data(&req.external_id)
             .await
     );
@@ -1,57 +1,59 @@
-use argon2::{self, password_hash::rand_core};
 use axum::{Json, extract::State, http::StatusCode};
-use time::{Duration, OffsetDateTime};
-use tracing::info;
+use tracing::{error, info};
 
 use crate::{
+    biz::auth::{
+        create_session, refresh_session, request_password_reset, resend_email_verification,
+        reset_password as reset_user_password, revoke_session, send_email_verification,
+        verify_email as verify_user_email,
+    },
+    biz::two_factor::{complete_login_challenge, create_login_challenge, is_two_factor_enabled},
     db::repositories::user::UserRepo,
-    middleware::auth::create_token,
+    middleware::{
+        client::ClientInfo,
+        rate_limit::{clear_login_failures, record_login_failure},
+    },
+    models::domain::{auth::Claims, user::UserStatus},
     models::dto::{
-        api_response::ApiResponse,
-        auth::{AuthResponse, LoginWithPasswordRequest, SignUpWithPasswordRequest},
+        api_response::{ApiResponse, GeneralResponse},
+        auth::{
+            AuthResponse, ForgotPasswordRequest, LoginResponse, LoginWithPasswordRequest,
+            LoginWithTotpRequest, RefreshTokenRequest, ResendVerificationRequest,
+            ResetPasswordRequest, SignUpResponse, SignUpWithPasswordRequest, VerifyEmailRequest,
+        },
         user::CreateUser,
-        user_session::CreateUserSession,
     },
     state::AppState,
+    utils::password::{hash_password, verify_dummy_password, verify_password},
 };
 
-const BEARER_TOKEN_EXPIRATION: Duration = Duration::days(365);
-
-fn hash_password(password: String) -> Result<String, anyhow::Error> {
-    // Hash the password using Argon2
-    let salt = argon2::password_hash::SaltString::generate(&mut rand_core::OsRng);
-    let argon2 = argon2::Argon2::default();
-    let hashed_password =
-        argon2::PasswordHasher::hash_password(&argon2, password.as_bytes(), &salt)
-            .map_err(|e| anyhow::anyhow!(e))?;
-    Ok(hashed_password.to_string())
-}
-
-fn verify_password(password: String, hashed_password: String) -> Result<bool, anyhow::Error> {
-    let argon2 = argon2::Argon2::default();
-    let parsed_hash =
-        argon2::PasswordHash::new(&hashed_password).map_err(|e| anyhow::anyhow!(e))?;
-    Ok(
-        argon2::PasswordVerifier::verify_password(&argon2, password.as_bytes(), &parsed_hash)
-            .is_ok(),
-    )
-}
-
+/// Checks the password. Accounts with two-factor authentication get a challenge
+/// token to exchange at `/auth/login_with_totp`, the others a session.
 #[utoipa::path(
     post,
     path = "/auth/login_with_password",
     request_body = LoginWithPasswordRequest,
     responses(
-        (status = 200, description = "Login successful", body = AuthResponse),
+        (status = 200, description = "Login successful or second factor required", body = LoginResponse),
+        (status = 401, description = "Invalid email or password"),
+        (status = 429, description = "Too many requests or failed logins, see Retry-After"),
         (status = 500, description = "Internal server error")
     )
 )]
 pub async fn login_with_password(
     State(state): State<AppState>,
+    client: ClientInfo,
     Json(req): Json<LoginWithPasswordRequest>,
-) -> ApiResponse<AuthResponse> {
-    // Validate credentials (implement your own logic here)
+) -> ApiResponse<LoginResponse> {
     info!("Login with password for email: {}", req.email);
+    // Unknown emails and wrong passwords get the same answer, both count
+    // toward the lockout of the email
+    let invalid_credentials = || {
+        ApiResponse::error(
+            StatusCode::UNAUTHORIZED,
+            "Invalid email or password".to_string(),
+        )
+    };
     let user_repo = UserRepo::new(state.pool.clone());
     let user = match user_repo.get_by_email(&req.email).await {
         Ok(u) => u,
@@ -59,51 +61,97 @@ pub async fn login_with_password(
             return ApiResponse::error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
         }
     };
-    if user.is_none() {
-        return ApiResponse::error(StatusCode::NOT_FOUND, "User not found".to_string());
-    }
-    let user = user.unwrap();
-    match verify_password(req.password, user.hashed_password.unwrap()) {
+    let Some((user, hashed_password)) =
+        user.and_then(|u| u.hashed_password.clone().map(|hash| (u, hash)))
+    else {
+        verify_dummy_password(req.password);
+        record_login_failure(&state, &req.email).await;
+        return invalid_credentials();
+    };
+    match verify_password(req.password, hashed_password) {
         Ok(true) => {}
         Ok(false) => {
-            return ApiResponse::error(StatusCode::UNAUTHORIZED, "Invalid password".to_string());
+            record_login_failure(&state, &req.email).await;
+            return invalid_credentials();
         }
         Err(e) => {
             return ApiResponse::error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
         }
     }
-    // Create a new user session
-    let create_session = CreateUserSession {
-        user_id: user.id,
-        expires_at: OffsetDateTime::now_utc() + BEARER_TOKEN_EXPIRATION,
-    };
-
-    // Create JWT token
-    let token = match create_token(create_session, &state).await {
-        Ok(t) => t,
-        Err(e) => {
-            return ApiResponse::error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
+    if user.status == UserStatus::Pending.to_string() {
+        return ApiResponse::error(StatusCode::FORBIDDEN, "Email address not verified");
+    }
+    if user.status != UserStatus::Active.to_string() {
+        return ApiResponse::error(StatusCode::FORBIDDEN, "Account is not active");
+    }
+    match is_two_factor_enabled(&state, user.id).await {
+        Ok(true) => {
+            return match create_login_challenge(&state, user.id).await {
+                Ok(challenge_token) => ApiResponse::success(LoginResponse {
+                    two_factor_required: true,
+                    challenge_token: Some(challenge_token),
+                    challenge_expires_in: Some(state.auth.login_challenge_ttl_secs),
+                    session: None,
+                }),
+                Err(e) => ApiResponse::from(e),
+            };
         }
-    };
-    return ApiResponse::success(AuthResponse {
-        user_id: user.id,
-        token: token,
-    });
+        Ok(false) => {}
+        Err(e) => return ApiResponse::from(e),
+    }
+    // Failures are forgotten once every factor passed
+    clear_login_failures(&state, &req.email).await;
+    // Create a new user session with its tokens
+    match create_session(&state, user.id, client).await {
+        Ok(res) => ApiResponse::success(LoginResponse {
+            two_factor_required: false,
+            challenge_token: None,
+            challenge_expires_in: None,
+            session: Some(res),
+        }),
+        Err(e) => ApiResponse::error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
 }
 
+/// Second step of the login of an account with two-factor authentication:
+/// the challenge token of the password step with a code of the authenticator
+/// app or a recovery code.
+#[utoipa::path(
+    post,
+    path = "/auth/login_with_totp",
+    request_body = LoginWithTotpRequest,
+    responses(
+        (status = 200, description = "Login successful", body = AuthResponse),
+        (status = 401, description = "Invalid code or expired challenge"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn login_with_totp(
+    State(state): State<AppState>,
+    client: ClientInfo,
+    Json(req): Json<LoginWithTotpRequest>,
+) -> ApiResponse<AuthResponse> {
+    match complete_login_challenge(&state, &req.challenge_token, &req.code, client).await {
+        Ok(res) => ApiResponse::success(res),
+        Err(e) => ApiResponse::from(e),
+    }
+}
+
+/// Creates a pending account and mails it a verification link, the account
+/// can log in once the address is verified.
 #[utoipa::path(
     post,
     path = "/auth/signup",
     request_body = SignUpWithPasswordRequest,
     responses(
-        (status = 200, description = "Signup successful", body = AuthResponse),
+        (status = 200, description = "Signup successful", body = SignUpResponse),
         (status = 500, description = "Internal server error")
     )
 )]
 pub async fn signup(
     State(state): State<AppState>,
     Json(req): Json<SignUpWithPasswordRequest>,
-) -> ApiResponse<AuthResponse> {
+) -> ApiResponse<SignUpResponse> {
     let user_repo = UserRepo::new(state.pool.clone());
     match user_repo.get_by_email(&req.email).await {
         Ok(u) => {
@@ -131,8 +179,10 @@ pub async fn signup(
     let user = match user_repo
         .create_user(CreateUser {
             email: req.email,
+            status: UserStatus::Pending,
             hashed_password: Some(hashed_password),
             name: Some(req.name),
+            base_currency: req.base_currency.unwrap_or_default(),
         })
         .await
     {
@@ -144,21 +194,158 @@ pub async fn signup(
             );
         }
     };
-    let create_session = CreateUserSession {
+    // The link can be sent again, a failure does not undo the signup
+    if let Err(e) = send_email_verification(&state, &user).await {
+        error!(
+            "Send email verification to user {} failed: {:?}",
+            user.id, e
+        );
+    }
+    ApiResponse::success(SignUpResponse {
         user_id: user.id,
-        expires_at: OffsetDateTime::now_utc() + BEARER_TOKEN_EXPIRATION,
-    };
-    let token = match create_token(create_session, &state).await {
-        Ok(t) => t,
+        status: UserStatus::Pending,
+    })
+}
+
+/// Verifies the email address with the token of the mailed link, activating
+/// the account, and logs it in.
+#[utoipa::path(
+    post,
+    path = "/auth/verify_email",
+    request_body = VerifyEmailRequest,
+    responses(
+        (status = 200, description = "Email verified", body = AuthResponse),
+        (status = 400, description = "Invalid or expired token"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn verify_email(
+    State(state): State<AppState>,
+    client: ClientInfo,
+    Json(req): Json<VerifyEmailRequest>,
+) -> ApiResponse<AuthResponse> {
+    match verify_user_email(&state, &req.token, client).await {
+        Ok(res) => ApiResponse::success(res),
+        Err(e) => ApiResponse::from(e),
+    }
+}
+
+/// Mails a new verification link to a pending account. Succeeds whether the
+/// address has an account or not.
+#[utoipa::path(
+    post,
+    path = "/auth/resend_verification",
+    request_body = ResendVerificationRequest,
+    responses(
+        (status = 200, description = "Link sent if the account is pending", body = ApiResponse<GeneralResponse>),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn resend_verification(
+    State(state): State<AppState>,
+    Json(req): Json<ResendVerificationRequest>,
+) -> ApiResponse<GeneralResponse> {
+    match resend_email_verification(&state, &req.email).await {
+        Ok(()) => ApiResponse::<GeneralResponse>::success_general_response(),
+        Err(e) => ApiResponse::from(e),
+    }
+}
+
+/// Mails a single-use password reset link. Succeeds whether the address has an
+/// account or not.
+#[utoipa::path(
+    post,
+    path = "/auth/forgot_password",
+    request_body = ForgotPasswordRequest,
+    responses(
+        (status = 200, description = "Link sent if the account exists", body = ApiResponse<GeneralResponse>),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn forgot_password(
+    State(state): State<AppState>,
+    Json(req): Json<ForgotPasswordRequest>,
+) -> ApiResponse<GeneralResponse> {
+    info!("Forgot password for email: {}", req.email);
+    match request_password_reset(&state, &req.email).await {
+        Ok(()) => ApiResponse::<GeneralResponse>::success_general_response(),
+        Err(e) => ApiResponse::from(e),
+    }
+}
+
+/// Sets a new password with the token of a reset link. Every session of the
+/// account is logged out.
+#[utoipa::path(
+    post,
+    path = "/auth/reset_password",
+    request_body = ResetPasswordRequest,
+    responses(
+        (status = 200, description = "Password reset", body = ApiResponse<GeneralResponse>),
+        (status = 400, description = "Invalid or expired token"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn reset_password(
+    State(state): State<AppState>,
+    Json(req): Json<ResetPasswordRequest>,
+) -> ApiResponse<GeneralResponse> {
+    if req.password.is_empty() {
+        return ApiResponse::error(StatusCode::BAD_REQUEST, "Password can not be empty");
+    }
+    let hashed_password = match hash_password(req.password) {
+        Ok(hash) => hash,
         Err(e) => {
             return ApiResponse::error(
                 StatusCode::INTERNAL_SERVER_ERROR,
-                format!("Failed to create token: {}", e),
+                format!("Failed to hash password: {}", e),
             );
         }
     };
-    return ApiResponse::success(AuthResponse {
-        user_id: user.id,
-        token: token,
-    });
+    match reset_user_password(&state, &req.token, &hashed_password).await {
+        Ok(()) => ApiResponse::<GeneralResponse>::success_general_response(),
+        Err(e) => ApiResponse::from(e),
+    }
+}
+
+/// Exchanges a refresh token for a new access token and refresh token. Each
+/// refresh token is single-use, presenting a used one revokes its session.
+#[utoipa::path(
+    post,
+    path = "/auth/refresh",
+    request_body = RefreshTokenRequest,
+    responses(
+        (status = 200, description = "Tokens refreshed", body = AuthResponse),
+        (status = 401, description = "Invalid, expired or reused refresh token"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn refresh_token(
+    State(state): State<AppState>,
+    client: ClientInfo,
+    Json(req): Json<RefreshTokenRequest>,
+) -> ApiResponse<AuthResponse> {
+    match refresh_session(&state, &req.refresh_token, client).await {
+        Ok(res) => ApiResponse::success(res),
+        Err(e) => ApiResponse::from(e),
+    }
+}
+
+/// Ends the session of the access token, its refresh token stops working too
+#[utoipa::path(
+    post,
+    path = "/auth/logout",
+    responses(
+        (status = 200, description = "Logged out", body = ApiResponse<GeneralResponse>),
+        (status = 401, description = "Invalid or missing access token"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn logout(State(state): State<AppState>, claims: Claims) -> ApiResponse<GeneralResponse> {
+    if let Err(e) = claims.require_session() {
+        return ApiResponse::from(e);
+    }
+    match revoke_session(&state, claims.session_id).await {
+        Ok(()) => ApiResponse::<GeneralResponse>::success_general_response(),
+        Err(e) => ApiResponse::from(e),
+    }
 }
@@ -1 +1,6 @@
+pub mod batch;
 pub mod coingecko;
+pub mod csv;
+pub mod exchange;
+pub mod job;
+pub mod plan;
@@ -0,0 +1,102 @@
+use axum::{
+    Extension,
+    extract::{Path, Query, State},
+    http::StatusCode,
+};
+use tracing::info;
+
+use crate::{
+    biz::import_plan::rollback_import_batch,
+    db::repositories::import_batch::ImportBatchRepo,
+    models::{
+        domain::auth::{Claims, TokenScope},
+        dto::{
+            api_response::ApiResponse,
+            import::{ImportBatchListResponse, ImportBatchQueryParams, ImportBatchResponse},
+            pagination::NumberPaginationResponse,
+        },
+    },
+    state::AppState,
+    to_api_res,
+};
+
+#[utoipa::path(
+    get,
+    path = "/api/imports",
+    params(ImportBatchQueryParams),
+    responses(
+        (status = 200, description = "Success", body = ApiResponse<ImportBatchListResponse>),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn get_import_batches(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Query(params): Query<ImportBatchQueryParams>,
+) -> ApiResponse<ImportBatchListResponse> {
+    to_api_res!(claims.require_scope(TokenScope::Import));
+    let portfolio_id: Option<i64> = match params.portfolio_id {
+        Some(ref id) => match id.parse() {
+            Ok(v) => Some(v),
+            Err(_) => {
+                return ApiResponse::error(
+                    StatusCode::BAD_REQUEST,
+                    format!("Invalid portfolio ID: {}", id),
+                );
+            }
+        },
+        None => None,
+    };
+    let batch_repo = ImportBatchRepo::new(state.pool.clone());
+    let rows = to_api_res!(
+        batch_repo
+            .get_multi_by_user_with_paging(claims.user_id, portfolio_id, params.page, params.limit)
+            .await
+    );
+    let total = to_api_res!(batch_repo.count_by_user(claims.user_id, portfolio_id).await);
+    ApiResponse::success(ImportBatchListResponse {
+        number_pagination: NumberPaginationResponse::new(params.page, params.limit, total as u32),
+        items: rows
+            .into_iter()
+            .map(ImportBatchResponse::from_db_row)
+            .collect(),
+    })
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/imports/{import_id}/rollback",
+    responses(
+        (status = 200, description = "Success", body = ApiResponse<ImportBatchResponse>),
+        (status = 404, description = "Import not found"),
+        (status = 409, description = "Import can not be rolled back"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn rollback_import(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(import_id): Path<String>,
+) -> ApiResponse<ImportBatchResponse> {
+    to_api_res!(claims.require_scope(TokenScope::Import));
+    let batch_id: i64 = match import_id.parse() {
+        Ok(v) => v,
+        Err(_) => {
+            return ApiResponse::error(
+                StatusCode::BAD_REQUEST,
+                format!("Invalid import ID: {}", import_id),
+            );
+        }
+    };
+    let batch_repo = ImportBatchRepo::new(state.pool.clone());
+    let batch = match to_api_res!(batch_repo.get_one_by_id(batch_id).await) {
+        Some(batch) if batch.user_id == claims.user_id => batch,
+        _ => return ApiResponse::error(StatusCode::NOT_FOUND, "Import not found"),
+    };
+    info!(
+        "Roll back import {} of portfolio {}",
+        batch.id, batch.portfolio_id
+    );
+    let batch = to_api_res!(rollback_import_batch(state.pool.clone(), &batch).await);
+    ApiResponse::success(ImportBatchResponse::from_db_row(batch))
+}
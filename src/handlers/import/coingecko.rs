@@ -1,26 +1,22 @@
-use std::collections::HashMap;
-
 use axum::{
     Extension,
-    extract::{Multipart, Path, State},
+    extract::{Multipart, Path, Query, State},
     http::StatusCode,
 };
 use tracing::info;
 
 use crate::{
-    biz::{asset::generate_asset_id, portfolio_asset::update_portfolio_asset_stat},
-    db::repositories::{
-        asset::AssetRepo, portfolio::PortfolioRepo, portfolio_asset::PortfolioAssetRepo,
-        transaction::TransactionRepo,
-    },
+    biz::{coingecko_import::plan_coingecko_import, import_plan::preview_or_apply},
+    db::repositories::portfolio::PortfolioRepo,
     models::{
-        common::asset::AssetType,
-        database::transaction::TransactionRow,
-        domain::{auth::Claims, coingecko::RawTransaction, transaction::BaseTransactionInfo},
+        domain::{
+            auth::{Claims, TokenScope},
+            import::ImportSource,
+        },
         dto::{
             api_response::{ApiResponse, GeneralResponse},
             coingecko::CoinDataResponse,
-            transaction::{CreateMultiTransaction, UpdateTransaction},
+            import::{ImportQueryParams, ImportResponse},
         },
     },
     state::AppState,
@@ -40,14 +36,14 @@ pub async fn get_coin_data_by_id(
     Path(coin_id): Path<String>,
 ) -> ApiResponse<CoinDataResponse> {
     info!("get coin data");
-    let response = state.clients.coingecko.get_coin_data(&coin_id).await;
+    let response = state.clients.price_provider.get_coin_data(&coin_id).await;
     match response {
         Ok(response) => ApiResponse::success(CoinDataResponse {
             market_data: response.market_data.limit_as_currency(),
             ..response
         }),
         Err(_) => {
-            info!("Can not get coin data from CoinGecko");
+            info!("Can not get coin data from the price provider");
             ApiResponse::error(
                 StatusCode::INTERNAL_SERVER_ERROR,
                 "Can not get coin data".to_string(),
@@ -56,25 +52,27 @@ pub async fn get_coin_data_by_id(
     }
 }
 
+/// Imports saved CoinGecko portfolio pages. The multipart form carries
+/// `portfolio_id` and one `file` per coin.
 #[utoipa::path(
     post,
     path = "/api/imports/coingecko/upload_portfolio_file",
+    params(ImportQueryParams),
     responses(
-        (status = 200, description = "User found", body = ApiResponse<GeneralResponse>),
+        (status = 200, description = "Success", body = ApiResponse<ImportResponse>),
         (status = 500, description = "Internal server error")
     )
 )]
 pub async fn import_portfolio_file(
     State(state): State<AppState>,
     Extension(claims): Extension<Claims>,
+    Query(params): Query<ImportQueryParams>,
     mut multipart: Multipart,
-) -> ApiResponse<GeneralResponse> {
-    let asset_repo = AssetRepo::new(state.pool.clone());
+) -> ApiResponse<ImportResponse> {
+    to_api_res!(claims.require_scope(TokenScope::Import));
     let portfolio_repo = PortfolioRepo::new(state.pool.clone());
-    let tx_repo = TransactionRepo::new(state.pool.clone());
     let mut portfolio_id: Option<i64> = None;
-    let mut target_coin_id: Option<String> = None;
-    let mut new_raw_txs: Vec<RawTransaction> = Vec::new();
+    let mut contents: Vec<String> = Vec::new();
     while let Some(field) = multipart.next_field().await.unwrap() {
         // Extract field_name and file_name before moving field
         let field_name = field.name();
@@ -110,17 +108,7 @@ pub async fn import_portfolio_file(
                         "Failed to read HTML file".to_string(),
                     );
                 }
-                let content = content.unwrap();
-                let parse_html_result = state.clients.coingecko.parse_html_contents(&content);
-                if let Err(e) = parse_html_result {
-                    info!("Failed to parse HTML file");
-                    return ApiResponse::from(e);
-                }
-                let (coin_id, raw_txs) = parse_html_result.unwrap();
-                info!("Coin ID: {}", coin_id);
-                info!("Raw transactions: {}", raw_txs.len());
-                target_coin_id = Some(coin_id);
-                new_raw_txs = raw_txs;
+                contents.push(content.unwrap());
             } else if name == &"portfolio_id" {
                 // Read the value of the portfolio_id field
                 let content = field.text().await;
@@ -147,21 +135,13 @@ pub async fn import_portfolio_file(
             }
         }
     }
-    if portfolio_id.is_none() || target_coin_id.is_none() {
-        info!("No portfolio id or target coin id");
+    let Some(portfolio_id) = portfolio_id.filter(|_| !contents.is_empty()) else {
+        info!("No portfolio id or file");
         return ApiResponse::error(
             StatusCode::BAD_REQUEST,
             "Portfolio ID or file upload is missing".to_string(),
         );
-    }
-    let portfolio_id = portfolio_id.unwrap();
-    let coin_id = target_coin_id.unwrap();
-    info!(
-        "Portfolio ID: {}, coin ID: {}, total tx: {}",
-        portfolio_id,
-        coin_id,
-        new_raw_txs.len()
-    );
+    };
     let portfolio = portfolio_repo.get_one_by_id(portfolio_id).await;
     match portfolio {
         Ok(Some(pfl)) => {
@@ -180,76 +160,118 @@ pub async fn import_portfolio_file(
             return ApiResponse::from(e);
         }
     }
-    let coin_data = to_api_res!(state.clients.coingecko.get_coin_data(&coin_id).await);
-    // create new asset if needed
-    let asset_id = generate_asset_id(&AssetType::Crypto, &coin_data.id);
-    let existed_asset = asset_repo.get_one_by_id(&asset_id).await;
-    match existed_asset {
-        Err(e) => return ApiResponse::from(e),
-        Ok(None) => {
-            return ApiResponse::error(StatusCode::BAD_REQUEST, "Asset does not exist");
-        }
-        Ok(Some(_)) => (),
-    }
-    // create portfolio asset
-    let pa_repo = PortfolioAssetRepo::new(state.pool.clone());
-    let exist_pa = pa_repo
-        .get_one_by_portfolio_id_and_asset_id(portfolio_id, &asset_id)
-        .await;
-    match exist_pa {
-        Err(e) => {
-            return ApiResponse::from(e);
-        }
-        Ok(None) => {
-            return ApiResponse::error(StatusCode::BAD_REQUEST, "Portfolio asset does not exist");
+    let plan = to_api_res!(
+        plan_coingecko_import(
+            &state,
+            claims.user_id,
+            portfolio_id,
+            ImportSource::Coingecko,
+            &contents,
+            None,
+        )
+        .await
+    );
+    info!(
+        "Import plan: {} new, {} updated, {} unchanged transactions",
+        plan.creates.len(),
+        plan.updates.len(),
+        plan.unchanged.len()
+    );
+    ApiResponse::success(to_api_res!(
+        preview_or_apply(&state, plan, params.dry_run).await
+    ))
+}
+
+/// Imports a JSON export of CoinGecko transactions. The multipart form carries
+/// `file`, `portfolio_id` and `coin_id`, which may be left out when the export
+/// names its coins.
+#[utoipa::path(
+    post,
+    path = "/api/imports/coingecko/upload_json_file",
+    params(ImportQueryParams),
+    responses(
+        (status = 200, description = "Success", body = ApiResponse<ImportResponse>),
+        (status = 400, description = "Invalid file"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn import_json_file(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Query(params): Query<ImportQueryParams>,
+    mut multipart: Multipart,
+) -> ApiResponse<ImportResponse> {
+    to_api_res!(claims.require_scope(TokenScope::Import));
+    let mut contents: Option<String> = None;
+    let mut portfolio_id: Option<String> = None;
+    let mut coin_id: Option<String> = None;
+    loop {
+        let field = match multipart.next_field().await {
+            Ok(Some(field)) => field,
+            Ok(None) => break,
+            Err(e) => return ApiResponse::error(StatusCode::BAD_REQUEST, e.body_text()),
+        };
+        let name = field.name().unwrap_or_default().to_string();
+        let value = match field.text().await {
+            Ok(value) => value,
+            Err(_) => {
+                return ApiResponse::error(
+                    StatusCode::BAD_REQUEST,
+                    format!("Failed to read {} field", name),
+                );
+            }
+        };
+        match name.as_str() {
+            "file" => contents = Some(value),
+            "portfolio_id" => portfolio_id = Some(value),
+            "coin_id" => coin_id = Some(value).filter(|v| !v.is_empty()),
+            _ => (),
         }
-        _ => {}
     }
-    // save txs when have new tx
-    let all_pa_txs = to_api_res!(
-        tx_repo
-            .get_multi_txs_by_portfolio_and_asset_with_paging(portfolio_id, &asset_id, 1, 10_000)
-            .await
-    );
-    let external_id_to_tx: HashMap<String, TransactionRow> = all_pa_txs
-        .into_iter()
-        .filter_map(|tx| tx.external_id.clone().map(|external_id| (external_id, tx)))
-        .collect();
-    let mut new_txs = Vec::new();
-    for raw_tx in new_raw_txs.into_iter() {
-        let base_tx_info = to_api_res!(BaseTransactionInfo::from_raw_tx(raw_tx));
-        if base_tx_info.external_id.is_none() {
+    let (Some(contents), Some(portfolio_id)) = (contents, portfolio_id) else {
+        return ApiResponse::error(
+            StatusCode::BAD_REQUEST,
+            "file and portfolio_id are required",
+        );
+    };
+    let pfl_id: i64 = match portfolio_id.parse() {
+        Ok(v) => v,
+        Err(_) => {
             return ApiResponse::error(
                 StatusCode::BAD_REQUEST,
-                "Transaction from Coingecko does not have id",
+                format!("Invalid portfolio ID: {}", portfolio_id),
             );
         }
-        let external_id = base_tx_info.external_id.clone().unwrap();
-        if let Some(tx) = external_id_to_tx.get(&external_id) {
-            // external transaction existed, update current transaction
-            let update_tx = UpdateTransaction {
-                tx_type: Some(base_tx_info.tx_type),
-                price: Some(base_tx_info.price),
-                quantity: Some(base_tx_info.quantity),
-                fees: Some(base_tx_info.fees),
-                currency: Some(base_tx_info.currency),
-                notes: base_tx_info.notes,
-                executed_at: Some(base_tx_info.executed_at),
-            };
-            to_api_res!(tx_repo.update_tx_by_id(tx.id, update_tx).await);
-        } else {
-            new_txs.push(base_tx_info);
-        }
-    }
-    let create_multi_txs = CreateMultiTransaction {
-        portfolio_id,
-        asset_id: asset_id.clone(),
-        transactions: new_txs,
     };
-    let new_txs = to_api_res!(tx_repo.create_multi_txs(create_multi_txs).await);
-    info!("Total transactions created: {}", new_txs);
-
-    to_api_res!(update_portfolio_asset_stat(state.pool.clone(), portfolio_id, &asset_id).await);
-
-    return ApiResponse::<GeneralResponse>::success_general_response();
+    let pfl_repo = PortfolioRepo::new(state.pool.clone());
+    let pfl_row = match to_api_res!(pfl_repo.get_one_by_id(pfl_id).await) {
+        None => return ApiResponse::error(StatusCode::NOT_FOUND, "Portfolio not found"),
+        Some(row) => row,
+    };
+    if pfl_row.owner_id != claims.user_id {
+        return ApiResponse::error(
+            StatusCode::FORBIDDEN,
+            "You are not the owner of this portfolio",
+        );
+    }
+    let plan = to_api_res!(
+        plan_coingecko_import(
+            &state,
+            claims.user_id,
+            pfl_id,
+            ImportSource::CoingeckoJson,
+            std::slice::from_ref(&contents),
+            coin_id.as_deref(),
+        )
+        .await
+    );
+    info!(
+        "JSON import plan: {} new, {} updated, {} unchanged transactions",
+        plan.creates.len(),
+        plan.updates.len(),
+        plan.unchanged.len()
+    );
+    ApiResponse::success(to_api_res!(
+        preview_or_apply(&state, plan, params.dry_run).await
+    ))
 }
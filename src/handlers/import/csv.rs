@@ -0,0 +1,151 @@
+use axum::{
+    Extension,
+    extract::{Multipart, Query, State},
+    http::StatusCode,
+};
+use tracing::info;
+
+use crate::{
+    biz::{
+        csv_import::{parse_csv_import, planned_txs},
+        import_plan::{build_import_plan, file_hash, preview_or_apply},
+    },
+    db::repositories::portfolio::PortfolioRepo,
+    models::{
+        domain::{
+            auth::{Claims, TokenScope},
+            import::ImportSource,
+        },
+        dto::{
+            api_response::ApiResponse,
+            import::{CsvColumnMapping, ImportQueryParams, ImportResponse, ImportRowResponse},
+        },
+    },
+    state::AppState,
+    to_api_res,
+};
+
+/// Imports transactions from a CSV file. The multipart form carries `file`,
+/// `portfolio_id` and `mapping` as a JSON `CsvColumnMapping`. A dry run returns
+/// every row with its validation errors and the planned changes.
+#[utoipa::path(
+    post,
+    path = "/api/imports/csv",
+    params(ImportQueryParams),
+    responses(
+        (status = 200, description = "Success", body = ApiResponse<ImportResponse>),
+        (status = 400, description = "Invalid file or mapping"),
+        (status = 422, description = "Some rows are invalid"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn import_csv(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Query(params): Query<ImportQueryParams>,
+    mut multipart: Multipart,
+) -> ApiResponse<ImportResponse> {
+    to_api_res!(claims.require_scope(TokenScope::Import));
+    let mut contents: Option<String> = None;
+    let mut portfolio_id: Option<String> = None;
+    let mut mapping: Option<CsvColumnMapping> = None;
+    loop {
+        let field = match multipart.next_field().await {
+            Ok(Some(field)) => field,
+            Ok(None) => break,
+            Err(e) => return ApiResponse::error(StatusCode::BAD_REQUEST, e.body_text()),
+        };
+        let name = field.name().unwrap_or_default().to_string();
+        let value = match field.text().await {
+            Ok(value) => value,
+            Err(_) => {
+                return ApiResponse::error(
+                    StatusCode::BAD_REQUEST,
+                    format!("Failed to read {} field", name),
+                );
+            }
+        };
+        match name.as_str() {
+            "file" => contents = Some(value),
+            "portfolio_id" => portfolio_id = Some(value),
+            "mapping" => match serde_json::from_str(&value) {
+                Ok(value) => mapping = Some(value),
+                Err(e) => {
+                    return ApiResponse::error(
+                        StatusCode::BAD_REQUEST,
+                        format!("Invalid column mapping: {}", e),
+                    );
+                }
+            },
+            _ => (),
+        }
+    }
+    let (Some(contents), Some(portfolio_id), Some(mapping)) = (contents, portfolio_id, mapping)
+    else {
+        return ApiResponse::error(
+            StatusCode::BAD_REQUEST,
+            "file, portfolio_id and mapping are required",
+        );
+    };
+    let pfl_id: i64 = match portfolio_id.parse() {
+        Ok(v) => v,
+        Err(_) => {
+            return ApiResponse::error(
+                StatusCode::BAD_REQUEST,
+                format!("Invalid portfolio ID: {}", portfolio_id),
+            );
+        }
+    };
+    let pfl_repo = PortfolioRepo::new(state.pool.clone());
+    let pfl_row = match to_api_res!(pfl_repo.get_one_by_id(pfl_id).await) {
+        None => return ApiResponse::error(StatusCode::NOT_FOUND, "Portfolio not found"),
+        Some(row) => row,
+    };
+    if pfl_row.owner_id != claims.user_id {
+        return ApiResponse::error(
+            StatusCode::FORBIDDEN,
+            "You are not the owner of this portfolio",
+        );
+    }
+
+    let (rows, new_assets) =
+        to_api_res!(parse_csv_import(&state, &pfl_row, &contents, &mapping).await);
+    let invalid_rows = rows.iter().filter(|row| !row.is_valid()).count();
+    info!(
+        "CSV import into portfolio {}: {} rows, {} invalid, dry run {}",
+        pfl_id,
+        rows.len(),
+        invalid_rows,
+        params.dry_run
+    );
+    if !params.dry_run && invalid_rows > 0 {
+        return ApiResponse::error(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            format!(
+                "{} rows are invalid, fix them before importing",
+                invalid_rows
+            ),
+        );
+    }
+    let plan = to_api_res!(
+        build_import_plan(
+            state.pool.clone(),
+            claims.user_id,
+            pfl_id,
+            ImportSource::Csv,
+            file_hash(&contents),
+            new_assets,
+            planned_txs(&rows),
+        )
+        .await
+    );
+    // A plan missing the invalid rows can not be committed
+    let mut response = if invalid_rows > 0 {
+        ImportResponse::from_plan(&plan, true, None)
+    } else {
+        to_api_res!(preview_or_apply(&state, plan, params.dry_run).await)
+    };
+    response.invalid_rows = invalid_rows;
+    response.rows = rows.iter().map(ImportRowResponse::from_row).collect();
+    ApiResponse::success(response)
+}
@@ -0,0 +1,170 @@
+use std::collections::HashMap;
+
+use axum::{
+    Extension,
+    extract::{Multipart, Path, Query, State},
+    http::StatusCode,
+};
+use tracing::info;
+
+use crate::{
+    biz::{
+        csv_import::planned_txs,
+        exchange_import::parse_exchange_import,
+        import_plan::{build_import_plan, file_hash, preview_or_apply},
+    },
+    db::repositories::portfolio::PortfolioRepo,
+    models::{
+        domain::{
+            auth::{Claims, TokenScope},
+            import::Exchange,
+        },
+        dto::{
+            api_response::ApiResponse,
+            import::{ExchangeImportOptions, ImportQueryParams, ImportResponse, ImportRowResponse},
+        },
+    },
+    state::AppState,
+    to_api_res,
+};
+
+/// Imports the trade history CSV export of an exchange (`binance`, `coinbase`
+/// or `kraken`). The multipart form carries `file`, `portfolio_id` and
+/// optionally `symbols`, a JSON object mapping exchange symbols to CoinGecko
+/// coin IDs. Each trade is split into transactions of its base asset, of its
+/// quote asset unless it is a fiat currency, and of the asset its fee was paid
+/// in when that is a third one.
+#[utoipa::path(
+    post,
+    path = "/api/imports/exchanges/{exchange}",
+    params(ImportQueryParams),
+    responses(
+        (status = 200, description = "Success", body = ApiResponse<ImportResponse>),
+        (status = 400, description = "Invalid file"),
+        (status = 422, description = "Some rows are invalid"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn import_exchange_csv(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(exchange): Path<String>,
+    Query(params): Query<ImportQueryParams>,
+    mut multipart: Multipart,
+) -> ApiResponse<ImportResponse> {
+    to_api_res!(claims.require_scope(TokenScope::Import));
+    let exchange: Exchange = match exchange.parse() {
+        Ok(v) => v,
+        Err(_) => {
+            return ApiResponse::error(
+                StatusCode::BAD_REQUEST,
+                format!("Unsupported exchange: {}", exchange),
+            );
+        }
+    };
+    let mut contents: Option<String> = None;
+    let mut portfolio_id: Option<String> = None;
+    let mut options = ExchangeImportOptions::default();
+    loop {
+        let field = match multipart.next_field().await {
+            Ok(Some(field)) => field,
+            Ok(None) => break,
+            Err(e) => return ApiResponse::error(StatusCode::BAD_REQUEST, e.body_text()),
+        };
+        let name = field.name().unwrap_or_default().to_string();
+        let value = match field.text().await {
+            Ok(value) => value,
+            Err(_) => {
+                return ApiResponse::error(
+                    StatusCode::BAD_REQUEST,
+                    format!("Failed to read {} field", name),
+                );
+            }
+        };
+        match name.as_str() {
+            "file" => contents = Some(value),
+            "portfolio_id" => portfolio_id = Some(value),
+            "symbols" if !value.is_empty() => {
+                match serde_json::from_str::<HashMap<String, String>>(&value) {
+                    Ok(symbols) => options.symbols = symbols,
+                    Err(e) => {
+                        return ApiResponse::error(
+                            StatusCode::BAD_REQUEST,
+                            format!("Invalid symbols: {}", e),
+                        );
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+    let (Some(contents), Some(portfolio_id)) = (contents, portfolio_id) else {
+        return ApiResponse::error(
+            StatusCode::BAD_REQUEST,
+            "file and portfolio_id are required",
+        );
+    };
+    let pfl_id: i64 = match portfolio_id.parse() {
+        Ok(v) => v,
+        Err(_) => {
+            return ApiResponse::error(
+                StatusCode::BAD_REQUEST,
+                format!("Invalid portfolio ID: {}", portfolio_id),
+            );
+        }
+    };
+    let pfl_repo = PortfolioRepo::new(state.pool.clone());
+    let pfl_row = match to_api_res!(pfl_repo.get_one_by_id(pfl_id).await) {
+        None => return ApiResponse::error(StatusCode::NOT_FOUND, "Portfolio not found"),
+        Some(row) => row,
+    };
+    if pfl_row.owner_id != claims.user_id {
+        return ApiResponse::error(
+            StatusCode::FORBIDDEN,
+            "You are not the owner of this portfolio",
+        );
+    }
+
+    let (rows, new_assets) =
+        to_api_res!(parse_exchange_import(&state, &pfl_row, exchange, &contents, &options).await);
+    let invalid_rows = rows.iter().filter(|row| !row.is_valid()).count();
+    info!(
+        "{} import into portfolio {}: {} transactions, {} invalid, dry run {}",
+        exchange,
+        pfl_id,
+        rows.len(),
+        invalid_rows,
+        params.dry_run
+    );
+    if !params.dry_run && invalid_rows > 0 {
+        return ApiResponse::error(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            format!(
+                "{} rows are invalid, fix them before importing",
+                invalid_rows
+            ),
+        );
+    }
+    // Trades imported before are matched by their exchange trade ID
+    let plan = to_api_res!(
+        build_import_plan(
+            state.pool.clone(),
+            claims.user_id,
+            pfl_id,
+            exchange.into(),
+            file_hash(&contents),
+            new_assets,
+            planned_txs(&rows),
+        )
+        .await
+    );
+    // A plan missing the invalid rows can not be committed
+    let mut response = if invalid_rows > 0 {
+        ImportResponse::from_plan(&plan, true, None)
+    } else {
+        to_api_res!(preview_or_apply(&state, plan, params.dry_run).await)
+    };
+    response.invalid_rows = invalid_rows;
+    response.rows = rows.iter().map(ImportRowResponse::from_row).collect();
+    ApiResponse::success(response)
+}
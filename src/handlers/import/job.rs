@@ -0,0 +1,316 @@
+use std::{collections::HashMap, convert::Infallible, time::Duration};
+
+use axum::{
+    Extension,
+    extract::{Multipart, Path, State},
+    http::StatusCode,
+    response::{
+        IntoResponse, Response,
+        sse::{Event, KeepAlive, Sse},
+    },
+};
+use futures_util::stream;
+use serde_json::json;
+use tracing::{error, info};
+
+use crate::{
+    db::repositories::{import_job::ImportJobRepo, portfolio::PortfolioRepo},
+    models::{
+        database::import_job::ImportJobRow,
+        domain::{
+            auth::{Claims, TokenScope},
+            import::{ImportJobStatus, ImportSource},
+        },
+        dto::{
+            api_response::ApiResponse,
+            import::{CreateImportJob, CsvColumnMapping, ExchangeImportOptions, ImportJobResponse},
+        },
+    },
+    state::AppState,
+    to_api_res,
+    utils::error::AppError,
+};
+
+// How often a streamed job is checked for progress
+const SSE_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Job of the given ID when it belongs to the user
+async fn find_user_job(
+    state: &AppState,
+    job_id: &str,
+    user_id: i64,
+) -> Result<Option<ImportJobRow>, AppError> {
+    let id: i64 = job_id
+        .parse()
+        .map_err(|_| AppError::InvalidInput(format!("Invalid import job ID: {}", job_id)))?;
+    let job = ImportJobRepo::new(state.pool.clone())
+        .get_one_by_id(id)
+        .await?;
+    Ok(job.filter(|job| job.user_id == user_id))
+}
+
+/// Queues a file to be imported in the background. The multipart form carries
+/// `source` (`coingecko`, `coingecko_json`, `csv`, `binance`, `coinbase` or
+/// `kraken`), `portfolio_id`, `file`, `mapping` as a JSON `CsvColumnMapping` for
+/// CSV files, `coin_id` for JSON exports that do not name their coin and
+/// `symbols` as a JSON object of coin IDs for exchange exports.
+#[utoipa::path(
+    post,
+    path = "/api/imports/jobs",
+    responses(
+        (status = 200, description = "Job queued", body = ApiResponse<ImportJobResponse>),
+        (status = 400, description = "Invalid form"),
+        (status = 404, description = "Portfolio not found"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn create_import_job(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    mut multipart: Multipart,
+) -> ApiResponse<ImportJobResponse> {
+    to_api_res!(claims.require_scope(TokenScope::Import));
+    let mut source: Option<String> = None;
+    let mut contents: Option<String> = None;
+    let mut portfolio_id: Option<String> = None;
+    let mut mapping: Option<String> = None;
+    let mut coin_id: Option<String> = None;
+    let mut symbols: Option<String> = None;
+    loop {
+        let field = match multipart.next_field().await {
+            Ok(Some(field)) => field,
+            Ok(None) => break,
+            Err(e) => return ApiResponse::error(StatusCode::BAD_REQUEST, e.body_text()),
+        };
+        let name = field.name().unwrap_or_default().to_string();
+        let value = match field.text().await {
+            Ok(value) => value,
+            Err(_) => {
+                return ApiResponse::error(
+                    StatusCode::BAD_REQUEST,
+                    format!("Failed to read {} field", name),
+                );
+            }
+        };
+        match name.as_str() {
+            "source" => source = Some(value),
+            "file" if contents.is_some() => {
+                return ApiResponse::error(
+                    StatusCode::BAD_REQUEST,
+                    "A job imports one file, queue a job per file",
+                );
+            }
+            "file" => contents = Some(value),
+            "portfolio_id" => portfolio_id = Some(value),
+            "mapping" => mapping = Some(value),
+            "coin_id" => coin_id = Some(value).filter(|v| !v.is_empty()),
+            "symbols" => symbols = Some(value).filter(|v| !v.is_empty()),
+            _ => (),
+        }
+    }
+    let (Some(source), Some(contents), Some(portfolio_id)) = (source, contents, portfolio_id)
+    else {
+        return ApiResponse::error(
+            StatusCode::BAD_REQUEST,
+            "source, file and portfolio_id are required",
+        );
+    };
+    let source: ImportSource = match source.parse() {
+        Ok(v) => v,
+        Err(_) => {
+            return ApiResponse::error(
+                StatusCode::BAD_REQUEST,
+                format!("Invalid import source: {}", source),
+            );
+        }
+    };
+    let options = match (source, mapping) {
+        (ImportSource::Csv, None) => {
+            return ApiResponse::error(StatusCode::BAD_REQUEST, "mapping is required");
+        }
+        (ImportSource::Csv, Some(mapping)) => {
+            match serde_json::from_str::<CsvColumnMapping>(&mapping) {
+                Ok(mapping) => Some(to_api_res!(
+                    serde_json::to_value(mapping).map_err(AppError::from)
+                )),
+                Err(e) => {
+                    return ApiResponse::error(
+                        StatusCode::BAD_REQUEST,
+                        format!("Invalid column mapping: {}", e),
+                    );
+                }
+            }
+        }
+        (ImportSource::CoingeckoJson, _) => coin_id.map(|coin_id| json!({ "coin_id": coin_id })),
+        (ImportSource::Coingecko, _) => None,
+        (ImportSource::Binance | ImportSource::Coinbase | ImportSource::Kraken, _) => match symbols
+            .as_deref()
+            .map(serde_json::from_str::<HashMap<String, String>>)
+        {
+            None => None,
+            Some(Ok(symbols)) => Some(json!(ExchangeImportOptions { symbols })),
+            Some(Err(e)) => {
+                return ApiResponse::error(
+                    StatusCode::BAD_REQUEST,
+                    format!("Invalid symbols: {}", e),
+                );
+            }
+        },
+    };
+    let pfl_id: i64 = match portfolio_id.parse() {
+        Ok(v) => v,
+        Err(_) => {
+            return ApiResponse::error(
+                StatusCode::BAD_REQUEST,
+                format!("Invalid portfolio ID: {}", portfolio_id),
+            );
+        }
+    };
+    let pfl_repo = PortfolioRepo::new(state.pool.clone());
+    let pfl_row = match to_api_res!(pfl_repo.get_one_by_id(pfl_id).await) {
+        None => return ApiResponse::error(StatusCode::NOT_FOUND, "Portfolio not found"),
+        Some(row) => row,
+    };
+    if pfl_row.owner_id != claims.user_id {
+        return ApiResponse::error(
+            StatusCode::FORBIDDEN,
+            "You are not the owner of this portfolio",
+        );
+    }
+    let job = to_api_res!(
+        ImportJobRepo::new(state.pool.clone())
+            .create_one(CreateImportJob {
+                user_id: claims.user_id,
+                portfolio_id: pfl_id,
+                source,
+                file_contents: contents,
+                options,
+            })
+            .await
+    );
+    info!(
+        "Import job {} queued for portfolio {}, source {}",
+        job.id, pfl_id, source
+    );
+    ApiResponse::success(ImportJobResponse::from_db_row(job))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/imports/jobs/{job_id}",
+    responses(
+        (status = 200, description = "Success", body = ApiResponse<ImportJobResponse>),
+        (status = 404, description = "Import job not found"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn get_import_job(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(job_id): Path<String>,
+) -> ApiResponse<ImportJobResponse> {
+    to_api_res!(claims.require_scope(TokenScope::Import));
+    match to_api_res!(find_user_job(&state, &job_id, claims.user_id).await) {
+        None => ApiResponse::error(StatusCode::NOT_FOUND, "Import job not found"),
+        Some(job) => ApiResponse::success(ImportJobResponse::from_db_row(job)),
+    }
+}
+
+/// Streams the job as a `progress` event every time it changes, until it
+/// succeeds or fails.
+#[utoipa::path(
+    get,
+    path = "/api/imports/jobs/{job_id}/events",
+    responses(
+        (status = 200, description = "Stream of ImportJobResponse progress events", content_type = "text/event-stream"),
+        (status = 404, description = "Import job not found"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn stream_import_job(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(job_id): Path<String>,
+) -> Response {
+    if let Err(e) = claims.require_scope(TokenScope::Import) {
+        return e.into_response();
+    }
+    let job = match find_user_job(&state, &job_id, claims.user_id).await {
+        Ok(Some(job)) => job,
+        Ok(None) => {
+            return ApiResponse::<()>::error(StatusCode::NOT_FOUND, "Import job not found")
+                .into_response();
+        }
+        Err(e) => return ApiResponse::<()>::from(e).into_response(),
+    };
+    // (job ID, last sent payload, whether the job has ended)
+    let initial: (i64, Option<String>, bool) = (job.id, None, false);
+    let events = stream::unfold(initial, move |(id, last_sent, ended)| {
+        let pool = state.pool.clone();
+        async move {
+            if ended {
+                return None;
+            }
+            loop {
+                let job = match ImportJobRepo::new(pool.clone()).get_one_by_id(id).await {
+                    Ok(Some(job)) => job,
+                    Ok(None) => return None,
+                    Err(e) => {
+                        error!("Stream import job {} failed: {:?}", id, e);
+                        return None;
+                    }
+                };
+                let ended = matches!(
+                    job.status.parse(),
+                    Ok(ImportJobStatus::Succeeded | ImportJobStatus::Failed)
+                );
+                let payload =
+                    serde_json::to_string(&ImportJobResponse::from_db_row(job)).unwrap_or_default();
+                if last_sent.as_deref() != Some(payload.as_str()) {
+                    let event = Event::default().event("progress").data(&payload);
+                    return Some((Ok::<_, Infallible>(event), (id, Some(payload), ended)));
+                }
+                tokio::time::sleep(SSE_POLL_INTERVAL).await;
+            }
+        }
+    });
+    Sse::new(events)
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/imports/jobs/{job_id}/retry",
+    responses(
+        (status = 200, description = "Job queued again", body = ApiResponse<ImportJobResponse>),
+        (status = 404, description = "Import job not found"),
+        (status = 409, description = "Import job has not failed"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn retry_import_job(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(job_id): Path<String>,
+) -> ApiResponse<ImportJobResponse> {
+    to_api_res!(claims.require_scope(TokenScope::Import));
+    let job = match to_api_res!(find_user_job(&state, &job_id, claims.user_id).await) {
+        None => return ApiResponse::error(StatusCode::NOT_FOUND, "Import job not found"),
+        Some(job) => job,
+    };
+    match to_api_res!(
+        ImportJobRepo::new(state.pool.clone())
+            .requeue_failed(job.id)
+            .await
+    ) {
+        None => ApiResponse::error(
+            StatusCode::CONFLICT,
+            "Only failed import jobs can be retried",
+        ),
+        Some(job) => {
+            info!("Import job {} queued again", job.id);
+            ApiResponse::success(ImportJobResponse::from_db_row(job))
+        }
+    }
+}
@@ -0,0 +1,70 @@
+use axum::{
+    Extension,
+    extract::{Path, State},
+    http::StatusCode,
+};
+use tracing::{error, info};
+
+use crate::{
+    biz::import_plan::{
+        apply_import_plan, delete_import_plan, get_import_plan, lock_import_plan,
+        unlock_import_plan,
+    },
+    models::{
+        domain::auth::{Claims, TokenScope},
+        dto::{api_response::ApiResponse, import::ImportResponse},
+    },
+    state::AppState,
+    to_api_res,
+};
+
+/// Commits the plan previewed by a dry run import. A plan can only be committed
+/// once, and not after the transactions it changes were edited.
+#[utoipa::path(
+    post,
+    path = "/api/imports/plans/{token}/commit",
+    responses(
+        (status = 200, description = "Success", body = ApiResponse<ImportResponse>),
+        (status = 404, description = "Import plan not found or expired"),
+        (status = 409, description = "Transactions changed since the preview or the plan is being committed"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn commit_import_plan(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(token): Path<String>,
+) -> ApiResponse<ImportResponse> {
+    to_api_res!(claims.require_scope(TokenScope::Import));
+    let plan = match to_api_res!(get_import_plan(&state.redis_conn, &token).await) {
+        Some(plan) if plan.user_id == claims.user_id => plan,
+        _ => {
+            return ApiResponse::error(StatusCode::NOT_FOUND, "Import plan not found or expired");
+        }
+    };
+    if !to_api_res!(lock_import_plan(&state.redis_conn, &token).await) {
+        return ApiResponse::error(
+            StatusCode::CONFLICT,
+            "Import plan is already being committed",
+        );
+    }
+    info!(
+        "Commit {} import plan into portfolio {}",
+        plan.source, plan.portfolio_id
+    );
+    let mut response = ImportResponse::from_plan(&plan, false, None);
+    let batch = match apply_import_plan(&state, plan, None).await {
+        Ok(batch) => batch,
+        Err(e) => {
+            if let Err(e) = unlock_import_plan(&state.redis_conn, &token).await {
+                error!("Unlock import plan failed: {:?}", e);
+            }
+            return ApiResponse::from(e);
+        }
+    };
+    if let Err(e) = delete_import_plan(&state.redis_conn, &token).await {
+        error!("Delete committed import plan failed: {:?}", e);
+    }
+    response.batch_id = Some(batch.id.to_string());
+    ApiResponse::success(response)
+}
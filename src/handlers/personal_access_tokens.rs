@@ -0,0 +1,109 @@
+use axum::{
+    Extension, Json,
+    extract::{Path, State},
+    http::StatusCode,
+};
+use tracing::info;
+
+use crate::biz::personal_access_token::create_personal_access_token;
+use crate::db::repositories::personal_access_token::PersonalAccessTokenRepo;
+use crate::models::domain::auth::Claims;
+use crate::models::dto::{
+    api_response::{ApiResponse, GeneralResponse},
+    personal_access_token::{
+        CreatePersonalAccessTokenRequest, CreatedPersonalAccessTokenResponse,
+        PersonalAccessTokenListResponse, PersonalAccessTokenResponse,
+    },
+};
+use crate::state::AppState;
+use crate::to_api_res;
+
+/// Creates a personal access token for scripts, limited to its scopes. The
+/// token is shown only in this response.
+#[utoipa::path(
+    post,
+    path = "/api/users/me/tokens",
+    request_body = CreatePersonalAccessTokenRequest,
+    responses(
+        (status = 200, description = "Success", body = ApiResponse<CreatedPersonalAccessTokenResponse>),
+        (status = 400, description = "Invalid name, scopes or expiry"),
+        (status = 403, description = "Personal access tokens can not create tokens"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn create_my_token(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Json(req): Json<CreatePersonalAccessTokenRequest>,
+) -> ApiResponse<CreatedPersonalAccessTokenResponse> {
+    to_api_res!(claims.require_session());
+    ApiResponse::success(to_api_res!(
+        create_personal_access_token(state.pool.clone(), claims.user_id, req).await
+    ))
+}
+
+/// Personal access tokens of the user that are not revoked, expired ones included
+#[utoipa::path(
+    get,
+    path = "/api/users/me/tokens",
+    responses(
+        (status = 200, description = "Success", body = ApiResponse<PersonalAccessTokenListResponse>),
+        (status = 403, description = "Personal access tokens can not list tokens"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn get_my_tokens(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+) -> ApiResponse<PersonalAccessTokenListResponse> {
+    to_api_res!(claims.require_session());
+    let rows = to_api_res!(
+        PersonalAccessTokenRepo::new(state.pool.clone())
+            .get_multi_by_user_id(claims.user_id)
+            .await
+    );
+    ApiResponse::success(PersonalAccessTokenListResponse {
+        items: rows
+            .into_iter()
+            .map(PersonalAccessTokenResponse::from_db_row)
+            .collect(),
+    })
+}
+
+/// Revokes a personal access token, it stops working at once
+#[utoipa::path(
+    delete,
+    path = "/api/users/me/tokens/{token_id}",
+    responses(
+        (status = 200, description = "Token revoked", body = ApiResponse<GeneralResponse>),
+        (status = 403, description = "Personal access tokens can not revoke tokens"),
+        (status = 404, description = "Token not found"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn revoke_my_token(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(token_id): Path<String>,
+) -> ApiResponse<GeneralResponse> {
+    to_api_res!(claims.require_session());
+    let Ok(token_id) = token_id.parse::<i64>() else {
+        return ApiResponse::error(
+            StatusCode::BAD_REQUEST,
+            format!("Invalid token ID: {}", token_id),
+        );
+    };
+    let revoked = to_api_res!(
+        PersonalAccessTokenRepo::new(state.pool.clone())
+            .revoke(claims.user_id, token_id)
+            .await
+    );
+    if !revoked {
+        return ApiResponse::error(StatusCode::NOT_FOUND, "Token not found");
+    }
+    info!(
+        "User {} revoked personal access token {}",
+        claims.user_id, token_id
+    );
+    ApiResponse::<GeneralResponse>::success_general_response()
+}
@@ -2,33 +2,59 @@ use std::collections::HashMap;
 
 use axum::{
     Extension, Json,
-    extract::{Path, State},
-    http::StatusCode,
+    extract::{Path, Query, State},
+    http::{StatusCode, header},
+    response::{IntoResponse, Response},
 };
 use rust_decimal::Decimal;
+use strum::IntoEnumIterator;
+use time::{Duration, OffsetDateTime};
 use tracing::info;
 
 use crate::{
+    biz::{
+        export::{build_portfolio_export, export_csv, export_xlsx},
+        fx::{FxRates, ensure_fx_rates, ensure_tx_fx_rates},
+        portfolio_asset::update_portfolio_stats,
+        portfolio_snapshot::{downsample_snapshots, rebuild_portfolio_snapshots},
+        returns::{compute_performance, daily_values},
+    },
     db::repositories::{
         asset::AssetRepo, portfolio::PortfolioRepo, portfolio_asset::PortfolioAssetRepo,
+        portfolio_snapshot::PortfolioSnapshotRepo, realized_gain::RealizedGainRepo,
+        tax_lot::TaxLotRepo, transaction::TransactionRepo, user::UserRepo,
     },
     models::{
         common::currency::Currency,
         database::asset::AssetRow,
-        domain::auth::Claims,
+        domain::auth::{Claims, TokenScope},
         dto::{
             api_response::{ApiResponse, GeneralResponse, IdResponse},
+            export::{ExportFormat, ExportQueryParams, PortfolioExport},
+            pagination::NumberPaginationResponse,
+            performance::{PerformancePeriod, PerformanceQueryParams, PerformanceResponse},
             portfolio::{
                 BriefPortfolioListResponse, BriefPortfolioResponse, CreatePortfolioRequest,
-                PortfolioResponse,
+                PortfolioResponse, UpdatePortfolioRequest,
             },
             portfolio_asset::{CreatePortfolioAssetRequest, PortfolioAssetResponse},
+            portfolio_snapshot::{
+                PortfolioHistoryPoint, PortfolioHistoryQueryParams, PortfolioHistoryResponse,
+            },
+            realized_gain::{
+                RealizedGainFilter, RealizedGainListResponse, RealizedGainQueryParams,
+                RealizedGainResponse, RealizedGainSummaryResponse,
+            },
+            tax_lot::{TaxLotListResponse, TaxLotQueryParams, TaxLotResponse},
         },
     },
     state::AppState,
     to_api_res,
+    utils::error::AppError,
 };
 
+const DEFAULT_HISTORY_DAYS: i64 = 30;
+
 #[utoipa::path(
     post,
     path = "/api/portfolios",
@@ -42,9 +68,27 @@ pub async fn create_portfolio(
     Extension(claims): Extension<Claims>,
     Json(req): Json<CreatePortfolioRequest>,
 ) -> ApiResponse<IdResponse> {
+    to_api_res!(claims.require_session());
     info!("Create portfolio with body request {:?}", req);
+    let base_currency = match req.base_currency {
+        Some(currency) => currency,
+        None => {
+            let user_repo = UserRepo::new(state.pool.clone());
+            match to_api_res!(user_repo.get_by_id(claims.user_id).await) {
+                Some(user) => user.base_currency.parse().unwrap_or_default(),
+                None => Currency::default(),
+            }
+        }
+    };
     let portfolio_repo = PortfolioRepo::new(state.pool.clone());
-    let new_portfolio = portfolio_repo.create_one(claims.user_id, &req.name).await;
+    let new_portfolio = portfolio_repo
+        .create_one(
+            claims.user_id,
+            &req.name,
+            req.cost_basis_method.unwrap_or_default(),
+            base_currency,
+        )
+        .await;
     if let Err(e) = new_portfolio {
         return ApiResponse::from(e);
     }
@@ -69,6 +113,7 @@ pub async fn create_portfolio_asset(
     Path(portfolio_id): Path<String>,
     Json(req): Json<CreatePortfolioAssetRequest>,
 ) -> ApiResponse<GeneralResponse> {
+    to_api_res!(claims.require_scope(TokenScope::WriteTransactions));
     let pfl_repo = PortfolioRepo::new(state.pool.clone());
     let pfl_id: i64 = portfolio_id.parse().unwrap();
     let pfl_rs = pfl_repo.get_one_by_id(pfl_id).await;
@@ -110,6 +155,80 @@ pub async fn create_portfolio_asset(
     return ApiResponse::<GeneralResponse>::success_general_response();
 }
 
+#[utoipa::path(
+    patch,
+    path = "/api/portfolios/{portfolio_id}",
+    request_body = UpdatePortfolioRequest,
+    responses(
+        (status = 200, description = "Success", body = ApiResponse<GeneralResponse>),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn update_portfolio(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(portfolio_id): Path<String>,
+    Json(req): Json<UpdatePortfolioRequest>,
+) -> ApiResponse<GeneralResponse> {
+    to_api_res!(claims.require_session());
+    info!(
+        "Update portfolio {} with body request {:?}",
+        portfolio_id, req
+    );
+    let pfl_id: i64 = match portfolio_id.parse() {
+        Ok(v) => v,
+        Err(_) => {
+            return ApiResponse::error(
+                StatusCode::BAD_REQUEST,
+                format!("Invalid portfolio ID: {}", portfolio_id),
+            );
+        }
+    };
+    let pfl_repo = PortfolioRepo::new(state.pool.clone());
+    let pfl_row = match to_api_res!(pfl_repo.get_one_by_id(pfl_id).await) {
+        None => return ApiResponse::error(StatusCode::NOT_FOUND, "Portfolio not found"),
+        Some(row) => row,
+    };
+    if pfl_row.owner_id != claims.user_id {
+        return ApiResponse::error(
+            StatusCode::FORBIDDEN,
+            "You are not the owner of this portfolio",
+        );
+    }
+    // Realized gains depend on how disposals are matched and on the currency
+    // they are reported in, so rebuild them
+    let method_changed = req
+        .cost_basis_method
+        .is_some_and(|m| m.to_string() != pfl_row.cost_basis_method);
+    let currency_changed = req
+        .base_currency
+        .is_some_and(|c| c.to_string() != pfl_row.base_currency);
+    if currency_changed && let Some(base_currency) = req.base_currency {
+        // Every transaction is converted to the new currency at its own day
+        let tx_rows = to_api_res!(
+            TransactionRepo::new(state.pool.clone())
+                .get_all_txs_by_portfolio(pfl_id)
+                .await
+        );
+        let mut fx_days = Vec::with_capacity(tx_rows.len());
+        for row in tx_rows.iter() {
+            let currency: Currency = to_api_res!(row.currency.parse().map_err(AppError::from));
+            fx_days.push((currency, row.executed_at.date()));
+        }
+        to_api_res!(ensure_tx_fx_rates(&state, base_currency, &fx_days).await);
+    }
+    to_api_res!(pfl_repo.update_one(pfl_id, &req).await);
+
+    if method_changed || currency_changed {
+        to_api_res!(update_portfolio_stats(state.pool.clone(), pfl_id).await);
+    }
+    // Past snapshots were valued in the previous currency
+    if currency_changed && let Some(pfl_row) = to_api_res!(pfl_repo.get_one_by_id(pfl_id).await) {
+        to_api_res!(rebuild_portfolio_snapshots(state.pool.clone(), &pfl_row).await);
+    }
+    ApiResponse::<GeneralResponse>::success_general_response()
+}
+
 #[utoipa::path(
     get,
     path = "/api/portfolios/{portfolio_id}",
@@ -120,8 +239,10 @@ pub async fn create_portfolio_asset(
 )]
 pub async fn get_portfolio_by_id(
     State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
     Path(portfolio_id): Path<String>,
 ) -> ApiResponse<PortfolioResponse> {
+    to_api_res!(claims.require_scope(TokenScope::ReadPortfolios));
     info!("Get portfolio with id {}", portfolio_id);
     let pfl_repo = PortfolioRepo::new(state.pool.clone());
     let pfl_rs = pfl_repo.get_one_by_id(portfolio_id.parse().unwrap()).await;
@@ -140,30 +261,48 @@ pub async fn get_portfolio_by_id(
     let asset_id_to_row: HashMap<String, AssetRow> =
         asset_rows.into_iter().map(|a| (a.id.clone(), a)).collect();
 
+    let external_ids: Vec<String> = asset_id_to_row
+        .values()
+        .map(|a| a.external_id.clone())
+        .collect();
+    let prices = to_api_res!(
+        state
+            .clients
+            .prices
+            .get_spot_prices(&external_ids, &Currency::USD.to_string())
+            .await
+    );
+
+    // Spot prices are quoted in USD, stats are in the base currency
+    let base_currency: Currency = pfl_row.base_currency.parse().unwrap_or_default();
+    let today = OffsetDateTime::now_utc().date();
+    to_api_res!(ensure_fx_rates(&state, &[base_currency], &[today]).await);
+    let fx_rates = to_api_res!(FxRates::load(state.pool.clone(), &[base_currency]).await);
+
     let mut assets_res: Vec<PortfolioAssetResponse> = Vec::new();
     for pa_row in pa_rows {
         let asset_row = asset_id_to_row.get(&pa_row.asset_id).unwrap();
-        let coin_data = to_api_res!(
-            state
-                .clients
-                .coingecko
-                .get_coin_data(&asset_row.external_id)
-                .await
-        );
-        let current_price: Decimal = coin_data
-            .get_current_price(Currency::USD)
-            .map(Decimal::from_f64_retain)
-            .flatten()
-            .unwrap_or(Decimal::ZERO);
+        let (current_price, price_stale) = match prices.get(&asset_row.external_id) {
+            Some(spot) => (
+                to_api_res!(fx_rates.convert(spot.price, Currency::USD, base_currency, today)),
+                spot.stale,
+            ),
+            None => (Decimal::ZERO, false),
+        };
         assets_res.push(PortfolioAssetResponse::from_db_row(
             asset_row,
             &pa_row,
             current_price,
+            price_stale,
         ));
     }
+    let prices_stale = assets_res.iter().any(|a| a.stats.price_stale);
     ApiResponse::success(PortfolioResponse {
         id: pfl_row.id.to_string(),
         name: pfl_row.name,
+        cost_basis_method: pfl_row.cost_basis_method.parse().unwrap_or_default(),
+        base_currency,
+        prices_stale,
         assets: assets_res,
     })
 }
@@ -180,6 +319,7 @@ pub async fn get_my_portfolios(
     State(state): State<AppState>,
     Extension(claims): Extension<Claims>,
 ) -> ApiResponse<BriefPortfolioListResponse> {
+    to_api_res!(claims.require_scope(TokenScope::ReadPortfolios));
     let portfolio_repo = PortfolioRepo::new(state.pool.clone());
     let portfolios = to_api_res!(portfolio_repo.get_multi_by_owner_id(claims.user_id).await);
     ApiResponse::success(BriefPortfolioListResponse {
@@ -189,3 +329,336 @@ pub async fn get_my_portfolios(
             .collect(),
     })
 }
+
+#[utoipa::path(
+    get,
+    path = "/api/portfolios/{portfolio_id}/realized",
+    params(RealizedGainQueryParams),
+    responses(
+        (status = 200, description = "Success", body = ApiResponse<RealizedGainListResponse>),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn get_realized_gains(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(portfolio_id): Path<String>,
+    Query(params): Query<RealizedGainQueryParams>,
+) -> ApiResponse<RealizedGainListResponse> {
+    to_api_res!(claims.require_scope(TokenScope::ReadPortfolios));
+    let pfl_id: i64 = match portfolio_id.parse() {
+        Ok(v) => v,
+        Err(_) => {
+            return ApiResponse::error(
+                StatusCode::BAD_REQUEST,
+                format!("Invalid portfolio ID: {}", portfolio_id),
+            );
+        }
+    };
+    let pfl_repo = PortfolioRepo::new(state.pool.clone());
+    match to_api_res!(pfl_repo.get_one_by_id(pfl_id).await) {
+        None => return ApiResponse::error(StatusCode::NOT_FOUND, "Portfolio not found"),
+        Some(row) => {
+            if row.owner_id != claims.user_id {
+                return ApiResponse::error(
+                    StatusCode::FORBIDDEN,
+                    "You are not the owner of this portfolio",
+                );
+            }
+        }
+    }
+
+    let filter = RealizedGainFilter::from_params(&params);
+    let rg_repo = RealizedGainRepo::new(state.pool.clone());
+    let rg_rows = to_api_res!(
+        rg_repo
+            .get_multi_by_portfolio_with_paging(pfl_id, &filter, params.page, params.limit)
+            .await
+    );
+    let summary = to_api_res!(rg_repo.summarize_by_portfolio(pfl_id, &filter).await);
+    ApiResponse::success(RealizedGainListResponse {
+        number_pagination: NumberPaginationResponse::new(
+            params.page,
+            params.limit,
+            summary.total_items as u32,
+        ),
+        summary: RealizedGainSummaryResponse::from_db_row(&summary),
+        items: rg_rows
+            .into_iter()
+            .map(RealizedGainResponse::from_db_row)
+            .collect(),
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/portfolios/{portfolio_id}/lots",
+    params(TaxLotQueryParams),
+    responses(
+        (status = 200, description = "Success", body = ApiResponse<TaxLotListResponse>),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn get_open_lots(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(portfolio_id): Path<String>,
+    Query(params): Query<TaxLotQueryParams>,
+) -> ApiResponse<TaxLotListResponse> {
+    to_api_res!(claims.require_scope(TokenScope::ReadPortfolios));
+    let pfl_id: i64 = match portfolio_id.parse() {
+        Ok(v) => v,
+        Err(_) => {
+            return ApiResponse::error(
+                StatusCode::BAD_REQUEST,
+                format!("Invalid portfolio ID: {}", portfolio_id),
+            );
+        }
+    };
+    let pfl_repo = PortfolioRepo::new(state.pool.clone());
+    match to_api_res!(pfl_repo.get_one_by_id(pfl_id).await) {
+        None => return ApiResponse::error(StatusCode::NOT_FOUND, "Portfolio not found"),
+        Some(row) => {
+            if row.owner_id != claims.user_id {
+                return ApiResponse::error(
+                    StatusCode::FORBIDDEN,
+                    "You are not the owner of this portfolio",
+                );
+            }
+        }
+    }
+
+    let lot_rows = to_api_res!(
+        TaxLotRepo::new(state.pool.clone())
+            .get_multi_by_portfolio(pfl_id, params.asset_id.as_deref())
+            .await
+    );
+    ApiResponse::success(TaxLotListResponse {
+        items: lot_rows
+            .into_iter()
+            .map(TaxLotResponse::from_db_row)
+            .collect(),
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/portfolios/{portfolio_id}/history",
+    params(PortfolioHistoryQueryParams),
+    responses(
+        (status = 200, description = "Success", body = ApiResponse<PortfolioHistoryResponse>),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn get_portfolio_history(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(portfolio_id): Path<String>,
+    Query(params): Query<PortfolioHistoryQueryParams>,
+) -> ApiResponse<PortfolioHistoryResponse> {
+    to_api_res!(claims.require_scope(TokenScope::ReadPortfolios));
+    let pfl_id: i64 = match portfolio_id.parse() {
+        Ok(v) => v,
+        Err(_) => {
+            return ApiResponse::error(
+                StatusCode::BAD_REQUEST,
+                format!("Invalid portfolio ID: {}", portfolio_id),
+            );
+        }
+    };
+    let pfl_repo = PortfolioRepo::new(state.pool.clone());
+    let pfl_row = match to_api_res!(pfl_repo.get_one_by_id(pfl_id).await) {
+        None => return ApiResponse::error(StatusCode::NOT_FOUND, "Portfolio not found"),
+        Some(row) => row,
+    };
+    if pfl_row.owner_id != claims.user_id {
+        return ApiResponse::error(
+            StatusCode::FORBIDDEN,
+            "You are not the owner of this portfolio",
+        );
+    }
+
+    let to = params.to.unwrap_or(OffsetDateTime::now_utc()).date();
+    let from = params
+        .from
+        .map(|from| from.date())
+        .unwrap_or(to - Duration::days(DEFAULT_HISTORY_DAYS));
+    if from > to {
+        return ApiResponse::error(StatusCode::BAD_REQUEST, "from must be before to");
+    }
+    let snapshot_repo = PortfolioSnapshotRepo::new(state.pool.clone());
+    let rows = to_api_res!(snapshot_repo.get_multi_in_range(pfl_id, from, to).await);
+    let points = downsample_snapshots(rows, params.interval)
+        .iter()
+        .map(PortfolioHistoryPoint::from_db_row)
+        .collect();
+    ApiResponse::success(PortfolioHistoryResponse {
+        portfolio_id: pfl_id.to_string(),
+        currency: pfl_row.base_currency,
+        interval: params.interval,
+        points,
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/portfolios/{portfolio_id}/performance",
+    params(PerformanceQueryParams),
+    responses(
+        (status = 200, description = "Success", body = ApiResponse<PerformanceResponse>),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn get_portfolio_performance(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(portfolio_id): Path<String>,
+    Query(params): Query<PerformanceQueryParams>,
+) -> ApiResponse<PerformanceResponse> {
+    to_api_res!(claims.require_scope(TokenScope::ReadPortfolios));
+    let pfl_id: i64 = match portfolio_id.parse() {
+        Ok(v) => v,
+        Err(_) => {
+            return ApiResponse::error(
+                StatusCode::BAD_REQUEST,
+                format!("Invalid portfolio ID: {}", portfolio_id),
+            );
+        }
+    };
+    let pfl_repo = PortfolioRepo::new(state.pool.clone());
+    let pfl_row = match to_api_res!(pfl_repo.get_one_by_id(pfl_id).await) {
+        None => return ApiResponse::error(StatusCode::NOT_FOUND, "Portfolio not found"),
+        Some(row) => row,
+    };
+    if pfl_row.owner_id != claims.user_id {
+        return ApiResponse::error(
+            StatusCode::FORBIDDEN,
+            "You are not the owner of this portfolio",
+        );
+    }
+    if let Some(ref asset_id) = params.asset_id {
+        let pa_repo = PortfolioAssetRepo::new(state.pool.clone());
+        let pa_row = to_api_res!(
+            pa_repo
+                .get_one_by_portfolio_id_and_asset_id(pfl_id, asset_id)
+                .await
+        );
+        if pa_row.is_none() {
+            return ApiResponse::error(StatusCode::NOT_FOUND, "Portfolio asset not found");
+        }
+    }
+
+    let base_currency: Currency = pfl_row.base_currency.parse().unwrap_or_default();
+    let today = OffsetDateTime::now_utc().date();
+    to_api_res!(ensure_fx_rates(&state, &[base_currency], &[today]).await);
+    let values = to_api_res!(
+        daily_values(
+            state.pool.clone(),
+            pfl_id,
+            params.asset_id.as_deref(),
+            base_currency,
+            today,
+        )
+        .await
+    );
+    let periods: Vec<PerformancePeriod> = match params.period {
+        Some(period) => vec![period],
+        None => PerformancePeriod::iter().collect(),
+    };
+    ApiResponse::success(PerformanceResponse {
+        portfolio_id: pfl_id.to_string(),
+        asset_id: params.asset_id,
+        currency: base_currency.to_string(),
+        items: compute_performance(&values, &periods),
+    })
+}
+
+/// Downloads every transaction of the portfolio with the metadata of its asset,
+/// and the holdings summary. JSON keeps decimals as strings and times in
+/// RFC 3339, as the API does, so nothing is lost. CSV holds one `sheet`.
+#[utoipa::path(
+    get,
+    path = "/api/portfolios/{portfolio_id}/export",
+    params(ExportQueryParams),
+    responses(
+        (status = 200, description = "JSON export", body = PortfolioExport, content_type = "application/json"),
+        (status = 200, description = "CSV export", content_type = "text/csv"),
+        (status = 200, description = "XLSX export", content_type = "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"),
+        (status = 403, description = "Not the owner of the portfolio"),
+        (status = 404, description = "Portfolio not found"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn export_portfolio(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(portfolio_id): Path<String>,
+    Query(params): Query<ExportQueryParams>,
+) -> Response {
+    if let Err(e) = claims.require_scope(TokenScope::ReadPortfolios) {
+        return e.into_response();
+    }
+    let pfl_id: i64 = match portfolio_id.parse() {
+        Ok(v) => v,
+        Err(_) => {
+            return ApiResponse::<()>::error(
+                StatusCode::BAD_REQUEST,
+                format!("Invalid portfolio ID: {}", portfolio_id),
+            )
+            .into_response();
+        }
+    };
+    let pfl_repo = PortfolioRepo::new(state.pool.clone());
+    let pfl_row = match pfl_repo.get_one_by_id(pfl_id).await {
+        Ok(Some(row)) => row,
+        Ok(None) => {
+            return ApiResponse::<()>::error(StatusCode::NOT_FOUND, "Portfolio not found")
+                .into_response();
+        }
+        Err(e) => return e.into_response(),
+    };
+    if pfl_row.owner_id != claims.user_id {
+        return ApiResponse::<()>::error(
+            StatusCode::FORBIDDEN,
+            "You are not the owner of this portfolio",
+        )
+        .into_response();
+    }
+
+    let export = match build_portfolio_export(state.pool.clone(), &pfl_row).await {
+        Ok(export) => export,
+        Err(e) => return e.into_response(),
+    };
+    info!(
+        "Export portfolio {} as {}: {} transactions",
+        pfl_id,
+        params.format,
+        export.transactions.len()
+    );
+    let body = match params.format {
+        ExportFormat::Json => serde_json::to_vec_pretty(&export).map_err(AppError::from),
+        ExportFormat::Csv => export_csv(&export, params.sheet).map(String::into_bytes),
+        ExportFormat::Xlsx => export_xlsx(&export),
+    };
+    let body = match body {
+        Ok(body) => body,
+        Err(e) => return e.into_response(),
+    };
+    let content_type = match params.format {
+        ExportFormat::Json => "application/json",
+        ExportFormat::Csv => "text/csv; charset=utf-8",
+        ExportFormat::Xlsx => "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+    };
+    let disposition = format!(
+        "attachment; filename=\"portfolio-{}.{}\"",
+        pfl_id, params.format
+    );
+    (
+        [
+            (header::CONTENT_TYPE, content_type.to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        body,
+    )
+        .into_response()
+}
@@ -0,0 +1,128 @@
+use axum::{
+    Extension,
+    extract::{Query, State},
+    http::{StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use tracing::info;
+
+use crate::{
+    biz::tax_report::{build_tax_report, tax_report_csv},
+    db::repositories::portfolio::PortfolioRepo,
+    models::{
+        domain::auth::{Claims, TokenScope},
+        dto::{
+            api_response::ApiResponse,
+            tax_report::{TaxReportFormat, TaxReportQueryParams, TaxReportResponse},
+        },
+    },
+    state::AppState,
+    utils::error::AppError,
+};
+
+/// Downloads the realized gains of the disposals made in a calendar year, from
+/// the lot matching of every portfolio of the user or of `portfolio_id`. A lot
+/// held longer than `[tax_report] long_term_threshold_days` is a long-term gain.
+/// CSV follows the columns of Form 8949.
+#[utoipa::path(
+    get,
+    path = "/api/reports/tax",
+    params(TaxReportQueryParams),
+    responses(
+        (status = 200, description = "JSON report", body = TaxReportResponse, content_type = "application/json"),
+        (status = 200, description = "CSV report", content_type = "text/csv"),
+        (status = 400, description = "Invalid year or portfolio ID"),
+        (status = 403, description = "Not the owner of the portfolio"),
+        (status = 404, description = "Portfolio not found"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn get_tax_report(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Query(params): Query<TaxReportQueryParams>,
+) -> Response {
+    if let Err(e) = claims.require_scope(TokenScope::ReadPortfolios) {
+        return e.into_response();
+    }
+    let pfl_id = match params.portfolio_id.as_deref() {
+        None => None,
+        Some(portfolio_id) => match portfolio_id.parse::<i64>() {
+            Ok(v) => Some(v),
+            Err(_) => {
+                return ApiResponse::<()>::error(
+                    StatusCode::BAD_REQUEST,
+                    format!("Invalid portfolio ID: {}", portfolio_id),
+                )
+                .into_response();
+            }
+        },
+    };
+    if let Some(pfl_id) = pfl_id {
+        match PortfolioRepo::new(state.pool.clone())
+            .get_one_by_id(pfl_id)
+            .await
+        {
+            Ok(Some(row)) if row.owner_id != claims.user_id => {
+                return ApiResponse::<()>::error(
+                    StatusCode::FORBIDDEN,
+                    "You are not the owner of this portfolio",
+                )
+                .into_response();
+            }
+            Ok(Some(_)) => (),
+            Ok(None) => {
+                return ApiResponse::<()>::error(StatusCode::NOT_FOUND, "Portfolio not found")
+                    .into_response();
+            }
+            Err(e) => return e.into_response(),
+        }
+    }
+
+    let report = match build_tax_report(
+        state.pool.clone(),
+        claims.user_id,
+        pfl_id,
+        params.year,
+        state.tax_report.long_term_threshold_days,
+    )
+    .await
+    {
+        Ok(report) => report,
+        Err(e) => return e.into_response(),
+    };
+    info!(
+        "Tax report {} of user {} as {}: {} disposals",
+        params.year,
+        claims.user_id,
+        params.format,
+        report.items.len()
+    );
+    let body = match params.format {
+        TaxReportFormat::Json => serde_json::to_vec_pretty(&report).map_err(AppError::from),
+        TaxReportFormat::Csv => tax_report_csv(&report).map(String::into_bytes),
+    };
+    let body = match body {
+        Ok(body) => body,
+        Err(e) => return e.into_response(),
+    };
+    let content_type = match params.format {
+        TaxReportFormat::Json => "application/json",
+        TaxReportFormat::Csv => "text/csv; charset=utf-8",
+    };
+    let filename = match pfl_id {
+        Some(pfl_id) => format!("tax-report-{}-{}.{}", params.year, pfl_id, params.format),
+        None => format!("tax-report-{}.{}", params.year, params.format),
+    };
+    (
+        [
+            (header::CONTENT_TYPE, content_type.to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", filename),
+            ),
+        ],
+        body,
+    )
+        .into_response()
+}
@@ -9,10 +9,13 @@ use crate::models::dto::pagination::NumberPaginationResponse;
 use crate::models::dto::transaction::{
     CreateMultiTransaction, CreateTransactionRequest, TransactionResponse,
 };
-use crate::models::{domain::auth::Claims, dto::transaction::UpdateTransaction};
+use crate::models::{
+    domain::auth::{Claims, TokenScope},
+    dto::transaction::UpdateTransaction,
+};
 use crate::models::{domain::transaction::BaseTransactionInfo, dto::api_response::GeneralResponse};
 use crate::{
-    biz::portfolio_asset::update_portfolio_asset_stat,
+    biz::{fx::ensure_portfolio_fx_rates, portfolio_asset::update_portfolio_asset_stat},
     db::repositories::transaction::TransactionRepo,
 };
 use crate::{
@@ -27,6 +30,7 @@ use crate::{
     state::AppState,
     to_api_res,
 };
+use time::UtcOffset;
 use tracing::info;
 
 #[utoipa::path(
@@ -42,6 +46,7 @@ pub async fn create_transaction(
     Extension(claims): Extension<Claims>,
     Json(req): Json<CreateTransactionRequest>,
 ) -> ApiResponse<IdResponse> {
+    to_api_res!(claims.require_scope(TokenScope::WriteTransactions));
     info!("Create transaction with body request {:?}", req);
     let pa_repo = PortfolioAssetRepo::new(state.pool.clone());
     let pfl_id: i64 = match req.portfolio_id.parse() {
@@ -76,12 +81,20 @@ pub async fn create_transaction(
 
     let base_tx = to_api_res!(BaseTransactionInfo::from_create_tx_req(req));
     let tx_id = base_tx.id.unwrap();
+    let fx_days = [(
+        base_tx.currency,
+        base_tx.executed_at.to_offset(UtcOffset::UTC).date(),
+    )];
+    // A transaction that can not be converted to the base currency is refused
+    // before it is written
+    to_api_res!(ensure_portfolio_fx_rates(&state, pfl_id, &fx_days).await);
     let tx_repo = TransactionRepo::new(state.pool.clone());
     to_api_res!(
         tx_repo
             .create_multi_txs(CreateMultiTransaction {
                 portfolio_id: pfl_id,
                 asset_id: asset_id.clone(),
+                import_batch_id: None,
                 transactions: vec![base_tx]
             })
             .await
@@ -105,9 +118,10 @@ pub async fn create_transaction(
 )]
 pub async fn get_transactions(
     State(state): State<AppState>,
-    Extension(_claims): Extension<Claims>,
+    Extension(claims): Extension<Claims>,
     Query(params): Query<TransactionQueryParams>,
 ) -> ApiResponse<TransactionListResponse> {
+    to_api_res!(claims.require_scope(TokenScope::ReadPortfolios));
     let pa_repo = PortfolioAssetRepo::new(state.pool.clone());
     let pfl_id: i64 = params.portfolio_id.parse().unwrap();
     let existing_pa = to_api_res!(
@@ -159,9 +173,10 @@ pub async fn get_transactions(
 )]
 pub async fn get_detail_transaction(
     State(state): State<AppState>,
-    Extension(_claims): Extension<Claims>,
+    Extension(claims): Extension<Claims>,
     Path(tx_id): Path<String>,
 ) -> ApiResponse<TransactionResponse> {
+    to_api_res!(claims.require_scope(TokenScope::ReadPortfolios));
     let tx_id: i64 = match tx_id.parse() {
         Ok(id) => id,
         Err(_) => {
@@ -193,6 +208,7 @@ pub async fn update_transaction(
     Path(tx_id): Path<String>,
     Json(req): Json<UpdateTransactionRequest>,
 ) -> ApiResponse<GeneralResponse> {
+    to_api_res!(claims.require_scope(TokenScope::WriteTransactions));
     let tx_id: i64 = match tx_id.parse() {
         Ok(id) => id,
         Err(_) => {
@@ -220,6 +236,15 @@ pub async fn update_transaction(
         None => return ApiResponse::error(StatusCode::NOT_FOUND, "Portfolio not found"),
     };
 
+    let fx_days = [(
+        req.currency
+            .unwrap_or_else(|| tx_row.currency.parse().unwrap_or_default()),
+        req.executed_at
+            .unwrap_or(tx_row.executed_at)
+            .to_offset(UtcOffset::UTC)
+            .date(),
+    )];
+    to_api_res!(ensure_portfolio_fx_rates(&state, tx_row.portfolio_id, &fx_days).await);
     to_api_res!(
         tx_repo
             .update_tx_by_id(tx_id, UpdateTransaction::from_req(req))
@@ -0,0 +1,117 @@
+use axum::{Extension, Json, extract::State};
+
+use crate::biz::two_factor::{
+    confirm_totp, disable_totp, enroll_totp, get_two_factor_status, regenerate_recovery_codes,
+};
+use crate::models::domain::auth::Claims;
+use crate::models::dto::{
+    api_response::{ApiResponse, GeneralResponse},
+    two_factor::{
+        RecoveryCodesResponse, TotpCodeRequest, TotpEnrollResponse, TwoFactorStatusResponse,
+    },
+};
+use crate::state::AppState;
+use crate::to_api_res;
+
+#[utoipa::path(
+    get,
+    path = "/api/users/me/2fa",
+    responses(
+        (status = 200, description = "Success", body = ApiResponse<TwoFactorStatusResponse>),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn get_my_two_factor(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+) -> ApiResponse<TwoFactorStatusResponse> {
+    to_api_res!(claims.require_session());
+    ApiResponse::success(to_api_res!(
+        get_two_factor_status(&state, claims.user_id).await
+    ))
+}
+
+/// Generates a TOTP secret to add to an authenticator app. Two-factor
+/// authentication is enabled once a code of it is confirmed.
+#[utoipa::path(
+    post,
+    path = "/api/users/me/2fa/totp/enroll",
+    responses(
+        (status = 200, description = "Success", body = ApiResponse<TotpEnrollResponse>),
+        (status = 409, description = "Two-factor authentication already enabled"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn enroll_my_totp(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+) -> ApiResponse<TotpEnrollResponse> {
+    to_api_res!(claims.require_session());
+    ApiResponse::success(to_api_res!(enroll_totp(&state, claims.user_id).await))
+}
+
+/// Enables two-factor authentication with a code of the enrolled secret. The
+/// recovery codes are returned only here.
+#[utoipa::path(
+    post,
+    path = "/api/users/me/2fa/totp/confirm",
+    request_body = TotpCodeRequest,
+    responses(
+        (status = 200, description = "Success", body = ApiResponse<RecoveryCodesResponse>),
+        (status = 400, description = "Invalid code or no enrollment"),
+        (status = 409, description = "Two-factor authentication already enabled"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn confirm_my_totp(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Json(req): Json<TotpCodeRequest>,
+) -> ApiResponse<RecoveryCodesResponse> {
+    to_api_res!(claims.require_session());
+    ApiResponse::success(to_api_res!(
+        confirm_totp(&state, claims.user_id, &req.code).await
+    ))
+}
+
+/// Replaces the recovery codes, the previous ones stop working
+#[utoipa::path(
+    post,
+    path = "/api/users/me/2fa/recovery_codes",
+    request_body = TotpCodeRequest,
+    responses(
+        (status = 200, description = "Success", body = ApiResponse<RecoveryCodesResponse>),
+        (status = 400, description = "Invalid code or two-factor authentication not enabled"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn regenerate_my_recovery_codes(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Json(req): Json<TotpCodeRequest>,
+) -> ApiResponse<RecoveryCodesResponse> {
+    to_api_res!(claims.require_session());
+    ApiResponse::success(to_api_res!(
+        regenerate_recovery_codes(&state, claims.user_id, &req.code).await
+    ))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/users/me/2fa/disable",
+    request_body = TotpCodeRequest,
+    responses(
+        (status = 200, description = "Two-factor authentication disabled", body = ApiResponse<GeneralResponse>),
+        (status = 400, description = "Invalid code or two-factor authentication not enabled"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn disable_my_totp(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Json(req): Json<TotpCodeRequest>,
+) -> ApiResponse<GeneralResponse> {
+    to_api_res!(claims.require_session());
+    to_api_res!(disable_totp(&state, claims.user_id, &req.code).await);
+    ApiResponse::<GeneralResponse>::success_general_response()
+}
@@ -1,13 +1,30 @@
-use axum::Extension;
+use axum::{Extension, Json};
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
+    extract::{Multipart, Path, Query, State},
+    http::{StatusCode, header},
+    response::{IntoResponse, Response},
 };
 use tracing::info;
 
-use crate::models::domain::auth::Claims;
-use crate::models::dto::{api_response::ApiResponse, user::UserMeResponse};
+use crate::biz::{
+    account::{change_password, delete_account, update_profile},
+    auth::{revoke_all_sessions, revoke_session},
+    backup::{build_account_backup, restore_account_backup},
+};
+use crate::db::repositories::user_session::UserSessionRepo;
+use crate::models::domain::auth::{Claims, TokenScope};
+use crate::models::dto::{
+    api_response::{ApiResponse, GeneralResponse},
+    backup::{AccountBackup, RestoreQueryParams, RestoreResponse},
+    user::{
+        ChangePasswordRequest, DeleteAccountRequest, DeleteAccountResponse, UpdateUserRequest,
+        UserMeResponse,
+    },
+    user_session::{RevokeSessionsResponse, UserSessionListResponse, UserSessionResponse},
+};
 use crate::state::AppState;
+use crate::to_api_res;
+use crate::utils::error::AppError;
 use crate::{db::repositories::user::UserRepo, models::dto::user::UserResponse};
 
 #[utoipa::path(
@@ -51,16 +68,259 @@ pub async fn get_user_me(
     let user = user_repo.get_by_id(claims.user_id).await;
     match user {
         Ok(Some(user)) => {
-            return ApiResponse::success(UserMeResponse {
-                id: user.id.to_string(),
-                status: user.status.parse().unwrap(),
-                email: user.email,
-                phone_number: user.phone_number,
-                name: user.name,
-                created_at: user.created_at,
-            });
+            return ApiResponse::success(UserMeResponse::from_db_row(user));
         }
         Ok(None) => return ApiResponse::error(StatusCode::NOT_FOUND, "User not found"),
         Err(e) => return ApiResponse::from(e),
     }
 }
+
+/// Changes the name and phone number of the user, omitted fields are kept
+#[utoipa::path(
+    patch,
+    path = "/api/users/me",
+    request_body = UpdateUserRequest,
+    responses(
+        (status = 200, description = "Profile updated", body = ApiResponse<UserMeResponse>),
+        (status = 400, description = "Invalid name or phone number"),
+        (status = 409, description = "Phone number is already in use"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn update_user_me(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Json(req): Json<UpdateUserRequest>,
+) -> ApiResponse<UserMeResponse> {
+    to_api_res!(claims.require_session());
+    ApiResponse::success(to_api_res!(
+        update_profile(&state, claims.user_id, req).await
+    ))
+}
+
+/// Sets a new password after checking the current one. Every other session is
+/// logged out.
+#[utoipa::path(
+    post,
+    path = "/api/users/me/password",
+    request_body = ChangePasswordRequest,
+    responses(
+        (status = 200, description = "Password changed", body = ApiResponse<GeneralResponse>),
+        (status = 400, description = "Current password is incorrect"),
+        (status = 403, description = "Too many failed attempts"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn change_my_password(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Json(req): Json<ChangePasswordRequest>,
+) -> ApiResponse<GeneralResponse> {
+    to_api_res!(claims.require_session());
+    to_api_res!(change_password(&state, claims.user_id, claims.session_id, req).await);
+    ApiResponse::<GeneralResponse>::success_general_response()
+}
+
+/// Deletes the account with its portfolios, transactions and sessions. Set
+/// `include_backup` to get a final export of the account in the response, or
+/// download `/api/users/me/backup` beforehand.
+#[utoipa::path(
+    delete,
+    path = "/api/users/me",
+    request_body = DeleteAccountRequest,
+    responses(
+        (status = 200, description = "Account deleted", body = ApiResponse<DeleteAccountResponse>),
+        (status = 400, description = "Password is incorrect"),
+        (status = 403, description = "Too many failed attempts"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn delete_user_me(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Json(req): Json<DeleteAccountRequest>,
+) -> ApiResponse<DeleteAccountResponse> {
+    to_api_res!(claims.require_session());
+    ApiResponse::success(to_api_res!(
+        delete_account(&state, claims.user_id, req).await
+    ))
+}
+
+/// Downloads every portfolio of the user with its assets and transactions, as
+/// one JSON document that can be restored on this server or another one.
+#[utoipa::path(
+    get,
+    path = "/api/users/me/backup",
+    responses(
+        (status = 200, description = "Backup of the account", body = AccountBackup, content_type = "application/json"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn download_backup(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+) -> Response {
+    if let Err(e) = claims.require_scope(TokenScope::ReadPortfolios) {
+        return e.into_response();
+    }
+    let backup = match build_account_backup(state.pool.clone(), claims.user_id).await {
+        Ok(backup) => backup,
+        Err(e) => return e.into_response(),
+    };
+    info!(
+        "Backup of user {}: {} portfolios",
+        claims.user_id,
+        backup.portfolios.len()
+    );
+    let body = match serde_json::to_vec_pretty(&backup) {
+        Ok(body) => body,
+        Err(e) => return AppError::from(e).into_response(),
+    };
+    let disposition = format!(
+        "attachment; filename=\"backup-{}-{}.json\"",
+        claims.user_id,
+        backup.created_at.date()
+    );
+    (
+        [
+            (header::CONTENT_TYPE, "application/json".to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        body,
+    )
+        .into_response()
+}
+
+/// Restores a backup uploaded as the `file` field of a multipart form. Every
+/// portfolio gets new IDs, `on_conflict` tells what to do with the ones the
+/// account already has.
+#[utoipa::path(
+    post,
+    path = "/api/users/me/restore",
+    params(RestoreQueryParams),
+    responses(
+        (status = 200, description = "Success", body = ApiResponse<RestoreResponse>),
+        (status = 400, description = "Invalid backup"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn restore_backup(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Query(params): Query<RestoreQueryParams>,
+    mut multipart: Multipart,
+) -> ApiResponse<RestoreResponse> {
+    to_api_res!(claims.require_session());
+    let mut contents: Option<String> = None;
+    loop {
+        let field = match multipart.next_field().await {
+            Ok(Some(field)) => field,
+            Ok(None) => break,
+            Err(e) => return ApiResponse::error(StatusCode::BAD_REQUEST, e.body_text()),
+        };
+        if field.name() != Some("file") {
+            continue;
+        }
+        match field.text().await {
+            Ok(value) => contents = Some(value),
+            Err(_) => {
+                return ApiResponse::error(StatusCode::BAD_REQUEST, "Failed to read file field");
+            }
+        }
+    }
+    let Some(contents) = contents else {
+        return ApiResponse::error(StatusCode::BAD_REQUEST, "file is required");
+    };
+    let backup: AccountBackup = match serde_json::from_str(&contents) {
+        Ok(backup) => backup,
+        Err(e) => {
+            return ApiResponse::error(StatusCode::BAD_REQUEST, format!("Invalid backup: {}", e));
+        }
+    };
+    ApiResponse::success(to_api_res!(
+        restore_account_backup(&state, claims.user_id, backup, params.on_conflict).await
+    ))
+}
+
+/// Sessions of the user still logged in, with the device and address they are
+/// used from
+#[utoipa::path(
+    get,
+    path = "/api/users/me/sessions",
+    responses(
+        (status = 200, description = "Success", body = ApiResponse<UserSessionListResponse>),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn get_my_sessions(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+) -> ApiResponse<UserSessionListResponse> {
+    to_api_res!(claims.require_session());
+    let sessions = to_api_res!(
+        UserSessionRepo::new(state.pool.clone())
+            .get_active_by_user_id(claims.user_id)
+            .await
+    );
+    ApiResponse::success(UserSessionListResponse {
+        items: sessions
+            .into_iter()
+            .map(|session| UserSessionResponse::from_domain(session, claims.session_id))
+            .collect(),
+    })
+}
+
+/// Logs a session of the user out, its tokens stop working at once
+#[utoipa::path(
+    delete,
+    path = "/api/users/me/sessions/{session_id}",
+    responses(
+        (status = 200, description = "Session revoked", body = ApiResponse<GeneralResponse>),
+        (status = 404, description = "Session not found"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn revoke_my_session(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(session_id): Path<String>,
+) -> ApiResponse<GeneralResponse> {
+    to_api_res!(claims.require_session());
+    let Ok(session_id) = session_id.parse::<i64>() else {
+        return ApiResponse::error(
+            StatusCode::BAD_REQUEST,
+            format!("Invalid session ID: {}", session_id),
+        );
+    };
+    let session = to_api_res!(
+        UserSessionRepo::new(state.pool.clone())
+            .get_by_id(session_id)
+            .await
+    );
+    match session {
+        Some(session) if session.user_id == claims.user_id => {
+            to_api_res!(revoke_session(&state, session_id).await);
+            info!("User {} revoked session {}", claims.user_id, session_id);
+            ApiResponse::<GeneralResponse>::success_general_response()
+        }
+        _ => ApiResponse::error(StatusCode::NOT_FOUND, "Session not found"),
+    }
+}
+
+/// Logs out everywhere: every session of the user is revoked, this one included
+#[utoipa::path(
+    delete,
+    path = "/api/users/me/sessions",
+    responses(
+        (status = 200, description = "Sessions revoked", body = ApiResponse<RevokeSessionsResponse>),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn revoke_all_my_sessions(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+) -> ApiResponse<RevokeSessionsResponse> {
+    to_api_res!(claims.require_session());
+    let revoked = to_api_res!(revoke_all_sessions(&state, claims.user_id).await);
+    ApiResponse::success(RevokeSessionsResponse { revoked })
+}
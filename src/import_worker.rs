@@ -0,0 +1,94 @@
+use std::time::Duration;
+
+use time::OffsetDateTime;
+use tracing::{error, info, warn};
+
+use crate::{
+    biz::import_job::run_import_job, db::repositories::import_job::ImportJobRepo, state::AppState,
+};
+
+/// Spawns the loop processing queued import jobs one at a time for the
+/// lifetime of the process.
+pub fn start(state: AppState) {
+    if !state.import_worker.enabled {
+        info!("Import worker is disabled");
+        return;
+    }
+    tokio::spawn(run_worker(state));
+}
+
+async fn run_worker(state: AppState) {
+    let poll_interval = Duration::from_secs(state.import_worker.poll_interval_secs.max(1));
+    let lease = time::Duration::seconds(state.import_worker.lease_secs.max(1) as i64);
+    let job_repo = ImportJobRepo::new(state.pool.clone());
+    info!("Import worker polling every {:?}", poll_interval);
+    loop {
+        match job_repo.requeue_expired().await {
+            Ok(0) => (),
+            Ok(count) => info!("Queued again {} abandoned import jobs", count),
+            Err(e) => error!("Queue abandoned import jobs failed: {:?}", e),
+        }
+        match job_repo.claim_next(lease).await {
+            // Look for the next job right away, the queue may hold more
+            Ok(Some(job)) => {
+                let (job_id, attempt) = (job.id, job.attempts);
+                let lease_expires_at = job
+                    .lease_expires_at
+                    .unwrap_or_else(|| OffsetDateTime::now_utc() + lease);
+                // A panicking importer must not take the worker down with it
+                let mut run = tokio::spawn(run_import_job(state.clone(), job));
+                let outcome = tokio::select! {
+                    outcome = &mut run => outcome,
+                    () = keep_lease(state.clone(), job_id, attempt, lease_expires_at, lease) => {
+                        // Another worker may run the job now, an unfinished
+                        // write is rolled back
+                        run.abort();
+                        continue;
+                    }
+                };
+                if let Err(e) = outcome {
+                    error!("Import job {} crashed: {:?}", job_id, e);
+                    if let Err(e) = job_repo
+                        .fail(job_id, attempt, "Import crashed unexpectedly".to_string())
+                        .await
+                    {
+                        error!("Record outcome of import job {} failed: {:?}", job_id, e);
+                    }
+                }
+            }
+            Ok(None) => tokio::time::sleep(poll_interval).await,
+            Err(e) => {
+                error!("Claim next import job failed: {:?}", e);
+                tokio::time::sleep(poll_interval).await;
+            }
+        }
+    }
+}
+
+/// Renews the lease of a running job once a third of it is spent. Returns
+/// when the attempt no longer holds the job.
+async fn keep_lease(
+    state: AppState,
+    job_id: i64,
+    attempt: i32,
+    mut lease_expires_at: OffsetDateTime,
+    lease: time::Duration,
+) {
+    let job_repo = ImportJobRepo::new(state.pool.clone());
+    loop {
+        let renew_at = lease_expires_at - lease * 2 / 3;
+        let wait = Duration::try_from(renew_at - OffsetDateTime::now_utc()).unwrap_or_default();
+        tokio::time::sleep(wait).await;
+        match job_repo.renew_lease(job_id, attempt, lease).await {
+            Ok(true) => lease_expires_at = OffsetDateTime::now_utc() + lease,
+            Ok(false) => {
+                warn!("Import job {} is no longer leased by this worker", job_id);
+                return;
+            }
+            Err(e) => {
+                error!("Renew lease of import job {} failed: {:?}", job_id, e);
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+        }
+    }
+}
@@ -4,7 +4,7 @@ use axum::{
     http::{HeaderValue, Method, header},
 };
 use config::load_config;
-use std::sync::Arc;
+use std::{net::SocketAddr, sync::Arc};
 use tokio::signal;
 use tower_http::{
     cors::{AllowOrigin, CorsLayer},
@@ -21,9 +21,11 @@ mod config;
 mod db;
 mod docs;
 mod handlers;
+mod import_worker;
 mod middleware;
 mod models;
 mod routes;
+mod scheduler;
 mod state;
 mod utils;
 
@@ -48,6 +50,10 @@ async fn main() -> Result<()> {
     state.health_check().await?;
     info!("Health check state passed");
 
+    // Run periodic background jobs next to the server
+    scheduler::start(state.clone());
+    import_worker::start(state.clone());
+
     // Create cors layer
     let cors_layer = CorsLayer::new()
         .allow_origin(AllowOrigin::list(
@@ -75,12 +81,30 @@ async fn main() -> Result<()> {
     // Create separate routers
     let public_routes = Router::new()
         .nest("/health", routes::health::create_router())
-        .nest("/auth", routes::auth::create_router())
+        .nest(
+            "/auth",
+            routes::auth::create_router()
+                .layer(axum::middleware::from_fn_with_state(
+                    state.clone(),
+                    middleware::rate_limit::limit_auth,
+                ))
+                .merge(routes::auth::create_login_router().layer(
+                    axum::middleware::from_fn_with_state(
+                        state.clone(),
+                        middleware::rate_limit::limit_login,
+                    ),
+                )),
+        )
         .layer(cors_layer)
         .merge(SwaggerUi::new("/docs").url("/api-docs/openapi.json", docs::api::ApiDoc::openapi()));
 
     let protected_routes = Router::new()
         .nest("/api", routes::api::create_router())
+        // Runs after authentication, so authenticated users have their own bucket
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            middleware::rate_limit::limit_api,
+        ))
         .layer(axum::middleware::from_fn_with_state(
             state.clone(),
             middleware::auth::require_authentication,
@@ -106,7 +130,12 @@ async fn main() -> Result<()> {
     let listener = tokio::net::TcpListener::bind(&addr).await?;
     // run server
     info!("Server starting...");
-    axum::serve(listener, app).await?;
+    // Peer addresses are recorded on user sessions
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await?;
 
     // Handle shutdown signal
     tokio::spawn(async move {
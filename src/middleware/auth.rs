@@ -14,46 +14,80 @@ use axum_extra::{
 use jsonwebtoken::{Header, Validation, decode, encode};
 use redis::AsyncCommands;
 use sqlx::PgPool;
-use time::OffsetDateTime;
+use time::{Duration, OffsetDateTime};
+use tracing::error;
 
-use crate::models::domain::auth::Claims;
+use crate::biz::personal_access_token::PERSONAL_ACCESS_TOKEN_PREFIX;
+use crate::db::repositories::personal_access_token::PersonalAccessTokenRepo;
+use crate::models::domain::{auth::Claims, user_session::UserSession};
 use crate::state::AppState;
-use crate::{
-    db::repositories::user_session::UserSessionRepo, models::dto::user_session::CreateUserSession,
-    utils::error::AppError,
-};
+use crate::utils::token::hash_token;
+use crate::{db::repositories::user_session::UserSessionRepo, utils::error::AppError};
 
-// Convert UserSession to JWT token
-pub async fn create_token(
-    session: CreateUserSession,
-    state: &AppState,
-) -> Result<String, AppError> {
-    let repo = UserSessionRepo::new(state.pool.clone());
-    let new_session = repo.create_user_session(session).await?;
+/// Requests within this time of the last one seen do not update the session
+const LAST_SEEN_RESOLUTION: Duration = Duration::minutes(1);
 
-    // Ensure the claims are properly formatted
-    let claims = Claims {
-        session_id: new_session.session_id,
-        user_id: new_session.user_id,
-        exp: new_session.expires_at.unix_timestamp() as usize,
-        iat: new_session.created_at.unix_timestamp() as usize,
-    };
-
-    let token = encode(&Header::default(), &claims, &state.encoding_key())
-        .map_err(|_| AppError::Unauthorized("Error when generating token".to_string()))?;
+/// Set of the cache keys of the tokens of a session, to drop them on revocation
+fn session_tokens_key(session_id: i64) -> String {
+    format!("session_tokens:{}", session_id)
+}
 
-    // Cache the validated claims using the connection
+/// Claims are cached until the token expires, so the signature is checked once
+async fn cache_claims(state: &AppState, token: &str, claims: &Claims) {
+    let ttl = claims.exp as i64 - OffsetDateTime::now_utc().unix_timestamp();
+    if ttl <= 0 {
+        return;
+    }
     let cache_key = format!("token:{}", token);
-
-    // Get a Redis connection and check cache
+    let tokens_key = session_tokens_key(claims.session_id);
     let mut redis_conn = state.redis_conn.clone();
-
-    if let Ok(json) = serde_json::to_string(&claims) {
-        let _: Result<(), _> = redis_conn
-            .set_ex(&cache_key, json, 30 * 60) // 30 minutes
+    if let Ok(json) = serde_json::to_string(claims) {
+        let _: Result<(), _> = redis::pipe()
+            .set_ex(&cache_key, json, ttl as u64)
+            .sadd(&tokens_key, &cache_key)
+            .expire(&tokens_key, ttl)
+            .query_async(&mut redis_conn)
             .await;
     }
+}
+
+/// Drops the cached claims of every token of the sessions, so revoked sessions
+/// are rejected right away
+pub async fn invalidate_cached_tokens(state: &AppState, session_ids: &[i64]) {
+    let mut redis_conn = state.redis_conn.clone();
+    for session_id in session_ids {
+        let tokens_key = session_tokens_key(*session_id);
+        let mut cache_keys: Vec<String> =
+            redis_conn.smembers(&tokens_key).await.unwrap_or_default();
+        cache_keys.push(tokens_key);
+        if let Err(e) = redis_conn.del::<_, ()>(&cache_keys).await {
+            error!(
+                "Invalidate cached tokens of session {} failed: {:?}",
+                session_id, e
+            );
+        }
+    }
+}
 
+/// Signs a short-lived access token of the session, it never outlives the session
+pub async fn create_access_token(
+    session: &UserSession,
+    state: &AppState,
+) -> Result<String, AppError> {
+    let now = OffsetDateTime::now_utc();
+    let expires_at =
+        (now + Duration::seconds(state.auth.access_token_ttl_secs as i64)).min(session.expires_at);
+    let claims = Claims {
+        session_id: session.session_id,
+        user_id: session.user_id,
+        exp: expires_at.unix_timestamp() as usize,
+        iat: now.unix_timestamp() as usize,
+        scopes: None,
+    };
+
+    let token = encode(&Header::default(), &claims, &state.encoding_key())
+        .map_err(|_| AppError::Unauthorized("Error when generating token".to_string()))?;
+    cache_claims(state, &token, &claims).await;
     Ok(token)
 }
 
@@ -65,15 +99,55 @@ async fn is_session_valid(session_id: i64, pool: PgPool) -> Result<bool, AppErro
             if !session.is_active {
                 return Err(AppError::Unauthorized("Session not active".to_string()));
             }
-            if session.expires_at < OffsetDateTime::now_utc() {
+            let now = OffsetDateTime::now_utc();
+            if session.expires_at < now {
                 return Err(AppError::Unauthorized("Session expired".to_string()));
             }
+            if now - session.last_seen_at > LAST_SEEN_RESOLUTION {
+                session_repo.touch(session_id, now).await?;
+            }
             Ok(true)
         }
         None => Err(AppError::Unauthorized("Session not found".to_string())),
     }
 }
 
+/// Claims of a personal access token. Tokens are looked up on every request,
+/// so a revoked token is rejected at once.
+async fn authenticate_personal_access_token(
+    state: &AppState,
+    token: &str,
+) -> Result<Claims, AppError> {
+    let token_repo = PersonalAccessTokenRepo::new(state.pool.clone());
+    let row = token_repo
+        .get_usable_by_hash(&hash_token(token))
+        .await?
+        .ok_or_else(|| {
+            AppError::Unauthorized("Invalid or expired personal access token".to_string())
+        })?;
+    let now = OffsetDateTime::now_utc();
+    if row
+        .last_used_at
+        .is_none_or(|last_used_at| now - last_used_at > LAST_SEEN_RESOLUTION)
+    {
+        token_repo.touch(row.id, now).await?;
+    }
+    Ok(Claims {
+        session_id: 0,
+        user_id: row.user_id,
+        exp: row.expires_at.map_or(usize::MAX, |expires_at| {
+            expires_at.unix_timestamp() as usize
+        }),
+        iat: row.created_at.unix_timestamp() as usize,
+        scopes: Some(
+            row.scopes
+                .iter()
+                .filter_map(|scope| scope.parse().ok())
+                .collect(),
+        ),
+    })
+}
+
 impl FromRequestParts<AppState> for Claims {
     type Rejection = AppError;
 
@@ -85,8 +159,12 @@ impl FromRequestParts<AppState> for Claims {
                 AppError::Unauthorized("Missing or invalid authorization header".to_string())
             })?;
 
-        // Try to get claims from cache first
         let token = bearer.token();
+        if token.starts_with(PERSONAL_ACCESS_TOKEN_PREFIX) {
+            return authenticate_personal_access_token(state, token).await;
+        }
+
+        // Try to get claims from cache first
         let cache_key = format!("token:{}", token);
 
         // Get a Redis connection and check cache
@@ -111,12 +189,7 @@ impl FromRequestParts<AppState> for Claims {
             return Err(AppError::Unauthorized("Session not valid".to_string()));
         }
 
-        // Cache the validated claims using the connection
-        if let Ok(json) = serde_json::to_string(&token_data.claims) {
-            let _: Result<(), _> = redis_conn
-                .set_ex(&cache_key, json, 30 * 60) // 30 minutes
-                .await;
-        }
+        cache_claims(state, token, &token_data.claims).await;
 
         Ok(token_data.claims)
     }
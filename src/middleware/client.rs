@@ -0,0 +1,202 @@
+use std::{
+    net::{IpAddr, SocketAddr},
+    str::FromStr,
+};
+
+use anyhow::anyhow;
+use axum::{
+    extract::{ConnectInfo, FromRequestParts},
+    http::{HeaderMap, header, request::Parts},
+};
+
+use crate::state::AppState;
+
+const X_FORWARDED_FOR: &str = "x-forwarded-for";
+
+/// A proxy address or network, e.g. `10.0.0.1` or `10.0.0.0/8`, whose
+/// `X-Forwarded-For` header is believed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TrustedProxy {
+    addr: IpAddr,
+    prefix_len: u8,
+}
+
+impl TrustedProxy {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, ip.to_canonical()) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX
+                    .checked_shl(32 - self.prefix_len as u32)
+                    .unwrap_or(0);
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX
+                    .checked_shl(128 - self.prefix_len as u32)
+                    .unwrap_or(0);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for TrustedProxy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (addr, prefix_len) = match s.split_once('/') {
+            Some((addr, prefix_len)) => (addr, Some(prefix_len)),
+            None => (s, None),
+        };
+        let addr: IpAddr = addr
+            .trim()
+            .parse()
+            .map_err(|_| anyhow!("Invalid trusted proxy: {}", s))?;
+        let max_len = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix_len = match prefix_len {
+            Some(len) => len
+                .trim()
+                .parse::<u8>()
+                .ok()
+                .filter(|len| *len <= max_len)
+                .ok_or_else(|| anyhow!("Invalid trusted proxy: {}", s))?,
+            None => max_len,
+        };
+        Ok(Self {
+            addr: addr.to_canonical(),
+            prefix_len,
+        })
+    }
+}
+
+/// Where a request comes from, recorded on the sessions it opens
+#[derive(Debug, Clone, Default)]
+pub struct ClientInfo {
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+impl ClientInfo {
+    /// The peer address, unless the peer is a trusted proxy. Then the
+    /// rightmost `X-Forwarded-For` address that is not a trusted proxy, since
+    /// every hop left of it could have been written by the client.
+    pub fn from_parts(
+        headers: &HeaderMap,
+        peer: Option<SocketAddr>,
+        trusted_proxies: &[TrustedProxy],
+    ) -> Self {
+        let is_trusted = |ip: IpAddr| trusted_proxies.iter().any(|proxy| proxy.contains(ip));
+        let mut ip_address = peer.map(|addr| addr.ip().to_canonical());
+        if let Some(peer_ip) = ip_address
+            && is_trusted(peer_ip)
+        {
+            let hops = headers
+                .get_all(X_FORWARDED_FOR)
+                .iter()
+                .filter_map(|h| h.to_str().ok())
+                .flat_map(|s| s.split(','))
+                .map(str::trim)
+                .collect::<Vec<_>>();
+            for hop in hops.into_iter().rev() {
+                // A hop that is not an address was not written by a proxy
+                let Ok(hop) = hop.parse::<IpAddr>() else {
+                    break;
+                };
+                ip_address = Some(hop.to_canonical());
+                if !is_trusted(hop) {
+                    break;
+                }
+            }
+        }
+        Self {
+            ip_address: ip_address.map(|ip| ip.to_string()),
+            user_agent: headers
+                .get(header::USER_AGENT)
+                .and_then(|h| h.to_str().ok())
+                .map(|s| s.chars().take(512).collect()),
+        }
+    }
+}
+
+impl FromRequestParts<AppState> for ClientInfo {
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let peer = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| *addr);
+        Ok(Self::from_parts(
+            &parts.headers,
+            peer,
+            &state.trusted_proxies,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+
+    use super::*;
+
+    fn client_ip(forwarded_for: Option<&str>, peer: &str, trusted: &[&str]) -> Option<String> {
+        let mut headers = HeaderMap::new();
+        if let Some(value) = forwarded_for {
+            headers.insert(X_FORWARDED_FOR, HeaderValue::from_str(value).unwrap());
+        }
+        let trusted = trusted
+            .iter()
+            .map(|proxy| proxy.parse().unwrap())
+            .collect::<Vec<TrustedProxy>>();
+        ClientInfo::from_parts(&headers, Some(peer.parse().unwrap()), &trusted).ip_address
+    }
+
+    #[test]
+    fn ignores_forwarded_for_from_untrusted_peer() {
+        assert_eq!(
+            client_ip(Some("1.2.3.4"), "203.0.113.7:5000", &[]),
+            Some("203.0.113.7".to_string())
+        );
+    }
+
+    #[test]
+    fn takes_rightmost_untrusted_hop() {
+        assert_eq!(
+            client_ip(
+                Some("6.6.6.6, 198.51.100.9, 10.0.0.2"),
+                "10.0.0.1:5000",
+                &["10.0.0.0/8"]
+            ),
+            Some("198.51.100.9".to_string())
+        );
+    }
+
+    #[test]
+    fn stops_at_hop_that_is_not_an_address() {
+        assert_eq!(
+            client_ip(
+                Some("198.51.100.9, garbage"),
+                "10.0.0.1:5000",
+                &["10.0.0.1"]
+            ),
+            Some("10.0.0.1".to_string())
+        );
+    }
+
+    #[test]
+    fn parses_trusted_proxies() {
+        let network: TrustedProxy = "192.168.0.0/16".parse().unwrap();
+        assert!(network.contains("192.168.4.20".parse().unwrap()));
+        assert!(!network.contains("192.169.0.1".parse().unwrap()));
+        assert!(network.contains("::ffff:192.168.1.1".parse().unwrap()));
+        let any: TrustedProxy = "::/0".parse().unwrap();
+        assert!(any.contains("2001:db8::1".parse().unwrap()));
+        assert!("10.0.0.0/33".parse::<TrustedProxy>().is_err());
+        assert!("proxy".parse::<TrustedProxy>().is_err());
+    }
+}
@@ -0,0 +1,236 @@
+use std::net::SocketAddr;
+
+use axum::{
+    body::{Body, to_bytes},
+    extract::{ConnectInfo, Request, State},
+    http::{StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use strum::Display;
+use tracing::{error, warn};
+
+use crate::config::RateLimitRule;
+use crate::middleware::client::ClientInfo;
+use crate::models::{domain::auth::Claims, dto::api_response::ApiResponse};
+use crate::state::AppState;
+use crate::utils::error::AppError;
+
+/// Public requests are buffered to read the account they are for
+const MAX_BUFFERED_BODY_BYTES: usize = 64 * 1024;
+
+/// Routes sharing a rate limit rule, each with its own buckets
+#[derive(Debug, Clone, Copy, PartialEq, Display)]
+#[strum(serialize_all = "snake_case")]
+pub enum RateLimitGroup {
+    Login,
+    Auth,
+    Api,
+}
+
+impl RateLimitGroup {
+    fn rule(self, state: &AppState) -> &RateLimitRule {
+        match self {
+            RateLimitGroup::Login => &state.rate_limit.login,
+            RateLimitGroup::Auth => &state.rate_limit.auth,
+            RateLimitGroup::Api => &state.rate_limit.api,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct AccountBody {
+    email: Option<String>,
+}
+
+fn normalize_email(email: &str) -> String {
+    email.trim().to_lowercase()
+}
+
+fn login_failures_key(email: &str) -> String {
+    format!("login_failures:{}", normalize_email(email))
+}
+
+fn login_lockout_key(email: &str) -> String {
+    format!("login_lockout:{}", normalize_email(email))
+}
+
+fn too_many_requests(retry_after_secs: u64, message: &str) -> Response {
+    (
+        StatusCode::TOO_MANY_REQUESTS,
+        [(header::RETRY_AFTER, retry_after_secs.max(1).to_string())],
+        ApiResponse::<()>::error(StatusCode::TOO_MANY_REQUESTS, message),
+    )
+        .into_response()
+}
+
+/// Counts a request in the current window of a bucket, returns the count and
+/// the seconds left in the window
+async fn hit(state: &AppState, key: &str, window_secs: u64) -> Result<(u64, u64), AppError> {
+    let mut redis_conn = state.redis_conn.clone();
+    let (count, ttl): (u64, i64) = redis::pipe()
+        .atomic()
+        .cmd("SET")
+        .arg(key)
+        .arg(0)
+        .arg("EX")
+        .arg(window_secs)
+        .arg("NX")
+        .ignore()
+        .incr(key, 1)
+        .ttl(key)
+        .query_async(&mut redis_conn)
+        .await?;
+    Ok((count, ttl.max(1) as u64))
+}
+
+/// Seconds the email stays locked after too many failed logins, if it is
+pub async fn login_lockout_remaining(state: &AppState, email: &str) -> Option<u64> {
+    let mut redis_conn = state.redis_conn.clone();
+    let ttl: Result<i64, _> = redis::cmd("TTL")
+        .arg(login_lockout_key(email))
+        .query_async(&mut redis_conn)
+        .await;
+    match ttl {
+        Ok(ttl) if ttl > 0 => Some(ttl as u64),
+        Ok(_) => None,
+        Err(e) => {
+            error!("Check login lockout failed: {:?}", e);
+            None
+        }
+    }
+}
+
+/// Counts a failed login of the email, whether it has an account or not, and
+/// locks it once the failures reach the limit
+pub async fn record_login_failure(state: &AppState, email: &str) {
+    let lockout = &state.rate_limit.lockout;
+    if !state.rate_limit.enabled || lockout.max_failures == 0 {
+        return;
+    }
+    let failures_key = login_failures_key(email);
+    let failures = match hit(state, &failures_key, lockout.failure_window_secs).await {
+        Ok((failures, _)) => failures,
+        Err(e) => {
+            error!("Record login failure failed: {:?}", e);
+            return;
+        }
+    };
+    if failures < lockout.max_failures {
+        return;
+    }
+    warn!(
+        "Locking logins of {} for {}s after {} failures",
+        normalize_email(email),
+        lockout.lockout_secs,
+        failures
+    );
+    let mut redis_conn = state.redis_conn.clone();
+    let locked: Result<(), _> = redis::pipe()
+        .set_ex(login_lockout_key(email), 1, lockout.lockout_secs)
+        .del(&failures_key)
+        .query_async(&mut redis_conn)
+        .await;
+    if let Err(e) = locked {
+        error!("Lock logins failed: {:?}", e);
+    }
+}
+
+/// Forgets the failed logins of the email after a successful one
+pub async fn clear_login_failures(state: &AppState, email: &str) {
+    let mut redis_conn = state.redis_conn.clone();
+    let cleared: Result<(), _> = redis::cmd("DEL")
+        .arg(login_failures_key(email))
+        .query_async(&mut redis_conn)
+        .await;
+    if let Err(e) = cleared {
+        error!("Clear login failures failed: {:?}", e);
+    }
+}
+
+/// Fixed window limits per client IP and per account. The account is the
+/// authenticated user, or the `email` of the JSON body of public routes. Login
+/// routes also refuse locked emails. Redis failures let requests through.
+async fn rate_limit(
+    state: AppState,
+    group: RateLimitGroup,
+    req: Request<Body>,
+    next: Next,
+) -> Response {
+    if !state.rate_limit.enabled {
+        return next.run(req).await;
+    }
+    let rule = group.rule(&state);
+    let (parts, body) = req.into_parts();
+    let peer = parts
+        .extensions
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| *addr);
+    let client = ClientInfo::from_parts(&parts.headers, peer, &state.trusted_proxies);
+    let (account, body) = match parts.extensions.get::<Claims>() {
+        Some(claims) => (Some(claims.user_id.to_string()), body),
+        None if group == RateLimitGroup::Api => (None, body),
+        None => {
+            let Ok(bytes) = to_bytes(body, MAX_BUFFERED_BODY_BYTES).await else {
+                return (
+                    StatusCode::PAYLOAD_TOO_LARGE,
+                    ApiResponse::<()>::error(
+                        StatusCode::PAYLOAD_TOO_LARGE,
+                        "Request body too large",
+                    ),
+                )
+                    .into_response();
+            };
+            let email = serde_json::from_slice::<AccountBody>(&bytes)
+                .ok()
+                .and_then(|body| body.email)
+                .map(|email| normalize_email(&email))
+                .filter(|email| !email.is_empty());
+            (email, Body::from(bytes))
+        }
+    };
+
+    if group == RateLimitGroup::Login
+        && let Some(email) = &account
+        && let Some(retry_after) = login_lockout_remaining(&state, email).await
+    {
+        return too_many_requests(retry_after, "Too many failed logins, try again later");
+    }
+    let buckets = [
+        ("ip", client.ip_address, rule.per_ip),
+        ("account", account, rule.per_account),
+    ];
+    for (kind, id, limit) in buckets {
+        let Some(id) = id else { continue };
+        if limit == 0 {
+            continue;
+        }
+        let key = format!("rate_limit:{}:{}:{}", group, kind, id);
+        match hit(&state, &key, rule.window_secs).await {
+            Ok((count, retry_after)) if count > limit => {
+                warn!("Rate limit of {} exceeded: {} requests", key, count);
+                return too_many_requests(retry_after, "Too many requests, try again later");
+            }
+            Ok(_) => {}
+            Err(e) => error!("Rate limit of {} failed: {:?}", key, e),
+        }
+    }
+    next.run(Request::from_parts(parts, body)).await
+}
+
+pub async fn limit_login(
+    State(state): State<AppState>,
+    req: Request<Body>,
+    next: Next,
+) -> Response {
+    rate_limit(state, RateLimitGroup::Login, req, next).await
+}
+
+pub async fn limit_auth(State(state): State<AppState>, req: Request<Body>, next: Next) -> Response {
+    rate_limit(state, RateLimitGroup::Auth, req, next).await
+}
+
+pub async fn limit_api(State(state): State<AppState>, req: Request<Body>, next: Next) -> Response {
+    rate_limit(state, RateLimitGroup::Api, req, next).await
+}
@@ -3,9 +3,47 @@ use strum::{EnumIter, EnumString};
 use strum_macros::Display;
 use utoipa::ToSchema;
 
-#[derive(Debug, Serialize, Deserialize, EnumString, EnumIter, Display, ToSchema)]
+#[derive(
+    Debug,
+    Default,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    Serialize,
+    Deserialize,
+    EnumString,
+    EnumIter,
+    Display,
+    ToSchema,
+)]
 #[strum(serialize_all = "UPPERCASE")]
+#[allow(clippy::upper_case_acronyms)]
 pub enum Currency {
+    #[default]
     USD,
+    EUR,
+    GBP,
+    JPY,
+    CNY,
+    CHF,
+    CAD,
+    AUD,
+    NZD,
+    HKD,
+    SGD,
+    KRW,
+    INR,
+    THB,
     VND,
+    USDT,
+    USDC,
+}
+
+impl Currency {
+    /// Stablecoins pegged to the US dollar, valued 1:1 against USD when no rate is stored
+    pub fn is_usd_stablecoin(&self) -> bool {
+        matches!(self, Currency::USDT | Currency::USDC)
+    }
 }
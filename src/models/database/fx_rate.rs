@@ -0,0 +1,16 @@
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use sqlx::prelude::FromRow;
+use time::{Date, OffsetDateTime};
+
+#[derive(Debug, Deserialize, FromRow)]
+#[allow(dead_code)]
+pub struct FxRateRow {
+    pub currency: String,
+    pub rate_date: Date,
+    /// Units of the currency per 1 USD
+    pub rate: Decimal,
+    pub source: String,
+    pub created_at: OffsetDateTime,
+    pub updated_at: OffsetDateTime,
+}
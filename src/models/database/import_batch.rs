@@ -0,0 +1,33 @@
+use serde::Deserialize;
+use sqlx::prelude::FromRow;
+use time::OffsetDateTime;
+
+#[derive(Debug, Deserialize, FromRow)]
+pub struct ImportBatchRow {
+    pub id: i64,
+    pub user_id: i64,
+    pub portfolio_id: i64,
+    pub source: String,
+    pub file_hash: String,
+    pub created_count: i32,
+    pub updated_count: i32,
+    pub unchanged_count: i32,
+    pub new_asset_ids: Vec<String>,
+    pub new_portfolio_asset_ids: Vec<String>,
+    pub status: String,
+    pub created_at: OffsetDateTime,
+    pub updated_at: OffsetDateTime,
+}
+
+#[derive(Debug, Deserialize, FromRow)]
+#[allow(dead_code)]
+pub struct ImportBatchChangeRow {
+    pub batch_id: i64,
+    pub tx_id: i64,
+    pub asset_id: String,
+    pub action: String,
+    pub previous: Option<serde_json::Value>,
+    pub previous_batch_id: Option<i64>,
+    /// The transaction as the import wrote it
+    pub after: serde_json::Value,
+}
@@ -0,0 +1,25 @@
+use serde::Deserialize;
+use sqlx::prelude::FromRow;
+use time::OffsetDateTime;
+
+#[derive(Debug, Deserialize, FromRow)]
+pub struct ImportJobRow {
+    pub id: i64,
+    pub user_id: i64,
+    pub portfolio_id: i64,
+    pub source: String,
+    pub file_contents: String,
+    pub options: Option<serde_json::Value>,
+    pub status: String,
+    pub attempts: i32,
+    pub rows_parsed: i32,
+    pub rows_inserted: i32,
+    pub error_count: i32,
+    pub errors: Vec<String>,
+    pub batch_id: Option<i64>,
+    pub created_at: OffsetDateTime,
+    pub started_at: Option<OffsetDateTime>,
+    pub finished_at: Option<OffsetDateTime>,
+    pub lease_expires_at: Option<OffsetDateTime>,
+    pub updated_at: OffsetDateTime,
+}
@@ -0,0 +1,14 @@
+use serde::Deserialize;
+use sqlx::prelude::FromRow;
+use time::OffsetDateTime;
+
+#[derive(Debug, Deserialize, FromRow)]
+pub struct JobRunRow {
+    pub id: i64,
+    pub job_name: String,
+    pub triggered_by: String,
+    pub status: String,
+    pub error: Option<String>,
+    pub started_at: OffsetDateTime,
+    pub finished_at: Option<OffsetDateTime>,
+}
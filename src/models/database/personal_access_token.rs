@@ -0,0 +1,19 @@
+use serde::Deserialize;
+use sqlx::prelude::FromRow;
+use time::OffsetDateTime;
+
+#[derive(Debug, Deserialize, FromRow)]
+#[allow(dead_code)]
+pub struct PersonalAccessTokenRow {
+    pub id: i64,
+    pub user_id: i64,
+    pub name: String,
+    pub token_hash: String,
+    /// Start of the token, shown so the user can tell tokens apart
+    pub token_prefix: String,
+    pub scopes: Vec<String>,
+    pub created_at: OffsetDateTime,
+    pub expires_at: Option<OffsetDateTime>,
+    pub last_used_at: Option<OffsetDateTime>,
+    pub revoked_at: Option<OffsetDateTime>,
+}
@@ -8,6 +8,8 @@ pub struct PortfolioRow {
     pub id: i64,
     pub owner_id: i64,
     pub name: String,
+    pub cost_basis_method: String,
+    pub base_currency: String,
     pub created_at: OffsetDateTime,
     pub updated_at: OffsetDateTime,
 }
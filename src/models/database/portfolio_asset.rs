@@ -16,4 +16,6 @@ pub struct PortfolioAssetRow {
     pub sold_amount: Decimal,
     pub total_revenue: Decimal,
     pub avg_sell_price: Decimal,
+    pub cost_basis: Decimal,
+    pub realized_pnl: Decimal,
 }
@@ -0,0 +1,18 @@
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use sqlx::prelude::FromRow;
+use time::{Date, OffsetDateTime};
+
+#[derive(Debug, Deserialize, FromRow)]
+#[allow(dead_code)]
+pub struct PortfolioSnapshotRow {
+    pub portfolio_id: i64,
+    pub snapshot_date: Date,
+    pub currency: String,
+    pub holdings_value: Decimal,
+    pub cost_basis: Decimal,
+    pub realized_pnl: Decimal,
+    pub unrealized_pnl: Decimal,
+    pub created_at: OffsetDateTime,
+    pub updated_at: OffsetDateTime,
+}
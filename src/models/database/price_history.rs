@@ -0,0 +1,16 @@
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use sqlx::prelude::FromRow;
+use time::{Date, OffsetDateTime};
+
+#[derive(Debug, Deserialize, FromRow)]
+#[allow(dead_code)]
+pub struct PriceHistoryRow {
+    pub asset_id: String,
+    pub currency: String,
+    pub price_date: Date,
+    pub close_price: Decimal,
+    pub source: String,
+    pub created_at: OffsetDateTime,
+    pub updated_at: OffsetDateTime,
+}
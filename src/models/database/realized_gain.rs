@@ -0,0 +1,52 @@
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use sqlx::prelude::FromRow;
+use time::OffsetDateTime;
+
+#[derive(Debug, Deserialize, FromRow)]
+#[allow(dead_code)]
+pub struct RealizedGainRow {
+    pub id: i64,
+    pub portfolio_id: i64,
+    pub asset_id: String,
+    pub sell_tx_id: i64,
+    pub buy_tx_id: Option<i64>,
+    pub quantity: Decimal,
+    pub cost_basis: Decimal,
+    pub proceeds: Decimal,
+    pub gain: Decimal,
+    pub acquired_at: Option<OffsetDateTime>,
+    pub disposed_at: OffsetDateTime,
+    pub holding_period_days: Option<i64>,
+    pub created_at: OffsetDateTime,
+}
+
+#[derive(Debug, Deserialize, FromRow)]
+pub struct RealizedGainSummaryRow {
+    pub total_items: i64,
+    pub total_quantity: Decimal,
+    pub total_cost_basis: Decimal,
+    pub total_proceeds: Decimal,
+    pub total_gain: Decimal,
+}
+
+/// A realized gain with the portfolio and asset it belongs to
+#[derive(Debug, Deserialize, FromRow)]
+pub struct TaxReportGainRow {
+    pub id: i64,
+    pub portfolio_id: i64,
+    pub portfolio_name: String,
+    pub base_currency: String,
+    pub asset_id: String,
+    pub asset_symbol: String,
+    pub asset_name: String,
+    pub sell_tx_id: i64,
+    pub buy_tx_id: Option<i64>,
+    pub quantity: Decimal,
+    pub cost_basis: Decimal,
+    pub proceeds: Decimal,
+    pub gain: Decimal,
+    pub acquired_at: Option<OffsetDateTime>,
+    pub disposed_at: OffsetDateTime,
+    pub holding_period_days: Option<i64>,
+}
@@ -0,0 +1,12 @@
+use serde::Deserialize;
+use sqlx::prelude::FromRow;
+use time::OffsetDateTime;
+
+#[derive(Debug, Deserialize, FromRow)]
+pub struct RefreshTokenRow {
+    pub id: i64,
+    pub session_id: i64,
+    pub expires_at: OffsetDateTime,
+    /// Set once the token was exchanged, presenting it again revokes the session
+    pub used_at: Option<OffsetDateTime>,
+}
@@ -0,0 +1,14 @@
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use sqlx::prelude::FromRow;
+use time::OffsetDateTime;
+
+#[derive(Debug, Deserialize, FromRow)]
+pub struct TaxLotRow {
+    pub tx_id: i64,
+    pub asset_id: String,
+    pub acquired_at: OffsetDateTime,
+    pub quantity: Decimal,
+    pub remaining_quantity: Decimal,
+    pub unit_cost: Decimal,
+}
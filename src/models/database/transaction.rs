@@ -16,6 +16,8 @@ pub struct TransactionRow {
     pub currency: String,
     pub executed_at: OffsetDateTime,
     pub notes: Option<String>,
+    pub realized_gain: Option<Decimal>,
     pub created_at: OffsetDateTime,
     pub updated_at: OffsetDateTime,
+    pub import_batch_id: Option<i64>,
 }
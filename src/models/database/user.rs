@@ -10,6 +10,7 @@ pub struct UserRow {
     #[serde(skip_serializing)]
     pub hashed_password: Option<String>,
     pub name: Option<String>,
+    pub base_currency: String,
     pub created_at: OffsetDateTime,
     pub updated_at: OffsetDateTime,
 }
@@ -0,0 +1,14 @@
+use serde::Deserialize;
+use sqlx::prelude::FromRow;
+use time::OffsetDateTime;
+
+#[derive(Debug, Deserialize, FromRow)]
+pub struct UserTotpRow {
+    pub user_id: i64,
+    /// Base32 secret shared with the authenticator app
+    pub secret: String,
+    /// `None` until the enrollment is confirmed with a code
+    pub enabled_at: Option<OffsetDateTime>,
+    /// Step of the last accepted code, codes of it or earlier steps are refused
+    pub last_used_step: Option<i64>,
+}
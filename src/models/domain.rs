@@ -1,8 +1,12 @@
 pub mod asset;
 pub mod auth;
 pub mod coingecko;
+pub mod import;
+pub mod job;
 pub mod portfolio;
 pub mod portfolio_asset;
+pub mod price;
+pub mod tax_lot;
 pub mod transaction;
 pub mod user;
 pub mod user_session;
@@ -1,9 +1,62 @@
 use serde::{Deserialize, Serialize};
+use strum::{Display, EnumString};
+use utoipa::ToSchema;
+
+use crate::utils::error::AppError;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Claims {
+    /// Session of the access token, 0 for personal access tokens
     pub session_id: i64,
     pub user_id: i64,
     pub exp: usize, // Required (validate_exp defaults to true in validation). Expiration time (as UTC timestamp)
     pub iat: usize, // Optional. Issued at (as UTC timestamp)
+    /// Scopes of a personal access token. Access tokens of a login session have
+    /// none and may do anything.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scopes: Option<Vec<TokenScope>>,
+}
+
+impl Claims {
+    /// Fails unless the credential is a login session or a personal access
+    /// token granted the scope
+    pub fn require_scope(&self, scope: TokenScope) -> Result<(), AppError> {
+        match &self.scopes {
+            Some(scopes) if !scopes.contains(&scope) => Err(AppError::Forbidden(format!(
+                "Token is missing the {} scope",
+                scope
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Fails for personal access tokens, for account management only a login
+    /// session may do
+    pub fn require_session(&self) -> Result<(), AppError> {
+        match self.scopes {
+            Some(_) => Err(AppError::Forbidden(
+                "Personal access tokens can not be used here".to_string(),
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+/// What a personal access token may do
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema, EnumString, Display,
+)]
+pub enum TokenScope {
+    /// Read portfolios, holdings, transactions and reports
+    #[serde(rename = "read:portfolios")]
+    #[strum(serialize = "read:portfolios")]
+    ReadPortfolios,
+    /// Create and update transactions, and the assets they need
+    #[serde(rename = "write:transactions")]
+    #[strum(serialize = "write:transactions")]
+    WriteTransactions,
+    /// Import files and manage import batches and jobs
+    #[serde(rename = "import")]
+    #[strum(serialize = "import")]
+    Import,
 }
@@ -0,0 +1,179 @@
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumString};
+use time::{Date, OffsetDateTime, UtcOffset};
+use utoipa::ToSchema;
+
+use crate::models::{
+    common::currency::Currency,
+    domain::transaction::{BaseTransactionInfo, TxType},
+    dto::asset::CreateAssetRepo,
+};
+
+/// Where imported transactions come from
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, EnumString, Display, ToSchema)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum ImportSource {
+    /// CoinGecko portfolio page saved as HTML
+    Coingecko,
+    /// CoinGecko transactions exported as JSON
+    CoingeckoJson,
+    /// Spreadsheet export with a column mapping
+    Csv,
+    /// Trade history exports of exchanges
+    Binance,
+    Coinbase,
+    Kraken,
+}
+
+impl ImportSource {
+    /// Exchange whose trade history the source reads, if any
+    pub fn exchange(&self) -> Option<Exchange> {
+        match self {
+            ImportSource::Binance => Some(Exchange::Binance),
+            ImportSource::Coinbase => Some(Exchange::Coinbase),
+            ImportSource::Kraken => Some(Exchange::Kraken),
+            _ => None,
+        }
+    }
+}
+
+/// Exchanges whose trade history CSV exports can be imported
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, EnumString, Display, ToSchema)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum Exchange {
+    Binance,
+    Coinbase,
+    Kraken,
+}
+
+impl From<Exchange> for ImportSource {
+    fn from(exchange: Exchange) -> Self {
+        match exchange {
+            Exchange::Binance => ImportSource::Binance,
+            Exchange::Coinbase => ImportSource::Coinbase,
+            Exchange::Kraken => ImportSource::Kraken,
+        }
+    }
+}
+
+/// A row of an imported file with every field that could be read, and the
+/// reasons it can not be imported.
+#[derive(Debug, Default)]
+pub struct ImportRow {
+    pub line: usize,
+    pub asset_id: Option<String>,
+    pub external_id: Option<String>,
+    pub tx_type: Option<TxType>,
+    pub quantity: Option<Decimal>,
+    pub price: Option<Decimal>,
+    pub fees: Option<Decimal>,
+    pub currency: Option<Currency>,
+    pub executed_at: Option<OffsetDateTime>,
+    pub notes: Option<String>,
+    pub errors: Vec<String>,
+}
+
+impl ImportRow {
+    pub fn is_valid(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// The transaction to store, `None` unless every field was read
+    pub fn to_tx_info(&self) -> Option<BaseTransactionInfo> {
+        if !self.is_valid() {
+            return None;
+        }
+        Some(BaseTransactionInfo {
+            id: None,
+            external_id: self.external_id.clone(),
+            fees: self.fees?,
+            executed_at: self.executed_at?,
+            notes: self.notes.clone(),
+            currency: self.currency?,
+            quantity: self.quantity?,
+            price: self.price?,
+            tx_type: self.tx_type?,
+        })
+    }
+}
+
+/// A transaction an import wants to store for an asset of the portfolio
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PlannedTx {
+    pub asset_id: String,
+    pub tx: BaseTransactionInfo,
+}
+
+/// An existing transaction an import would overwrite. `updated_at` is the time
+/// of its last change when the plan was made, so stale plans can be refused.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PlannedUpdate {
+    pub tx_id: i64,
+    pub asset_id: String,
+    pub updated_at: OffsetDateTime,
+    pub previous_batch_id: Option<i64>,
+    pub before: BaseTransactionInfo,
+    pub after: BaseTransactionInfo,
+}
+
+/// Everything an import would change in a portfolio. Dry runs store it so the
+/// real run commits exactly what was previewed.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ImportPlan {
+    pub user_id: i64,
+    pub portfolio_id: i64,
+    pub source: ImportSource,
+    /// SHA-256 of the imported file
+    pub file_hash: String,
+    /// Assets missing from the catalog, created from the provider metadata
+    #[serde(default)]
+    pub new_assets: Vec<CreateAssetRepo>,
+    /// Assets the portfolio does not hold yet, attached along with the transactions
+    #[serde(default)]
+    pub new_portfolio_assets: Vec<String>,
+    pub creates: Vec<PlannedTx>,
+    pub updates: Vec<PlannedUpdate>,
+    pub unchanged: Vec<PlannedTx>,
+}
+
+impl ImportPlan {
+    /// Currency and execution day of every transaction the plan writes
+    pub fn fx_days(&self) -> Vec<(Currency, Date)> {
+        self.creates
+            .iter()
+            .map(|create| &create.tx)
+            .chain(self.updates.iter().map(|update| &update.after))
+            .map(|tx| (tx.currency, tx.executed_at.to_offset(UtcOffset::UTC).date()))
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, EnumString, Display, ToSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+#[strum(serialize_all = "SCREAMING_SNAKE_CASE")]
+pub enum ImportBatchStatus {
+    Completed,
+    RolledBack,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, EnumString, Display, ToSchema)]
+#[serde(rename_all = "UPPERCASE")]
+#[strum(serialize_all = "UPPERCASE")]
+pub enum ImportJobStatus {
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+}
+
+/// What an import did to a transaction
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, EnumString, Display)]
+#[serde(rename_all = "UPPERCASE")]
+#[strum(serialize_all = "UPPERCASE")]
+pub enum ImportChangeAction {
+    Created,
+    Updated,
+}
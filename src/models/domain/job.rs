@@ -0,0 +1,47 @@
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumIter, EnumString};
+use utoipa::ToSchema;
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    Serialize,
+    Deserialize,
+    EnumString,
+    EnumIter,
+    Display,
+    ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum JobName {
+    /// Refreshes the spot price of every held asset and stores it as today's close
+    PriceRefresh,
+    /// Syncs daily close prices and writes the daily portfolio snapshots
+    DailySnapshots,
+    /// Refreshes symbol, name and image of every asset from the price provider
+    AssetMetadataRefresh,
+    /// Stores today's exchange rates of the supported currencies
+    FxRatesRefresh,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, EnumString, Display, ToSchema)]
+#[serde(rename_all = "UPPERCASE")]
+#[strum(serialize_all = "UPPERCASE")]
+pub enum JobTrigger {
+    Scheduled,
+    Manual,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, EnumString, Display, ToSchema)]
+#[serde(rename_all = "UPPERCASE")]
+#[strum(serialize_all = "UPPERCASE")]
+pub enum JobStatus {
+    Running,
+    Succeeded,
+    Failed,
+}
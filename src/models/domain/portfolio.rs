@@ -1 +1,21 @@
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumString};
+use utoipa::ToSchema;
 
+/// How disposals are matched against open lots when computing realized gains.
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize, EnumString, Display, ToSchema,
+)]
+#[serde(rename_all = "UPPERCASE")]
+#[strum(serialize_all = "UPPERCASE")]
+pub enum CostBasisMethod {
+    /// First in, first out
+    #[default]
+    Fifo,
+    /// Last in, first out
+    Lifo,
+    /// Highest cost in, first out
+    Hifo,
+    /// Pooled average cost of all open lots
+    Average,
+}
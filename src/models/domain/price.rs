@@ -0,0 +1,17 @@
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+/// Spot price as stored in the Redis price cache
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedPrice {
+    pub price: Decimal,
+    /// Unix timestamp in seconds of when the price was fetched from the provider
+    pub fetched_at: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct SpotPrice {
+    pub price: Decimal,
+    /// The provider could not be reached and the price is older than the cache TTL
+    pub stale: bool,
+}
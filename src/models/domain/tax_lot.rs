@@ -0,0 +1,109 @@
+use rust_decimal::Decimal;
+use time::OffsetDateTime;
+
+use super::transaction::TxType;
+
+/// An acquisition (BUY or TRANSFER_IN) that still has quantity left to dispose.
+#[derive(Debug, Clone)]
+pub struct TaxLot {
+    pub tx_id: i64,
+    pub acquired_at: OffsetDateTime,
+    pub quantity: Decimal,
+    pub remaining_quantity: Decimal,
+    pub unit_cost: Decimal,
+}
+
+/// The part of a disposal that was closed against a single lot.
+#[derive(Debug, Clone)]
+pub struct LotMatch {
+    pub lot_tx_id: i64,
+    pub acquired_at: OffsetDateTime,
+    pub quantity: Decimal,
+    pub cost_basis: Decimal,
+}
+
+/// A SELL or TRANSFER_OUT together with the lots it was matched against.
+#[derive(Debug, Clone)]
+#[allow(dead_code)]
+pub struct Disposal {
+    pub tx_id: i64,
+    pub tx_type: TxType,
+    pub disposed_at: OffsetDateTime,
+    pub quantity: Decimal,
+    /// Net proceeds after fees, `None` for transfers
+    pub proceeds: Option<Decimal>,
+    pub cost_basis: Decimal,
+    /// Quantity that could not be matched against any open lot
+    pub unmatched_quantity: Decimal,
+    pub matches: Vec<LotMatch>,
+}
+
+impl Disposal {
+    pub fn realized_gain(&self) -> Option<Decimal> {
+        self.proceeds.map(|proceeds| proceeds - self.cost_basis)
+    }
+}
+
+/// One ledger line of a SELL: the slice of the sale closed against a single lot.
+#[derive(Debug, Clone)]
+pub struct RealizedGainEntry {
+    pub sell_tx_id: i64,
+    /// `None` for quantity sold without any open lot to match
+    pub buy_tx_id: Option<i64>,
+    pub quantity: Decimal,
+    pub cost_basis: Decimal,
+    pub proceeds: Decimal,
+    pub acquired_at: Option<OffsetDateTime>,
+    pub disposed_at: OffsetDateTime,
+}
+
+impl RealizedGainEntry {
+    pub fn gain(&self) -> Decimal {
+        self.proceeds - self.cost_basis
+    }
+
+    pub fn holding_period_days(&self) -> Option<i64> {
+        self.acquired_at
+            .map(|acquired_at| (self.disposed_at - acquired_at).whole_days())
+    }
+}
+
+impl Disposal {
+    /// Splits the proceeds of a SELL across its matched lots pro rata by quantity.
+    /// Transfers realize nothing and produce no entries.
+    pub fn ledger_entries(&self) -> Vec<RealizedGainEntry> {
+        let Some(proceeds) = self.proceeds else {
+            return Vec::new();
+        };
+        let share = |quantity: Decimal| {
+            (proceeds * quantity)
+                .checked_div(self.quantity)
+                .unwrap_or(Decimal::ZERO)
+        };
+        let mut entries: Vec<RealizedGainEntry> = self
+            .matches
+            .iter()
+            .map(|m| RealizedGainEntry {
+                sell_tx_id: self.tx_id,
+                buy_tx_id: Some(m.lot_tx_id),
+                quantity: m.quantity,
+                cost_basis: m.cost_basis,
+                proceeds: share(m.quantity),
+                acquired_at: Some(m.acquired_at),
+                disposed_at: self.disposed_at,
+            })
+            .collect();
+        if self.unmatched_quantity > Decimal::ZERO {
+            entries.push(RealizedGainEntry {
+                sell_tx_id: self.tx_id,
+                buy_tx_id: None,
+                quantity: self.unmatched_quantity,
+                cost_basis: Decimal::ZERO,
+                proceeds: share(self.unmatched_quantity),
+                acquired_at: None,
+                disposed_at: self.disposed_at,
+            });
+        }
+        entries
+    }
+}
@@ -5,13 +5,16 @@ use time::{OffsetDateTime, format_description::well_known::Rfc3339};
 use utoipa::ToSchema;
 
 use crate::{
-    models::{common::currency::Currency, dto::transaction::CreateTransactionRequest},
+    models::{
+        common::currency::Currency, database::transaction::TransactionRow,
+        dto::transaction::CreateTransactionRequest,
+    },
     utils::{error::AppError, snowflake::SNOWFLAKE_GENERATOR},
 };
 
 use super::coingecko::RawTransaction;
 
-#[derive(Debug, Serialize, Deserialize, EnumString, Display, ToSchema)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, EnumString, Display, ToSchema)]
 #[serde(rename_all = "UPPERCASE")]
 #[strum(serialize_all = "UPPERCASE")]
 pub enum TxType {
@@ -21,6 +24,27 @@ pub enum TxType {
     TransferOut,
 }
 
+impl TxType {
+    /// Reads the transaction types used by spreadsheets and exchange exports,
+    /// ignoring case, spaces, dashes and underscores.
+    pub fn from_import_label(label: &str) -> Option<Self> {
+        let label: String = label
+            .chars()
+            .filter(|c| !matches!(c, ' ' | '-' | '_'))
+            .collect::<String>()
+            .to_uppercase();
+        match label.as_str() {
+            "BUY" | "BOUGHT" => Some(TxType::Buy),
+            "SELL" | "SOLD" => Some(TxType::Sell),
+            "TRANSFERIN" | "DEPOSIT" | "RECEIVE" | "RECEIVED" => Some(TxType::TransferIn),
+            "TRANSFEROUT" | "WITHDRAW" | "WITHDRAWAL" | "SEND" | "SENT" => {
+                Some(TxType::TransferOut)
+            }
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct BaseTransactionInfo {
     pub id: Option<i64>,
@@ -49,6 +73,32 @@ impl BaseTransactionInfo {
         })
     }
 
+    pub fn from_db_row(row: &TransactionRow) -> Result<Self, AppError> {
+        Ok(Self {
+            id: Some(row.id),
+            external_id: row.external_id.clone(),
+            fees: row.fees,
+            executed_at: row.executed_at,
+            notes: row.notes.clone(),
+            currency: row.currency.parse()?,
+            quantity: row.quantity,
+            price: row.price,
+            tx_type: row.tx_type.parse()?,
+        })
+    }
+
+    /// Whether storing `other` over this transaction would change it. Missing
+    /// notes keep the current ones.
+    pub fn differs_from(&self, other: &BaseTransactionInfo) -> bool {
+        self.tx_type != other.tx_type
+            || self.quantity != other.quantity
+            || self.price != other.price
+            || self.fees != other.fees
+            || self.currency != other.currency
+            || self.executed_at != other.executed_at
+            || (other.notes.is_some() && self.notes != other.notes)
+    }
+
     pub fn from_create_tx_req(req: CreateTransactionRequest) -> Result<Self, AppError> {
         Ok(Self {
             id: Some(SNOWFLAKE_GENERATOR.generate().unwrap()),
@@ -13,6 +13,14 @@ pub enum UserStatus {
     Pending,
 }
 
+/// What a token mailed to a user allows, once
+#[derive(Debug, Clone, Copy, PartialEq, EnumString, Display)]
+#[strum(serialize_all = "snake_case")]
+pub enum UserTokenPurpose {
+    EmailVerification,
+    PasswordReset,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct User {
     pub id: i64,
@@ -8,4 +8,7 @@ pub struct UserSession {
     pub is_active: bool,
     pub created_at: OffsetDateTime,
     pub expires_at: OffsetDateTime,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub last_seen_at: OffsetDateTime,
 }
@@ -1,11 +1,22 @@
 pub mod api_response;
 pub mod asset;
 pub mod auth;
+pub mod backup;
 pub mod coingecko;
+pub mod export;
 pub mod health;
+pub mod import;
+pub mod job;
 pub mod pagination;
+pub mod performance;
+pub mod personal_access_token;
 pub mod portfolio;
 pub mod portfolio_asset;
+pub mod portfolio_snapshot;
+pub mod realized_gain;
+pub mod tax_lot;
+pub mod tax_report;
 pub mod transaction;
+pub mod two_factor;
 pub mod user;
 pub mod user_session;
@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
+use crate::models::{common::currency::Currency, domain::user::UserStatus};
+
 #[derive(Deserialize, ToSchema)]
 pub struct LoginWithPasswordRequest {
     pub email: String,
@@ -12,10 +14,70 @@ pub struct SignUpWithPasswordRequest {
     pub email: String,
     pub password: String,
     pub name: String,
+    /// Default currency of new portfolios, USD when omitted
+    pub base_currency: Option<Currency>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct RefreshTokenRequest {
+    pub refresh_token: String,
 }
 
 #[derive(Serialize, Deserialize, ToSchema)]
 pub struct AuthResponse {
     pub user_id: i64,
+    /// Access token sent as `Authorization: Bearer`
+    pub token: String,
+    /// Seconds until the access token expires
+    pub expires_in: u64,
+    /// Single-use token exchanged at `/auth/refresh` for new tokens
+    pub refresh_token: String,
+}
+
+/// The account is pending until the address is verified with the mailed token
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct SignUpResponse {
+    pub user_id: i64,
+    pub status: UserStatus,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct VerifyEmailRequest {
+    pub token: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct ResendVerificationRequest {
+    pub email: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct ForgotPasswordRequest {
+    pub email: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct ResetPasswordRequest {
     pub token: String,
+    pub password: String,
+}
+
+/// Outcome of a password login. Accounts with two-factor authentication get a
+/// challenge token to send with a code to `/auth/login_with_totp`, the others
+/// get their tokens right away.
+#[derive(Serialize, ToSchema)]
+pub struct LoginResponse {
+    pub two_factor_required: bool,
+    pub challenge_token: Option<String>,
+    /// Seconds left to send the code of the challenge
+    pub challenge_expires_in: Option<u64>,
+    #[serde(flatten)]
+    pub session: Option<AuthResponse>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct LoginWithTotpRequest {
+    pub challenge_token: String,
+    /// Code of the authenticator app, or an unused recovery code
+    pub code: String,
 }
@@ -0,0 +1,192 @@
+use serde::{Deserialize, Serialize};
+use strum::Display;
+use time::OffsetDateTime;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::models::{
+    common::{asset::AssetType, currency::Currency},
+    database::{asset::AssetRow, portfolio::PortfolioRow, transaction::TransactionRow},
+    domain::{import::PlannedTx, portfolio::CostBasisMethod, transaction::TxType},
+};
+use crate::utils::datetime::{deserialize_datetime, serialize_datetime};
+
+/// Version of the backup format written by this server
+pub const BACKUP_VERSION: u32 = 1;
+
+/// An asset used by the backed up portfolios, so it can be created on a server
+/// that does not have it
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct BackupAsset {
+    pub id: String,
+    pub asset_type: AssetType,
+    pub external_id: String,
+    pub source: String,
+    pub symbol: String,
+    pub name: String,
+    pub image: serde_json::Value,
+    pub ext: serde_json::Value,
+}
+
+impl BackupAsset {
+    pub fn from_db_row(row: AssetRow) -> Self {
+        Self {
+            id: row.id,
+            asset_type: row.asset_type.parse().unwrap(),
+            external_id: row.external_id,
+            source: row.source,
+            symbol: row.symbol,
+            name: row.name,
+            image: row.image,
+            ext: row.ext,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct BackupTransaction {
+    pub id: String,
+    pub external_id: Option<String>,
+    pub asset_id: String,
+    pub tx_type: TxType,
+    pub quantity: String,
+    pub price: String,
+    pub fees: String,
+    pub currency: Currency,
+    #[serde(
+        serialize_with = "serialize_datetime",
+        deserialize_with = "deserialize_datetime"
+    )]
+    pub executed_at: OffsetDateTime,
+    pub notes: Option<String>,
+}
+
+impl BackupTransaction {
+    pub fn from_db_row(row: TransactionRow) -> Self {
+        Self {
+            id: row.id.to_string(),
+            external_id: row.external_id,
+            asset_id: row.asset_id,
+            tx_type: row.tx_type.parse().unwrap(),
+            quantity: row.quantity.to_string(),
+            price: row.price.to_string(),
+            fees: row.fees.to_string(),
+            currency: row.currency.parse().unwrap(),
+            executed_at: row.executed_at,
+            notes: row.notes,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct BackupPortfolio {
+    pub id: String,
+    pub name: String,
+    pub cost_basis_method: CostBasisMethod,
+    pub base_currency: Currency,
+    #[serde(
+        serialize_with = "serialize_datetime",
+        deserialize_with = "deserialize_datetime"
+    )]
+    pub created_at: OffsetDateTime,
+    /// Assets held in the portfolio, including the ones without transactions
+    pub asset_ids: Vec<String>,
+    pub transactions: Vec<BackupTransaction>,
+}
+
+impl BackupPortfolio {
+    pub fn from_db_row(row: &PortfolioRow) -> Self {
+        Self {
+            id: row.id.to_string(),
+            name: row.name.clone(),
+            cost_basis_method: row.cost_basis_method.parse().unwrap_or_default(),
+            base_currency: row.base_currency.parse().unwrap_or_default(),
+            created_at: row.created_at,
+            asset_ids: Vec::new(),
+            transactions: Vec::new(),
+        }
+    }
+}
+
+/// Everything a user owns, in one JSON document. Stats, tax lots and snapshots
+/// are left out as they are recomputed on restore.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct AccountBackup {
+    pub version: u32,
+    #[serde(
+        serialize_with = "serialize_datetime",
+        deserialize_with = "deserialize_datetime"
+    )]
+    pub created_at: OffsetDateTime,
+    pub assets: Vec<BackupAsset>,
+    pub portfolios: Vec<BackupPortfolio>,
+}
+
+/// What to do with a backed up portfolio the account already has, the same one
+/// or one with the same name
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum RestoreConflict {
+    /// Keep the existing portfolio, the backed up one is not restored
+    #[default]
+    Skip,
+    /// Restore the backed up portfolio next to the existing one, under a new name
+    Rename,
+    /// Delete the existing portfolio with all its data, then restore
+    Replace,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct RestoreQueryParams {
+    #[serde(default)]
+    pub on_conflict: RestoreConflict,
+}
+
+/// A portfolio to write on restore, with its new IDs
+#[derive(Debug)]
+pub struct RestorePortfolio {
+    pub id: i64,
+    pub name: String,
+    pub cost_basis_method: CostBasisMethod,
+    pub base_currency: Currency,
+    pub created_at: OffsetDateTime,
+    pub asset_ids: Vec<String>,
+    pub transactions: Vec<PlannedTx>,
+}
+
+/// Everything a restore writes, in a single database transaction
+#[derive(Debug)]
+pub struct RestoreBackup {
+    pub owner_id: i64,
+    /// Portfolios replaced by the restore, deleted first
+    pub replaced_portfolio_ids: Vec<i64>,
+    pub assets: Vec<BackupAsset>,
+    pub portfolios: Vec<RestorePortfolio>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Display, ToSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+#[strum(serialize_all = "SCREAMING_SNAKE_CASE")]
+pub enum RestoreStatus {
+    Restored,
+    Renamed,
+    Replaced,
+    Skipped,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RestoredPortfolioResponse {
+    /// ID of the portfolio in the backup
+    pub backup_id: String,
+    /// ID of the restored portfolio, none when skipped
+    pub id: Option<String>,
+    pub name: String,
+    pub status: RestoreStatus,
+    pub transactions: usize,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RestoreResponse {
+    pub portfolios: Vec<RestoredPortfolioResponse>,
+    /// Assets the server did not have, created from the backup
+    pub new_assets: Vec<String>,
+}
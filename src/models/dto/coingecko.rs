@@ -1,7 +1,4 @@
-use crate::{
-    models::common::{asset::AssetImage, currency::Currency},
-    utils::coingecko::filter_market_data_by_currency,
-};
+use crate::{models::common::asset::AssetImage, utils::coingecko::filter_market_data_by_currency};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use utoipa::ToSchema;
@@ -16,6 +13,17 @@ pub struct MarketData {
 }
 
 impl MarketData {
+    /// Market data that only carries current prices, used by offline providers
+    pub fn from_prices(current_price: HashMap<String, f64>) -> Self {
+        Self {
+            current_price,
+            market_cap: HashMap::new(),
+            total_volume: HashMap::new(),
+            high_24h: HashMap::new(),
+            low_24h: HashMap::new(),
+        }
+    }
+
     pub fn limit_as_currency(self) -> Self {
         Self {
             current_price: filter_market_data_by_currency(&self.current_price),
@@ -37,11 +45,41 @@ pub struct CoinDataResponse {
     pub market_data: MarketData,
 }
 
-impl CoinDataResponse {
-    pub fn get_current_price(&self, currency: Currency) -> Option<f64> {
+/// Prices keyed by coin ID, then by lowercase currency
+pub type SimplePriceResponse = HashMap<String, HashMap<String, f64>>;
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ExchangeRate {
+    pub value: f64,
+}
+
+/// Exchange rates against BTC, keyed by lowercase currency
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ExchangeRatesResponse {
+    pub rates: HashMap<String, ExchangeRate>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct MarketChartResponse {
+    /// Pairs of unix timestamp in milliseconds and price
+    pub prices: Vec<(i64, f64)>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct HistoryMarketData {
+    pub current_price: HashMap<String, f64>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct CoinHistoryResponse {
+    pub id: String,
+    pub market_data: Option<HistoryMarketData>,
+}
+
+impl CoinHistoryResponse {
+    pub fn get_price(&self, vs_currency: &str) -> Option<f64> {
         self.market_data
-            .current_price
-            .get(&currency.to_string())
-            .copied()
+            .as_ref()
+            .and_then(|m| m.current_price.get(&vs_currency.to_lowercase()).copied())
     }
 }
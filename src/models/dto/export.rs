@@ -0,0 +1,162 @@
+use serde::{Deserialize, Serialize};
+use strum::Display;
+use time::OffsetDateTime;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::models::{
+    common::{asset::AssetType, currency::Currency},
+    database::{
+        asset::AssetRow, portfolio::PortfolioRow, portfolio_asset::PortfolioAssetRow,
+        transaction::TransactionRow,
+    },
+    domain::{portfolio::CostBasisMethod, transaction::TxType},
+};
+use crate::utils::datetime::{deserialize_datetime, serialize_datetime};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize, Display, ToSchema)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum ExportFormat {
+    Csv,
+    #[default]
+    Json,
+    Xlsx,
+}
+
+/// What a CSV export holds, as it can only have one table
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportSheet {
+    #[default]
+    Transactions,
+    Holdings,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct ExportQueryParams {
+    #[serde(default)]
+    pub format: ExportFormat,
+    /// Table of CSV exports, the other formats hold both
+    #[serde(default)]
+    pub sheet: ExportSheet,
+}
+
+/// A transaction with the metadata of its asset. Values are kept as written in
+/// `TransactionResponse` so nothing is lost.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ExportedTransaction {
+    pub id: String,
+    pub external_id: Option<String>,
+    pub asset_id: String,
+    pub asset_type: AssetType,
+    /// CoinGecko coin ID of crypto assets
+    pub asset_external_id: String,
+    pub asset_symbol: String,
+    pub asset_name: String,
+    pub tx_type: TxType,
+    pub quantity: String,
+    pub price: String,
+    pub fees: String,
+    pub currency: Currency,
+    #[serde(
+        serialize_with = "serialize_datetime",
+        deserialize_with = "deserialize_datetime"
+    )]
+    pub executed_at: OffsetDateTime,
+    pub notes: Option<String>,
+    pub realized_gain: Option<String>,
+}
+
+impl ExportedTransaction {
+    pub fn from_db_row(row: TransactionRow, asset_row: &AssetRow) -> Self {
+        Self {
+            id: row.id.to_string(),
+            external_id: row.external_id,
+            asset_id: row.asset_id,
+            asset_type: asset_row.asset_type.parse().unwrap(),
+            asset_external_id: asset_row.external_id.clone(),
+            asset_symbol: asset_row.symbol.clone(),
+            asset_name: asset_row.name.clone(),
+            tx_type: row.tx_type.parse().unwrap(),
+            quantity: row.quantity.to_string(),
+            price: row.price.to_string(),
+            fees: row.fees.to_string(),
+            currency: row.currency.parse().unwrap(),
+            executed_at: row.executed_at,
+            notes: row.notes,
+            realized_gain: row.realized_gain.map(|gain| gain.to_string()),
+        }
+    }
+}
+
+/// Stats of an asset of the portfolio, in the portfolio base currency
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ExportedHolding {
+    pub asset_id: String,
+    pub asset_type: AssetType,
+    pub asset_symbol: String,
+    pub asset_name: String,
+    pub holding_amount: String,
+    pub total_cost: String,
+    pub avg_buy_price: String,
+    pub sold_amount: String,
+    pub total_revenue: String,
+    pub avg_sell_price: String,
+    pub cost_basis: String,
+    pub realized_pnl: String,
+}
+
+impl ExportedHolding {
+    pub fn from_db_row(row: &PortfolioAssetRow, asset_row: &AssetRow) -> Self {
+        Self {
+            asset_id: row.asset_id.clone(),
+            asset_type: asset_row.asset_type.parse().unwrap(),
+            asset_symbol: asset_row.symbol.clone(),
+            asset_name: asset_row.name.clone(),
+            holding_amount: row.holding_amount.to_string(),
+            total_cost: row.total_cost.to_string(),
+            avg_buy_price: row.avg_buy_price.to_string(),
+            sold_amount: row.sold_amount.to_string(),
+            total_revenue: row.total_revenue.to_string(),
+            avg_sell_price: row.avg_sell_price.to_string(),
+            cost_basis: row.cost_basis.to_string(),
+            realized_pnl: row.realized_pnl.to_string(),
+        }
+    }
+}
+
+/// Everything held in a portfolio, in the JSON export
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct PortfolioExport {
+    pub id: String,
+    pub name: String,
+    pub cost_basis_method: CostBasisMethod,
+    pub base_currency: Currency,
+    #[serde(
+        serialize_with = "serialize_datetime",
+        deserialize_with = "deserialize_datetime"
+    )]
+    pub created_at: OffsetDateTime,
+    #[serde(
+        serialize_with = "serialize_datetime",
+        deserialize_with = "deserialize_datetime"
+    )]
+    pub exported_at: OffsetDateTime,
+    pub holdings: Vec<ExportedHolding>,
+    pub transactions: Vec<ExportedTransaction>,
+}
+
+impl PortfolioExport {
+    pub fn from_db_row(row: &PortfolioRow) -> Self {
+        Self {
+            id: row.id.to_string(),
+            name: row.name.clone(),
+            cost_basis_method: row.cost_basis_method.parse().unwrap_or_default(),
+            base_currency: row.base_currency.parse().unwrap_or_default(),
+            created_at: row.created_at,
+            exported_at: OffsetDateTime::now_utc(),
+            holdings: Vec::new(),
+            transactions: Vec::new(),
+        }
+    }
+}
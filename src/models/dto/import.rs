@@ -0,0 +1,354 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::biz::asset::generate_asset_id;
+use crate::models::{
+    common::currency::Currency,
+    database::{import_batch::ImportBatchRow, import_job::ImportJobRow},
+    domain::{
+        import::{
+            ImportBatchStatus, ImportChangeAction, ImportJobStatus, ImportPlan, ImportRow,
+            ImportSource, PlannedTx,
+        },
+        transaction::{BaseTransactionInfo, TxType},
+    },
+    dto::asset::CreateAssetRepo,
+};
+use crate::utils::datetime::{serialize_datetime, serialize_optional_datetime};
+
+use super::pagination::{NumberPaginationResponse, default_limit, default_page};
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct ImportQueryParams {
+    /// Only compute what the import would change, nothing is written
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+fn default_decimal_separator() -> char {
+    '.'
+}
+
+fn default_delimiter() -> char {
+    ','
+}
+
+/// Header names of the CSV columns holding each transaction field
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CsvColumnMapping {
+    pub date: String,
+    pub tx_type: String,
+    pub quantity: String,
+    pub price: String,
+    pub fee: Option<String>,
+    pub currency: Option<String>,
+    /// Column holding the asset ID, CoinGecko coin ID or symbol
+    pub asset: String,
+    pub notes: Option<String>,
+    /// Column holding a unique ID of the row, used to skip rows imported before
+    pub external_id: Option<String>,
+    /// Format such as `[day]/[month]/[year] [hour]:[minute]`, RFC 3339 when missing.
+    /// Dates without an offset are read as UTC
+    pub date_format: Option<String>,
+    #[serde(default = "default_decimal_separator")]
+    pub decimal_separator: char,
+    #[serde(default = "default_delimiter")]
+    pub delimiter: char,
+    /// Currency of rows without a currency column, the portfolio base currency when missing
+    pub default_currency: Option<Currency>,
+    /// CoinGecko coin ID of symbols of assets not held in the portfolio, e.g.
+    /// `{"QNT": "quant-network"}`, for symbols that are not well known
+    #[serde(default)]
+    pub symbols: HashMap<String, String>,
+}
+
+/// Options of exchange trade history imports
+#[derive(Debug, Default, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ExchangeImportOptions {
+    /// CoinGecko coin ID of exchange symbols, e.g. `{"QNT": "quant-network"}`, for
+    /// symbols not held in the portfolio or shared by several coins
+    #[serde(default)]
+    pub symbols: HashMap<String, String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ImportRowResponse {
+    /// Position of the row in the file, the header being row 1
+    pub line: usize,
+    pub asset_id: Option<String>,
+    pub external_id: Option<String>,
+    pub tx_type: Option<TxType>,
+    pub quantity: Option<String>,
+    pub price: Option<String>,
+    pub fees: Option<String>,
+    pub currency: Option<Currency>,
+    #[serde(serialize_with = "serialize_optional_datetime")]
+    pub executed_at: Option<OffsetDateTime>,
+    pub errors: Vec<String>,
+}
+
+impl ImportRowResponse {
+    pub fn from_row(row: &ImportRow) -> Self {
+        Self {
+            line: row.line,
+            asset_id: row.asset_id.clone(),
+            external_id: row.external_id.clone(),
+            tx_type: row.tx_type,
+            quantity: row.quantity.map(|v| v.to_string()),
+            price: row.price.map(|v| v.to_string()),
+            fees: row.fees.map(|v| v.to_string()),
+            currency: row.currency,
+            executed_at: row.executed_at,
+            errors: row.errors.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ImportTxResponse {
+    pub asset_id: String,
+    pub external_id: Option<String>,
+    pub tx_type: TxType,
+    pub quantity: String,
+    pub price: String,
+    pub fees: String,
+    pub currency: Currency,
+    #[serde(serialize_with = "serialize_datetime")]
+    pub executed_at: OffsetDateTime,
+    pub notes: Option<String>,
+}
+
+impl ImportTxResponse {
+    pub fn from_tx_info(asset_id: &str, tx: &BaseTransactionInfo) -> Self {
+        Self {
+            asset_id: asset_id.to_string(),
+            external_id: tx.external_id.clone(),
+            tx_type: tx.tx_type,
+            quantity: tx.quantity.to_string(),
+            price: tx.price.to_string(),
+            fees: tx.fees.to_string(),
+            currency: tx.currency,
+            executed_at: tx.executed_at,
+            notes: tx.notes.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ImportTxChangeResponse {
+    pub id: String,
+    pub before: ImportTxResponse,
+    pub after: ImportTxResponse,
+}
+
+/// What an import changes in the portfolio. Dry runs come with a token that
+/// commits exactly this plan, unless some rows are invalid.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ImportResponse {
+    pub dry_run: bool,
+    pub token: Option<String>,
+    /// Batch recording the import, to roll it back
+    pub batch_id: Option<String>,
+    /// Assets created because the catalog did not have them
+    pub new_assets: Vec<String>,
+    /// Assets attached to the portfolio by the import
+    pub new_portfolio_assets: Vec<String>,
+    pub created: Vec<ImportTxResponse>,
+    pub updated: Vec<ImportTxChangeResponse>,
+    pub unchanged: Vec<ImportTxResponse>,
+    pub invalid_rows: usize,
+    /// Every parsed row with its validation errors, for imports of tabular files
+    pub rows: Vec<ImportRowResponse>,
+}
+
+impl ImportResponse {
+    pub fn from_plan(plan: &ImportPlan, dry_run: bool, token: Option<String>) -> Self {
+        Self {
+            dry_run,
+            token,
+            batch_id: None,
+            new_assets: plan
+                .new_assets
+                .iter()
+                .map(|a| generate_asset_id(&a.asset_type, &a.external_id))
+                .collect(),
+            new_portfolio_assets: plan.new_portfolio_assets.clone(),
+            created: plan
+                .creates
+                .iter()
+                .map(|p| ImportTxResponse::from_tx_info(&p.asset_id, &p.tx))
+                .collect(),
+            updated: plan
+                .updates
+                .iter()
+                .map(|u| ImportTxChangeResponse {
+                    id: u.tx_id.to_string(),
+                    before: ImportTxResponse::from_tx_info(&u.asset_id, &u.before),
+                    after: ImportTxResponse::from_tx_info(&u.asset_id, &u.after),
+                })
+                .collect(),
+            unchanged: plan
+                .unchanged
+                .iter()
+                .map(|p| ImportTxResponse::from_tx_info(&p.asset_id, &p.tx))
+                .collect(),
+            invalid_rows: 0,
+            rows: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct CreateImportBatch {
+    pub user_id: i64,
+    pub portfolio_id: i64,
+    pub source: String,
+    pub file_hash: String,
+    pub created_count: i32,
+    pub updated_count: i32,
+    pub unchanged_count: i32,
+}
+
+/// Attempt of an import job, the worker holds the job while it is the latest
+/// attempt and the job is running
+#[derive(Debug, Clone, Copy)]
+pub struct ImportJobClaim {
+    pub job_id: i64,
+    pub attempt: i32,
+}
+
+/// Everything one import writes, in a single database transaction
+#[derive(Debug)]
+pub struct ApplyImportBatch {
+    pub batch: CreateImportBatch,
+    pub new_assets: Vec<CreateAssetRepo>,
+    pub new_portfolio_assets: Vec<String>,
+    pub creates: Vec<PlannedTx>,
+    /// Transactions to overwrite, by ID
+    pub updates: Vec<(i64, BaseTransactionInfo)>,
+    pub changes: Vec<CreateImportBatchChange>,
+    /// Job writing the import, marked succeeded with the batch
+    pub job: Option<ImportJobClaim>,
+}
+
+#[derive(Debug)]
+pub struct CreateImportBatchChange {
+    pub tx_id: i64,
+    pub asset_id: String,
+    pub action: ImportChangeAction,
+    pub previous: Option<serde_json::Value>,
+    pub previous_batch_id: Option<i64>,
+    /// The transaction as the import wrote it
+    pub after: serde_json::Value,
+}
+
+#[derive(Debug)]
+pub struct CreateImportJob {
+    pub user_id: i64,
+    pub portfolio_id: i64,
+    pub source: ImportSource,
+    pub file_contents: String,
+    pub options: Option<serde_json::Value>,
+}
+
+/// Progress of an import job, `batch_id` is set once its transactions are written
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ImportJobResponse {
+    pub id: String,
+    pub portfolio_id: String,
+    pub source: ImportSource,
+    pub status: ImportJobStatus,
+    pub attempts: i32,
+    pub rows_parsed: i32,
+    /// Transactions created or updated, set once the import is written
+    pub rows_inserted: i32,
+    pub error_count: i32,
+    pub errors: Vec<String>,
+    pub batch_id: Option<String>,
+    #[serde(serialize_with = "serialize_datetime")]
+    pub created_at: OffsetDateTime,
+    #[serde(serialize_with = "serialize_optional_datetime")]
+    pub started_at: Option<OffsetDateTime>,
+    #[serde(serialize_with = "serialize_optional_datetime")]
+    pub finished_at: Option<OffsetDateTime>,
+    #[serde(serialize_with = "serialize_datetime")]
+    pub updated_at: OffsetDateTime,
+}
+
+impl ImportJobResponse {
+    pub fn from_db_row(row: ImportJobRow) -> Self {
+        Self {
+            id: row.id.to_string(),
+            portfolio_id: row.portfolio_id.to_string(),
+            source: row.source.parse().unwrap(),
+            status: row.status.parse().unwrap(),
+            attempts: row.attempts,
+            rows_parsed: row.rows_parsed,
+            rows_inserted: row.rows_inserted,
+            error_count: row.error_count,
+            errors: row.errors,
+            batch_id: row.batch_id.map(|id| id.to_string()),
+            created_at: row.created_at,
+            started_at: row.started_at,
+            finished_at: row.finished_at,
+            updated_at: row.updated_at,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct ImportBatchQueryParams {
+    #[serde(default = "default_page")]
+    pub page: u32,
+    #[serde(default = "default_limit")]
+    pub limit: u32,
+    pub portfolio_id: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ImportBatchResponse {
+    pub id: String,
+    pub portfolio_id: String,
+    pub source: String,
+    pub file_hash: String,
+    pub created_count: i32,
+    pub updated_count: i32,
+    pub unchanged_count: i32,
+    /// Assets the import added to the catalog
+    pub new_assets: Vec<String>,
+    /// Assets the import attached to the portfolio
+    pub new_portfolio_assets: Vec<String>,
+    pub status: ImportBatchStatus,
+    #[serde(serialize_with = "serialize_datetime")]
+    pub created_at: OffsetDateTime,
+    #[serde(serialize_with = "serialize_datetime")]
+    pub updated_at: OffsetDateTime,
+}
+
+impl ImportBatchResponse {
+    pub fn from_db_row(row: ImportBatchRow) -> Self {
+        Self {
+            id: row.id.to_string(),
+            portfolio_id: row.portfolio_id.to_string(),
+            source: row.source,
+            file_hash: row.file_hash,
+            created_count: row.created_count,
+            updated_count: row.updated_count,
+            unchanged_count: row.unchanged_count,
+            new_assets: row.new_asset_ids,
+            new_portfolio_assets: row.new_portfolio_asset_ids,
+            status: row.status.parse().unwrap(),
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ImportBatchListResponse {
+    pub number_pagination: NumberPaginationResponse,
+    pub items: Vec<ImportBatchResponse>,
+}
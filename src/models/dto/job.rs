@@ -0,0 +1,51 @@
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::models::database::job_run::JobRunRow;
+use crate::models::domain::job::{JobName, JobStatus, JobTrigger};
+use crate::utils::datetime::{serialize_datetime, serialize_optional_datetime};
+
+use super::pagination::{NumberPaginationResponse, default_limit, default_page};
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct JobRunQueryParams {
+    #[serde(default = "default_page")]
+    pub page: u32,
+    #[serde(default = "default_limit")]
+    pub limit: u32,
+    pub job_name: Option<JobName>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct JobRunResponse {
+    pub id: String,
+    pub job_name: JobName,
+    pub triggered_by: JobTrigger,
+    pub status: JobStatus,
+    pub error: Option<String>,
+    #[serde(serialize_with = "serialize_datetime")]
+    pub started_at: OffsetDateTime,
+    #[serde(serialize_with = "serialize_optional_datetime")]
+    pub finished_at: Option<OffsetDateTime>,
+}
+
+impl JobRunResponse {
+    pub fn from_db_row(row: JobRunRow) -> Self {
+        Self {
+            id: row.id.to_string(),
+            job_name: row.job_name.parse().unwrap(),
+            triggered_by: row.triggered_by.parse().unwrap(),
+            status: row.status.parse().unwrap(),
+            error: row.error,
+            started_at: row.started_at,
+            finished_at: row.finished_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct JobRunListResponse {
+    pub number_pagination: NumberPaginationResponse,
+    pub items: Vec<JobRunResponse>,
+}
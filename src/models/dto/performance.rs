@@ -0,0 +1,57 @@
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumIter, EnumString};
+use utoipa::{IntoParams, ToSchema};
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Serialize, Deserialize, EnumString, EnumIter, Display, ToSchema,
+)]
+pub enum PerformancePeriod {
+    #[serde(rename = "1D")]
+    #[strum(serialize = "1D")]
+    OneDay,
+    #[serde(rename = "7D")]
+    #[strum(serialize = "7D")]
+    SevenDays,
+    #[serde(rename = "MTD")]
+    #[strum(serialize = "MTD")]
+    MonthToDate,
+    #[serde(rename = "YTD")]
+    #[strum(serialize = "YTD")]
+    YearToDate,
+    #[serde(rename = "1Y")]
+    #[strum(serialize = "1Y")]
+    OneYear,
+    #[serde(rename = "ALL")]
+    #[strum(serialize = "ALL")]
+    AllTime,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct PerformanceQueryParams {
+    /// Only compute this period, all periods when omitted
+    pub period: Option<PerformancePeriod>,
+    /// Only compute the performance of this asset of the portfolio
+    pub asset_id: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PerformanceResponseItem {
+    pub period: PerformancePeriod,
+    pub start_date: String,
+    pub end_date: String,
+    pub start_value: f64,
+    pub end_value: f64,
+    pub net_cash_flow: f64,
+    /// Chained daily return, unaffected by the timing of deposits and withdrawals
+    pub time_weighted_return: Option<f64>,
+    /// Annualized internal rate of return (XIRR) of the cash flows
+    pub money_weighted_return: Option<f64>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PerformanceResponse {
+    pub portfolio_id: String,
+    pub asset_id: Option<String>,
+    pub currency: String,
+    pub items: Vec<PerformanceResponseItem>,
+}
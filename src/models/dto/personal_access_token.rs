@@ -0,0 +1,76 @@
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use utoipa::ToSchema;
+
+use crate::models::{
+    database::personal_access_token::PersonalAccessTokenRow, domain::auth::TokenScope,
+};
+use crate::utils::datetime::{
+    deserialize_optional_datetime, serialize_datetime, serialize_optional_datetime,
+};
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreatePersonalAccessTokenRequest {
+    pub name: String,
+    pub scopes: Vec<TokenScope>,
+    /// RFC 3339 time the token stops working, never when omitted
+    #[serde(default, deserialize_with = "deserialize_optional_datetime")]
+    pub expires_at: Option<OffsetDateTime>,
+}
+
+#[derive(Debug)]
+pub struct CreatePersonalAccessToken {
+    pub user_id: i64,
+    pub name: String,
+    pub token_hash: String,
+    pub token_prefix: String,
+    pub scopes: Vec<TokenScope>,
+    pub expires_at: Option<OffsetDateTime>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PersonalAccessTokenResponse {
+    pub id: String,
+    pub name: String,
+    /// Start of the token, to tell tokens apart
+    pub token_prefix: String,
+    pub scopes: Vec<TokenScope>,
+    #[serde(serialize_with = "serialize_datetime")]
+    pub created_at: OffsetDateTime,
+    #[serde(serialize_with = "serialize_optional_datetime")]
+    pub expires_at: Option<OffsetDateTime>,
+    #[serde(serialize_with = "serialize_optional_datetime")]
+    pub last_used_at: Option<OffsetDateTime>,
+}
+
+impl PersonalAccessTokenResponse {
+    pub fn from_db_row(row: PersonalAccessTokenRow) -> Self {
+        Self {
+            id: row.id.to_string(),
+            name: row.name,
+            token_prefix: row.token_prefix,
+            scopes: row
+                .scopes
+                .iter()
+                .filter_map(|scope| scope.parse().ok())
+                .collect(),
+            created_at: row.created_at,
+            expires_at: row.expires_at,
+            last_used_at: row.last_used_at,
+        }
+    }
+}
+
+/// A new token, the only time its value is shown
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CreatedPersonalAccessTokenResponse {
+    /// Sent as `Authorization: Bearer`
+    pub token: String,
+    #[serde(flatten)]
+    pub details: PersonalAccessTokenResponse,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PersonalAccessTokenListResponse {
+    pub items: Vec<PersonalAccessTokenResponse>,
+}
@@ -1,19 +1,36 @@
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
-use crate::models::database::portfolio::PortfolioRow;
+use crate::models::{
+    common::currency::Currency, database::portfolio::PortfolioRow,
+    domain::portfolio::CostBasisMethod,
+};
 
 use super::portfolio_asset::PortfolioAssetResponse;
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct CreatePortfolioRequest {
     pub name: String,
+    pub cost_basis_method: Option<CostBasisMethod>,
+    /// Defaults to the base currency of the user
+    pub base_currency: Option<Currency>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct UpdatePortfolioRequest {
+    pub name: Option<String>,
+    pub cost_basis_method: Option<CostBasisMethod>,
+    pub base_currency: Option<Currency>,
 }
 
 #[derive(Debug, Serialize, ToSchema)]
 pub struct PortfolioResponse {
     pub id: String,
     pub name: String,
+    pub cost_basis_method: CostBasisMethod,
+    pub base_currency: Currency,
+    /// At least one asset is valued with a stale cached price
+    pub prices_stale: bool,
     pub assets: Vec<PortfolioAssetResponse>,
 }
 
@@ -21,6 +38,8 @@ pub struct PortfolioResponse {
 pub struct BriefPortfolioResponse {
     pub id: String,
     pub name: String,
+    pub cost_basis_method: CostBasisMethod,
+    pub base_currency: Currency,
 }
 
 impl BriefPortfolioResponse {
@@ -28,6 +47,8 @@ impl BriefPortfolioResponse {
         Self {
             id: row.id.to_string(),
             name: row.name,
+            cost_basis_method: row.cost_basis_method.parse().unwrap_or_default(),
+            base_currency: row.base_currency.parse().unwrap_or_default(),
         }
     }
 }
@@ -16,11 +16,16 @@ pub struct UpdatePortfolioAsset {
     pub sold_amount: Decimal,
     pub total_revenue: Decimal,
     pub avg_sell_price: Decimal,
+    pub cost_basis: Decimal,
+    pub realized_pnl: Decimal,
+    pub open_lots: Vec<TaxLot>,
+    pub disposals: Vec<Disposal>,
 }
 
 use crate::models::{
     common::asset::{AssetImage, AssetType},
     database::{asset::AssetRow, portfolio_asset::PortfolioAssetRow},
+    domain::tax_lot::{Disposal, TaxLot},
 };
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
@@ -30,17 +35,31 @@ pub struct CreatePortfolioRequest {
 
 #[derive(Debug, Serialize, ToSchema)]
 pub struct PortfolioAssetStat {
+    pub current_price: f64,
+    /// The current price is a cached value, the price provider could not be reached
+    pub price_stale: bool,
     pub holding_amount: f64,
     pub holding_value: f64,
     pub total_cost: f64,
     pub avg_buy_price: f64,
     pub total_revenue: f64,
     pub avg_sell_price: f64,
+    pub cost_basis: f64,
+    pub realized_pnl: f64,
+    pub unrealized_pnl: f64,
     pub profit_loss: f64,
 }
 impl PortfolioAssetStat {
-    pub fn from_db_row(pfl_asset_row: &PortfolioAssetRow, current_price: Decimal) -> Self {
+    pub fn from_db_row(
+        pfl_asset_row: &PortfolioAssetRow,
+        current_price: Decimal,
+        price_stale: bool,
+    ) -> Self {
+        let unrealized_pnl =
+            pfl_asset_row.holding_amount * current_price - pfl_asset_row.cost_basis;
         Self {
+            current_price: current_price.to_f64().unwrap(),
+            price_stale,
             holding_amount: pfl_asset_row.holding_amount.to_f64().unwrap(),
             holding_value: (pfl_asset_row.holding_amount * current_price)
                 .to_f64()
@@ -49,9 +68,10 @@ impl PortfolioAssetStat {
             avg_buy_price: pfl_asset_row.avg_buy_price.to_f64().unwrap(),
             total_revenue: pfl_asset_row.total_revenue.to_f64().unwrap(),
             avg_sell_price: pfl_asset_row.avg_sell_price.to_f64().unwrap(),
-            profit_loss: (pfl_asset_row.holding_amount * current_price
-                + pfl_asset_row.total_revenue
-                - pfl_asset_row.total_cost)
+            cost_basis: pfl_asset_row.cost_basis.to_f64().unwrap(),
+            realized_pnl: pfl_asset_row.realized_pnl.to_f64().unwrap(),
+            unrealized_pnl: unrealized_pnl.to_f64().unwrap(),
+            profit_loss: (pfl_asset_row.realized_pnl + unrealized_pnl)
                 .to_f64()
                 .unwrap(),
         }
@@ -73,6 +93,7 @@ impl PortfolioAssetResponse {
         asset_row: &AssetRow,
         pfl_asset_row: &PortfolioAssetRow,
         current_price: Decimal,
+        price_stale: bool,
     ) -> Self {
         Self {
             id: asset_row.id.to_string(),
@@ -80,7 +101,7 @@ impl PortfolioAssetResponse {
             symbol: asset_row.symbol.clone(),
             name: asset_row.name.clone(),
             image: serde_json::from_value(asset_row.image.clone()).unwrap(),
-            stats: PortfolioAssetStat::from_db_row(pfl_asset_row, current_price),
+            stats: PortfolioAssetStat::from_db_row(pfl_asset_row, current_price, price_stale),
         }
     }
 }
@@ -0,0 +1,76 @@
+use rust_decimal::{Decimal, prelude::ToPrimitive};
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumString};
+use time::{Date, OffsetDateTime};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::models::database::portfolio_snapshot::PortfolioSnapshotRow;
+use crate::utils::datetime::deserialize_optional_datetime;
+
+#[derive(Debug)]
+pub struct CreatePortfolioSnapshot {
+    pub portfolio_id: i64,
+    pub snapshot_date: Date,
+    pub currency: String,
+    pub holdings_value: Decimal,
+    pub cost_basis: Decimal,
+    pub realized_pnl: Decimal,
+    pub unrealized_pnl: Decimal,
+}
+
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize, EnumString, Display, ToSchema,
+)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum HistoryInterval {
+    #[default]
+    Day,
+    Week,
+    Month,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct PortfolioHistoryQueryParams {
+    /// RFC 3339 start of the range, defaults to 30 days before `to`
+    #[serde(default, deserialize_with = "deserialize_optional_datetime")]
+    pub from: Option<OffsetDateTime>,
+    /// RFC 3339 end of the range, defaults to now
+    #[serde(default, deserialize_with = "deserialize_optional_datetime")]
+    pub to: Option<OffsetDateTime>,
+    #[serde(default)]
+    pub interval: HistoryInterval,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PortfolioHistoryPoint {
+    pub date: String,
+    pub holdings_value: f64,
+    pub cost_basis: f64,
+    pub realized_pnl: f64,
+    pub unrealized_pnl: f64,
+    pub profit_loss: f64,
+}
+
+impl PortfolioHistoryPoint {
+    pub fn from_db_row(row: &PortfolioSnapshotRow) -> Self {
+        Self {
+            date: row.snapshot_date.to_string(),
+            holdings_value: row.holdings_value.to_f64().unwrap_or_default(),
+            cost_basis: row.cost_basis.to_f64().unwrap_or_default(),
+            realized_pnl: row.realized_pnl.to_f64().unwrap_or_default(),
+            unrealized_pnl: row.unrealized_pnl.to_f64().unwrap_or_default(),
+            profit_loss: (row.realized_pnl + row.unrealized_pnl)
+                .to_f64()
+                .unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PortfolioHistoryResponse {
+    pub portfolio_id: String,
+    pub currency: String,
+    pub interval: HistoryInterval,
+    pub points: Vec<PortfolioHistoryPoint>,
+}
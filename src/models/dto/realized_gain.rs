@@ -0,0 +1,103 @@
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::models::database::realized_gain::{RealizedGainRow, RealizedGainSummaryRow};
+use crate::utils::datetime::{
+    deserialize_optional_datetime, serialize_datetime, serialize_optional_datetime,
+};
+
+use super::pagination::{NumberPaginationResponse, default_limit, default_page};
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct RealizedGainQueryParams {
+    #[serde(default = "default_page")]
+    pub page: u32,
+    #[serde(default = "default_limit")]
+    pub limit: u32,
+    pub asset_id: Option<String>,
+    /// Only include disposals at or after this RFC 3339 time
+    #[serde(default, deserialize_with = "deserialize_optional_datetime")]
+    pub from: Option<OffsetDateTime>,
+    /// Only include disposals before this RFC 3339 time
+    #[serde(default, deserialize_with = "deserialize_optional_datetime")]
+    pub to: Option<OffsetDateTime>,
+}
+
+#[derive(Debug)]
+pub struct RealizedGainFilter {
+    pub asset_id: Option<String>,
+    pub from: Option<OffsetDateTime>,
+    pub to: Option<OffsetDateTime>,
+}
+
+impl RealizedGainFilter {
+    pub fn from_params(params: &RealizedGainQueryParams) -> Self {
+        Self {
+            asset_id: params.asset_id.clone(),
+            from: params.from,
+            to: params.to,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RealizedGainResponse {
+    pub id: String,
+    pub asset_id: String,
+    pub sell_tx_id: String,
+    pub buy_tx_id: Option<String>,
+    pub quantity: String,
+    pub cost_basis: String,
+    pub proceeds: String,
+    pub gain: String,
+    #[serde(serialize_with = "serialize_optional_datetime")]
+    pub acquired_at: Option<OffsetDateTime>,
+    #[serde(serialize_with = "serialize_datetime")]
+    pub disposed_at: OffsetDateTime,
+    pub holding_period_days: Option<i64>,
+}
+
+impl RealizedGainResponse {
+    pub fn from_db_row(row: RealizedGainRow) -> Self {
+        Self {
+            id: row.id.to_string(),
+            asset_id: row.asset_id,
+            sell_tx_id: row.sell_tx_id.to_string(),
+            buy_tx_id: row.buy_tx_id.map(|id| id.to_string()),
+            quantity: row.quantity.to_string(),
+            cost_basis: row.cost_basis.to_string(),
+            proceeds: row.proceeds.to_string(),
+            gain: row.gain.to_string(),
+            acquired_at: row.acquired_at,
+            disposed_at: row.disposed_at,
+            holding_period_days: row.holding_period_days,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RealizedGainSummaryResponse {
+    pub total_quantity: String,
+    pub total_cost_basis: String,
+    pub total_proceeds: String,
+    pub total_gain: String,
+}
+
+impl RealizedGainSummaryResponse {
+    pub fn from_db_row(row: &RealizedGainSummaryRow) -> Self {
+        Self {
+            total_quantity: row.total_quantity.to_string(),
+            total_cost_basis: row.total_cost_basis.to_string(),
+            total_proceeds: row.total_proceeds.to_string(),
+            total_gain: row.total_gain.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RealizedGainListResponse {
+    pub number_pagination: NumberPaginationResponse,
+    pub summary: RealizedGainSummaryResponse,
+    pub items: Vec<RealizedGainResponse>,
+}
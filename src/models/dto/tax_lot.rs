@@ -0,0 +1,44 @@
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::models::database::tax_lot::TaxLotRow;
+use crate::utils::datetime::serialize_datetime;
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct TaxLotQueryParams {
+    pub asset_id: Option<String>,
+}
+
+/// An acquisition not yet fully disposed of, costs are in the portfolio base currency
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TaxLotResponse {
+    pub tx_id: String,
+    pub asset_id: String,
+    #[serde(serialize_with = "serialize_datetime")]
+    pub acquired_at: OffsetDateTime,
+    pub quantity: String,
+    pub remaining_quantity: String,
+    /// Cost per unit, fees included
+    pub unit_cost: String,
+    pub cost_basis: String,
+}
+
+impl TaxLotResponse {
+    pub fn from_db_row(row: TaxLotRow) -> Self {
+        Self {
+            tx_id: row.tx_id.to_string(),
+            asset_id: row.asset_id,
+            acquired_at: row.acquired_at,
+            quantity: row.quantity.to_string(),
+            remaining_quantity: row.remaining_quantity.to_string(),
+            unit_cost: row.unit_cost.to_string(),
+            cost_basis: (row.remaining_quantity * row.unit_cost).to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TaxLotListResponse {
+    pub items: Vec<TaxLotResponse>,
+}
@@ -0,0 +1,114 @@
+use serde::{Deserialize, Serialize};
+use strum::Display;
+use time::OffsetDateTime;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::models::database::realized_gain::TaxReportGainRow;
+use crate::utils::datetime::{serialize_datetime, serialize_optional_datetime};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize, Display, ToSchema)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum TaxReportFormat {
+    Csv,
+    #[default]
+    Json,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct TaxReportQueryParams {
+    /// Calendar year of the disposals, in UTC
+    pub year: i32,
+    /// Only report this portfolio, every portfolio of the user when missing
+    pub portfolio_id: Option<String>,
+    #[serde(default)]
+    pub format: TaxReportFormat,
+}
+
+/// Short-term unless the lot was held longer than the long-term threshold.
+/// Sales without a matching lot have no holding period and are short-term.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum HoldingTerm {
+    ShortTerm,
+    LongTerm,
+}
+
+impl HoldingTerm {
+    pub fn classify(holding_period_days: Option<i64>, long_term_threshold_days: i64) -> Self {
+        match holding_period_days {
+            Some(days) if days > long_term_threshold_days => HoldingTerm::LongTerm,
+            _ => HoldingTerm::ShortTerm,
+        }
+    }
+}
+
+/// One disposal of a lot, amounts in the base currency of its portfolio
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TaxReportItem {
+    pub id: String,
+    pub portfolio_id: String,
+    pub portfolio_name: String,
+    pub asset_id: String,
+    pub asset_symbol: String,
+    pub asset_name: String,
+    pub sell_tx_id: String,
+    pub buy_tx_id: Option<String>,
+    pub quantity: String,
+    /// `None` for quantity sold without any open lot to match
+    #[serde(serialize_with = "serialize_optional_datetime")]
+    pub acquired_at: Option<OffsetDateTime>,
+    #[serde(serialize_with = "serialize_datetime")]
+    pub disposed_at: OffsetDateTime,
+    pub holding_period_days: Option<i64>,
+    pub term: HoldingTerm,
+    pub proceeds: String,
+    pub cost_basis: String,
+    pub gain: String,
+    pub currency: String,
+}
+
+impl TaxReportItem {
+    pub fn from_db_row(row: TaxReportGainRow, long_term_threshold_days: i64) -> Self {
+        Self {
+            id: row.id.to_string(),
+            portfolio_id: row.portfolio_id.to_string(),
+            portfolio_name: row.portfolio_name,
+            asset_id: row.asset_id,
+            asset_symbol: row.asset_symbol,
+            asset_name: row.asset_name,
+            sell_tx_id: row.sell_tx_id.to_string(),
+            buy_tx_id: row.buy_tx_id.map(|id| id.to_string()),
+            quantity: row.quantity.normalize().to_string(),
+            acquired_at: row.acquired_at,
+            disposed_at: row.disposed_at,
+            holding_period_days: row.holding_period_days,
+            term: HoldingTerm::classify(row.holding_period_days, long_term_threshold_days),
+            proceeds: row.proceeds.to_string(),
+            cost_basis: row.cost_basis.to_string(),
+            gain: row.gain.to_string(),
+            currency: row.base_currency,
+        }
+    }
+}
+
+/// Sums of the disposals of one term in one currency
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TaxReportTotal {
+    pub term: HoldingTerm,
+    pub currency: String,
+    pub items: usize,
+    pub proceeds: String,
+    pub cost_basis: String,
+    pub gain: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TaxReportResponse {
+    pub year: i32,
+    pub portfolio_id: Option<String>,
+    pub long_term_threshold_days: i64,
+    pub totals: Vec<TaxReportTotal>,
+    /// Short-term disposals first, then long-term ones, each by disposal date
+    pub items: Vec<TaxReportItem>,
+}
@@ -16,6 +16,7 @@ use super::pagination::{NumberPaginationResponse, default_limit, default_page};
 pub struct CreateMultiTransaction {
     pub portfolio_id: i64,
     pub asset_id: String,
+    pub import_batch_id: Option<i64>,
     pub transactions: Vec<BaseTransactionInfo>,
 }
 
@@ -83,6 +84,7 @@ pub struct TransactionResponse {
     pub quantity: String,
     pub price: String,
     pub tx_type: TxType,
+    pub realized_gain: Option<String>,
 }
 impl TransactionResponse {
     pub fn from_db_row(row: TransactionRow) -> Self {
@@ -98,6 +100,7 @@ impl TransactionResponse {
             quantity: row.quantity.to_string(),
             price: row.price.to_string(),
             tx_type: row.tx_type.parse().unwrap(),
+            realized_gain: row.realized_gain.map(|gain| gain.to_string()),
         }
     }
 }
@@ -0,0 +1,36 @@
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use utoipa::ToSchema;
+
+use crate::utils::datetime::serialize_optional_datetime;
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TwoFactorStatusResponse {
+    pub enabled: bool,
+    #[serde(serialize_with = "serialize_optional_datetime")]
+    pub enabled_at: Option<OffsetDateTime>,
+    /// Recovery codes not used yet
+    pub recovery_codes_left: i64,
+}
+
+/// Secret of an enrollment, added to an authenticator app by scanning the URI
+/// as a QR code or typing the secret
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TotpEnrollResponse {
+    /// Base32 secret
+    pub secret: String,
+    pub otpauth_uri: String,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct TotpCodeRequest {
+    /// Code of the authenticator app, recovery codes are accepted where an
+    /// enabled two-factor authentication is required
+    pub code: String,
+}
+
+/// Single-use codes replacing the authenticator app, shown only once
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RecoveryCodesResponse {
+    pub recovery_codes: Vec<String>,
+}
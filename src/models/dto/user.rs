@@ -2,13 +2,36 @@ use serde::{Deserialize, Serialize};
 use time::OffsetDateTime;
 use utoipa::ToSchema;
 
-use crate::{models::domain::user::UserStatus, utils::datetime::serialize_datetime};
+use crate::{
+    models::{
+        common::currency::Currency, database::user::UserRow, domain::user::UserStatus,
+        dto::backup::AccountBackup,
+    },
+    utils::datetime::serialize_datetime,
+};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CreateUser {
     pub email: String,
+    pub status: UserStatus,
     pub hashed_password: Option<String>,
     pub name: Option<String>,
+    pub base_currency: Currency,
+}
+
+/// Fields of the profile to change, `None` keeps the current value
+#[derive(Debug)]
+pub struct UpdateUser {
+    pub name: Option<String>,
+    pub phone_number: Option<Option<String>>,
+}
+
+/// What was removed with an account
+#[derive(Debug)]
+pub struct DeletedAccount {
+    pub portfolios: usize,
+    pub transactions: u64,
+    pub session_ids: Vec<i64>,
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
@@ -24,6 +47,54 @@ pub struct UserMeResponse {
     pub email: String,
     pub phone_number: Option<String>,
     pub name: Option<String>,
+    pub base_currency: Currency,
     #[serde(serialize_with = "serialize_datetime")]
     pub created_at: OffsetDateTime,
 }
+
+impl UserMeResponse {
+    pub fn from_db_row(user: UserRow) -> Self {
+        Self {
+            id: user.id.to_string(),
+            status: user.status.parse().unwrap(),
+            email: user.email,
+            phone_number: user.phone_number,
+            name: user.name,
+            base_currency: user.base_currency.parse().unwrap_or_default(),
+            created_at: user.created_at,
+        }
+    }
+}
+
+/// Omitted fields are kept. An empty `phone_number` removes the number.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdateUserRequest {
+    pub name: Option<String>,
+    /// International format, e.g. `+44 20 7946 0958`. Spaces, dashes and
+    /// parentheses are dropped before it is stored.
+    pub phone_number: Option<String>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct ChangePasswordRequest {
+    pub current_password: String,
+    pub new_password: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct DeleteAccountRequest {
+    /// Current password of the account, confirming the deletion
+    pub password: String,
+    /// Returns a backup of every portfolio, taken right before the deletion
+    #[serde(default)]
+    pub include_backup: bool,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct DeleteAccountResponse {
+    pub deleted_portfolios: usize,
+    pub deleted_transactions: u64,
+    /// Final export of the account, when `include_backup` was set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub backup: Option<AccountBackup>,
+}
@@ -1,8 +1,59 @@
+use serde::Serialize;
 use time::OffsetDateTime;
+use utoipa::ToSchema;
+
+use crate::models::domain::user_session::UserSession;
+use crate::utils::datetime::serialize_datetime;
 
 // Input type for creating user sessions
 #[derive(Debug)]
 pub struct CreateUserSession {
     pub user_id: i64,
     pub expires_at: OffsetDateTime,
+    /// Hash of the first refresh token of the session
+    pub refresh_token_hash: String,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct UserSessionResponse {
+    pub id: String,
+    /// User-Agent of the device that logged in
+    pub user_agent: Option<String>,
+    /// IP address of the last login or refresh
+    pub ip_address: Option<String>,
+    /// Whether this is the session of the token making the request
+    pub current: bool,
+    #[serde(serialize_with = "serialize_datetime")]
+    pub created_at: OffsetDateTime,
+    #[serde(serialize_with = "serialize_datetime")]
+    pub last_seen_at: OffsetDateTime,
+    #[serde(serialize_with = "serialize_datetime")]
+    pub expires_at: OffsetDateTime,
+}
+
+impl UserSessionResponse {
+    pub fn from_domain(session: UserSession, current_session_id: i64) -> Self {
+        Self {
+            id: session.session_id.to_string(),
+            user_agent: session.user_agent,
+            ip_address: session.ip_address,
+            current: session.session_id == current_session_id,
+            created_at: session.created_at,
+            last_seen_at: session.last_seen_at,
+            expires_at: session.expires_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct UserSessionListResponse {
+    pub items: Vec<UserSessionResponse>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RevokeSessionsResponse {
+    /// Number of sessions logged out
+    pub revoked: usize,
 }
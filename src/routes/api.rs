@@ -1,16 +1,19 @@
 use crate::state::AppState;
 use axum::Router;
+pub mod admin;
 pub mod assets;
 pub mod imports;
 pub mod portfolios;
+pub mod reports;
 pub mod transactions;
 pub mod users;
 
 /// Creates the protected API router.
 ///
-/// All routes under this router require JWT authentication.
+/// All routes under this router require a JWT or a personal access token.
 /// Handlers in this router can access the authenticated user via:
-/// `Extension(claims): Extension<Claims>`
+/// `Extension(claims): Extension<Claims>`, and check the scopes of personal
+/// access tokens with `claims.require_scope` or `claims.require_session`.
 pub fn create_router() -> Router<AppState> {
     Router::new()
         .nest("/users", users::create_router())
@@ -18,4 +21,6 @@ pub fn create_router() -> Router<AppState> {
         .nest("/assets", assets::create_router())
         .nest("/imports", imports::create_router())
         .nest("/transactions", transactions::create_router())
+        .nest("/reports", reports::create_router())
+        .nest("/admin", admin::create_router())
 }
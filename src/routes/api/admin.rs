@@ -0,0 +1,14 @@
+use crate::{
+    handlers::admin::{get_job_runs, trigger_job},
+    state::AppState,
+};
+use axum::{
+    Router,
+    routing::{get, post},
+};
+
+pub fn create_router() -> Router<AppState> {
+    Router::new()
+        .route("/jobs/runs", get(get_job_runs))
+        .route("/jobs/{job_name}/trigger", post(trigger_job))
+}
@@ -1,8 +1,30 @@
 pub mod coingecko;
 
-use crate::state::AppState;
-use axum::Router;
+use crate::{
+    handlers::import::{
+        batch::{get_import_batches, rollback_import},
+        csv::import_csv,
+        exchange::import_exchange_csv,
+        job::{create_import_job, get_import_job, retry_import_job, stream_import_job},
+        plan::commit_import_plan,
+    },
+    state::AppState,
+};
+use axum::{
+    Router,
+    routing::{get, post},
+};
 
 pub fn create_router() -> Router<AppState> {
-    Router::new().nest("/coingecko", coingecko::create_router())
+    Router::new()
+        .route("/", get(get_import_batches))
+        .route("/{id}/rollback", post(rollback_import))
+        .route("/csv", post(import_csv))
+        .route("/exchanges/{exchange}", post(import_exchange_csv))
+        .route("/jobs", post(create_import_job))
+        .route("/jobs/{id}", get(get_import_job))
+        .route("/jobs/{id}/events", get(stream_import_job))
+        .route("/jobs/{id}/retry", post(retry_import_job))
+        .route("/plans/{token}/commit", post(commit_import_plan))
+        .nest("/coingecko", coingecko::create_router())
 }
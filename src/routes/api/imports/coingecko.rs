@@ -4,7 +4,7 @@ use axum::{
 };
 
 use crate::{
-    handlers::import::coingecko::{get_coin_data_by_id, import_portfolio_file},
+    handlers::import::coingecko::{get_coin_data_by_id, import_json_file, import_portfolio_file},
     state::AppState,
 };
 
@@ -12,4 +12,5 @@ pub fn create_router() -> Router<AppState> {
     Router::new()
         .route("/coin_data/{id}", get(get_coin_data_by_id))
         .route("/upload_portfolio_file", post(import_portfolio_file))
+        .route("/upload_json_file", post(import_json_file))
 }
@@ -1,6 +1,8 @@
 use crate::{
     handlers::portfolios::{
-        create_portfolio, create_portfolio_asset, get_my_portfolios, get_portfolio_by_id,
+        create_portfolio, create_portfolio_asset, export_portfolio, get_my_portfolios,
+        get_open_lots, get_portfolio_by_id, get_portfolio_history, get_portfolio_performance,
+        get_realized_gains, update_portfolio,
     },
     state::AppState,
 };
@@ -13,5 +15,10 @@ pub fn create_router() -> Router<AppState> {
     Router::new()
         .route("/", post(create_portfolio).get(get_my_portfolios))
         .route("/{id}/assets", post(create_portfolio_asset))
-        .route("/{id}", get(get_portfolio_by_id))
+        .route("/{id}/realized", get(get_realized_gains))
+        .route("/{id}/lots", get(get_open_lots))
+        .route("/{id}/history", get(get_portfolio_history))
+        .route("/{id}/performance", get(get_portfolio_performance))
+        .route("/{id}/export", get(export_portfolio))
+        .route("/{id}", get(get_portfolio_by_id).patch(update_portfolio))
 }
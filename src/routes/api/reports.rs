@@ -0,0 +1,6 @@
+use crate::{handlers::reports::get_tax_report, state::AppState};
+use axum::{Router, routing::get};
+
+pub fn create_router() -> Router<AppState> {
+    Router::new().route("/tax", get(get_tax_report))
+}
@@ -1,11 +1,42 @@
 use crate::{
-    handlers::users::{get_user_by_id, get_user_me},
+    handlers::personal_access_tokens::{create_my_token, get_my_tokens, revoke_my_token},
+    handlers::two_factor::{
+        confirm_my_totp, disable_my_totp, enroll_my_totp, get_my_two_factor,
+        regenerate_my_recovery_codes,
+    },
+    handlers::users::{
+        change_my_password, delete_user_me, download_backup, get_my_sessions, get_user_by_id,
+        get_user_me, restore_backup, revoke_all_my_sessions, revoke_my_session, update_user_me,
+    },
     state::AppState,
 };
-use axum::{Router, routing::get};
+use axum::{
+    Router,
+    routing::{delete, get, post},
+};
 
 pub fn create_router() -> Router<AppState> {
     Router::new()
         .route("/{id}", get(get_user_by_id))
-        .route("/me", get(get_user_me))
+        .route(
+            "/me",
+            get(get_user_me)
+                .patch(update_user_me)
+                .delete(delete_user_me),
+        )
+        .route("/me/password", post(change_my_password))
+        .route("/me/backup", get(download_backup))
+        .route("/me/restore", post(restore_backup))
+        .route(
+            "/me/sessions",
+            get(get_my_sessions).delete(revoke_all_my_sessions),
+        )
+        .route("/me/sessions/{id}", delete(revoke_my_session))
+        .route("/me/tokens", get(get_my_tokens).post(create_my_token))
+        .route("/me/tokens/{id}", delete(revoke_my_token))
+        .route("/me/2fa", get(get_my_two_factor))
+        .route("/me/2fa/totp/enroll", post(enroll_my_totp))
+        .route("/me/2fa/totp/confirm", post(confirm_my_totp))
+        .route("/me/2fa/recovery_codes", post(regenerate_my_recovery_codes))
+        .route("/me/2fa/disable", post(disable_my_totp))
 }
@@ -1,11 +1,26 @@
 use crate::{
-    handlers::auth::{login_with_password, signup},
+    handlers::auth::{
+        forgot_password, login_with_password, login_with_totp, logout, refresh_token,
+        resend_verification, reset_password, signup, verify_email,
+    },
     state::AppState,
 };
 use axum::{Router, routing::post};
 
+/// Login routes, rate limited apart from the other auth routes
+pub fn create_login_router() -> Router<AppState> {
+    Router::new()
+        .route("/login_with_password", post(login_with_password))
+        .route("/login_with_totp", post(login_with_totp))
+}
+
 pub fn create_router() -> Router<AppState> {
     Router::new()
         .route("/signup", post(signup))
-        .route("/login_with_password", post(login_with_password))
+        .route("/refresh", post(refresh_token))
+        .route("/logout", post(logout))
+        .route("/verify_email", post(verify_email))
+        .route("/resend_verification", post(resend_verification))
+        .route("/forgot_password", post(forgot_password))
+        .route("/reset_password", post(reset_password))
 }
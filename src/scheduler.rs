@@ -0,0 +1,165 @@
+use std::time::Duration;
+
+use redis::Script;
+use strum::IntoEnumIterator;
+use time::OffsetDateTime;
+use tokio::time::MissedTickBehavior;
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::{
+    biz::{
+        asset::refresh_asset_metadata, fx::refresh_fx_rates,
+        portfolio_snapshot::refresh_daily_snapshots, price_history::refresh_spot_prices,
+    },
+    db::repositories::job_run::JobRunRepo,
+    models::{
+        database::job_run::JobRunRow,
+        domain::job::{JobName, JobStatus, JobTrigger},
+    },
+    state::AppState,
+    utils::error::AppError,
+};
+
+// Deletes the lock only if it is still owned by the given token
+const RELEASE_LOCK_SCRIPT: &str = r#"
+if redis.call("GET", KEYS[1]) == ARGV[1] then
+    return redis.call("DEL", KEYS[1])
+end
+return 0
+"#;
+
+/// Holds the Redis lock of a job so only one replica runs it at a time.
+pub struct JobLock {
+    key: String,
+    token: String,
+}
+
+fn lock_key(job_name: JobName) -> String {
+    format!("job_lock:{}", job_name)
+}
+
+fn job_interval(state: &AppState, job_name: JobName) -> Duration {
+    let config = &state.scheduler;
+    let secs = match job_name {
+        JobName::PriceRefresh => config.price_refresh_interval_secs,
+        JobName::DailySnapshots => config.daily_snapshots_interval_secs,
+        JobName::AssetMetadataRefresh => config.asset_metadata_interval_secs,
+        JobName::FxRatesRefresh => config.fx_rates_interval_secs,
+    };
+    Duration::from_secs(secs.max(1))
+}
+
+async fn run_job(state: &AppState, job_name: JobName) -> Result<(), AppError> {
+    match job_name {
+        JobName::PriceRefresh => refresh_spot_prices(state).await,
+        JobName::DailySnapshots => refresh_daily_snapshots(state).await,
+        JobName::AssetMetadataRefresh => refresh_asset_metadata(state).await,
+        JobName::FxRatesRefresh => refresh_fx_rates(state).await,
+    }
+}
+
+/// Spawns one loop per registered job for the lifetime of the process.
+pub fn start(state: AppState) {
+    if !state.scheduler.enabled {
+        info!("Scheduler is disabled");
+        return;
+    }
+    for job_name in JobName::iter() {
+        let interval = job_interval(&state, job_name);
+        info!("Scheduling job {} every {:?}", job_name, interval);
+        tokio::spawn(run_periodic(state.clone(), job_name, interval));
+    }
+}
+
+async fn run_periodic(state: AppState, job_name: JobName, every: Duration) {
+    let mut interval = tokio::time::interval(every);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    loop {
+        interval.tick().await;
+        // Another replica may have run the job since our last tick
+        let latest = JobRunRepo::new(state.pool.clone())
+            .get_latest_started_at(job_name)
+            .await;
+        match latest {
+            Ok(Some(started_at)) if OffsetDateTime::now_utc() - started_at < every => continue,
+            Ok(_) => (),
+            Err(e) => {
+                error!("Get latest run of job {} failed: {:?}", job_name, e);
+                continue;
+            }
+        }
+        match start_job(&state, job_name, JobTrigger::Scheduled).await {
+            Ok(Some((run, lock))) => execute_job(state.clone(), job_name, run.id, lock).await,
+            Ok(None) => info!("Job {} is running on another replica", job_name),
+            Err(e) => error!("Start job {} failed: {:?}", job_name, e),
+        }
+    }
+}
+
+/// Takes the job lock and records a new run. Returns `None` when the job is
+/// already running, here or on another replica.
+pub async fn start_job(
+    state: &AppState,
+    job_name: JobName,
+    triggered_by: JobTrigger,
+) -> Result<Option<(JobRunRow, JobLock)>, AppError> {
+    let lock = JobLock {
+        key: lock_key(job_name),
+        token: Uuid::new_v4().to_string(),
+    };
+    let mut redis_conn = state.redis_conn.clone();
+    let acquired: Option<String> = redis::cmd("SET")
+        .arg(&lock.key)
+        .arg(&lock.token)
+        .arg("NX")
+        .arg("EX")
+        .arg(state.scheduler.lock_ttl_secs)
+        .query_async(&mut redis_conn)
+        .await?;
+    if acquired.is_none() {
+        return Ok(None);
+    }
+    match JobRunRepo::new(state.pool.clone())
+        .create_one(job_name, triggered_by)
+        .await
+    {
+        Ok(run) => Ok(Some((run, lock))),
+        Err(e) => {
+            release_lock(state, &lock).await;
+            Err(e)
+        }
+    }
+}
+
+/// Runs a started job, records its outcome and releases its lock.
+pub async fn execute_job(state: AppState, job_name: JobName, run_id: i64, lock: JobLock) {
+    info!("Job {} started, run {}", job_name, run_id);
+    let (status, error) = match run_job(&state, job_name).await {
+        Ok(()) => (JobStatus::Succeeded, None),
+        Err(e) => {
+            error!("Job {} failed: {:?}", job_name, e);
+            (JobStatus::Failed, Some(format!("{:?}", e)))
+        }
+    };
+    if let Err(e) = JobRunRepo::new(state.pool.clone())
+        .finish(run_id, status, error)
+        .await
+    {
+        error!("Record run {} of job {} failed: {:?}", run_id, job_name, e);
+    }
+    release_lock(&state, &lock).await;
+    info!("Job {} finished with {}, run {}", job_name, status, run_id);
+}
+
+async fn release_lock(state: &AppState, lock: &JobLock) {
+    let mut redis_conn = state.redis_conn.clone();
+    let released: Result<i64, _> = Script::new(RELEASE_LOCK_SCRIPT)
+        .key(&lock.key)
+        .arg(&lock.token)
+        .invoke_async(&mut redis_conn)
+        .await;
+    if let Err(e) = released {
+        error!("Release lock {} failed: {}", lock.key, e);
+    }
+}
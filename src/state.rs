@@ -1,18 +1,31 @@
 use std::sync::Arc;
 
-use anyhow::Error;
+use anyhow::{Error, anyhow};
 use jsonwebtoken::{DecodingKey, EncodingKey};
 use redis::{AsyncCommands, Client, aio::ConnectionManager};
 use sqlx::PgPool;
 
-use crate::{clients::app_client::AppClients, config::Settings, db::postgres::init_pg_pool};
+use crate::{
+    clients::app_client::AppClients,
+    config::{
+        AuthConfig, ImportWorkerConfig, RateLimitConfig, SchedulerConfig, Settings, TaxReportConfig,
+    },
+    db::postgres::init_pg_pool,
+    middleware::client::TrustedProxy,
+};
 
 #[derive(Clone)]
 pub struct AppStateInner {
     pub pool: PgPool,
     secret_key: String,
     pub redis_conn: ConnectionManager,
+    pub auth: AuthConfig,
     pub clients: AppClients,
+    pub scheduler: SchedulerConfig,
+    pub import_worker: ImportWorkerConfig,
+    pub tax_report: TaxReportConfig,
+    pub rate_limit: RateLimitConfig,
+    pub trusted_proxies: Vec<TrustedProxy>,
 }
 
 pub type AppState = Arc<AppStateInner>;
@@ -23,12 +36,25 @@ impl AppStateInner {
         let pg_pool = init_pg_pool(&app_settings.postgres.url).await?;
         let redis = Client::open(app_settings.redis.url.clone())?;
         let redis_conn = ConnectionManager::new(redis).await?;
-        let clients = AppClients::new(&app_settings.clients);
+        let clients = AppClients::new(&app_settings.clients, redis_conn.clone())
+            .map_err(|e| anyhow!("Failed to init clients: {:?}", e))?;
+        let trusted_proxies = app_settings
+            .server
+            .trusted_proxies
+            .iter()
+            .map(|proxy| proxy.parse())
+            .collect::<Result<Vec<TrustedProxy>, _>>()?;
         Ok(Self {
             pool: pg_pool,
             secret_key: app_settings.server.secret_key.clone(),
             redis_conn,
+            auth: app_settings.auth.clone(),
             clients,
+            scheduler: app_settings.scheduler.clone(),
+            import_worker: app_settings.import_worker.clone(),
+            tax_report: app_settings.tax_report.clone(),
+            rate_limit: app_settings.rate_limit.clone(),
+            trusted_proxies,
         })
     }
 
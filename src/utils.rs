@@ -2,5 +2,8 @@ pub mod datetime;
 pub mod error;
 // pub mod log_formatter;
 pub mod coingecko;
+pub mod csv;
 pub mod macros;
+pub mod password;
 pub mod snowflake;
+pub mod token;
@@ -1,8 +1,15 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
+use scraper::{Html, Selector};
+use serde::Deserialize;
 use strum::IntoEnumIterator;
+use time::{OffsetDateTime, format_description::well_known::Rfc3339};
+use tracing::info;
 
-use crate::models::common::currency::Currency;
+use crate::{
+    models::{common::currency::Currency, domain::coingecko::RawTransaction},
+    utils::error::AppError,
+};
 
 pub fn filter_market_data_by_currency(market_data: &HashMap<String, f64>) -> HashMap<String, f64> {
     let currency_map: HashMap<String, Currency> =
@@ -11,7 +18,7 @@ pub fn filter_market_data_by_currency(market_data: &HashMap<String, f64>) -> Has
     market_data
         .iter()
         .filter_map(|(key, value)| {
-            if currency_map.contains_key(&key.to_lowercase()) {
+            if currency_map.contains_key(&key.to_uppercase()) {
                 Some((key.clone(), *value))
             } else {
                 None
@@ -19,3 +26,192 @@ pub fn filter_market_data_by_currency(market_data: &HashMap<String, f64>) -> Has
         })
         .collect()
 }
+
+/// Why a CoinGecko portfolio file could not be read
+#[derive(Debug)]
+pub enum CoingeckoFileError {
+    /// The page does not say which coin its transactions belong to
+    MissingCoinSlug,
+    NoTransactions,
+    InvalidTransaction {
+        index: usize,
+        reason: String,
+    },
+    InvalidJson(String),
+}
+
+impl std::fmt::Display for CoingeckoFileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CoingeckoFileError::MissingCoinSlug => {
+                write!(f, "The page is not the CoinGecko portfolio page of a coin")
+            }
+            CoingeckoFileError::NoTransactions => write!(f, "The file has no transactions"),
+            CoingeckoFileError::InvalidTransaction { index, reason } => {
+                write!(f, "Transaction {} is invalid: {}", index + 1, reason)
+            }
+            CoingeckoFileError::InvalidJson(reason) => write!(f, "Invalid JSON export: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for CoingeckoFileError {}
+
+impl From<CoingeckoFileError> for AppError {
+    fn from(err: CoingeckoFileError) -> Self {
+        AppError::InvalidInput(err.to_string())
+    }
+}
+
+/// Reads the transactions of a saved CoinGecko portfolio page of one coin. Only
+/// the data attributes are used so layout changes do not break the import.
+pub fn parse_html_contents(
+    contents: &str,
+) -> Result<(String, Vec<RawTransaction>), CoingeckoFileError> {
+    let doc = Html::parse_document(contents);
+    let coin_slug_sel = Selector::parse("[data-coin-slug]").unwrap();
+    let coin_id = doc
+        .select(&coin_slug_sel)
+        .filter_map(|el| el.value().attr("data-coin-slug"))
+        .find(|slug| !slug.is_empty())
+        .ok_or(CoingeckoFileError::MissingCoinSlug)?;
+
+    let tx_sel = Selector::parse("[data-portfolio-coin-transaction-data]").unwrap();
+    let mut transactions: Vec<RawTransaction> = Vec::new();
+    let mut seen_ids = HashSet::new();
+    for el in doc.select(&tx_sel) {
+        let Some(transaction_data) = el.value().attr("data-portfolio-coin-transaction-data") else {
+            continue;
+        };
+        let transaction: RawTransaction = serde_json::from_str(transaction_data).map_err(|e| {
+            CoingeckoFileError::InvalidTransaction {
+                index: transactions.len(),
+                reason: e.to_string(),
+            }
+        })?;
+        // A transaction may be rendered more than once, e.g. for mobile layouts
+        if seen_ids.insert(transaction.id) {
+            transactions.push(transaction);
+        }
+    }
+    info!(
+        "Coin ID: {}, found {} transactions",
+        coin_id,
+        transactions.len()
+    );
+    if transactions.is_empty() {
+        return Err(CoingeckoFileError::NoTransactions);
+    }
+    Ok((coin_id.to_string(), transactions))
+}
+
+/// Transaction of a JSON export, as written by `coingecko_exporter` with numbers
+/// and `transaction_dt`, or as found in the page with strings and
+/// `transaction_timestamp`
+#[derive(Debug, Deserialize)]
+struct ExportedTransaction {
+    id: u32,
+    transaction_type: String,
+    currency: String,
+    quantity: serde_json::Value,
+    price: serde_json::Value,
+    #[serde(alias = "transaction_dt")]
+    transaction_timestamp: String,
+    #[serde(default)]
+    fees: serde_json::Value,
+    notes: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CoinExport {
+    coin_id: String,
+    transactions: Vec<ExportedTransaction>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum JsonExport {
+    Transactions(Vec<ExportedTransaction>),
+    Coin(CoinExport),
+    Coins(Vec<CoinExport>),
+}
+
+fn json_number_to_string(value: serde_json::Value) -> Result<String, String> {
+    match value {
+        serde_json::Value::Null => Ok("0".to_string()),
+        serde_json::Value::Number(n) => Ok(n.to_string()),
+        serde_json::Value::String(s) => Ok(s),
+        other => Err(format!("{} is not a number", other)),
+    }
+}
+
+// Older exports wrote timestamps such as `2025-02-19T9:51:00.0.000Z`
+fn normalize_timestamp(timestamp: &str) -> Result<String, String> {
+    if OffsetDateTime::parse(timestamp, &Rfc3339).is_ok() {
+        return Ok(timestamp.to_string());
+    }
+    let invalid = || format!("invalid timestamp {}", timestamp);
+    let (date, time) = timestamp.split_once('T').ok_or_else(invalid)?;
+    let time = time.trim_end_matches('Z');
+    let time = time.split('.').next().unwrap_or(time);
+    let mut parts = time.splitn(2, ':');
+    let hour: u8 = parts
+        .next()
+        .and_then(|h| h.parse().ok())
+        .ok_or_else(invalid)?;
+    let rest = parts.next().ok_or_else(invalid)?;
+    let normalized = format!("{}T{:02}:{}Z", date, hour, rest);
+    OffsetDateTime::parse(&normalized, &Rfc3339).map_err(|_| invalid())?;
+    Ok(normalized)
+}
+
+impl ExportedTransaction {
+    fn into_raw(self) -> Result<RawTransaction, String> {
+        Ok(RawTransaction {
+            id: self.id,
+            transaction_type: self.transaction_type,
+            currency: self.currency,
+            quantity: json_number_to_string(self.quantity)?,
+            price: json_number_to_string(self.price)?,
+            transaction_timestamp: normalize_timestamp(&self.transaction_timestamp)?,
+            fees: json_number_to_string(self.fees)?,
+            notes: self.notes.filter(|notes| !notes.is_empty()),
+        })
+    }
+}
+
+/// Transactions of one coin of a JSON export, with its ID when the file has one
+pub type CoinTransactions = (Option<String>, Vec<RawTransaction>);
+
+/// Reads a JSON export of CoinGecko transactions: a bare array, an object with
+/// `coin_id` and `transactions`, or an array of such objects for several coins.
+/// Returns the transactions of each coin, with its ID when the file has one.
+pub fn parse_json_export(contents: &str) -> Result<Vec<CoinTransactions>, CoingeckoFileError> {
+    let export: JsonExport = serde_json::from_str(contents)
+        .map_err(|e| CoingeckoFileError::InvalidJson(e.to_string()))?;
+    let coins = match export {
+        JsonExport::Transactions(transactions) => vec![(None, transactions)],
+        JsonExport::Coin(coin) => vec![(Some(coin.coin_id), coin.transactions)],
+        JsonExport::Coins(coins) => coins
+            .into_iter()
+            .map(|coin| (Some(coin.coin_id), coin.transactions))
+            .collect(),
+    };
+    if coins.iter().all(|(_, exported)| exported.is_empty()) {
+        return Err(CoingeckoFileError::NoTransactions);
+    }
+    let mut index = 0;
+    let mut result = Vec::with_capacity(coins.len());
+    for (coin_id, exported) in coins {
+        let mut transactions = Vec::with_capacity(exported.len());
+        for tx in exported {
+            transactions.push(
+                tx.into_raw()
+                    .map_err(|reason| CoingeckoFileError::InvalidTransaction { index, reason })?,
+            );
+            index += 1;
+        }
+        result.push((coin_id, transactions));
+    }
+    Ok(result)
+}
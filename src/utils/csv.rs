@@ -0,0 +1,72 @@
+use crate::utils::error::AppError;
+
+/// Splits CSV contents into records of fields. Handles quoted fields with
+/// escaped quotes and line breaks, CRLF line endings and a leading BOM. Blank
+/// lines are skipped.
+pub fn parse_csv(contents: &str, delimiter: char) -> Result<Vec<Vec<String>>, AppError> {
+    let contents = contents.strip_prefix('\u{feff}').unwrap_or(contents);
+    let mut records = Vec::new();
+    let mut record: Vec<String> = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = contents.chars().peekable();
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    field.push('"');
+                    chars.next();
+                }
+                '"' => in_quotes = false,
+                _ => field.push(c),
+            }
+            continue;
+        }
+        match c {
+            '"' if field.is_empty() => in_quotes = true,
+            '\r' if chars.peek() == Some(&'\n') => (),
+            '\n' | '\r' => {
+                record.push(std::mem::take(&mut field));
+                if record.iter().any(|f| !f.is_empty()) {
+                    records.push(std::mem::take(&mut record));
+                } else {
+                    record.clear();
+                }
+            }
+            c if c == delimiter => record.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    if in_quotes {
+        return Err(AppError::InvalidInput(
+            "CSV file has an unterminated quoted field".to_string(),
+        ));
+    }
+    record.push(field);
+    if record.iter().any(|f| !f.is_empty()) {
+        records.push(record);
+    }
+    Ok(records)
+}
+
+/// Writes records as CSV, quoting fields that hold the delimiter, quotes or line
+/// breaks. Lines end with CRLF as RFC 4180 asks.
+pub fn write_csv(records: &[Vec<String>], delimiter: char) -> String {
+    let mut contents = String::new();
+    for record in records {
+        for (idx, field) in record.iter().enumerate() {
+            if idx > 0 {
+                contents.push(delimiter);
+            }
+            if field.contains([delimiter, '"', '\n', '\r']) {
+                contents.push('"');
+                contents.push_str(&field.replace('"', "\"\""));
+                contents.push('"');
+            } else {
+                contents.push_str(field);
+            }
+        }
+        contents.push_str("\r\n");
+    }
+    contents
+}
@@ -42,3 +42,17 @@ where
         None => Ok(None),
     }
 }
+
+/// Serializes an optional OffsetDateTime the same way as `serialize_datetime`, or null
+pub fn serialize_optional_datetime<S>(
+    datetime: &Option<OffsetDateTime>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match datetime {
+        Some(datetime) => serialize_datetime(datetime, serializer),
+        None => serializer.serialize_none(),
+    }
+}
@@ -2,7 +2,9 @@ use axum::{
     http::StatusCode,
     response::{IntoResponse, Response},
 };
+use redis::RedisError;
 use rust_decimal::Error as DecimalError;
+use rust_xlsxwriter::XlsxError;
 use serde_json::Error as SerdeError;
 use sqlx::Error as SqlxError;
 use strum::Display;
@@ -15,14 +17,24 @@ use crate::models::dto::api_response::ApiResponse;
 #[derive(Debug, Display)]
 pub enum AppError {
     SqlError(SqlxError),
+    RedisError(RedisError),
     SerdeError(SerdeError),
     CoinGeckoError(String),
+    PriceProviderError(String),
+    FxRateNotFound(String),
     HttpError(String),
     Unauthorized(String),
+    Forbidden(String),
+    InvalidInput(String),
+    Conflict(String),
     TimeParseError(TimeParseError),
     TimeFormatError(TimeFormatError),
     StrumParseError(StrumParseError),
     DecimalError(DecimalError),
+    XlsxError(XlsxError),
+    MailerError(String),
+    TotpError(String),
+    PasswordError(String),
 }
 
 impl AppError {
@@ -35,6 +47,13 @@ impl AppError {
                     "Internal Server Error".to_string(),
                 )
             }
+            AppError::RedisError(err) => {
+                error!("Redis error: {}", err);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Internal Server Error".to_string(),
+                )
+            }
             AppError::SerdeError(err) => {
                 error!("Serde error: {}", err);
                 (StatusCode::BAD_REQUEST, "Invalid data".to_string())
@@ -46,6 +65,17 @@ impl AppError {
                     "Internal Server Error".to_string(),
                 )
             }
+            AppError::PriceProviderError(msg) => {
+                error!("Price provider error: {}", msg);
+                (
+                    StatusCode::BAD_GATEWAY,
+                    "Error communicating with price provider".to_string(),
+                )
+            }
+            AppError::FxRateNotFound(currency) => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                format!("No FX rate available for {}", currency),
+            ),
             AppError::HttpError(msg) => {
                 error!("HTTP error: {}", msg);
                 (
@@ -54,6 +84,9 @@ impl AppError {
                 )
             }
             AppError::Unauthorized(msg) => (StatusCode::UNAUTHORIZED, msg),
+            AppError::Forbidden(msg) => (StatusCode::FORBIDDEN, msg),
+            AppError::InvalidInput(msg) => (StatusCode::BAD_REQUEST, msg),
+            AppError::Conflict(msg) => (StatusCode::CONFLICT, msg),
             AppError::TimeParseError(err) => {
                 error!("Time parse error: {}", err);
                 (StatusCode::BAD_REQUEST, "Invalid datetime format".into())
@@ -70,6 +103,34 @@ impl AppError {
                 error!("Decimal error: {}", err);
                 (StatusCode::BAD_REQUEST, "Invalid decimal value".into())
             }
+            AppError::XlsxError(err) => {
+                error!("XLSX error: {}", err);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Internal Server Error".to_string(),
+                )
+            }
+            AppError::MailerError(msg) => {
+                error!("Mailer error: {}", msg);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Internal Server Error".to_string(),
+                )
+            }
+            AppError::TotpError(msg) => {
+                error!("TOTP error: {}", msg);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Internal Server Error".to_string(),
+                )
+            }
+            AppError::PasswordError(msg) => {
+                error!("Password error: {}", msg);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Internal Server Error".to_string(),
+                )
+            }
         }
     }
 }
@@ -87,6 +148,12 @@ impl From<SqlxError> for AppError {
     }
 }
 
+impl From<RedisError> for AppError {
+    fn from(err: RedisError) -> Self {
+        AppError::RedisError(err)
+    }
+}
+
 impl From<SerdeError> for AppError {
     fn from(value: SerdeError) -> Self {
         AppError::SerdeError(value)
@@ -122,3 +189,9 @@ impl From<DecimalError> for AppError {
         AppError::DecimalError(err)
     }
 }
+
+impl From<XlsxError> for AppError {
+    fn from(err: XlsxError) -> Self {
+        AppError::XlsxError(err)
+    }
+}
@@ -0,0 +1,32 @@
+use std::sync::LazyLock;
+
+use argon2::{self, password_hash::rand_core};
+
+pub fn hash_password(password: String) -> Result<String, anyhow::Error> {
+    // Hash the password using Argon2
+    let salt = argon2::password_hash::SaltString::generate(&mut rand_core::OsRng);
+    let argon2 = argon2::Argon2::default();
+    let hashed_password =
+        argon2::PasswordHasher::hash_password(&argon2, password.as_bytes(), &salt)
+            .map_err(|e| anyhow::anyhow!(e))?;
+    Ok(hashed_password.to_string())
+}
+
+pub fn verify_password(password: String, hashed_password: String) -> Result<bool, anyhow::Error> {
+    let argon2 = argon2::Argon2::default();
+    let parsed_hash =
+        argon2::PasswordHash::new(&hashed_password).map_err(|e| anyhow::anyhow!(e))?;
+    Ok(
+        argon2::PasswordVerifier::verify_password(&argon2, password.as_bytes(), &parsed_hash)
+            .is_ok(),
+    )
+}
+
+/// Hash checked when the account does not exist, so a login takes as long
+/// whether the email has an account or not
+static DUMMY_PASSWORD_HASH: LazyLock<String> =
+    LazyLock::new(|| hash_password("dummy-password".to_string()).unwrap_or_default());
+
+pub fn verify_dummy_password(password: String) {
+    let _ = verify_password(password, DUMMY_PASSWORD_HASH.clone());
+}